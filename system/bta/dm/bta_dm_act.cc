@@ -519,6 +519,13 @@ void bta_dm_set_dev_name(const std::vector<uint8_t>& name) {
   bta_dm_set_eir((char*)name.data());
 }
 
+/** Sets whether the inquiry response TX power is included in EIR */
+void bta_dm_set_eir_tx_power(bool include, int8_t tx_power) {
+  bta_dm_cb.eir_tx_power_valid = include;
+  bta_dm_cb.eir_tx_power = tx_power;
+  bta_dm_set_eir(NULL);
+}
+
 /** Sets discoverability, connectability and pairability */
 bool BTA_DmSetVisibility(bt_scan_mode_t mode) {
   tBTA_DM_DISC disc_mode_param;
@@ -3095,11 +3102,15 @@ static void bta_dm_set_eir(char* local_name) {
     p_length = NULL;
   }
 
-  /* if Inquiry Tx Resp Power compiled */
-  if ((p_bta_dm_eir_cfg->bta_dm_eir_inq_tx_power) && (free_eir_length >= 3)) {
+  /* if Inquiry Tx Resp Power reported by the controller or compiled */
+  if ((bta_dm_cb.eir_tx_power_valid ||
+       p_bta_dm_eir_cfg->bta_dm_eir_inq_tx_power) &&
+      (free_eir_length >= 3)) {
     UINT8_TO_STREAM(p, 2); /* Length field */
     UINT8_TO_STREAM(p, HCI_EIR_TX_POWER_LEVEL_TYPE);
-    UINT8_TO_STREAM(p, *(p_bta_dm_eir_cfg->bta_dm_eir_inq_tx_power));
+    UINT8_TO_STREAM(p, bta_dm_cb.eir_tx_power_valid
+                           ? bta_dm_cb.eir_tx_power
+                           : *(p_bta_dm_eir_cfg->bta_dm_eir_inq_tx_power));
     free_eir_length -= 3;
   }
 
@@ -68,6 +68,12 @@ void BTA_DmSetDeviceName(const char* p_name) {
   do_in_main_thread(FROM_HERE, base::Bind(bta_dm_set_dev_name, name));
 }
 
+/** This function sets whether the inquiry response TX power is in the EIR */
+void BTA_DmSetEirTxPower(bool include, int8_t tx_power) {
+  do_in_main_thread(FROM_HERE,
+                    base::Bind(bta_dm_set_eir_tx_power, include, tx_power));
+}
+
 /*******************************************************************************
  *
  * Function         BTA_DmSearch
@@ -337,6 +337,11 @@ typedef struct {
 
 #endif
 
+  /* inquiry response TX power reported by the controller, used in EIR when
+   * valid instead of the compiled in configuration */
+  bool eir_tx_power_valid;
+  int8_t eir_tx_power;
+
   tBTA_DM_ENCRYPT_CBACK* p_encrypt_cback;
   alarm_t* switch_delay_timer;
 } tBTA_DM_CB;
@@ -470,6 +475,7 @@ extern void bta_dm_disable();
 extern void bta_dm_init_cb(void);
 extern void bta_dm_deinit_cb(void);
 extern void bta_dm_set_dev_name(const std::vector<uint8_t>&);
+extern void bta_dm_set_eir_tx_power(bool include, int8_t tx_power);
 extern void bta_dm_set_visibility(tBTA_DM_DISC, tBTA_DM_CONN);
 extern void bta_dm_set_scan_config(tBTA_DM_MSG* p_data);
 extern void bta_dm_vendor_spec_command(tBTA_DM_MSG* p_data);
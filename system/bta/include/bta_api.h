@@ -693,6 +693,19 @@ extern void BTA_EnableTestMode(void);
  ******************************************************************************/
 extern void BTA_DmSetDeviceName(const char* p_name);
 
+/*******************************************************************************
+ *
+ * Function         BTA_DmSetEirTxPower
+ *
+ * Description      This function sets whether the inquiry response TX power
+ *                  reported by the controller is included in the EIR.
+ *
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+extern void BTA_DmSetEirTxPower(bool include, int8_t tx_power);
+
 /*******************************************************************************
  *
  * Function         BTA_DmSetVisibility
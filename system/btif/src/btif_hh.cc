@@ -31,6 +31,7 @@
 
 #include <base/logging.h>
 
+#include <algorithm>
 #include <cstdint>
 
 #include "bta_hh_co.h"
@@ -1009,6 +1010,24 @@ static void btif_hh_upstreams_evt(uint16_t event, char* p_param) {
                                 p_data->dscp_info.version,
                                 p_data->dscp_info.ctry_code, len,
                                 p_data->dscp_info.descriptor.dsc_list);
+
+        // Share the descriptor with HAL clients that want it, e.g. to find
+        // the battery report.
+        if (bt_hh_callbacks && bt_hh_callbacks->hid_info_cb) {
+          bthh_hid_info_t hid_info = {};
+          hid_info.attr_mask = p_dev->attr_mask;
+          hid_info.sub_class = p_dev->sub_class;
+          hid_info.app_id = p_dev->app_id;
+          hid_info.vendor_id = p_data->dscp_info.vendor_id;
+          hid_info.product_id = p_data->dscp_info.product_id;
+          hid_info.version = p_data->dscp_info.version;
+          hid_info.ctry_code = p_data->dscp_info.ctry_code;
+          hid_info.dl_len = std::min<int>(len, BTHH_MAX_DSC_LEN);
+          memcpy(hid_info.dsc_list, p_data->dscp_info.descriptor.dsc_list,
+                 hid_info.dl_len);
+          HAL_CBACK(bt_hh_callbacks, hid_info_cb, &p_dev->bd_addr, hid_info);
+        }
+
         if (btif_hh_add_added_dev(p_dev->bd_addr, p_dev->attr_mask)) {
           tBTA_HH_DEV_DSCP_INFO dscp_info;
           bt_status_t ret;
@@ -1142,6 +1142,10 @@ bool Controller::IsSupported(bluetooth::hci::OpCode op_code) const {
   return impl_->is_supported(op_code);
 }
 
+std::array<uint8_t, 64> Controller::GetLocalSupportedCommands() const {
+  return impl_->local_supported_commands_;
+}
+
 const ModuleFactory Controller::Factory = ModuleFactory([]() { return new Controller(); });
 
 void Controller::ListDependencies(ModuleList* list) const {
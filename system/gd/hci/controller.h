@@ -16,6 +16,8 @@
 
 #pragma once
 
+#include <array>
+
 #include "common/contextual_callback.h"
 #include "hci/address.h"
 #include "hci/hci_packets.h"
@@ -179,6 +181,9 @@ class Controller : public Module {
 
   virtual bool IsSupported(OpCode op_code) const;
 
+  // Supported commands bitmap as returned by Read Local Supported Commands.
+  virtual std::array<uint8_t, 64> GetLocalSupportedCommands() const;
+
   static const ModuleFactory Factory;
 
   static constexpr uint64_t kDefaultEventMask = 0x3dbfffffffffffff;
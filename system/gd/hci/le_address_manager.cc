@@ -317,6 +317,30 @@ void LeAddressManager::rotate_random_address() {
   set_random_address();
 }
 
+void LeAddressManager::SetPrivateAddressRotationTime(
+    std::chrono::milliseconds minimum_rotation_time, std::chrono::milliseconds maximum_rotation_time) {
+  handler_->CallOn(
+      this, &LeAddressManager::set_private_address_rotation_time, minimum_rotation_time, maximum_rotation_time);
+}
+
+void LeAddressManager::set_private_address_rotation_time(
+    std::chrono::milliseconds minimum_rotation_time, std::chrono::milliseconds maximum_rotation_time) {
+  if (address_policy_ != AddressPolicy::USE_RESOLVABLE_ADDRESS &&
+      address_policy_ != AddressPolicy::USE_NON_RESOLVABLE_ADDRESS) {
+    LOG_WARN("Address policy %d doesn't rotate the address", address_policy_);
+    return;
+  }
+  ASSERT(minimum_rotation_time <= maximum_rotation_time);
+
+  LOG_INFO("Updating rotation time.");
+  minimum_rotation_time_ = minimum_rotation_time;
+  maximum_rotation_time_ = maximum_rotation_time;
+  // The rotation is only scheduled while clients are registered
+  if (!registered_clients_.empty()) {
+    schedule_rotate_random_address();
+  }
+}
+
 void LeAddressManager::prepare_to_update_irk(UpdateIRKCommand update_irk_command) {
   Command command = {CommandType::UPDATE_IRK, update_irk_command};
   cached_commands_.push(std::move(command));
@@ -391,6 +415,9 @@ hci::Address LeAddressManager::generate_nrpa() {
 }
 
 std::chrono::milliseconds LeAddressManager::GetNextPrivateAddressIntervalMs() {
+  if (maximum_rotation_time_ <= minimum_rotation_time_) {
+    return minimum_rotation_time_;
+  }
   auto interval_random_part_max_ms = maximum_rotation_time_ - minimum_rotation_time_;
   auto random_ms = std::chrono::milliseconds(os::GenerateRandom()) % (interval_random_part_max_ms);
   return minimum_rotation_time_ + random_ms;
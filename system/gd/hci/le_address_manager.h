@@ -72,6 +72,10 @@ class LeAddressManager {
       std::chrono::milliseconds minimum_rotation_time,
       std::chrono::milliseconds maximum_rotation_time);
   virtual AddressPolicy GetAddressPolicy();
+  // Only changes how often the random address is rotated. The IRK and the current address are kept,
+  // the next rotation is rescheduled. Ignored for policies that don't rotate the address.
+  void SetPrivateAddressRotationTime(
+      std::chrono::milliseconds minimum_rotation_time, std::chrono::milliseconds maximum_rotation_time);
   void AckPause(LeAddressManagerCallback* callback);
   void AckResume(LeAddressManagerCallback* callback);
   virtual AddressPolicy Register(LeAddressManagerCallback* callback);
@@ -145,6 +149,8 @@ class LeAddressManager {
   void rotate_random_address();
   void schedule_rotate_random_address();
   void set_random_address();
+  void set_private_address_rotation_time(
+      std::chrono::milliseconds minimum_rotation_time, std::chrono::milliseconds maximum_rotation_time);
   void prepare_to_update_irk(UpdateIRKCommand command);
   void update_irk(UpdateIRKCommand command);
   hci::Address generate_rpa();
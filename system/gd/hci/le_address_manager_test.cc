@@ -251,6 +251,21 @@ TEST_F(LeAddressManagerTest, rotator_non_resolvable_address_for_single_client) {
   sync_handler(handler_);
 }
 
+TEST_F(LeAddressManagerTest, set_private_address_rotation_time) {
+  Octet16 irk = {0xec, 0x02, 0x34, 0xa3, 0x57, 0xc8, 0xad, 0x05, 0x34, 0x10, 0x10, 0xa6, 0x0a, 0x39, 0x7d, 0x9b};
+  AddressWithType remote_address(Address::kEmpty, AddressType::RANDOM_DEVICE_ADDRESS);
+  le_address_manager_->SetPrivacyPolicyForInitiatorAddressForTest(
+      LeAddressManager::AddressPolicy::USE_RESOLVABLE_ADDRESS,
+      remote_address,
+      irk,
+      std::chrono::milliseconds(1000),
+      std::chrono::milliseconds(3000));
+
+  le_address_manager_->SetPrivateAddressRotationTime(std::chrono::seconds(5), std::chrono::seconds(5));
+  sync_handler(handler_);
+  ASSERT_EQ(le_address_manager_->GetNextPrivateAddressIntervalMs(), std::chrono::milliseconds(5000));
+}
+
 // TODO handle the case "register during rotate_random_address" and enable this
 TEST_F(LeAddressManagerTest, DISABLED_rotator_address_for_multiple_clients) {
   AllocateClients(2);
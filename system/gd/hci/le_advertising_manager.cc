@@ -848,6 +848,43 @@ struct LeAdvertisingManager::impl : public bluetooth::hci::LeAddressManagerCallb
     }
   }
 
+  // Replaces the data of a set without disabling it. The controller only swaps data atomically when it is
+  // written with a single command, so on an enabled extended set, data that needs more than one fragment is
+  // rejected instead of being written while the set advertises.
+  void update_data(AdvertiserId advertiser_id, bool set_scan_rsp, std::vector<GapData> data) {
+    bool enabled = enabled_sets_[advertiser_id].advertising_handle_ != kInvalidHandle && !paused;
+    if (advertising_api_type_ == AdvertisingApiType::EXTENDED && enabled) {
+      uint16_t data_len = 0;
+      for (size_t i = 0; i < data.size(); i++) {
+        data_len += data[i].size();
+      }
+      // The Flags data type will be added by set_data() function, we should count it here.
+      if (!set_scan_rsp && advertising_sets_[advertiser_id].connectable && !data_has_flags(data)) {
+        data_len += kLenOfFlags;
+      }
+
+      if (data_len > kLeMaximumFragmentLength) {
+        LOG_WARN(
+            "data len %d of enabled advertising set %d exceeds %d, can't update it in place",
+            data_len,
+            advertiser_id,
+            kLeMaximumFragmentLength);
+        if (advertising_callbacks_ != nullptr) {
+          if (set_scan_rsp) {
+            advertising_callbacks_->OnScanResponseDataSet(
+                advertiser_id, AdvertisingCallback::AdvertisingStatus::DATA_TOO_LARGE);
+          } else {
+            advertising_callbacks_->OnAdvertisingDataSet(
+                advertiser_id, AdvertisingCallback::AdvertisingStatus::DATA_TOO_LARGE);
+          }
+        }
+        return;
+      }
+    }
+
+    set_data(advertiser_id, set_scan_rsp, data);
+  }
+
   void send_data_fragment(
       AdvertiserId advertiser_id, bool set_scan_rsp, std::vector<GapData> data, Operation operation) {
     if (operation == Operation::COMPLETE_ADVERTISEMENT || operation == Operation::LAST_FRAGMENT) {
@@ -1550,6 +1587,10 @@ void LeAdvertisingManager::SetData(AdvertiserId advertiser_id, bool set_scan_rsp
   CallOn(pimpl_.get(), &impl::set_data, advertiser_id, set_scan_rsp, data);
 }
 
+void LeAdvertisingManager::UpdateData(AdvertiserId advertiser_id, bool set_scan_rsp, std::vector<GapData> data) {
+  CallOn(pimpl_.get(), &impl::update_data, advertiser_id, set_scan_rsp, data);
+}
+
 void LeAdvertisingManager::EnableAdvertiser(
     AdvertiserId advertiser_id, bool enable, uint16_t duration, uint8_t max_extended_advertising_events) {
   CallOn(pimpl_.get(), &impl::enable_advertiser, advertiser_id, enable, duration, max_extended_advertising_events);
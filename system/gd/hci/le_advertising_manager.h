@@ -134,6 +134,10 @@ class LeAdvertisingManager : public bluetooth::Module {
 
   void SetData(AdvertiserId advertiser_id, bool set_scan_rsp, std::vector<GapData> data);
 
+  // Like SetData(), but never disables the set or writes it in fragments while it is enabled. Data that doesn't
+  // fit in a single command is reported as DATA_TOO_LARGE on an enabled extended set.
+  void UpdateData(AdvertiserId advertiser_id, bool set_scan_rsp, std::vector<GapData> data);
+
   void EnableAdvertiser(
       AdvertiserId advertiser_id, bool enable, uint16_t duration, uint8_t max_extended_advertising_events);
 
@@ -969,6 +969,50 @@ TEST_F(LeExtendedAdvertisingAPITest, set_scan_response_fragments_test) {
   test_hci_layer_->IncomingEvent(LeSetExtendedScanResponseDataCompleteBuilder::Create(uint8_t{1}, ErrorCode::SUCCESS));
 }
 
+TEST_F(LeExtendedAdvertisingAPITest, update_data_test) {
+  // Update advertising data, it fits in a single command
+  std::vector<GapData> advertising_data{};
+  GapData data_item{};
+  data_item.data_type_ = GapDataType::TX_POWER_LEVEL;
+  data_item.data_ = {0x00};
+  advertising_data.push_back(data_item);
+  test_hci_layer_->SetCommandFuture(1);
+  le_advertising_manager_->UpdateData(advertiser_id_, false, advertising_data);
+  ASSERT_EQ(OpCode::LE_SET_EXTENDED_ADVERTISING_DATA, test_hci_layer_->GetCommand().GetOpCode());
+  EXPECT_CALL(
+      mock_advertising_callback_,
+      OnAdvertisingDataSet(advertiser_id_, AdvertisingCallback::AdvertisingStatus::SUCCESS));
+  test_hci_layer_->IncomingEvent(LeSetExtendedAdvertisingDataCompleteBuilder::Create(uint8_t{1}, ErrorCode::SUCCESS));
+  sync_client_handler();
+}
+
+TEST_F(LeExtendedAdvertisingAPITest, update_data_fragments_test) {
+  // Update advertising data that needs two fragments while the set is enabled
+  std::vector<GapData> advertising_data{};
+  for (uint8_t i = 0; i < 2; i++) {
+    GapData data_item{};
+    data_item.data_.push_back(0xda);
+    data_item.data_type_ = GapDataType::SERVICE_DATA_128_BIT_UUIDS;
+    uint8_t uuid[16] = {0xf0, 0x34, 0x9b, 0x5f, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10, 0x00, 0x00, 0x00, 0x10, 0x00, i};
+    std::copy_n(uuid, 16, std::back_inserter(data_item.data_));
+    uint8_t service_data[200];
+    std::copy_n(service_data, 200, std::back_inserter(data_item.data_));
+    advertising_data.push_back(data_item);
+  }
+
+  EXPECT_CALL(
+      mock_advertising_callback_,
+      OnAdvertisingDataSet(advertiser_id_, AdvertisingCallback::AdvertisingStatus::DATA_TOO_LARGE));
+  le_advertising_manager_->UpdateData(advertiser_id_, false, advertising_data);
+
+  EXPECT_CALL(
+      mock_advertising_callback_,
+      OnScanResponseDataSet(advertiser_id_, AdvertisingCallback::AdvertisingStatus::DATA_TOO_LARGE));
+  le_advertising_manager_->UpdateData(advertiser_id_, true, advertising_data);
+
+  sync_client_handler();
+}
+
 TEST_F(LeExtendedAdvertisingAPITest, set_data_with_invalid_ad_structure) {
   // Set advertising data with AD structure that length greater than 251
   std::vector<GapData> advertising_data{};
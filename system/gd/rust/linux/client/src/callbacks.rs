@@ -5,12 +5,16 @@ use crate::dbus_iface::{
 };
 use crate::ClientContext;
 use crate::{console_yellow, print_info};
-use bt_topshim::btif::{BtBondState, BtSspVariant};
+use bt_topshim::btif::{BtBondFailReason, BtBondState, BtSspVariant};
+use bt_topshim::controller::KeypressNotificationType;
 use bt_topshim::profiles::gatt::GattStatus;
+use btstack::background_connections::ConnectionOrigin;
 use btstack::bluetooth::{
     BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
 };
-use btstack::bluetooth_gatt::{BluetoothGattService, IBluetoothGattCallback, LePhy};
+use btstack::bluetooth_gatt::{
+    BluetoothGattService, GattConnectionPriority, IBluetoothGattCallback, LePhy,
+};
 use btstack::suspend::ISuspendCallback;
 use btstack::RPCProxy;
 use dbus::nonblock::SyncConnection;
@@ -53,6 +57,26 @@ impl IBluetoothManagerCallback for BtManagerCallback {
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool) {
         self.context.lock().unwrap().set_adapter_enabled(hci_interface, enabled);
     }
+
+    fn on_adapter_failover(&self, from_hci_interface: i32, to_hci_interface: i32) {
+        print_info!("Failing over from hci{} to hci{}", from_hci_interface, to_hci_interface);
+
+        // Follow the manager to the adapter it moved the stack to.
+        let mut context = self.context.lock().unwrap();
+        if context.default_adapter == from_hci_interface {
+            context.set_adapter_enabled(from_hci_interface, false);
+            context.default_adapter = to_hci_interface;
+        }
+    }
+
+    fn on_default_adapter_changed(&self, hci_interface: i32) {
+        print_info!("Default adapter is now hci{}", hci_interface);
+        self.context.lock().unwrap().default_adapter = hci_interface;
+    }
+
+    fn on_adapter_firmware_changed(&self, hci_interface: i32) {
+        print_info!("hci{} firmware changed", hci_interface);
+    }
 }
 
 impl manager_service::RPCProxy for BtManagerCallback {
@@ -185,8 +209,17 @@ impl IBluetoothCallback for BtCallback {
         }
     }
 
-    fn on_bond_state_changed(&self, status: u32, address: String, state: u32) {
+    fn on_bond_state_changed(
+        &self,
+        status: u32,
+        address: String,
+        state: u32,
+        fail_reason: BtBondFailReason,
+    ) {
         print_info!("Bonding state changed: [{}] state: {}, Status = {}", address, state, status);
+        if fail_reason != BtBondFailReason::None {
+            print_info!("Bonding failed: [{}] reason: {:?}", address, fail_reason);
+        }
 
         // Clear bonding attempt if bonding fails or succeeds
         match BtBondState::from(state) {
@@ -213,6 +246,37 @@ impl IBluetoothCallback for BtCallback {
             });
         }
     }
+
+    fn on_bond_conflict(&self, remote_device: BluetoothDevice, fail_reason: BtBondFailReason) {
+        print_info!(
+            "Bond conflict with [{}]: {:?}. The device may have forgotten the bond, \
+            run 'bond repair {}' to pair again.",
+            remote_device.address,
+            fail_reason,
+            remote_device.address
+        );
+    }
+
+    fn on_keypress_notification(
+        &self,
+        remote_device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    ) {
+        print_info!("Passkey entry on [{}]: {:?}", remote_device.address, notification_type);
+    }
+
+    fn on_local_address_override_completed(
+        &self,
+        requested: String,
+        readback: String,
+        verified: bool,
+    ) {
+        if verified {
+            print_info!("Local address is now {}, restart the adapter to use it", readback);
+        } else {
+            print_info!("Failed to set local address to {}, read back {}", requested, readback);
+        }
+    }
 }
 
 impl RPCProxy for BtCallback {
@@ -260,8 +324,13 @@ impl BtConnectionCallback {
 }
 
 impl IBluetoothConnectionCallback for BtConnectionCallback {
-    fn on_device_connected(&self, remote_device: BluetoothDevice) {
-        print_info!("Connected: [{}]: {}", remote_device.address, remote_device.name);
+    fn on_device_connected(&self, remote_device: BluetoothDevice, origin: ConnectionOrigin) {
+        print_info!(
+            "Connected: [{}]: {} ({:?})",
+            remote_device.address,
+            remote_device.name,
+            origin
+        );
     }
 
     fn on_device_disconnected(&self, remote_device: BluetoothDevice) {
@@ -436,6 +505,20 @@ impl IBluetoothGattCallback for BtGattCallback {
         );
     }
 
+    fn on_connection_priority_updated(
+        &self,
+        addr: String,
+        priority: GattConnectionPriority,
+        status: i32,
+    ) {
+        print_info!(
+            "Connection priority updated: addr = {}, priority = {:?}, status = {}",
+            addr,
+            priority,
+            status
+        );
+    }
+
     fn on_service_changed(&self, addr: String) {
         print_info!("Service changed for {}", addr,);
     }
@@ -6,8 +6,9 @@ use crate::callbacks::BtGattCallback;
 use crate::ClientContext;
 use crate::{console_red, console_yellow, print_error, print_info};
 use bt_topshim::btif::BtTransport;
+use btstack::battery_manager::IBluetoothBattery;
 use btstack::bluetooth::{BluetoothDevice, IBluetooth};
-use btstack::bluetooth_gatt::IBluetoothGatt;
+use btstack::bluetooth_gatt::{GattConnectionPriority, IBluetoothGatt};
 use btstack::uuid::{Profile, UuidHelper};
 use manager_service::iface_bluetooth_manager::IBluetoothManager;
 
@@ -86,6 +87,9 @@ fn build_commands() -> HashMap<String, CommandOption> {
         CommandOption {
             description: String::from(
                 "Enable/Disable/Show default bluetooth adapter. (e.g. adapter enable)\n
+                 Show hardware details of the default adapter (e.g. adapter info)\n
+                 Show the bytes exchanged with each device (e.g. adapter traffic)\n
+                 Collect and show the controller debug dump (e.g. adapter debug-dump start)\n
                  Discoverable On/Off (e.g. adapter discoverable on)",
             ),
             function_pointer: CommandHandler::cmd_adapter,
@@ -245,14 +249,49 @@ impl CommandHandler {
         }
 
         let default_adapter = self.context.lock().unwrap().default_adapter;
-        enforce_arg_len(args, 1, "adapter <enable|disable|show|discoverable>", || {
-            match &args[0][0..] {
+        enforce_arg_len(
+            args,
+            1,
+            "adapter <enable|disable|show|traffic|discoverable|debug-dump>",
+            || match &args[0][0..] {
                 "enable" => {
                     self.context.lock().unwrap().manager_dbus.start(default_adapter);
                 }
                 "disable" => {
                     self.context.lock().unwrap().manager_dbus.stop(default_adapter);
                 }
+                "info" => {
+                    let info =
+                        self.context.lock().unwrap().manager_dbus.get_adapter_info(default_adapter);
+                    let info = match info {
+                        Ok(info) => info,
+                        Err(e) => {
+                            print_error!("Unable to get adapter info: {}", e);
+                            return;
+                        }
+                    };
+                    print_info!("Bus: {}", info.bus);
+                    print_info!("Driver: {}", info.driver);
+                    print_info!(
+                        "Vendor/Product/Revision: {:04x}/{:04x}/{:04x}",
+                        info.vendor_id,
+                        info.product_id,
+                        info.revision
+                    );
+                    print_info!("Chipset: {}", info.chipset);
+                    print_info!(
+                        "HCI version/revision: {}/{:04x}",
+                        info.hci_version,
+                        info.hci_revision
+                    );
+                    print_info!(
+                        "LMP version/subversion: {}/{:04x}",
+                        info.lmp_version,
+                        info.lmp_subversion
+                    );
+                    print_info!("Manufacturer: {:04x}", info.manufacturer);
+                    print_info!("Firmware: {}", info.firmware);
+                }
                 "show" => {
                     if !self.context.lock().unwrap().adapter_ready {
                         self.adapter_not_ready();
@@ -273,6 +312,17 @@ impl CommandHandler {
                     let cod = adapter_dbus.get_bluetooth_class();
                     let multi_adv_supported = adapter_dbus.is_multi_advertisement_supported();
                     let le_ext_adv_supported = adapter_dbus.is_le_extended_advertising_supported();
+                    let offload_codecs: Vec<String> = adapter_dbus
+                        .get_offload_codecs()
+                        .iter()
+                        .map(|c| match c.codec.is_vendor() {
+                            true => format!(
+                                "{:04x}:{:04x}",
+                                c.codec.company_id, c.codec.vendor_codec_id
+                            ),
+                            false => format!("{:#04x}", c.codec.coding_format),
+                        })
+                        .collect();
                     let uuid_helper = UuidHelper::new();
                     let enabled_profiles = uuid_helper.get_enabled_profiles();
                     let connected_profiles: Vec<Profile> = enabled_profiles
@@ -288,6 +338,7 @@ impl CommandHandler {
                     print_info!("Class: {:#06x}", cod);
                     print_info!("IsMultiAdvertisementSupported: {}", multi_adv_supported);
                     print_info!("IsLeExtendedAdvertisingSupported: {}", le_ext_adv_supported);
+                    print_info!("Offload codecs: {}", DisplayList(offload_codecs));
                     print_info!("Connected profiles: {:?}", connected_profiles);
                     print_info!(
                         "Uuids: {}",
@@ -299,6 +350,41 @@ impl CommandHandler {
                         )
                     );
                 }
+                "traffic" => {
+                    if !self.context.lock().unwrap().adapter_ready {
+                        self.adapter_not_ready();
+                        return;
+                    }
+
+                    let context = self.context.lock().unwrap();
+                    let adapter_dbus = context.adapter_dbus.as_ref().unwrap();
+                    print_info!("Bytes by device and profile:");
+                    for t in adapter_dbus.get_device_traffic() {
+                        print_info!(
+                            "  {} {:?}: received {}, sent {}",
+                            t.address,
+                            t.profile,
+                            t.rx_bytes,
+                            t.tx_bytes
+                        );
+                    }
+
+                    print_info!("Snapshots:");
+                    for snapshot in adapter_dbus.get_traffic_snapshots() {
+                        let rx: u64 = snapshot.traffic.iter().map(|t| t.rx_bytes).sum();
+                        let tx: u64 = snapshot.traffic.iter().map(|t| t.tx_bytes).sum();
+                        print_info!(
+                            "  {}ms: {} devices, received {}, sent {}",
+                            snapshot.timestamp_ms,
+                            snapshot.traffic.len(),
+                            rx,
+                            tx
+                        );
+                    }
+
+                    let overflows = adapter_dbus.get_buffer_overflows();
+                    print_info!("Buffer overflows: ACL {}, SCO {}", overflows.acl, overflows.sco);
+                }
                 "discoverable" => match &args[1][0..] {
                     "on" => {
                         let discoverable = self
@@ -330,11 +416,33 @@ impl CommandHandler {
                     }
                     _ => println!("Invalid argument for adapter discoverable '{}'", args[1]),
                 },
+                "debug-dump" => {
+                    if !self.context.lock().unwrap().adapter_ready {
+                        self.adapter_not_ready();
+                        return;
+                    }
+
+                    let mut context = self.context.lock().unwrap();
+                    let adapter_dbus = context.adapter_dbus.as_mut().unwrap();
+                    match args.get(1).map(|s| &s[0..]) {
+                        Some("start") => {
+                            let started = adapter_dbus.start_controller_debug_dump();
+                            print_info!(
+                                "Start controller debug dump: {}",
+                                if started { "succeeded" } else { "failed" }
+                            );
+                        }
+                        Some("show") => {
+                            print_info!("{}", adapter_dbus.get_controller_debug_dump());
+                        }
+                        _ => println!("Usage: adapter debug-dump <start|show>"),
+                    }
+                }
                 _ => {
                     println!("Invalid argument '{}'", args[0]);
                 }
-            }
-        });
+            },
+        );
     }
 
     fn cmd_get_address(&mut self, _args: &Vec<String>) {
@@ -372,61 +480,82 @@ impl CommandHandler {
             return;
         }
 
-        enforce_arg_len(args, 2, "bond <add|remove|cancel> <address>", || match &args[0][0..] {
-            "add" => {
-                let device = BluetoothDevice {
-                    address: String::from(&args[1]),
-                    name: String::from("Classic Device"),
-                };
+        enforce_arg_len(args, 2, "bond <add|remove|cancel|repair> <address>", || {
+            match &args[0][0..] {
+                "add" => {
+                    let device = BluetoothDevice {
+                        address: String::from(&args[1]),
+                        name: String::from("Classic Device"),
+                    };
 
-                let bonding_attempt =
-                    &self.context.lock().unwrap().bonding_attempt.as_ref().cloned();
+                    let bonding_attempt =
+                        &self.context.lock().unwrap().bonding_attempt.as_ref().cloned();
 
-                if bonding_attempt.is_some() {
-                    print_info!(
-                        "Already bonding [{}]. Cancel bonding first.",
-                        bonding_attempt.as_ref().unwrap().address,
-                    );
-                    return;
+                    if bonding_attempt.is_some() {
+                        print_info!(
+                            "Already bonding [{}]. Cancel bonding first.",
+                            bonding_attempt.as_ref().unwrap().address,
+                        );
+                        return;
+                    }
+
+                    let success = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_ref()
+                        .unwrap()
+                        .create_bond(device.clone(), BtTransport::Auto);
+
+                    if success {
+                        self.context.lock().unwrap().bonding_attempt = Some(device);
+                    }
                 }
+                "remove" => {
+                    let device = BluetoothDevice {
+                        address: String::from(&args[1]),
+                        name: String::from("Classic Device"),
+                    };
 
-                let success = self
-                    .context
-                    .lock()
-                    .unwrap()
-                    .adapter_dbus
-                    .as_ref()
-                    .unwrap()
-                    .create_bond(device.clone(), BtTransport::Auto);
+                    self.context.lock().unwrap().adapter_dbus.as_ref().unwrap().remove_bond(device);
+                }
+                "cancel" => {
+                    let device = BluetoothDevice {
+                        address: String::from(&args[1]),
+                        name: String::from("Classic Device"),
+                    };
 
-                if success {
-                    self.context.lock().unwrap().bonding_attempt = Some(device);
+                    self.context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_ref()
+                        .unwrap()
+                        .cancel_bond_process(device);
                 }
-            }
-            "remove" => {
-                let device = BluetoothDevice {
-                    address: String::from(&args[1]),
-                    name: String::from("Classic Device"),
-                };
+                "repair" => {
+                    let device = BluetoothDevice {
+                        address: String::from(&args[1]),
+                        name: String::from("Classic Device"),
+                    };
 
-                self.context.lock().unwrap().adapter_dbus.as_ref().unwrap().remove_bond(device);
-            }
-            "cancel" => {
-                let device = BluetoothDevice {
-                    address: String::from(&args[1]),
-                    name: String::from("Classic Device"),
-                };
+                    let success = self
+                        .context
+                        .lock()
+                        .unwrap()
+                        .adapter_dbus
+                        .as_mut()
+                        .unwrap()
+                        .forget_and_repair(device);
 
-                self.context
-                    .lock()
-                    .unwrap()
-                    .adapter_dbus
-                    .as_ref()
-                    .unwrap()
-                    .cancel_bond_process(device);
-            }
-            _ => {
-                println!("Invalid argument '{}'", args[0]);
+                    if !success {
+                        print_info!("Can't repair bond with [{}]", &args[1]);
+                    }
+                }
+                _ => {
+                    println!("Invalid argument '{}'", args[0]);
+                }
             }
         });
     }
@@ -487,7 +616,7 @@ impl CommandHandler {
                         name: String::from("Classic Device"),
                     };
 
-                    let (name, alias, device_type, class, bonded, connected, uuids) = {
+                    let (name, alias, device_type, class, bonded, connected, uuids, battery) = {
                         let ctx = self.context.lock().unwrap();
                         let adapter = ctx.adapter_dbus.as_ref().unwrap();
 
@@ -498,8 +627,13 @@ impl CommandHandler {
                         let bonded = adapter.get_bond_state(device.clone());
                         let connected = adapter.get_connection_state(device.clone());
                         let uuids = adapter.get_remote_uuids(device.clone());
+                        let battery = ctx
+                            .battery_manager_dbus
+                            .as_ref()
+                            .unwrap()
+                            .get_battery_information(device.address.clone());
 
-                        (name, alias, device_type, class, bonded, connected, uuids)
+                        (name, alias, device_type, class, bonded, connected, uuids, battery)
                     };
 
                     print_info!("Address: {}", &device.address);
@@ -518,6 +652,21 @@ impl CommandHandler {
                                 .collect::<Vec<String>>()
                         )
                     );
+                    if battery.batteries.is_empty() {
+                        print_info!("Battery: unknown");
+                    } else {
+                        print_info!(
+                            "Battery: {}% ({})",
+                            battery.level,
+                            DisplayList(
+                                battery
+                                    .batteries
+                                    .iter()
+                                    .map(|b| format!("{:?}: {}%", b.source, b.level))
+                                    .collect::<Vec<String>>()
+                            )
+                        );
+                    }
                 }
                 "set-alias" => {
                     if args.len() < 3 {
@@ -657,6 +806,68 @@ impl CommandHandler {
                     .unwrap()
                     .discover_services(client_id.unwrap(), addr);
             }
+            "client-request-priority" => {
+                if args.len() < 3 {
+                    println!(
+                        "usage: gatt client-request-priority <addr> <balanced|high|low-power>"
+                    );
+                    return;
+                }
+
+                let client_id = self.context.lock().unwrap().gatt_client_id;
+                if client_id.is_none() {
+                    println!("GATT client is not yet registered.");
+                    return;
+                }
+
+                let priority = match &args[2][0..] {
+                    "balanced" => GattConnectionPriority::Balanced,
+                    "high" => GattConnectionPriority::High,
+                    "low-power" => GattConnectionPriority::LowPower,
+                    _ => {
+                        println!("Invalid priority '{}'", args[2]);
+                        return;
+                    }
+                };
+
+                let addr = String::from(&args[1]);
+                let requested = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .gatt_dbus
+                    .as_mut()
+                    .unwrap()
+                    .request_connection_priority(client_id.unwrap(), addr, priority);
+                if !requested {
+                    println!("Not connected to {}", args[1]);
+                }
+            }
+            "metrics" => {
+                let metrics =
+                    self.context.lock().unwrap().gatt_dbus.as_ref().unwrap().get_gatt_metrics();
+                print_info!("GATT usage by service/characteristic:");
+                for m in metrics {
+                    print_info!(
+                        "  {} / {}: reads {} ({} failed), writes {} ({} failed), notifications {}",
+                        UuidHelper::to_string(&m.service_uuid),
+                        UuidHelper::to_string(&m.characteristic_uuid),
+                        m.reads,
+                        m.read_errors,
+                        m.writes,
+                        m.write_errors,
+                        m.notifications
+                    );
+                }
+
+                let orphans =
+                    self.context.lock().unwrap().gatt_dbus.as_ref().unwrap().get_orphan_stats();
+                print_info!(
+                    "Reclaimed from clients that went away: {} GATT clients, {} connections",
+                    orphans.clients,
+                    orphans.connections
+                );
+            }
             _ => {
                 println!("Invalid argument '{}'", args[0]);
             }
@@ -1,20 +1,39 @@
 //! D-Bus proxy implementations of the APIs.
 
-use bt_topshim::btif::{BtDeviceType, BtSspVariant, BtTransport, Uuid128Bit};
+use bt_topshim::btif::{BtBondFailReason, BtDeviceType, BtSspVariant, BtTransport, Uuid128Bit};
+use bt_topshim::controller::{
+    CodecDirection, CodecId, CodecTransport, KeypressNotificationType, LeAddressType,
+    LeTestPayload, LeTestPhy, LocalCodecCapabilities,
+};
 use bt_topshim::profiles::gatt::GattStatus;
+use bt_topshim::profiles::stats::ProfileInfo;
 
 use btstack::bluetooth::{
     BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
 };
 use btstack::bluetooth_gatt::{
     BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService,
-    GattWriteRequestStatus, GattWriteType, IBluetoothGatt, IBluetoothGattCallback,
-    IScannerCallback, LePhy, ScanFilter, ScanSettings,
+    GattConnectionPriority, GattOrphanStats, GattWriteRequestStatus, GattWriteType, IBluetoothGatt,
+    IBluetoothGattCallback, IPeriodicSyncCallback, IScannerCallback, LePhy, ScanFilter,
+    ScanSettings,
 };
+use btstack::gatt_metrics::GattAttributeMetrics;
+use btstack::periodic_sync::{PeriodicSync, SyncTransferMode};
 
 use btstack::suspend::{ISuspend, ISuspendCallback, SuspendType};
 
-use btstack::uuid::Profile;
+use btstack::background_connections::{
+    BackgroundConnectionKind, BackgroundDevice, ConnectionOrigin,
+};
+use btstack::battery_manager::{
+    Battery, BatterySet, BatterySource, IBluetoothBattery, IBluetoothBatteryCallback,
+};
+use btstack::device_ranking::{RankedDevice, RankingUseCase, RankingWeights};
+use btstack::feature_support::{CapabilityStatus, ControllerFeature, OffloadCodec};
+use btstack::power_profile::{PowerModule, PowerProfile};
+use btstack::radio_test::{RadioTestKind, RadioTestParams, RadioTestResult, RadioTestStatus};
+use btstack::traffic::{BufferOverflows, DeviceTraffic, TrafficProfile, TrafficSnapshot};
+use btstack::uuid::{Profile, ProfileState};
 use dbus::arg::{AppendAll, RefArg};
 use dbus::nonblock::SyncConnection;
 
@@ -25,7 +44,7 @@ use dbus_macros::{
 };
 
 use manager_service::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterInfo, AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
 };
 
 use num_traits::{FromPrimitive, ToPrimitive};
@@ -39,15 +58,34 @@ fn make_object_path(idx: i32, name: &str) -> dbus::Path {
     dbus::Path::new(format!("/org/chromium/bluetooth/hci{}/{}", idx, name)).unwrap()
 }
 
+impl_dbus_arg_enum!(BackgroundConnectionKind);
+impl_dbus_arg_enum!(BatterySource);
+impl_dbus_arg_enum!(BtBondFailReason);
 impl_dbus_arg_enum!(BtDeviceType);
 impl_dbus_arg_enum!(BtSspVariant);
 impl_dbus_arg_enum!(BtTransport);
+impl_dbus_arg_enum!(CodecDirection);
+impl_dbus_arg_enum!(CodecTransport);
+impl_dbus_arg_enum!(ConnectionOrigin);
+impl_dbus_arg_enum!(ControllerFeature);
+impl_dbus_arg_enum!(GattConnectionPriority);
 impl_dbus_arg_enum!(GattStatus);
 impl_dbus_arg_enum!(GattWriteRequestStatus);
 impl_dbus_arg_enum!(GattWriteType);
+impl_dbus_arg_enum!(KeypressNotificationType);
+impl_dbus_arg_enum!(LeAddressType);
 impl_dbus_arg_enum!(LePhy);
+impl_dbus_arg_enum!(LeTestPayload);
+impl_dbus_arg_enum!(LeTestPhy);
+impl_dbus_arg_enum!(PowerModule);
+impl_dbus_arg_enum!(PowerProfile);
 impl_dbus_arg_enum!(Profile);
+impl_dbus_arg_enum!(RadioTestKind);
+impl_dbus_arg_enum!(RadioTestStatus);
+impl_dbus_arg_enum!(RankingUseCase);
 impl_dbus_arg_enum!(SuspendType);
+impl_dbus_arg_enum!(SyncTransferMode);
+impl_dbus_arg_enum!(TrafficProfile);
 
 // Represents Uuid128Bit as an array in D-Bus.
 impl DBusArg for Uuid128Bit {
@@ -94,12 +132,165 @@ pub struct BluetoothGattServiceDBus {
     pub included_services: Vec<BluetoothGattService>,
 }
 
+#[dbus_propmap(GattAttributeMetrics)]
+pub struct GattAttributeMetricsDBus {
+    service_uuid: Uuid128Bit,
+    characteristic_uuid: Uuid128Bit,
+    reads: u32,
+    read_errors: u32,
+    writes: u32,
+    write_errors: u32,
+    notifications: u32,
+}
+
+#[dbus_propmap(GattOrphanStats)]
+pub struct GattOrphanStatsDBus {
+    clients: u32,
+    connections: u32,
+}
+
+#[dbus_propmap(PeriodicSync)]
+pub struct PeriodicSyncDBus {
+    sync_handle: u16,
+    address: String,
+    sid: u8,
+    phy: u8,
+    interval: u16,
+}
+
+#[dbus_propmap(DeviceTraffic)]
+pub struct DeviceTrafficDBus {
+    address: String,
+    profile: TrafficProfile,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+#[dbus_propmap(TrafficSnapshot)]
+pub struct TrafficSnapshotDBus {
+    timestamp_ms: u64,
+    traffic: Vec<DeviceTraffic>,
+}
+
+#[dbus_propmap(BufferOverflows)]
+pub struct BufferOverflowsDBus {
+    acl: u32,
+    sco: u32,
+}
+
 #[dbus_propmap(BluetoothDevice)]
 pub struct BluetoothDeviceDBus {
     address: String,
     name: String,
 }
 
+#[dbus_propmap(RankedDevice)]
+pub struct RankedDeviceDBus {
+    device: BluetoothDevice,
+    score: i32,
+    explanation: String,
+}
+
+#[dbus_propmap(RankingWeights)]
+pub struct RankingWeightsDBus {
+    bonded: i32,
+    connected: i32,
+    profile_match: i32,
+    rssi: i32,
+    recency: i32,
+}
+
+#[dbus_propmap(BackgroundDevice)]
+pub struct BackgroundDeviceDBus {
+    address: String,
+    kind: BackgroundConnectionKind,
+    address_type: LeAddressType,
+    in_accept_list: bool,
+}
+
+#[dbus_propmap(CodecId)]
+pub struct CodecIdDBus {
+    coding_format: u8,
+    company_id: u16,
+    vendor_codec_id: u16,
+}
+
+#[dbus_propmap(LocalCodecCapabilities)]
+pub struct LocalCodecCapabilitiesDBus {
+    codec: CodecId,
+    transport: CodecTransport,
+    direction: CodecDirection,
+    sample_rates: Vec<u32>,
+    channel_counts: Vec<u8>,
+    raw: Vec<Vec<u8>>,
+}
+
+#[dbus_propmap(OffloadCodec)]
+pub struct OffloadCodecDBus {
+    codec: CodecId,
+    transports: Vec<CodecTransport>,
+    capabilities: Vec<LocalCodecCapabilities>,
+}
+
+#[dbus_propmap(CapabilityStatus)]
+pub struct CapabilityStatusDBus {
+    name: String,
+    available: bool,
+    missing_features: Vec<ControllerFeature>,
+    reason: String,
+}
+
+#[dbus_propmap(ProfileInfo)]
+pub struct ProfileInfoDBus {
+    name: String,
+    version: u32,
+    enabled: bool,
+    last_error: String,
+}
+
+#[dbus_propmap(ProfileState)]
+pub struct ProfileStateDBus {
+    profile: Profile,
+    supported: bool,
+    enabled: bool,
+    initialized: bool,
+    connected: bool,
+    reason: String,
+}
+
+#[dbus_propmap(RadioTestParams)]
+pub struct RadioTestParamsDBus {
+    kind: RadioTestKind,
+    channel: u8,
+    length: u8,
+    payload: LeTestPayload,
+    phy: LeTestPhy,
+    timeout_ms: u32,
+}
+
+#[dbus_propmap(RadioTestResult)]
+pub struct RadioTestResultDBus {
+    kind: RadioTestKind,
+    channel: u8,
+    status: u8,
+    num_packets: u16,
+    duration_ms: u64,
+    timed_out: bool,
+}
+
+#[dbus_propmap(Battery)]
+pub struct BatteryDBus {
+    source: BatterySource,
+    level: u32,
+}
+
+#[dbus_propmap(BatterySet)]
+pub struct BatterySetDBus {
+    address: String,
+    level: u32,
+    batteries: Vec<Battery>,
+}
+
 struct ClientDBusProxy {
     conn: Arc<SyncConnection>,
     bus_name: String,
@@ -200,7 +391,34 @@ impl IBluetoothCallback for IBluetoothCallbackDBus {
     }
 
     #[dbus_method("OnBondStateChanged")]
-    fn on_bond_state_changed(&self, status: u32, address: String, state: u32) {}
+    fn on_bond_state_changed(
+        &self,
+        status: u32,
+        address: String,
+        state: u32,
+        fail_reason: BtBondFailReason,
+    ) {
+    }
+
+    #[dbus_method("OnBondConflict")]
+    fn on_bond_conflict(&self, remote_device: BluetoothDevice, fail_reason: BtBondFailReason) {}
+
+    #[dbus_method("OnKeypressNotification")]
+    fn on_keypress_notification(
+        &self,
+        remote_device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    ) {
+    }
+
+    #[dbus_method("OnLocalAddressOverrideCompleted")]
+    fn on_local_address_override_completed(
+        &self,
+        requested: String,
+        readback: String,
+        verified: bool,
+    ) {
+    }
 }
 
 #[allow(dead_code)]
@@ -226,7 +444,7 @@ impl btstack::RPCProxy for IBluetoothConnectionCallbackDBus {
 )]
 impl IBluetoothConnectionCallback for IBluetoothConnectionCallbackDBus {
     #[dbus_method("OnDeviceConnected")]
-    fn on_device_connected(&self, remote_device: BluetoothDevice) {}
+    fn on_device_connected(&self, remote_device: BluetoothDevice, origin: ConnectionOrigin) {}
 
     #[dbus_method("OnDeviceDisconnected")]
     fn on_device_disconnected(&self, remote_device: BluetoothDevice) {}
@@ -453,6 +671,187 @@ impl IBluetooth for BluetoothDBus {
     fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
         dbus_generated!()
     }
+
+    #[dbus_method("GetRankedDevices")]
+    fn get_ranked_devices(&self, use_case: RankingUseCase) -> Vec<RankedDevice> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetDeviceRankingWeights")]
+    fn set_device_ranking_weights(&mut self, weights: RankingWeights) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("AddBackgroundConnection")]
+    fn add_background_connection(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RemoveBackgroundConnection")]
+    fn remove_background_connection(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetBackgroundConnections")]
+    fn get_background_connections(&self) -> Vec<BackgroundDevice> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetServiceRefreshInterval")]
+    fn set_service_refresh_interval(&mut self, interval_sec: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RequestServiceRefresh")]
+    fn request_service_refresh(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetPowerProfile")]
+    fn set_power_profile(&mut self, profile: PowerProfile) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPowerProfile")]
+    fn get_power_profile(&self) -> PowerProfile {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetPowerProfileOverride")]
+    fn set_power_profile_override(&mut self, module: PowerModule, profile: PowerProfile) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ClearPowerProfileOverride")]
+    fn clear_power_profile_override(&mut self, module: PowerModule) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetEffectivePowerProfile")]
+    fn get_effective_power_profile(&self, module: PowerModule) -> PowerProfile {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetCapabilities")]
+    fn get_capabilities(&self) -> Vec<CapabilityStatus> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetOffloadCodecs")]
+    fn get_offload_codecs(&self) -> Vec<OffloadCodec> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetRadioTestMode")]
+    fn set_radio_test_mode(&mut self, enabled: bool) -> RadioTestStatus {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StartRadioTest")]
+    fn start_radio_test(&mut self, params: RadioTestParams) -> RadioTestStatus {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StopRadioTest")]
+    fn stop_radio_test(&mut self) -> RadioTestStatus {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetRadioTestResults")]
+    fn get_radio_test_results(&self) -> Vec<RadioTestResult> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ForgetAndRepair")]
+    fn forget_and_repair(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SendKeypressNotification")]
+    fn send_keypress_notification(
+        &self,
+        device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetRpaTimeout")]
+    fn set_rpa_timeout(&mut self, timeout_s: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetRpaTimeout")]
+    fn get_rpa_timeout(&self) -> u16 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OverrideLocalAddress")]
+    fn override_local_address(&mut self, address: String) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StartControllerDebugDump")]
+    fn start_controller_debug_dump(&mut self) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetControllerDebugDump")]
+    fn get_controller_debug_dump(&mut self) -> String {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetTracingEnabled")]
+    fn set_tracing_enabled(&mut self, enabled: bool) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("WriteTrace")]
+    fn write_trace(&self) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetProfileInfo")]
+    fn get_profile_info(&self) -> Vec<ProfileInfo> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetProfileStates")]
+    fn get_profile_states(&self) -> Vec<ProfileState> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetDeviceTraffic")]
+    fn get_device_traffic(&self) -> Vec<DeviceTraffic> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetTrafficSnapshots")]
+    fn get_traffic_snapshots(&self) -> Vec<TrafficSnapshot> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetBufferOverflows")]
+    fn get_buffer_overflows(&self) -> BufferOverflows {
+        dbus_generated!()
+    }
+}
+
+#[dbus_propmap(AdapterInfo)]
+pub struct AdapterInfoDbus {
+    hci_interface: i32,
+    bus: String,
+    driver: String,
+    vendor_id: u16,
+    product_id: u16,
+    revision: u16,
+    chipset: String,
+    hci_version: u8,
+    hci_revision: u16,
+    lmp_version: u8,
+    lmp_subversion: u16,
+    manufacturer: u16,
+    firmware: String,
 }
 
 #[dbus_propmap(AdapterWithEnabled)]
@@ -519,6 +918,17 @@ impl IBluetoothManager for BluetoothManagerDBus {
     fn get_available_adapters(&mut self) -> Vec<AdapterWithEnabled> {
         dbus_generated!()
     }
+
+    // Not generated, the manager replies with a D-Bus error if there is no such adapter.
+    fn get_adapter_info(&mut self, hci_interface: i32) -> Result<AdapterInfo, String> {
+        let result: Result<(dbus::arg::PropMap,), _> =
+            self.client_proxy.method_withresult("GetAdapterInfo", (hci_interface,));
+        match result {
+            Ok((info,)) => <AdapterInfo as DBusArg>::from_dbus(info, None, None, None)
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.message().unwrap_or_default().to_string()),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -548,6 +958,15 @@ impl IBluetoothManagerCallback for IBluetoothManagerCallbackDBus {
 
     #[dbus_method("OnHciEnabledChanged")]
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool) {}
+
+    #[dbus_method("OnAdapterFailover")]
+    fn on_adapter_failover(&self, from_hci_interface: i32, to_hci_interface: i32) {}
+
+    #[dbus_method("OnDefaultAdapterChanged")]
+    fn on_default_adapter_changed(&self, hci_interface: i32) {}
+
+    #[dbus_method("OnAdapterFirmwareChanged")]
+    fn on_adapter_firmware_changed(&self, hci_interface: i32) {}
 }
 
 pub(crate) struct BluetoothGattDBus {
@@ -737,6 +1156,91 @@ impl IBluetoothGatt for BluetoothGattDBus {
     ) {
         dbus_generated!()
     }
+
+    #[dbus_method("RequestConnectionPriority")]
+    fn request_connection_priority(
+        &mut self,
+        client_id: i32,
+        addr: String,
+        priority: GattConnectionPriority,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("PurgeOrphans")]
+    fn purge_orphans(&mut self) -> i32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetOrphanStats")]
+    fn get_orphan_stats(&self) -> GattOrphanStats {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetGattMetrics")]
+    fn get_gatt_metrics(&self) -> Vec<GattAttributeMetrics> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ResetGattMetrics")]
+    fn reset_gatt_metrics(&mut self) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RegisterPeriodicSyncCallback")]
+    fn register_periodic_sync_callback(
+        &mut self,
+        callback: Box<dyn IPeriodicSyncCallback + Send>,
+    ) -> u32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("UnregisterPeriodicSyncCallback")]
+    fn unregister_periodic_sync_callback(&mut self, callback_id: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SyncToPeriodicAdvertising")]
+    fn sync_to_periodic_advertising(
+        &mut self,
+        addr: String,
+        sid: u8,
+        skip: u16,
+        timeout: u16,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("CancelSync")]
+    fn cancel_sync(&mut self, addr: String, sid: u8) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StopSync")]
+    fn stop_sync(&mut self, sync_handle: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("TransferSync")]
+    fn transfer_sync(&mut self, addr: String, service_data: u16, sync_handle: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetSyncTransferParameters")]
+    fn set_sync_transfer_parameters(
+        &mut self,
+        addr: String,
+        mode: SyncTransferMode,
+        skip: u16,
+        timeout: u16,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPeriodicSyncs")]
+    fn get_periodic_syncs(&self) -> Vec<PeriodicSync> {
+        dbus_generated!()
+    }
 }
 
 #[allow(dead_code)]
@@ -818,10 +1322,62 @@ impl IBluetoothGattCallback for IBluetoothGattCallbackDBus {
     ) {
     }
 
+    #[dbus_method("OnConnectionPriorityUpdated")]
+    fn on_connection_priority_updated(
+        &self,
+        addr: String,
+        priority: GattConnectionPriority,
+        status: i32,
+    ) {
+    }
+
     #[dbus_method("OnServiceChanged")]
     fn on_service_changed(&self, addr: String) {}
 }
 
+#[allow(dead_code)]
+struct IPeriodicSyncCallbackDBus {}
+
+impl btstack::RPCProxy for IPeriodicSyncCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
+        0
+    }
+    fn get_object_id(&self) -> String {
+        String::from("")
+    }
+    fn unregister(&mut self, _id: u32) -> bool {
+        false
+    }
+    fn export_for_rpc(self: Box<Self>) {}
+}
+
+#[generate_dbus_exporter(
+    export_periodic_sync_callback_dbus_obj,
+    "org.chromium.bluetooth.PeriodicSyncCallback"
+)]
+impl IPeriodicSyncCallback for IPeriodicSyncCallbackDBus {
+    #[dbus_method("OnSyncStarted")]
+    fn on_sync_started(&self, status: u8, sync: PeriodicSync) {}
+
+    #[dbus_method("OnSyncReport")]
+    fn on_sync_report(
+        &self,
+        sync_handle: u16,
+        tx_power: i32,
+        rssi: i32,
+        data_status: u8,
+        data: Vec<u8>,
+    ) {
+    }
+
+    #[dbus_method("OnSyncLost")]
+    fn on_sync_lost(&self, sync_handle: u16) {}
+
+    #[dbus_method("OnSyncTransferred")]
+    fn on_sync_transferred(&self, status: u8, addr: String) {}
+}
+
 pub(crate) struct SuspendDBus {
     client_proxy: ClientDBusProxy,
 }
@@ -891,3 +1447,79 @@ impl ISuspendCallback for ISuspendCallbackDBus {
     #[dbus_method("OnResumed")]
     fn on_resumed(&self, suspend_id: u32) {}
 }
+
+pub(crate) struct BatteryManagerDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BatteryManagerDBus {
+    pub(crate) fn new(conn: Arc<SyncConnection>, index: i32) -> BatteryManagerDBus {
+        BatteryManagerDBus {
+            client_proxy: ClientDBusProxy {
+                conn: conn.clone(),
+                bus_name: String::from("org.chromium.bluetooth"),
+                objpath: make_object_path(index, "battery_manager"),
+                interface: String::from("org.chromium.bluetooth.BatteryManager"),
+            },
+        }
+    }
+}
+
+#[generate_dbus_interface_client]
+impl IBluetoothBattery for BatteryManagerDBus {
+    #[dbus_method("RegisterBatteryCallback")]
+    fn register_battery_callback(
+        &mut self,
+        _callback: Box<dyn IBluetoothBatteryCallback + Send>,
+    ) -> u32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("UnregisterBatteryCallback")]
+    fn unregister_battery_callback(&mut self, _callback_id: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetBatteryInformation")]
+    fn get_battery_information(&self, _address: String) -> BatterySet {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetBatteryLevel")]
+    fn set_battery_level(&mut self, _address: String, _source: BatterySource, _level: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RemoveBatteryLevel")]
+    fn remove_battery_level(&mut self, _address: String, _source: BatterySource) -> bool {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothBatteryCallbackDBus {}
+
+impl btstack::RPCProxy for IBluetoothBatteryCallbackDBus {
+    // Placeholder implementations just to satisfy impl RPCProxy requirements.
+    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
+        0
+    }
+    fn get_object_id(&self) -> String {
+        String::from("")
+    }
+    fn unregister(&mut self, _id: u32) -> bool {
+        false
+    }
+    fn export_for_rpc(self: Box<Self>) {}
+}
+
+#[generate_dbus_exporter(
+    export_battery_manager_callback_dbus_obj,
+    "org.chromium.bluetooth.BatteryManagerCallback"
+)]
+impl IBluetoothBatteryCallback for IBluetoothBatteryCallbackDBus {
+    #[dbus_method("OnBatteryInfoUpdated")]
+    fn on_battery_info_updated(&self, battery_set: BatterySet) {}
+    #[dbus_method("OnBatteryInfoRemoved")]
+    fn on_battery_info_removed(&self, address: String) {}
+}
@@ -9,7 +9,9 @@ use tokio::sync::mpsc;
 
 use crate::callbacks::{BtCallback, BtConnectionCallback, BtManagerCallback, SuspendCallback};
 use crate::command_handler::CommandHandler;
-use crate::dbus_iface::{BluetoothDBus, BluetoothGattDBus, BluetoothManagerDBus, SuspendDBus};
+use crate::dbus_iface::{
+    BatteryManagerDBus, BluetoothDBus, BluetoothGattDBus, BluetoothManagerDBus, SuspendDBus,
+};
 use crate::editor::AsyncEditor;
 use bt_topshim::topstack;
 use btstack::bluetooth::{BluetoothDevice, IBluetooth};
@@ -68,6 +70,9 @@ pub(crate) struct ClientContext {
     /// Proxy for suspend interface.
     pub(crate) suspend_dbus: Option<SuspendDBus>,
 
+    /// Proxy for battery manager interface.
+    pub(crate) battery_manager_dbus: Option<BatteryManagerDBus>,
+
     /// Channel to send actions to take in the foreground
     fg: mpsc::Sender<ForegroundActions>,
 
@@ -102,6 +107,7 @@ impl ClientContext {
             adapter_dbus: None,
             gatt_dbus: None,
             suspend_dbus: None,
+            battery_manager_dbus: None,
             fg: tx,
             dbus_connection,
             dbus_crossroads,
@@ -141,6 +147,8 @@ impl ClientContext {
 
         self.suspend_dbus = Some(SuspendDBus::new(conn.clone(), idx));
 
+        self.battery_manager_dbus = Some(BatteryManagerDBus::new(conn.clone(), idx));
+
         // Trigger callback registration in the foreground
         let fg = self.fg.clone();
         tokio::spawn(async move {
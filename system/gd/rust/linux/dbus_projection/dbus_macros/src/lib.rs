@@ -161,7 +161,13 @@ pub fn generate_dbus_exporter(attr: TokenStream, item: TokenStream) -> TokenStre
             let mut ret = quote! {Ok(())};
             if let ReturnType::Type(_, t) = method.sig.output {
                 output_type = quote! {<#t as DBusArg>::DBusType,};
-                ret = quote! {Ok((<#t as DBusArg>::to_dbus(ret).unwrap(),))};
+                ret = quote! {
+                    match <#t as DBusArg>::to_dbus(ret) {
+                        Ok(ret) => Ok((ret,)),
+                        // Failed methods, see the DBusArg impl of Result, reply with an error.
+                        Err(e) => Err(dbus_crossroads::MethodErr::failed(&e.to_string())),
+                    }
+                };
                 output_names = quote! { "out", };
             }
 
@@ -866,6 +872,28 @@ pub fn generate_dbus_arg(_item: TokenStream) -> TokenStream {
                 Ok(list)
             }
         }
+
+        // A method returning an error replies with a D-Bus error carrying its message, and the
+        // value is sent as is otherwise.
+        impl<T: DBusArg, E: Into<Box<dyn Error>>> DBusArg for Result<T, E> {
+            type DBusType = T::DBusType;
+
+            fn from_dbus(
+                data: T::DBusType,
+                conn: Option<Arc<dbus::nonblock::SyncConnection>>,
+                remote: Option<BusName<'static>>,
+                disconnect_watcher: Option<Arc<Mutex<DisconnectWatcher>>>,
+            ) -> Result<Result<T, E>, Box<dyn Error>> {
+                Ok(Ok(T::from_dbus(data, conn, remote, disconnect_watcher)?))
+            }
+
+            fn to_dbus(data: Result<T, E>) -> Result<T::DBusType, Box<dyn Error>> {
+                match data {
+                    Ok(data) => T::to_dbus(data),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
     };
 
     debug_output_to_file(&gen, format!("out-generate_dbus_arg.rs"));
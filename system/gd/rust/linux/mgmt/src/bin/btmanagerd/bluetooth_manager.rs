@@ -1,22 +1,37 @@
 use log::{error, info, warn};
 
 use manager_service::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterInfo, AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
 };
 
+use std::cmp;
 use std::collections::HashMap;
 use std::process::Command;
 use std::sync::atomic::Ordering;
+use tokio::time::{sleep, Duration};
 
 use crate::{config_util, state_machine, ManagerContext};
 
 const BLUEZ_INIT_TARGET: &str = "bluetoothd";
 
+/// Delay before moving back to an adapter that was failed over from, doubled on every attempt.
+const RETRY_ADAPTER_INITIAL_DELAY: Duration = Duration::from_secs(30);
+const RETRY_ADAPTER_MAX_DELAY: Duration = Duration::from_secs(3600);
+
 /// Implementation of IBluetoothManager.
 pub struct BluetoothManager {
     manager_context: ManagerContext,
     callbacks: HashMap<u32, Box<dyn IBluetoothManagerCallback + Send>>,
     cached_devices: HashMap<i32, bool>,
+    /// Adapter left because it kept failing to start, and how many times it was retried since.
+    /// Cleared once it proved stable again or isn't wanted anymore.
+    broken_adapter: Option<(i32, u32)>,
+    /// Adapter the stack runs on and the broken adapter it was moved to on trial. The default
+    /// adapter only changes once the broken adapter proved stable.
+    probe: Option<(i32, i32)>,
+    /// Firmware of each adapter as seen the last time its stack started. Kept when an adapter goes
+    /// away, since reloading the driver is how new firmware usually gets loaded.
+    firmware: HashMap<i32, (String, u16, u16)>,
 }
 
 impl BluetoothManager {
@@ -25,6 +40,9 @@ impl BluetoothManager {
             manager_context,
             callbacks: HashMap::new(),
             cached_devices: HashMap::new(),
+            broken_adapter: None,
+            probe: None,
+            firmware: HashMap::new(),
         }
     }
 
@@ -35,6 +53,7 @@ impl BluetoothManager {
         } else {
             // Remove device and ignore if it's not there
             self.cached_devices.remove(&hci_device);
+            self.forget_broken_adapter(hci_device);
         }
 
         for (_, callback) in &self.callbacks {
@@ -56,6 +75,143 @@ impl BluetoothManager {
         for (_, callback) in &self.callbacks {
             callback.on_hci_enabled_changed(hci_device, enabled);
         }
+
+        if enabled {
+            self.check_firmware(hci_device);
+        }
+    }
+
+    /// Tells clients if the firmware of `hci_device` differs from the last time its stack started.
+    fn check_firmware(&mut self, hci_device: i32) {
+        let info = match config_util::read_adapter_info(hci_device) {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("Unable to read the firmware of hci{}: {}", hci_device, e);
+                return;
+            }
+        };
+
+        let firmware = (info.firmware, info.hci_revision, info.lmp_subversion);
+        match self.firmware.insert(hci_device, firmware.clone()) {
+            Some(previous) if previous != firmware => {
+                info!("hci{} firmware changed from {:?} to {:?}", hci_device, previous, firmware);
+                for (_, callback) in &self.callbacks {
+                    callback.on_adapter_firmware_changed(hci_device);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Switches the stack from `from` to `to` and makes `to` the default adapter.
+    fn move_to_adapter(&mut self, from: i32, to: i32) {
+        self.manager_context.proxy.failover_bluetooth(from, to);
+        self.announce_default_adapter(from, to);
+    }
+
+    /// Makes `to`, which the stack already runs on, the default adapter instead of `from`.
+    fn announce_default_adapter(&mut self, from: i32, to: i32) {
+        if !config_util::modify_hci_n_enabled(to, true) {
+            error!("Config is not successfully modified");
+        }
+
+        for (_, callback) in &self.callbacks {
+            callback.on_adapter_failover(from, to);
+            callback.on_default_adapter_changed(to);
+        }
+    }
+
+    /// Schedules moving from `from` back to the broken adapter `to`, backing off exponentially.
+    fn schedule_retry(&mut self, from: i32, to: i32) {
+        let attempts = match self.broken_adapter {
+            Some((hci, attempts)) if hci == to => attempts + 1,
+            _ => 0,
+        };
+        self.broken_adapter = Some((to, attempts));
+
+        let delay = cmp::min(
+            RETRY_ADAPTER_INITIAL_DELAY * 2u32.pow(cmp::min(attempts, 16)),
+            RETRY_ADAPTER_MAX_DELAY,
+        );
+        let tx = self.manager_context.proxy.get_tx();
+        tokio::spawn(async move {
+            sleep(delay).await;
+            let _ = tx.send(state_machine::Message::RetryAdapter(from, to)).await;
+        });
+    }
+
+    /// Moves the stack off `hci_device`, which keeps failing to start, and schedules moving back
+    /// to it later.
+    pub(crate) fn callback_restart_loop(&mut self, hci_device: i32) {
+        // A broken adapter on trial goes back to the adapter it was probed from, which clients
+        // still see as the default one.
+        if let Some((from, to)) = self.probe {
+            if to == hci_device {
+                info!("hci{} still fails to start, going back to hci{}", to, from);
+                self.probe = None;
+                self.manager_context.proxy.failover_bluetooth(to, from);
+                self.schedule_retry(from, to);
+                return;
+            }
+        }
+
+        let target = match self.cached_devices.keys().filter(|hci| **hci != hci_device).min() {
+            Some(t) => *t,
+            None => {
+                warn!("hci{} keeps failing to start and there is no other adapter", hci_device);
+                return;
+            }
+        };
+
+        info!("hci{} keeps failing to start, failing over to hci{}", hci_device, target);
+        self.move_to_adapter(hci_device, target);
+        self.schedule_retry(target, hci_device);
+    }
+
+    /// Probes the broken adapter `to` by moving the stack from `from` to it on trial, if it is
+    /// still wanted. `from` stays the default adapter until the probe is done.
+    pub(crate) fn callback_retry_adapter(&mut self, from: i32, to: i32) {
+        if self.broken_adapter.map(|(hci, _)| hci) != Some(to) {
+            return;
+        }
+
+        if !self.cached_devices.contains_key(&to)
+            || !state_machine::state_to_enabled(self.manager_context.proxy.get_state())
+        {
+            self.forget_broken_adapter(to);
+            return;
+        }
+
+        info!("Probing hci{}", to);
+        self.probe = Some((from, to));
+        self.manager_context.proxy.probe_bluetooth(from, to);
+    }
+
+    /// Makes the probed adapter `to` the default one if it stayed up, otherwise goes back to
+    /// `from` and retries later.
+    pub(crate) fn callback_probe_done(&mut self, from: i32, to: i32, stable: bool) {
+        if self.probe != Some((from, to)) {
+            return;
+        }
+        self.probe = None;
+
+        if stable {
+            info!("hci{} is stable again, moving back to it", to);
+            self.broken_adapter = None;
+            self.announce_default_adapter(from, to);
+        } else {
+            info!("hci{} isn't stable yet, going back to hci{}", to, from);
+            self.manager_context.proxy.failover_bluetooth(to, from);
+            self.schedule_retry(from, to);
+        }
+    }
+
+    /// Stops retrying `hci_device`, e.g. because it went away or the stack moved elsewhere.
+    pub(crate) fn forget_broken_adapter(&mut self, hci_device: i32) {
+        if self.broken_adapter.map(|(hci, _)| hci) == Some(hci_device) {
+            info!("Not retrying hci{} anymore", hci_device);
+            self.broken_adapter = None;
+        }
     }
 
     pub(crate) fn callback_disconnected(&mut self, id: u32) {
@@ -89,6 +245,9 @@ impl IBluetoothManager for BluetoothManager {
             error!("Config is not successfully modified");
         }
 
+        // Stopping on purpose gives up on retrying a broken adapter.
+        self.broken_adapter = None;
+
         // Ignore the request if adapter is already disabled.
         if !*self.cached_devices.get(&hci_interface).unwrap_or(&false) {
             return;
@@ -156,4 +315,8 @@ impl IBluetoothManager for BluetoothManager {
 
         adapters
     }
+
+    fn get_adapter_info(&mut self, hci_interface: i32) -> Result<AdapterInfo, String> {
+        config_util::read_adapter_info(hci_interface)
+    }
 }
@@ -4,12 +4,29 @@ use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_expor
 use dbus_projection::{dbus_generated, DisconnectWatcher};
 
 use manager_service::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterInfo, AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
 };
 use manager_service::RPCProxy;
 
 use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
 
+#[dbus_propmap(AdapterInfo)]
+pub struct AdapterInfoDbus {
+    hci_interface: i32,
+    bus: String,
+    driver: String,
+    vendor_id: u16,
+    product_id: u16,
+    revision: u16,
+    chipset: String,
+    hci_version: u8,
+    hci_revision: u16,
+    lmp_version: u8,
+    lmp_subversion: u16,
+    manufacturer: u16,
+    firmware: String,
+}
+
 #[dbus_propmap(AdapterWithEnabled)]
 pub struct AdapterWithEnabledDbus {
     hci_interface: i32,
@@ -55,6 +72,11 @@ impl IBluetoothManager for BluetoothManagerDBus {
     fn get_available_adapters(&mut self) -> Vec<AdapterWithEnabled> {
         dbus_generated!()
     }
+
+    #[dbus_method("GetAdapterInfo")]
+    fn get_adapter_info(&mut self, hci_interface: i32) -> Result<AdapterInfo, String> {
+        dbus_generated!()
+    }
 }
 
 /// D-Bus projection of IBluetoothManagerCallback.
@@ -67,4 +89,13 @@ impl IBluetoothManagerCallback for BluetoothManagerCallbackDBus {
 
     #[dbus_method("OnHciEnabledChanged")]
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool) {}
+
+    #[dbus_method("OnAdapterFailover")]
+    fn on_adapter_failover(&self, from_hci_interface: i32, to_hci_interface: i32) {}
+
+    #[dbus_method("OnDefaultAdapterChanged")]
+    fn on_default_adapter_changed(&self, hci_interface: i32) {}
+
+    #[dbus_method("OnAdapterFirmwareChanged")]
+    fn on_adapter_firmware_changed(&self, hci_interface: i32) {}
 }
@@ -1,9 +1,15 @@
 use log::LevelFilter;
+use manager_service::iface_bluetooth_manager::AdapterInfo;
 use serde_json::{Map, Value};
 
+use crate::state_machine;
+
 // Directory for Bluetooth hci devices
 pub const HCI_DEVICES_DIR: &str = "/sys/class/bluetooth";
 
+// Directory of the kernel debug entries of hci devices
+const HCI_DEBUGFS_DIR: &str = "/sys/kernel/debug/bluetooth";
+
 // File to store the Bluetooth daemon to use (bluez or floss)
 const BLUETOOTH_DAEMON_CURRENT: &str = "/var/lib/bluetooth/bluetooth-daemon.current";
 
@@ -130,6 +136,97 @@ fn hci_devices_string_to_int(devices: Vec<String>) -> Vec<i32> {
         .collect()
 }
 
+/// Reads the hardware details of hci N from its sysfs device, the driver debug entries and the
+/// controller version btadapterd last recorded for it.
+pub fn read_adapter_info(n: i32) -> Result<AdapterInfo, String> {
+    let hci = format!("{}/hci{}", HCI_DEVICES_DIR, n);
+    if !std::path::Path::new(&hci).exists() {
+        return Err(format!("hci{} doesn't exist", n));
+    }
+
+    let device = format!("{}/device", hci);
+    let mut info = match std::fs::read_to_string(format!("{}/uevent", device)) {
+        Ok(uevent) => parse_device_uevent(&uevent),
+        Err(_) => AdapterInfo::default(),
+    };
+
+    info.hci_interface = n;
+    info.bus = std::fs::read_link(format!("{}/subsystem", device))
+        .ok()
+        .and_then(|path| Some(path.file_name()?.to_str()?.to_string()))
+        .unwrap_or_default();
+
+    // Not every driver reports these, and debugfs may not be mounted.
+    let debugfs = format!("{}/hci{}", HCI_DEBUGFS_DIR, n);
+    info.chipset = read_trimmed(&format!("{}/hardware_info", debugfs))
+        .or_else(|| match info.bus.as_str() {
+            // The device of a USB adapter is its interface, the strings belong to the parent.
+            "usb" => read_trimmed(&format!("{}/../product", device)),
+            _ => None,
+        })
+        .unwrap_or_default();
+    info.firmware = read_trimmed(&format!("{}/firmware_info", debugfs)).unwrap_or_default();
+
+    let version = format!("{}/bluetooth{}.version", state_machine::PID_DIR, n);
+    if let Ok(version) = std::fs::read_to_string(version) {
+        parse_controller_version(&version, &mut info);
+    }
+    Ok(info)
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    let value = std::fs::read_to_string(path).ok()?.trim().to_string();
+    match value.is_empty() {
+        true => None,
+        false => Some(value),
+    }
+}
+
+/// Parses the controller version recorded by btadapterd, see CreateVersionFile in
+/// main/shim/stack.cc.
+fn parse_controller_version(version: &str, info: &mut AdapterInfo) {
+    for line in version.lines() {
+        match line.split_once('=') {
+            Some(("HCI_VERSION", v)) => info.hci_version = v.parse().unwrap_or_default(),
+            Some(("HCI_REVISION", v)) => info.hci_revision = v.parse().unwrap_or_default(),
+            Some(("LMP_VERSION", v)) => info.lmp_version = v.parse().unwrap_or_default(),
+            Some(("LMP_SUBVERSION", v)) => info.lmp_subversion = v.parse().unwrap_or_default(),
+            Some(("MANUFACTURER", v)) => info.manufacturer = v.parse().unwrap_or_default(),
+            _ => (),
+        }
+    }
+}
+
+fn parse_device_uevent(uevent: &str) -> AdapterInfo {
+    let mut info = AdapterInfo::default();
+    for line in uevent.lines() {
+        match line.split_once('=') {
+            Some(("DRIVER", driver)) => info.driver = driver.to_string(),
+            // USB devices, as hex vendor/product/bcdDevice.
+            Some(("PRODUCT", product)) => {
+                let ids: Vec<u16> =
+                    product.split('/').filter_map(|id| u16::from_str_radix(id, 16).ok()).collect();
+                if let [vendor_id, product_id, revision] = ids[..] {
+                    info.vendor_id = vendor_id;
+                    info.product_id = product_id;
+                    info.revision = revision;
+                }
+            }
+            // SDIO devices, as hex vendor:device.
+            Some(("SDIO_ID", id)) => {
+                let ids: Vec<u16> =
+                    id.split(':').filter_map(|id| u16::from_str_radix(id, 16).ok()).collect();
+                if let [vendor_id, product_id] = ids[..] {
+                    info.vendor_id = vendor_id;
+                    info.product_id = product_id;
+                }
+            }
+            _ => (),
+        }
+    }
+    info
+}
+
 pub fn list_pid_files(pid_dir: &str) -> Vec<String> {
     match std::fs::read_dir(pid_dir) {
         Ok(entries) => entries
@@ -213,6 +310,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_usb_device_uevent() {
+        let info = parse_device_uevent(
+            "DEVTYPE=usb_interface\nDRIVER=btusb\nPRODUCT=8087/26/2\nTYPE=224/1/1\n",
+        );
+        assert_eq!(info.driver, "btusb");
+        assert_eq!((info.vendor_id, info.product_id, info.revision), (0x8087, 0x26, 0x2));
+    }
+
+    #[test]
+    fn parse_sdio_device_uevent() {
+        let info = parse_device_uevent("DRIVER=btmrvl_sdio\nSDIO_CLASS=09\nSDIO_ID=02DF:912E\n");
+        assert_eq!(info.driver, "btmrvl_sdio");
+        assert_eq!((info.vendor_id, info.product_id, info.revision), (0x2df, 0x912e, 0));
+
+        assert_eq!(parse_device_uevent("PRODUCT=8087/26\n"), AdapterInfo::default());
+    }
+
+    #[test]
+    fn parse_recorded_controller_version() {
+        let mut info = AdapterInfo::default();
+        parse_controller_version(
+            "HCI_VERSION=11\nHCI_REVISION=4096\nLMP_VERSION=11\nLMP_SUBVERSION=8977\n\
+             MANUFACTURER=2\n",
+            &mut info,
+        );
+        assert_eq!((info.hci_version, info.hci_revision), (11, 4096));
+        assert_eq!((info.lmp_version, info.lmp_subversion), (11, 8977));
+        assert_eq!(info.manufacturer, 2);
+
+        let mut info = AdapterInfo::default();
+        parse_controller_version("HCI_VERSION=abc\nLMP_VERSION=300\n", &mut info);
+        assert_eq!(info, AdapterInfo::default());
+    }
+
+    #[test]
+    fn read_missing_adapter_info() {
+        assert!(read_adapter_info(i32::MAX).is_err());
+    }
+
     #[test]
     fn test_hci_devices_string_to_int_none() {
         assert_eq!(hci_devices_string_to_int(vec!["somethingelse".to_string()]), Vec::<i32>::new());
@@ -8,6 +8,7 @@ use regex::Regex;
 use std::cmp;
 use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::io::unix::AsyncFd;
 use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
@@ -39,6 +40,7 @@ pub enum AdapterStateActions {
     StopBluetooth(i32),
     BluetoothStarted(i32, i32), // PID and HCI
     BluetoothStopped(i32),
+    FailoverBluetooth(i32, i32), // From and to HCI
 }
 
 /// Enum of all the messages that state machine handles.
@@ -49,6 +51,8 @@ pub enum Message {
     HciDeviceChange(inotify::EventMask, Option<String>),
     CallbackDisconnected(u32),
     CommandTimeout(),
+    RetryAdapter(i32, i32),     // From and to HCI
+    AdapterProbeDone(i32, i32), // From and to HCI
 }
 
 pub struct StateMachineContext {
@@ -89,6 +93,12 @@ const COMMAND_TIMEOUT_DURATION: Duration = Duration::from_secs(3);
 /// /sys/class/bluetooth to become available.
 const HCI_DEVICE_SLEEP_MAX_SECONDS: u64 = 64;
 
+/// Number of consecutive restarts of an adapter after which it is considered broken.
+const MAX_RESTART_FAILURES: u32 = 3;
+
+/// An adapter that stayed up this long before stopping isn't crash looping.
+const RESTART_STABLE_DURATION: Duration = Duration::from_secs(60);
+
 impl StateMachineProxy {
     pub fn start_bluetooth(&self, hci_interface: i32) {
         let tx = self.tx.clone();
@@ -112,6 +122,31 @@ impl StateMachineProxy {
         });
     }
 
+    pub fn failover_bluetooth(&self, from_hci_interface: i32, to_hci_interface: i32) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let _ = tx
+                .send(Message::AdapterStateChange(AdapterStateActions::FailoverBluetooth(
+                    from_hci_interface,
+                    to_hci_interface,
+                )))
+                .await;
+        });
+    }
+
+    /// Moves the stack from `from_hci_interface` to `to_hci_interface` on trial. Once the new
+    /// adapter had the time to prove stable, `Message::AdapterProbeDone` is sent.
+    pub fn probe_bluetooth(&self, from_hci_interface: i32, to_hci_interface: i32) {
+        self.failover_bluetooth(from_hci_interface, to_hci_interface);
+
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            // Starting may take up to the command timeout before it is retried.
+            sleep(RESTART_STABLE_DURATION + COMMAND_TIMEOUT_DURATION).await;
+            let _ = tx.send(Message::AdapterProbeDone(from_hci_interface, to_hci_interface)).await;
+        });
+    }
+
     pub fn get_state(&self) -> State {
         // This assumes that self.state is never locked for a long period, i.e. never lock() and
         // await for something else without unlocking. Otherwise this function will block.
@@ -410,6 +445,17 @@ pub async fn mainloop(
                             }
                         }
                     }
+                    AdapterStateActions::FailoverBluetooth(from, to) => {
+                        next_state = State::TurningOn;
+                        hci = from;
+
+                        match context.state_machine.action_failover_bluetooth(from, to) {
+                            true => {
+                                command_timeout.reset(COMMAND_TIMEOUT_DURATION);
+                            }
+                            false => warn!("unexpected failover from hci{} to hci{}", from, to),
+                        }
+                    }
                 };
 
                 // Only emit enabled event for certain transitions
@@ -449,6 +495,12 @@ pub async fn mainloop(
                 }
                 (inotify::EventMask::DELETE, Some(fname)) => {
                     if let Some(hci) = get_hci_index_from_pid_path(&fname) {
+                        // The previous adapter's process going away after a failover.
+                        if hci != context.state_machine.hci_interface {
+                            debug!("Ignoring bluetooth stopped for hci={}", hci);
+                            continue;
+                        }
+
                         debug!("Sending bluetooth stopped action for hci={}", hci);
                         context
                             .tx
@@ -506,6 +558,30 @@ pub async fn mainloop(
                     _ => command_timeout.reset(COMMAND_TIMEOUT_DURATION),
                 }
             }
+
+            // Move back to an adapter that was failed over from
+            Message::RetryAdapter(from, to) => {
+                if context.state_machine.hci_interface != from {
+                    debug!("Not retrying hci{}, hci{} is no longer running", to, from);
+                    bluetooth_manager.lock().unwrap().forget_broken_adapter(to);
+                    continue;
+                }
+
+                bluetooth_manager.lock().unwrap().callback_retry_adapter(from, to);
+            }
+
+            // An adapter that was moved back to on trial should have proven stable by now
+            Message::AdapterProbeDone(from, to) => {
+                let stable =
+                    context.state_machine.hci_interface == to && context.state_machine.is_stable();
+                bluetooth_manager.lock().unwrap().callback_probe_done(from, to, stable);
+            }
+        }
+
+        // Let the manager move away from an adapter that keeps failing to start
+        if context.state_machine.take_restart_loop() {
+            let hci = context.state_machine.hci_interface;
+            bluetooth_manager.lock().unwrap().callback_restart_loop(hci);
         }
     }
 }
@@ -613,6 +689,9 @@ struct ManagerStateMachine {
     process_manager: Box<dyn ProcessManager + Send>,
     hci_interface: i32,
     bluetooth_pid: i32,
+    /// Restarts of the current adapter since it last came up for good.
+    restart_failures: u32,
+    started_at: Option<Instant>,
 }
 
 impl ManagerStateMachine {
@@ -643,6 +722,8 @@ impl ManagerStateMachine {
             process_manager: process_manager,
             hci_interface: 0,
             bluetooth_pid: 0,
+            restart_failures: 0,
+            started_at: None,
         }
     }
 
@@ -653,6 +734,7 @@ impl ManagerStateMachine {
             State::Off => {
                 *state = State::TurningOn;
                 self.hci_interface = hci_interface;
+                self.restart_failures = 0;
                 self.process_manager.start(format!("{}", hci_interface));
                 true
             }
@@ -703,6 +785,7 @@ impl ManagerStateMachine {
         }
         *state = State::On;
         self.bluetooth_pid = pid;
+        self.started_at = Some(Instant::now());
         true
     }
 
@@ -719,6 +802,10 @@ impl ManagerStateMachine {
             }
             State::On => {
                 warn!("Bluetooth stopped unexpectedly, try restarting");
+                if self.started_at.map_or(false, |t| t.elapsed() >= RESTART_STABLE_DURATION) {
+                    self.restart_failures = 0;
+                }
+                self.restart_failures += 1;
                 *state = State::TurningOn;
                 self.process_manager.start(format!("{}", self.hci_interface));
                 false
@@ -730,6 +817,31 @@ impl ManagerStateMachine {
         }
     }
 
+    /// Returns true if we are switching the bluetooth process to another adapter.
+    pub fn action_failover_bluetooth(&mut self, from: i32, to: i32) -> bool {
+        if self.hci_interface != from {
+            warn!(
+                "We are running hci{} but attempting to fail over from hci{}",
+                self.hci_interface, from
+            );
+            return false;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::On | State::TurningOn => {
+                *state = State::TurningOn;
+                self.process_manager.stop(self.hci_interface.to_string());
+                self.hci_interface = to;
+                self.restart_failures = 0;
+                self.process_manager.start(format!("{}", to));
+                true
+            }
+            // Otherwise no op
+            _ => false,
+        }
+    }
+
     /// Triggered on Bluetooth start/stop timeout.  Return the actions that the
     /// state machine has taken, for the external context to reset the timer.
     pub fn action_on_command_timeout(&mut self) -> StateMachineTimeoutActions {
@@ -737,6 +849,7 @@ impl ManagerStateMachine {
         match *state {
             State::TurningOn => {
                 info!("Restarting bluetooth {}", self.hci_interface);
+                self.restart_failures += 1;
                 *state = State::TurningOn;
                 self.process_manager.stop(format! {"{}", self.hci_interface});
                 self.process_manager.start(format! {"{}", self.hci_interface});
@@ -750,6 +863,23 @@ impl ManagerStateMachine {
             _ => StateMachineTimeoutActions::Noop,
         }
     }
+
+    /// Returns true if the current adapter is up and didn't restart for RESTART_STABLE_DURATION.
+    pub fn is_stable(&self) -> bool {
+        *self.state.lock().unwrap() == State::On
+            && self.started_at.map_or(false, |t| t.elapsed() >= RESTART_STABLE_DURATION)
+    }
+
+    /// Returns true once the current adapter has been restarted MAX_RESTART_FAILURES times in a
+    /// row, then starts counting again.
+    pub fn take_restart_loop(&mut self) -> bool {
+        if self.restart_failures < MAX_RESTART_FAILURES {
+            return false;
+        }
+
+        self.restart_failures = 0;
+        true
+    }
 }
 
 #[cfg(test)]
@@ -960,6 +1090,64 @@ mod tests {
         })
     }
 
+    #[test]
+    fn on_failover_should_switch_adapter() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            process_manager.expect_stop();
+            process_manager.expect_start();
+            let mut state_machine = ManagerStateMachine::new(Box::new(process_manager));
+            state_machine.action_start_bluetooth(0);
+            state_machine.action_on_bluetooth_started(0, 0);
+            assert_eq!(state_machine.action_failover_bluetooth(1, 2), false);
+            assert_eq!(state_machine.action_failover_bluetooth(0, 1), true);
+            assert_eq!(*state_machine.state.lock().unwrap(), State::TurningOn);
+            assert_eq!(state_machine.hci_interface, 1);
+            state_machine.action_on_bluetooth_started(0, 1);
+            assert_eq!(*state_machine.state.lock().unwrap(), State::On);
+        })
+    }
+
+    #[test]
+    fn repeated_restarts_should_be_restart_loop() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            // Crash once, then time out twice while restarting
+            process_manager.expect_start();
+            process_manager.expect_stop();
+            process_manager.expect_start();
+            process_manager.expect_stop();
+            process_manager.expect_start();
+            let mut state_machine = ManagerStateMachine::new(Box::new(process_manager));
+            state_machine.action_start_bluetooth(0);
+            state_machine.action_on_bluetooth_started(0, 0);
+            state_machine.action_on_bluetooth_stopped();
+            assert_eq!(state_machine.take_restart_loop(), false);
+            state_machine.action_on_command_timeout();
+            assert_eq!(state_machine.take_restart_loop(), false);
+            state_machine.action_on_command_timeout();
+            assert_eq!(state_machine.take_restart_loop(), true);
+            assert_eq!(state_machine.take_restart_loop(), false);
+        })
+    }
+
+    #[test]
+    fn just_started_should_not_be_stable() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            let mut state_machine = ManagerStateMachine::new(Box::new(process_manager));
+            assert_eq!(state_machine.is_stable(), false);
+            state_machine.action_start_bluetooth(0);
+            state_machine.action_on_bluetooth_started(0, 0);
+            assert_eq!(state_machine.is_stable(), false);
+            state_machine.started_at = Some(Instant::now() - RESTART_STABLE_DURATION);
+            assert_eq!(state_machine.is_stable(), true);
+        })
+    }
+
     #[test]
     fn path_to_hci_interface() {
         assert_eq!(get_hci_index_from_pid_path("/var/run/bluetooth/bluetooth0.pid"), Some(0));
@@ -6,6 +6,33 @@ pub struct AdapterWithEnabled {
     pub enabled: bool,
 }
 
+/// Hardware details of an adapter, read from its kernel device. Unknown fields are empty or 0.
+///
+/// The versions come from HCI Read Local Version Information, which btadapterd records whenever
+/// its stack starts. They are 0 until the stack ran on the adapter since boot.
+#[derive(Debug, Default, PartialEq)]
+pub struct AdapterInfo {
+    pub hci_interface: i32,
+    /// Bus the adapter is attached to, e.g. "usb" or "serial".
+    pub bus: String,
+    /// Kernel driver of the adapter, e.g. "btusb".
+    pub driver: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Revision of the device, e.g. the USB bcdDevice.
+    pub revision: u16,
+    /// Chipset as reported by the driver, or the USB product string.
+    pub chipset: String,
+    pub hci_version: u8,
+    pub hci_revision: u16,
+    pub lmp_version: u8,
+    pub lmp_subversion: u16,
+    /// Company identifier of the controller manufacturer.
+    pub manufacturer: u16,
+    /// Firmware build as reported by the driver, e.g. the firmware file it loaded.
+    pub firmware: String,
+}
+
 /// Bluetooth stack management API.
 pub trait IBluetoothManager {
     /// Starts the Bluetooth stack.
@@ -28,10 +55,17 @@ pub trait IBluetoothManager {
 
     /// Returns a list of available HCI devices and if they are enabled.
     fn get_available_adapters(&mut self) -> Vec<AdapterWithEnabled>;
+
+    /// Returns the hardware details of an HCI device, or an error if there is no such device.
+    fn get_adapter_info(&mut self, hci_interface: i32) -> Result<AdapterInfo, String>;
 }
 
 /// Interface of Bluetooth Manager callbacks.
 pub trait IBluetoothManagerCallback: RPCProxy {
     fn on_hci_device_changed(&self, hci_interface: i32, present: bool);
     fn on_hci_enabled_changed(&self, hci_interface: i32, enabled: bool);
+    fn on_adapter_failover(&self, from_hci_interface: i32, to_hci_interface: i32);
+    fn on_default_adapter_changed(&self, hci_interface: i32);
+    /// The firmware of an adapter differs from the last time its stack started.
+    fn on_adapter_firmware_changed(&self, hci_interface: i32);
 }
@@ -0,0 +1,84 @@
+use btstack::battery_manager::{
+    Battery, BatterySet, BatterySource, IBluetoothBattery, IBluetoothBatteryCallback,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
+
+use dbus_projection::{dbus_generated, impl_dbus_arg_enum, DisconnectWatcher};
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
+use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
+
+impl_dbus_arg_enum!(BatterySource);
+
+#[dbus_propmap(Battery)]
+pub struct BatteryDBus {
+    source: BatterySource,
+    level: u32,
+}
+
+#[dbus_propmap(BatterySet)]
+pub struct BatterySetDBus {
+    address: String,
+    level: u32,
+    batteries: Vec<Battery>,
+}
+
+#[allow(dead_code)]
+struct BatteryManagerCallbackDBus {}
+
+#[dbus_proxy_obj(BatteryManagerCallback, "org.chromium.bluetooth.BatteryManagerCallback")]
+impl IBluetoothBatteryCallback for BatteryManagerCallbackDBus {
+    #[dbus_method("OnBatteryInfoUpdated")]
+    fn on_battery_info_updated(&self, battery_set: BatterySet) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnBatteryInfoRemoved")]
+    fn on_battery_info_removed(&self, address: String) {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothBatteryDBus {}
+
+#[generate_dbus_exporter(export_battery_manager_dbus_obj, "org.chromium.bluetooth.BatteryManager")]
+impl IBluetoothBattery for IBluetoothBatteryDBus {
+    #[dbus_method("RegisterBatteryCallback")]
+    fn register_battery_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothBatteryCallback + Send>,
+    ) -> u32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("UnregisterBatteryCallback")]
+    fn unregister_battery_callback(&mut self, callback_id: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetBatteryInformation")]
+    fn get_battery_information(&self, address: String) -> BatterySet {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetBatteryLevel")]
+    fn set_battery_level(&mut self, address: String, source: BatterySource, level: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RemoveBatteryLevel")]
+    fn remove_battery_level(&mut self, address: String, source: BatterySource) -> bool {
+        dbus_generated!()
+    }
+}
@@ -1,11 +1,24 @@
 extern crate bt_shim;
 
-use bt_topshim::btif::{BtDeviceType, BtSspVariant, BtTransport, Uuid128Bit};
+use bt_topshim::btif::{BtBondFailReason, BtDeviceType, BtSspVariant, BtTransport, Uuid128Bit};
+use bt_topshim::controller::{
+    CodecDirection, CodecId, CodecTransport, KeypressNotificationType, LeAddressType,
+    LeTestPayload, LeTestPhy, LocalCodecCapabilities,
+};
+use bt_topshim::profiles::stats::ProfileInfo;
 
+use btstack::background_connections::{
+    BackgroundConnectionKind, BackgroundDevice, ConnectionOrigin,
+};
 use btstack::bluetooth::{
     BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
 };
-use btstack::uuid::Profile;
+use btstack::device_ranking::{RankedDevice, RankingUseCase, RankingWeights};
+use btstack::feature_support::{CapabilityStatus, ControllerFeature, OffloadCodec};
+use btstack::power_profile::{PowerModule, PowerProfile};
+use btstack::radio_test::{RadioTestKind, RadioTestParams, RadioTestResult, RadioTestStatus};
+use btstack::traffic::{BufferOverflows, DeviceTraffic, TrafficProfile, TrafficSnapshot};
+use btstack::uuid::{Profile, ProfileState};
 use btstack::RPCProxy;
 
 use dbus::arg::RefArg;
@@ -30,6 +43,120 @@ pub struct BluetoothDeviceDBus {
     name: String,
 }
 
+#[dbus_propmap(RankedDevice)]
+pub struct RankedDeviceDBus {
+    device: BluetoothDevice,
+    score: i32,
+    explanation: String,
+}
+
+#[dbus_propmap(RankingWeights)]
+pub struct RankingWeightsDBus {
+    bonded: i32,
+    connected: i32,
+    profile_match: i32,
+    rssi: i32,
+    recency: i32,
+}
+
+#[dbus_propmap(BackgroundDevice)]
+pub struct BackgroundDeviceDBus {
+    address: String,
+    kind: BackgroundConnectionKind,
+    address_type: LeAddressType,
+    in_accept_list: bool,
+}
+
+#[dbus_propmap(CodecId)]
+pub struct CodecIdDBus {
+    coding_format: u8,
+    company_id: u16,
+    vendor_codec_id: u16,
+}
+
+#[dbus_propmap(LocalCodecCapabilities)]
+pub struct LocalCodecCapabilitiesDBus {
+    codec: CodecId,
+    transport: CodecTransport,
+    direction: CodecDirection,
+    sample_rates: Vec<u32>,
+    channel_counts: Vec<u8>,
+    raw: Vec<Vec<u8>>,
+}
+
+#[dbus_propmap(OffloadCodec)]
+pub struct OffloadCodecDBus {
+    codec: CodecId,
+    transports: Vec<CodecTransport>,
+    capabilities: Vec<LocalCodecCapabilities>,
+}
+
+#[dbus_propmap(CapabilityStatus)]
+pub struct CapabilityStatusDBus {
+    name: String,
+    available: bool,
+    missing_features: Vec<ControllerFeature>,
+    reason: String,
+}
+
+#[dbus_propmap(ProfileInfo)]
+pub struct ProfileInfoDBus {
+    name: String,
+    version: u32,
+    enabled: bool,
+    last_error: String,
+}
+
+#[dbus_propmap(ProfileState)]
+pub struct ProfileStateDBus {
+    profile: Profile,
+    supported: bool,
+    enabled: bool,
+    initialized: bool,
+    connected: bool,
+    reason: String,
+}
+
+#[dbus_propmap(RadioTestParams)]
+pub struct RadioTestParamsDBus {
+    kind: RadioTestKind,
+    channel: u8,
+    length: u8,
+    payload: LeTestPayload,
+    phy: LeTestPhy,
+    timeout_ms: u32,
+}
+
+#[dbus_propmap(RadioTestResult)]
+pub struct RadioTestResultDBus {
+    kind: RadioTestKind,
+    channel: u8,
+    status: u8,
+    num_packets: u16,
+    duration_ms: u64,
+    timed_out: bool,
+}
+
+#[dbus_propmap(DeviceTraffic)]
+pub struct DeviceTrafficDBus {
+    address: String,
+    profile: TrafficProfile,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+#[dbus_propmap(TrafficSnapshot)]
+pub struct TrafficSnapshotDBus {
+    timestamp_ms: u64,
+    traffic: Vec<DeviceTraffic>,
+}
+
+#[dbus_propmap(BufferOverflows)]
+pub struct BufferOverflowsDBus {
+    acl: u32,
+    sco: u32,
+}
+
 #[allow(dead_code)]
 struct BluetoothCallbackDBus {}
 
@@ -70,15 +197,58 @@ impl IBluetoothCallback for BluetoothCallbackDBus {
         dbus_generated!()
     }
     #[dbus_method("OnBondStateChanged")]
-    fn on_bond_state_changed(&self, status: u32, address: String, state: u32) {
+    fn on_bond_state_changed(
+        &self,
+        status: u32,
+        address: String,
+        state: u32,
+        fail_reason: BtBondFailReason,
+    ) {
+        dbus_generated!()
+    }
+    #[dbus_method("OnBondConflict")]
+    fn on_bond_conflict(&self, remote_device: BluetoothDevice, fail_reason: BtBondFailReason) {
+        dbus_generated!()
+    }
+    #[dbus_method("OnKeypressNotification")]
+    fn on_keypress_notification(
+        &self,
+        remote_device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    ) {
+        dbus_generated!()
+    }
+    #[dbus_method("OnLocalAddressOverrideCompleted")]
+    fn on_local_address_override_completed(
+        &self,
+        requested: String,
+        readback: String,
+        verified: bool,
+    ) {
         dbus_generated!()
     }
 }
 
+impl_dbus_arg_enum!(BackgroundConnectionKind);
+impl_dbus_arg_enum!(BtBondFailReason);
 impl_dbus_arg_enum!(BtDeviceType);
 impl_dbus_arg_enum!(BtSspVariant);
 impl_dbus_arg_enum!(BtTransport);
+impl_dbus_arg_enum!(CodecDirection);
+impl_dbus_arg_enum!(CodecTransport);
+impl_dbus_arg_enum!(ConnectionOrigin);
+impl_dbus_arg_enum!(ControllerFeature);
+impl_dbus_arg_enum!(KeypressNotificationType);
+impl_dbus_arg_enum!(LeAddressType);
+impl_dbus_arg_enum!(LeTestPayload);
+impl_dbus_arg_enum!(LeTestPhy);
+impl_dbus_arg_enum!(PowerModule);
+impl_dbus_arg_enum!(PowerProfile);
 impl_dbus_arg_enum!(Profile);
+impl_dbus_arg_enum!(RadioTestKind);
+impl_dbus_arg_enum!(RadioTestStatus);
+impl_dbus_arg_enum!(RankingUseCase);
+impl_dbus_arg_enum!(TrafficProfile);
 
 #[allow(dead_code)]
 struct BluetoothConnectionCallbackDBus {}
@@ -86,7 +256,7 @@ struct BluetoothConnectionCallbackDBus {}
 #[dbus_proxy_obj(BluetoothConnectionCallback, "org.chromium.bluetooth.BluetoothConnectionCallback")]
 impl IBluetoothConnectionCallback for BluetoothConnectionCallbackDBus {
     #[dbus_method("OnDeviceConnected")]
-    fn on_device_connected(&self, remote_device: BluetoothDevice) {
+    fn on_device_connected(&self, remote_device: BluetoothDevice, origin: ConnectionOrigin) {
         dbus_generated!()
     }
 
@@ -304,4 +474,168 @@ impl IBluetooth for IBluetoothDBus {
     fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool {
         dbus_generated!()
     }
+
+    #[dbus_method("GetRankedDevices")]
+    fn get_ranked_devices(&self, use_case: RankingUseCase) -> Vec<RankedDevice> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetDeviceRankingWeights")]
+    fn set_device_ranking_weights(&mut self, weights: RankingWeights) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("AddBackgroundConnection")]
+    fn add_background_connection(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RemoveBackgroundConnection")]
+    fn remove_background_connection(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetBackgroundConnections")]
+    fn get_background_connections(&self) -> Vec<BackgroundDevice> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetServiceRefreshInterval")]
+    fn set_service_refresh_interval(&mut self, interval_sec: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RequestServiceRefresh")]
+    fn request_service_refresh(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetPowerProfile")]
+    fn set_power_profile(&mut self, profile: PowerProfile) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPowerProfile")]
+    fn get_power_profile(&self) -> PowerProfile {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetPowerProfileOverride")]
+    fn set_power_profile_override(&mut self, module: PowerModule, profile: PowerProfile) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ClearPowerProfileOverride")]
+    fn clear_power_profile_override(&mut self, module: PowerModule) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetEffectivePowerProfile")]
+    fn get_effective_power_profile(&self, module: PowerModule) -> PowerProfile {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetCapabilities")]
+    fn get_capabilities(&self) -> Vec<CapabilityStatus> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetOffloadCodecs")]
+    fn get_offload_codecs(&self) -> Vec<OffloadCodec> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetRadioTestMode")]
+    fn set_radio_test_mode(&mut self, enabled: bool) -> RadioTestStatus {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StartRadioTest")]
+    fn start_radio_test(&mut self, params: RadioTestParams) -> RadioTestStatus {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StopRadioTest")]
+    fn stop_radio_test(&mut self) -> RadioTestStatus {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetRadioTestResults")]
+    fn get_radio_test_results(&self) -> Vec<RadioTestResult> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ForgetAndRepair")]
+    fn forget_and_repair(&mut self, device: BluetoothDevice) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SendKeypressNotification")]
+    fn send_keypress_notification(
+        &self,
+        device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetRpaTimeout")]
+    fn set_rpa_timeout(&mut self, timeout_s: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetRpaTimeout")]
+    fn get_rpa_timeout(&self) -> u16 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OverrideLocalAddress")]
+    fn override_local_address(&mut self, address: String) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StartControllerDebugDump")]
+    fn start_controller_debug_dump(&mut self) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetControllerDebugDump")]
+    fn get_controller_debug_dump(&mut self) -> String {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetTracingEnabled")]
+    fn set_tracing_enabled(&mut self, enabled: bool) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("WriteTrace")]
+    fn write_trace(&self) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetProfileInfo")]
+    fn get_profile_info(&self) -> Vec<ProfileInfo> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetProfileStates")]
+    fn get_profile_states(&self) -> Vec<ProfileState> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetDeviceTraffic")]
+    fn get_device_traffic(&self) -> Vec<DeviceTraffic> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetTrafficSnapshots")]
+    fn get_traffic_snapshots(&self) -> Vec<TrafficSnapshot> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetBufferOverflows")]
+    fn get_buffer_overflows(&self) -> BufferOverflows {
+        dbus_generated!()
+    }
 }
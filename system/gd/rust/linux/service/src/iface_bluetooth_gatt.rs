@@ -2,9 +2,12 @@ use bt_topshim::{btif::Uuid128Bit, profiles::gatt::GattStatus};
 
 use btstack::bluetooth_gatt::{
     BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService,
-    GattWriteRequestStatus, GattWriteType, IBluetoothGatt, IBluetoothGattCallback,
-    IScannerCallback, LePhy, RSSISettings, ScanFilter, ScanSettings, ScanType,
+    GattConnectionPriority, GattOrphanStats, GattWriteRequestStatus, GattWriteType, IBluetoothGatt,
+    IBluetoothGattCallback, IPeriodicSyncCallback, IScannerCallback, LePhy, RSSISettings,
+    ScanFilter, ScanSettings, ScanType,
 };
+use btstack::gatt_metrics::GattAttributeMetrics;
+use btstack::periodic_sync::{PeriodicSync, SyncTransferMode};
 use btstack::RPCProxy;
 
 use dbus::arg::RefArg;
@@ -112,6 +115,16 @@ impl IBluetoothGattCallback for BluetoothGattCallbackDBus {
         dbus_generated!()
     }
 
+    #[dbus_method("OnConnectionPriorityUpdated")]
+    fn on_connection_priority_updated(
+        &self,
+        addr: String,
+        priority: GattConnectionPriority,
+        status: i32,
+    ) {
+        dbus_generated!()
+    }
+
     #[dbus_method("OnServiceChanged")]
     fn on_service_changed(&self, addr: String) {
         dbus_generated!()
@@ -147,6 +160,39 @@ impl IScannerCallback for ScannerCallbackDBus {
     }
 }
 
+#[allow(dead_code)]
+struct PeriodicSyncCallbackDBus {}
+
+#[dbus_proxy_obj(PeriodicSyncCallback, "org.chromium.bluetooth.PeriodicSyncCallback")]
+impl IPeriodicSyncCallback for PeriodicSyncCallbackDBus {
+    #[dbus_method("OnSyncStarted")]
+    fn on_sync_started(&self, status: u8, sync: PeriodicSync) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnSyncReport")]
+    fn on_sync_report(
+        &self,
+        sync_handle: u16,
+        tx_power: i32,
+        rssi: i32,
+        data_status: u8,
+        data: Vec<u8>,
+    ) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnSyncLost")]
+    fn on_sync_lost(&self, sync_handle: u16) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnSyncTransferred")]
+    fn on_sync_transferred(&self, status: u8, addr: String) {
+        dbus_generated!()
+    }
+}
+
 #[dbus_propmap(BluetoothGattDescriptor)]
 pub struct BluetoothGattDescriptorDBus {
     uuid: Uuid128Bit,
@@ -174,6 +220,32 @@ pub struct BluetoothGattServiceDBus {
     included_services: Vec<BluetoothGattService>,
 }
 
+#[dbus_propmap(GattAttributeMetrics)]
+pub struct GattAttributeMetricsDBus {
+    service_uuid: Uuid128Bit,
+    characteristic_uuid: Uuid128Bit,
+    reads: u32,
+    read_errors: u32,
+    writes: u32,
+    write_errors: u32,
+    notifications: u32,
+}
+
+#[dbus_propmap(GattOrphanStats)]
+pub struct GattOrphanStatsDBus {
+    clients: u32,
+    connections: u32,
+}
+
+#[dbus_propmap(PeriodicSync)]
+pub struct PeriodicSyncDBus {
+    sync_handle: u16,
+    address: String,
+    sid: u8,
+    phy: u8,
+    interval: u16,
+}
+
 #[dbus_propmap(RSSISettings)]
 pub struct RSSISettingsDBus {
     low_threshold: i32,
@@ -188,11 +260,13 @@ struct ScanSettingsDBus {
     rssi_settings: RSSISettings,
 }
 
+impl_dbus_arg_enum!(GattConnectionPriority);
 impl_dbus_arg_enum!(GattStatus);
 impl_dbus_arg_enum!(GattWriteRequestStatus);
 impl_dbus_arg_enum!(GattWriteType);
 impl_dbus_arg_enum!(LePhy);
 impl_dbus_arg_enum!(ScanType);
+impl_dbus_arg_enum!(SyncTransferMode);
 
 #[dbus_propmap(ScanFilter)]
 struct ScanFilterDBus {}
@@ -374,4 +448,89 @@ impl IBluetoothGatt for IBluetoothGattDBus {
     ) {
         dbus_generated!()
     }
+
+    #[dbus_method("RequestConnectionPriority")]
+    fn request_connection_priority(
+        &mut self,
+        client_id: i32,
+        addr: String,
+        priority: GattConnectionPriority,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("PurgeOrphans")]
+    fn purge_orphans(&mut self) -> i32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetOrphanStats")]
+    fn get_orphan_stats(&self) -> GattOrphanStats {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetGattMetrics")]
+    fn get_gatt_metrics(&self) -> Vec<GattAttributeMetrics> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ResetGattMetrics")]
+    fn reset_gatt_metrics(&mut self) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RegisterPeriodicSyncCallback")]
+    fn register_periodic_sync_callback(
+        &mut self,
+        callback: Box<dyn IPeriodicSyncCallback + Send>,
+    ) -> u32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("UnregisterPeriodicSyncCallback")]
+    fn unregister_periodic_sync_callback(&mut self, callback_id: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SyncToPeriodicAdvertising")]
+    fn sync_to_periodic_advertising(
+        &mut self,
+        addr: String,
+        sid: u8,
+        skip: u16,
+        timeout: u16,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("CancelSync")]
+    fn cancel_sync(&mut self, addr: String, sid: u8) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("StopSync")]
+    fn stop_sync(&mut self, sync_handle: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("TransferSync")]
+    fn transfer_sync(&mut self, addr: String, service_data: u16, sync_handle: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetSyncTransferParameters")]
+    fn set_sync_transfer_parameters(
+        &mut self,
+        addr: String,
+        mode: SyncTransferMode,
+        skip: u16,
+        timeout: u16,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPeriodicSyncs")]
+    fn get_periodic_syncs(&self) -> Vec<PeriodicSync> {
+        dbus_generated!()
+    }
 }
@@ -2,22 +2,29 @@ use dbus::{channel::MatchingReceiver, message::MatchRule};
 use dbus_crossroads::Crossroads;
 use dbus_tokio::connection;
 use futures::future;
-use log::LevelFilter;
+use log::{error, info, LevelFilter};
 use std::error::Error;
 use std::sync::{Arc, Mutex};
 use syslog::{BasicLogger, Facility, Formatter3164};
 
 use bt_topshim::{btif::get_btinterface, topstack};
 use btstack::{
+    battery_manager::BatteryManager,
+    battery_service::BatteryService,
     bluetooth::{get_bt_dispatcher, Bluetooth, IBluetooth},
     bluetooth_gatt::BluetoothGatt,
     bluetooth_media::BluetoothMedia,
+    feature_support::FeatureRegistry,
+    startup::StartupOrchestrator,
     suspend::Suspend,
+    tracer::Tracer,
+    traffic::TrafficAccounting,
     Stack,
 };
 use dbus_projection::DisconnectWatcher;
 
 mod dbus_arg;
+mod iface_battery_manager;
 mod iface_bluetooth;
 mod iface_bluetooth_gatt;
 mod iface_bluetooth_media;
@@ -61,13 +68,37 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let intf = Arc::new(Mutex::new(get_btinterface().unwrap()));
     let suspend = Arc::new(Mutex::new(Box::new(Suspend::new(tx.clone()))));
-    let bluetooth_gatt = Arc::new(Mutex::new(Box::new(BluetoothGatt::new(intf.clone()))));
-    let bluetooth_media =
-        Arc::new(Mutex::new(Box::new(BluetoothMedia::new(tx.clone(), intf.clone()))));
+    let feature_registry = Arc::new(Mutex::new(FeatureRegistry::new()));
+    let tracer = Arc::new(Mutex::new(Tracer::new()));
+    let traffic = Arc::new(Mutex::new(TrafficAccounting::new()));
+    let battery_manager = Arc::new(Mutex::new(Box::new(BatteryManager::new(tx.clone()))));
+    let bluetooth_gatt = Arc::new(Mutex::new(Box::new(BluetoothGatt::new(
+        intf.clone(),
+        feature_registry.clone(),
+        tracer.clone(),
+        traffic.clone(),
+    ))));
+    let battery_service = Arc::new(Mutex::new(Box::new(BatteryService::new(
+        tx.clone(),
+        bluetooth_gatt.clone(),
+        battery_manager.clone(),
+    ))));
+    let bluetooth_media = Arc::new(Mutex::new(Box::new(BluetoothMedia::new(
+        tx.clone(),
+        intf.clone(),
+        tracer.clone(),
+        battery_manager.clone(),
+        traffic.clone(),
+    ))));
     let bluetooth = Arc::new(Mutex::new(Box::new(Bluetooth::new(
         tx.clone(),
         intf.clone(),
+        bluetooth_gatt.clone(),
         bluetooth_media.clone(),
+        battery_manager.clone(),
+        feature_registry.clone(),
+        tracer.clone(),
+        traffic.clone(),
     ))));
 
     // Args don't include arg[0] which is the binary name
@@ -106,6 +137,8 @@ fn main() -> Result<(), Box<dyn Error>> {
             bluetooth_gatt.clone(),
             bluetooth_media.clone(),
             suspend.clone(),
+            battery_manager.clone(),
+            battery_service.clone(),
         ));
 
         // Set up the disconnect watcher to monitor client disconnects.
@@ -145,18 +178,75 @@ fn main() -> Result<(), Box<dyn Error>> {
             disconnect_watcher.clone(),
         );
 
-        // Hold locks and initialize all interfaces. This must be done AFTER DBus is
+        iface_battery_manager::export_battery_manager_dbus_obj(
+            make_object_name(adapter_index, "battery_manager"),
+            conn.clone(),
+            &mut cr,
+            battery_manager,
+            disconnect_watcher.clone(),
+        );
+
+        // Initialize all interfaces in dependency order. This must be done AFTER DBus is
         // initialized so DBus can properly enforce user policies.
+        let mut startup = StartupOrchestrator::new();
         {
-            intf.lock().unwrap().initialize(get_bt_dispatcher(tx.clone()), args);
-
-            bluetooth_media.lock().unwrap().set_adapter(bluetooth.clone());
-
-            let mut bluetooth = bluetooth.lock().unwrap();
-            bluetooth.init_profiles();
-            bluetooth.enable();
+            let intf = intf.clone();
+            let tx = tx.clone();
+            startup.add_module(
+                "intf",
+                &[],
+                Box::new(move || {
+                    match intf.lock().unwrap().initialize(get_bt_dispatcher(tx), args) {
+                        true => Ok(()),
+                        false => Err("libbluetooth failed to initialize".to_string()),
+                    }
+                }),
+            );
+        }
+        {
+            let bluetooth = bluetooth.clone();
+            let bluetooth_media = bluetooth_media.clone();
+            startup.add_module(
+                "media",
+                &["intf"],
+                Box::new(move || {
+                    bluetooth_media.lock().unwrap().set_adapter(bluetooth);
+                    Ok(())
+                }),
+            );
+        }
+        {
+            let bluetooth = bluetooth.clone();
+            startup.add_module(
+                "adapter",
+                &["intf", "media"],
+                Box::new(move || {
+                    let mut bluetooth = bluetooth.lock().unwrap();
+                    bluetooth.init_profiles();
+                    bluetooth.enable();
+                    Ok(())
+                }),
+            );
+        }
+        {
+            let bluetooth_gatt = bluetooth_gatt.clone();
+            let tx = tx.clone();
+            startup.add_module(
+                "gatt",
+                &["adapter"],
+                Box::new(move || {
+                    bluetooth_gatt.lock().unwrap().init_profiles(tx);
+                    Ok(())
+                }),
+            );
+        }
 
-            bluetooth_gatt.lock().unwrap().init_profiles(tx.clone());
+        match startup.run() {
+            Ok(report) => info!("Stack started in {:?}", report.total()),
+            Err(e) => {
+                error!("Stack startup failed: {}", e);
+                std::process::exit(1);
+            }
         }
 
         // Start listening on DBus after exporting interfaces and initializing
@@ -0,0 +1,384 @@
+//! Background (auto) connections to known devices.
+//!
+//! Keeps the set of devices that are allowed to reconnect on their own and decides which of them
+//! get a slot in the controller filter accept list. When there are more devices than slots, HID
+//! devices win over audio devices, which win over everything else; within the same kind the
+//! device that was added first wins.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use bt_topshim::controller::LeAddressType;
+
+use crate::uuid::Profile;
+
+/// How long a local connection request is remembered when no connection or failure is reported.
+const LOCAL_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a background connection is for. Lower values have higher priority for accept list slots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum BackgroundConnectionKind {
+    Hid = 0,
+    Audio,
+    Other,
+}
+
+impl BackgroundConnectionKind {
+    /// Picks the kind from the profiles supported by the device.
+    pub fn from_profiles(profiles: &[Profile]) -> BackgroundConnectionKind {
+        if profiles.iter().any(|p| matches!(p, Profile::Hid | Profile::Hogp)) {
+            BackgroundConnectionKind::Hid
+        } else if profiles.iter().any(|p| {
+            matches!(p, Profile::A2dpSink | Profile::Hfp | Profile::Hsp | Profile::LeAudio)
+        }) {
+            BackgroundConnectionKind::Audio
+        } else {
+            BackgroundConnectionKind::Other
+        }
+    }
+}
+
+impl Default for BackgroundConnectionKind {
+    fn default() -> Self {
+        BackgroundConnectionKind::Other
+    }
+}
+
+/// Who caused a connection, reported along with connection events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum ConnectionOrigin {
+    /// Neither requested locally nor expected from the accept list, i.e. most likely the remote.
+    Unknown = 0,
+    /// Requested by a local client, e.g. via `IBluetooth::connect_all_enabled_profiles`.
+    Local,
+    /// Established through the filter accept list.
+    Background,
+}
+
+/// A change that needs to be applied to the controller filter accept list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AcceptListUpdate {
+    Add(String, LeAddressType),
+    Remove(String, LeAddressType),
+}
+
+/// A device allowed to connect in the background.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BackgroundDevice {
+    pub address: String,
+    pub kind: BackgroundConnectionKind,
+    /// Type of `address` as known to the stack, used for the accept list entry.
+    pub address_type: LeAddressType,
+    /// Whether the device currently has a slot in the filter accept list. LE devices without a
+    /// slot have to be connected explicitly.
+    pub in_accept_list: bool,
+}
+
+struct Entry {
+    address: String,
+    kind: BackgroundConnectionKind,
+    is_le: bool,
+    address_type: LeAddressType,
+}
+
+pub struct BackgroundConnections {
+    /// Number of filter accept list slots we may use.
+    capacity: usize,
+    /// Devices in the order they were added.
+    devices: Vec<Entry>,
+    /// Accept list entries we added, with the address type they were added with.
+    in_accept_list: HashMap<String, LeAddressType>,
+    local_attempts: HashMap<String, Instant>,
+}
+
+impl BackgroundConnections {
+    pub fn new() -> Self {
+        BackgroundConnections {
+            capacity: 0,
+            devices: vec![],
+            in_accept_list: HashMap::new(),
+            local_attempts: HashMap::new(),
+        }
+    }
+
+    /// Adds a device or updates its kind and address type. Only `is_le` devices can use the
+    /// accept list; classic devices are expected to page us and are only tracked for connection
+    /// origin tagging.
+    pub fn add(
+        &mut self,
+        address: String,
+        kind: BackgroundConnectionKind,
+        is_le: bool,
+        address_type: LeAddressType,
+    ) -> Vec<AcceptListUpdate> {
+        match self.devices.iter_mut().find(|e| e.address == address) {
+            Some(entry) => {
+                entry.kind = kind;
+                entry.is_le = is_le;
+                entry.address_type = address_type;
+            }
+            None => self.devices.push(Entry { address, kind, is_le, address_type }),
+        }
+
+        self.rebalance()
+    }
+
+    /// Removes a device. Returns None if it wasn't added.
+    pub fn remove(&mut self, address: &str) -> Option<Vec<AcceptListUpdate>> {
+        let len = self.devices.len();
+        self.devices.retain(|e| e.address != address);
+        if self.devices.len() == len {
+            return None;
+        }
+
+        Some(self.rebalance())
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.devices.iter().any(|e| e.address == address)
+    }
+
+    /// Sets the number of accept list slots, e.g. once the controller is up.
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<AcceptListUpdate> {
+        self.capacity = capacity;
+        self.rebalance()
+    }
+
+    /// Records that the stack refused to add `address` to the accept list, e.g. because the rest
+    /// of the stack uses more of it than expected. The device is reported without a slot, and
+    /// adding it is tried again on the next change.
+    pub fn accept_list_add_failed(&mut self, address: &str) {
+        self.in_accept_list.remove(address);
+    }
+
+    /// Forgets what is in the accept list, e.g. because the controller went away. The next
+    /// `set_capacity` adds everything again.
+    pub fn reset_accept_list(&mut self) {
+        self.in_accept_list.clear();
+        self.local_attempts.clear();
+        self.capacity = 0;
+    }
+
+    pub fn get_devices(&self) -> Vec<BackgroundDevice> {
+        self.devices
+            .iter()
+            .map(|e| BackgroundDevice {
+                address: e.address.clone(),
+                kind: e.kind,
+                address_type: e.address_type,
+                in_accept_list: self.in_accept_list.contains_key(&e.address),
+            })
+            .collect()
+    }
+
+    /// Records that a local client asked to connect to `address`. The attempt is forgotten once
+    /// the connection completes or fails, or after `LOCAL_ATTEMPT_TIMEOUT` if neither is reported.
+    pub fn mark_local_attempt(&mut self, address: String) {
+        self.local_attempts.retain(|_, started| started.elapsed() < LOCAL_ATTEMPT_TIMEOUT);
+        self.local_attempts.insert(address, Instant::now());
+    }
+
+    /// Forgets a local connection attempt to `address` that failed.
+    pub fn clear_local_attempt(&mut self, address: &str) {
+        self.local_attempts.remove(address);
+    }
+
+    /// Returns the origin of a connection that just completed.
+    pub fn take_origin(&mut self, address: &str) -> ConnectionOrigin {
+        let local = match self.local_attempts.remove(address) {
+            Some(started) => started.elapsed() < LOCAL_ATTEMPT_TIMEOUT,
+            None => false,
+        };
+
+        if local {
+            ConnectionOrigin::Local
+        } else if self.contains(address) {
+            ConnectionOrigin::Background
+        } else {
+            ConnectionOrigin::Unknown
+        }
+    }
+
+    fn rebalance(&mut self) -> Vec<AcceptListUpdate> {
+        // Stable sort keeps insertion order within the same kind.
+        let mut candidates: Vec<&Entry> = self.devices.iter().filter(|e| e.is_le).collect();
+        candidates.sort_by_key(|e| e.kind);
+
+        let wanted: HashSet<&String> =
+            candidates.iter().take(self.capacity).map(|e| &e.address).collect();
+
+        // Removals go first so that added devices never exceed the capacity. An entry whose
+        // address type changed is removed with the old type and added again.
+        let mut removed: Vec<(&String, &LeAddressType)> = self
+            .in_accept_list
+            .iter()
+            .filter(|(address, address_type)| {
+                !wanted.contains(address)
+                    || candidates
+                        .iter()
+                        .any(|e| e.address == **address && e.address_type != **address_type)
+            })
+            .collect();
+        removed.sort_by_key(|(address, _)| *address);
+        let mut updates: Vec<AcceptListUpdate> =
+            removed.into_iter().map(|(a, t)| AcceptListUpdate::Remove(a.clone(), *t)).collect();
+
+        for e in candidates.iter().filter(|e| wanted.contains(&e.address)) {
+            if self.in_accept_list.get(&e.address) != Some(&e.address_type) {
+                updates.push(AcceptListUpdate::Add(e.address.clone(), e.address_type));
+            }
+        }
+
+        self.in_accept_list = candidates
+            .iter()
+            .filter(|e| wanted.contains(&e.address))
+            .map(|e| (e.address.clone(), e.address_type))
+            .collect();
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_list_overflow_prefers_hid() {
+        let mut bg = BackgroundConnections::new();
+        assert!(bg
+            .add(
+                "00:00:00:00:00:01".into(),
+                BackgroundConnectionKind::Other,
+                true,
+                LeAddressType::Public
+            )
+            .is_empty());
+        assert!(bg
+            .add(
+                "00:00:00:00:00:02".into(),
+                BackgroundConnectionKind::Audio,
+                true,
+                LeAddressType::Public
+            )
+            .is_empty());
+
+        assert_eq!(
+            bg.set_capacity(2),
+            vec![
+                AcceptListUpdate::Add("00:00:00:00:00:02".into(), LeAddressType::Public),
+                AcceptListUpdate::Add("00:00:00:00:00:01".into(), LeAddressType::Public),
+            ]
+        );
+
+        // A HID device pushes out the lowest priority device.
+        assert_eq!(
+            bg.add(
+                "00:00:00:00:00:03".into(),
+                BackgroundConnectionKind::Hid,
+                true,
+                LeAddressType::Public
+            ),
+            vec![
+                AcceptListUpdate::Remove("00:00:00:00:00:01".into(), LeAddressType::Public),
+                AcceptListUpdate::Add("00:00:00:00:00:03".into(), LeAddressType::Public),
+            ]
+        );
+
+        // Classic devices never take a slot.
+        assert!(bg
+            .add(
+                "00:00:00:00:00:04".into(),
+                BackgroundConnectionKind::Hid,
+                false,
+                LeAddressType::Public
+            )
+            .is_empty());
+
+        assert_eq!(
+            bg.remove("00:00:00:00:00:03"),
+            Some(vec![
+                AcceptListUpdate::Remove("00:00:00:00:00:03".into(), LeAddressType::Public),
+                AcceptListUpdate::Add("00:00:00:00:00:01".into(), LeAddressType::Public),
+            ])
+        );
+        assert_eq!(bg.remove("00:00:00:00:00:03"), None);
+        assert_eq!(bg.get_devices().iter().filter(|d| d.in_accept_list).count(), 2);
+    }
+
+    #[test]
+    fn test_connection_origin() {
+        let mut bg = BackgroundConnections::new();
+        bg.add(
+            "00:00:00:00:00:01".into(),
+            BackgroundConnectionKind::Hid,
+            true,
+            LeAddressType::Public,
+        );
+        bg.mark_local_attempt("00:00:00:00:00:01".into());
+
+        assert_eq!(bg.take_origin("00:00:00:00:00:01"), ConnectionOrigin::Local);
+        assert_eq!(bg.take_origin("00:00:00:00:00:01"), ConnectionOrigin::Background);
+        assert_eq!(bg.take_origin("00:00:00:00:00:02"), ConnectionOrigin::Unknown);
+
+        // A failed attempt doesn't tag a later connection as local.
+        bg.mark_local_attempt("00:00:00:00:00:01".into());
+        bg.clear_local_attempt("00:00:00:00:00:01");
+        assert_eq!(bg.take_origin("00:00:00:00:00:01"), ConnectionOrigin::Background);
+    }
+
+    #[test]
+    fn test_accept_list_address_type_change() {
+        let mut bg = BackgroundConnections::new();
+        bg.set_capacity(1);
+        bg.add(
+            "00:00:00:00:00:01".into(),
+            BackgroundConnectionKind::Hid,
+            true,
+            LeAddressType::Public,
+        );
+
+        assert_eq!(
+            bg.add(
+                "00:00:00:00:00:01".into(),
+                BackgroundConnectionKind::Hid,
+                true,
+                LeAddressType::RandomIdentity
+            ),
+            vec![
+                AcceptListUpdate::Remove("00:00:00:00:00:01".into(), LeAddressType::Public),
+                AcceptListUpdate::Add("00:00:00:00:00:01".into(), LeAddressType::RandomIdentity),
+            ]
+        );
+        assert_eq!(bg.get_devices()[0].address_type, LeAddressType::RandomIdentity);
+    }
+    #[test]
+    fn test_accept_list_add_failed() {
+        let mut bg = BackgroundConnections::new();
+        bg.set_capacity(2);
+        bg.add(
+            "00:00:00:00:00:01".into(),
+            BackgroundConnectionKind::Hid,
+            true,
+            LeAddressType::Public,
+        );
+        bg.accept_list_add_failed("00:00:00:00:00:01");
+        assert!(!bg.get_devices()[0].in_accept_list);
+
+        // The refused device is tried again along with the next change.
+        assert_eq!(
+            bg.add(
+                "00:00:00:00:00:02".into(),
+                BackgroundConnectionKind::Other,
+                true,
+                LeAddressType::Public
+            ),
+            vec![
+                AcceptListUpdate::Add("00:00:00:00:00:01".into(), LeAddressType::Public),
+                AcceptListUpdate::Add("00:00:00:00:00:02".into(), LeAddressType::Public),
+            ]
+        );
+        assert_eq!(bg.get_devices().iter().filter(|d| d.in_accept_list).count(), 2);
+    }
+}
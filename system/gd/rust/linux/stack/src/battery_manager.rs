@@ -0,0 +1,298 @@
+//! Battery levels of remote devices.
+//!
+//! A device can report its battery level over several profiles, e.g. a headset over both HFP and
+//! the GATT Battery Service. The levels are kept per source and merged into one level per device,
+//! taken from the most precise source available. The stack reads HF indicators, the GATT Battery
+//! Service of bonded LE devices and the battery report of HID devices itself. Clients can report
+//! levels it can't read through `IBluetoothBattery::set_battery_level`. All levels of a device are
+//! dropped once it disconnects, and all levels once the adapter is off.
+
+use crate::{Message, RPCProxy};
+use log::warn;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::mpsc::Sender;
+
+/// Highest battery level, in percent.
+const MAX_BATTERY_LEVEL: u32 = 100;
+
+/// Where a battery level comes from, from the least to the most precise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum BatterySource {
+    /// HF indicator sent by a hands-free device.
+    Hfp = 0,
+    /// Battery report of an HID device.
+    Hid,
+    /// GATT Battery Service.
+    Gatt,
+}
+
+impl Default for BatterySource {
+    fn default() -> Self {
+        BatterySource::Hfp
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Battery {
+    pub source: BatterySource,
+    /// Battery level in percent.
+    pub level: u32,
+}
+
+/// Battery levels of a device.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatterySet {
+    pub address: String,
+    /// Level of the most precise source, in percent.
+    pub level: u32,
+    /// Levels of every source, the most precise first.
+    pub batteries: Vec<Battery>,
+}
+
+/// Defines the battery API.
+pub trait IBluetoothBattery {
+    /// Adds an observer of the battery levels of all devices.
+    ///
+    /// Returns the id of the callback, used to unregister it.
+    fn register_battery_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothBatteryCallback + Send>,
+    ) -> u32;
+
+    /// Removes an observer added by `register_battery_callback`.
+    ///
+    /// Returns false if `callback_id` is not recognized.
+    fn unregister_battery_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Returns the battery levels of `address`. `batteries` is empty if no source reported any.
+    fn get_battery_information(&self, address: String) -> BatterySet;
+
+    /// Reports the battery level of `address` read from `source` by a client, e.g. from a vendor
+    /// specific GATT service. The level stays until it is removed or changed, or the device
+    /// disconnects.
+    ///
+    /// Returns false if `level` is above 100.
+    fn set_battery_level(&mut self, address: String, source: BatterySource, level: u32) -> bool;
+
+    /// Removes the battery level of `address` reported from `source`, e.g. once disconnected.
+    ///
+    /// Returns false if there was none.
+    fn remove_battery_level(&mut self, address: String, source: BatterySource) -> bool;
+}
+
+/// Battery level events.
+pub trait IBluetoothBatteryCallback: RPCProxy {
+    /// Triggered when a battery level of `battery_set.address` changed or was added.
+    fn on_battery_info_updated(&self, battery_set: BatterySet);
+
+    /// Triggered when the last battery level of `address` was removed.
+    fn on_battery_info_removed(&self, address: String);
+}
+
+/// Implementation of the battery API.
+pub struct BatteryManager {
+    tx: Sender<Message>,
+    callbacks: HashMap<u32, Box<dyn IBluetoothBatteryCallback + Send>>,
+    levels: HashMap<String, BTreeMap<BatterySource, u32>>,
+}
+
+impl BatteryManager {
+    pub fn new(tx: Sender<Message>) -> BatteryManager {
+        BatteryManager { tx, callbacks: HashMap::new(), levels: HashMap::new() }
+    }
+
+    pub(crate) fn remove_callback(&mut self, id: u32) -> bool {
+        match self.callbacks.get_mut(&id) {
+            Some(callback) => {
+                callback.unregister(id);
+                self.callbacks.remove(&id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records the level of `address` reported by a profile of the stack.
+    pub(crate) fn update_level(&mut self, address: &str, source: BatterySource, level: u32) {
+        if level > MAX_BATTERY_LEVEL {
+            warn!("[{}]: Ignoring battery level {} from {:?}", address, level, source);
+            return;
+        }
+
+        let levels = self.levels.entry(String::from(address)).or_default();
+        if levels.insert(source, level) == Some(level) {
+            return;
+        }
+
+        if let Some(battery_set) = self.get_battery_set(address) {
+            for callback in self.callbacks.values() {
+                callback.on_battery_info_updated(battery_set.clone());
+            }
+        }
+    }
+
+    /// Drops the level of `address` reported from `source`. Returns false if there was none.
+    pub(crate) fn remove_level(&mut self, address: &str, source: BatterySource) -> bool {
+        let levels = match self.levels.get_mut(address) {
+            Some(levels) => levels,
+            None => return false,
+        };
+
+        if levels.remove(&source).is_none() {
+            return false;
+        }
+
+        match self.get_battery_set(address) {
+            Some(battery_set) => {
+                for callback in self.callbacks.values() {
+                    callback.on_battery_info_updated(battery_set.clone());
+                }
+            }
+            None => {
+                self.levels.remove(address);
+                for callback in self.callbacks.values() {
+                    callback.on_battery_info_removed(String::from(address));
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Drops every level of `address`, e.g. once it disconnected.
+    pub(crate) fn remove_device(&mut self, address: &str) {
+        if self.levels.remove(address).is_none() {
+            return;
+        }
+
+        for callback in self.callbacks.values() {
+            callback.on_battery_info_removed(String::from(address));
+        }
+    }
+
+    /// Drops the levels of every device, e.g. once the adapter is off.
+    pub(crate) fn clear(&mut self) {
+        let addresses: Vec<String> = self.levels.keys().cloned().collect();
+        for address in addresses {
+            self.remove_device(&address);
+        }
+    }
+
+    fn get_battery_set(&self, address: &str) -> Option<BatterySet> {
+        let batteries: Vec<Battery> = self
+            .levels
+            .get(address)?
+            .iter()
+            .rev()
+            .map(|(source, level)| Battery { source: *source, level: *level })
+            .collect();
+
+        Some(BatterySet {
+            address: String::from(address),
+            level: batteries.first()?.level,
+            batteries,
+        })
+    }
+}
+
+impl IBluetoothBattery for BatteryManager {
+    fn register_battery_callback(
+        &mut self,
+        mut callback: Box<dyn IBluetoothBatteryCallback + Send>,
+    ) -> u32 {
+        let tx = self.tx.clone();
+
+        let id = callback.register_disconnect(Box::new(move |cb_id| {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let _result = tx.send(Message::BatteryCallbackDisconnected(cb_id)).await;
+            });
+        }));
+
+        self.callbacks.insert(id, callback);
+        id
+    }
+
+    fn unregister_battery_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_callback(callback_id)
+    }
+
+    fn get_battery_information(&self, address: String) -> BatterySet {
+        match self.get_battery_set(&address) {
+            Some(battery_set) => battery_set,
+            None => BatterySet { address, ..Default::default() },
+        }
+    }
+
+    fn set_battery_level(&mut self, address: String, source: BatterySource, level: u32) -> bool {
+        if level > MAX_BATTERY_LEVEL {
+            return false;
+        }
+
+        self.update_level(&address, source, level);
+        true
+    }
+
+    fn remove_battery_level(&mut self, address: String, source: BatterySource) -> bool {
+        self.remove_level(&address, source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_sources() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let mut manager = BatteryManager::new(tx);
+        let address = String::from("aa:bb:cc:dd:ee:ff");
+
+        assert!(manager.get_battery_information(address.clone()).batteries.is_empty());
+        assert!(!manager.set_battery_level(address.clone(), BatterySource::Gatt, 101));
+
+        manager.update_level(&address, BatterySource::Hfp, 60);
+        assert!(manager.set_battery_level(address.clone(), BatterySource::Gatt, 57));
+
+        // The most precise source wins.
+        let battery_set = manager.get_battery_information(address.clone());
+        assert_eq!(battery_set.level, 57);
+        assert_eq!(
+            battery_set.batteries,
+            vec![
+                Battery { source: BatterySource::Gatt, level: 57 },
+                Battery { source: BatterySource::Hfp, level: 60 },
+            ]
+        );
+
+        assert!(manager.remove_battery_level(address.clone(), BatterySource::Gatt));
+        assert!(!manager.remove_battery_level(address.clone(), BatterySource::Gatt));
+        assert_eq!(manager.get_battery_information(address.clone()).level, 60);
+
+        assert!(manager.remove_battery_level(address.clone(), BatterySource::Hfp));
+        assert!(manager.get_battery_information(address).batteries.is_empty());
+    }
+
+    #[test]
+    fn test_remove_device() {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let mut manager = BatteryManager::new(tx);
+        let first = String::from("aa:bb:cc:dd:ee:ff");
+        let second = String::from("11:22:33:44:55:66");
+
+        manager.update_level(&first, BatterySource::Hfp, 60);
+        assert!(manager.set_battery_level(first.clone(), BatterySource::Gatt, 57));
+        assert!(manager.set_battery_level(second.clone(), BatterySource::Hid, 20));
+
+        // Levels reported by clients go away with the connection too.
+        manager.remove_device(&first);
+        assert!(manager.get_battery_information(first.clone()).batteries.is_empty());
+        assert_eq!(manager.get_battery_information(second.clone()).level, 20);
+
+        manager.update_level(&first, BatterySource::Hfp, 60);
+        manager.clear();
+        assert!(manager.get_battery_information(first).batteries.is_empty());
+        assert!(manager.get_battery_information(second).batteries.is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+//! GATT Battery Service client.
+//!
+//! Once a bonded device connects over LE, the stack looks up the Battery Service on it with its
+//! own GATT client, reads the Battery Level characteristic and subscribes to its notifications.
+//! The levels go to the battery manager as coming from `BatterySource::Gatt`.
+
+use crate::battery_manager::{BatteryManager, BatterySource};
+use crate::bluetooth_gatt::{
+    BluetoothGatt, BluetoothGattCharacteristic, BluetoothGattService, GattConnectionPriority,
+    IBluetoothGatt, IBluetoothGattCallback, LePhy,
+};
+use crate::uuid::UuidHelper;
+use crate::{Message, RPCProxy};
+use bt_topshim::btif::BtTransport;
+use bt_topshim::profiles::gatt::GattStatus;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
+
+/// UUID the stack registers its Battery Service client with.
+const APP_UUID: &str = "6e0f5c1e-5f0a-4b7e-9d2c-0b3a7c4f91d2";
+
+pub const BATTERY_SERVICE: &str = "0000180F-0000-1000-8000-00805F9B34FB";
+pub const BATTERY_LEVEL: &str = "00002A19-0000-1000-8000-00805F9B34FB";
+const CLIENT_CHARACTERISTIC_CONFIG: &str = "00002902-0000-1000-8000-00805F9B34FB";
+
+/// Client Characteristic Configuration value enabling notifications.
+const ENABLE_NOTIFICATION_VALUE: [u8; 2] = [0x01, 0x00];
+
+/// Actions of the Battery Service client, run on the stack main loop.
+pub enum BatteryServiceActions {
+    /// The adapter is on, so the client can be registered.
+    Enable,
+    /// The adapter is off, taking the registration and connections with it.
+    Disable,
+    /// A bonded device connected over LE.
+    Connect(String),
+
+    // Events of the GATT client.
+    OnClientRegistered(i32, i32),
+    OnClientConnectionState(String, bool),
+    OnSearchComplete(String, Vec<BluetoothGattService>, i32),
+    OnCharacteristicRead(String, i32, i32, Vec<u8>),
+    OnNotify(String, i32, Vec<u8>),
+}
+
+/// Reads the battery level of connected devices over the GATT Battery Service.
+pub struct BatteryService {
+    tx: Sender<Message>,
+    gatt: Arc<Mutex<Box<BluetoothGatt>>>,
+    battery_manager: Arc<Mutex<Box<BatteryManager>>>,
+    /// Whether the client should be registered, i.e. the adapter is on.
+    enabled: bool,
+    client_id: Option<i32>,
+    /// Handle of the Battery Level characteristic of each connected device that has one.
+    handles: HashMap<String, i32>,
+}
+
+impl BatteryService {
+    pub fn new(
+        tx: Sender<Message>,
+        gatt: Arc<Mutex<Box<BluetoothGatt>>>,
+        battery_manager: Arc<Mutex<Box<BatteryManager>>>,
+    ) -> BatteryService {
+        BatteryService {
+            tx,
+            gatt,
+            battery_manager,
+            enabled: false,
+            client_id: None,
+            handles: HashMap::new(),
+        }
+    }
+
+    pub fn dispatch_battery_service_actions(&mut self, action: BatteryServiceActions) {
+        match action {
+            BatteryServiceActions::Enable => self.enable(),
+            BatteryServiceActions::Disable => self.disable(),
+            BatteryServiceActions::Connect(address) => self.connect(address),
+            BatteryServiceActions::OnClientRegistered(status, client_id) => {
+                self.on_client_registered(status, client_id)
+            }
+            BatteryServiceActions::OnClientConnectionState(address, connected) => {
+                self.on_client_connection_state(address, connected)
+            }
+            BatteryServiceActions::OnSearchComplete(address, services, status) => {
+                self.on_search_complete(address, services, status)
+            }
+            BatteryServiceActions::OnCharacteristicRead(address, status, handle, value) => {
+                if status == GattStatus::Success as i32 {
+                    self.on_battery_level(address, handle, value);
+                }
+            }
+            BatteryServiceActions::OnNotify(address, handle, value) => {
+                self.on_battery_level(address, handle, value)
+            }
+        }
+    }
+
+    fn enable(&mut self) {
+        if self.enabled {
+            return;
+        }
+        self.enabled = true;
+
+        let callback = Box::new(BatteryServiceCallback { tx: self.tx.clone() });
+        self.gatt.lock().unwrap().register_client(String::from(APP_UUID), callback, false);
+    }
+
+    fn disable(&mut self) {
+        self.enabled = false;
+        self.handles.clear();
+        if let Some(client_id) = self.client_id.take() {
+            self.gatt.lock().unwrap().unregister_client(client_id);
+        }
+    }
+
+    fn connect(&mut self, address: String) {
+        let client_id = match self.client_id {
+            Some(client_id) => client_id,
+            None => return,
+        };
+
+        // Rides on the existing link, without keeping it up.
+        self.gatt.lock().unwrap().client_connect(
+            client_id,
+            address,
+            false,
+            BtTransport::Le as i32,
+            true,
+            LePhy::Phy1m as i32,
+        );
+    }
+
+    fn on_client_registered(&mut self, status: i32, client_id: i32) {
+        if status != GattStatus::Success as i32 {
+            warn!("Failed to register the Battery Service client, status {}", status);
+            self.enabled = false;
+            return;
+        }
+
+        // The adapter went off while registering.
+        if !self.enabled {
+            self.gatt.lock().unwrap().unregister_client(client_id);
+            return;
+        }
+
+        self.client_id = Some(client_id);
+    }
+
+    fn on_client_connection_state(&mut self, address: String, connected: bool) {
+        let client_id = match self.client_id {
+            Some(client_id) => client_id,
+            None => return,
+        };
+
+        if !connected {
+            self.handles.remove(&address);
+            return;
+        }
+
+        self.gatt.lock().unwrap().discover_service_by_uuid(
+            client_id,
+            address,
+            String::from(BATTERY_SERVICE),
+        );
+    }
+
+    fn on_search_complete(
+        &mut self,
+        address: String,
+        services: Vec<BluetoothGattService>,
+        status: i32,
+    ) {
+        let client_id = match self.client_id {
+            Some(client_id) => client_id,
+            None => return,
+        };
+
+        if status != GattStatus::Success as i32 {
+            return;
+        }
+
+        let characteristic = match find_battery_level(&services) {
+            Some(characteristic) => characteristic,
+            None => {
+                debug!("[{}]: No Battery Service", address);
+                return;
+            }
+        };
+
+        let handle = characteristic.instance_id;
+        self.handles.insert(address.clone(), handle);
+
+        let gatt = self.gatt.lock().unwrap();
+        if characteristic.properties & BluetoothGattCharacteristic::PROPERTY_READ != 0 {
+            gatt.read_characteristic(client_id, address.clone(), handle, 0);
+        }
+
+        if characteristic.properties & BluetoothGattCharacteristic::PROPERTY_NOTIFY != 0 {
+            gatt.register_for_notification(client_id, address.clone(), handle, true);
+
+            let ccc = UuidHelper::from_string(CLIENT_CHARACTERISTIC_CONFIG);
+            if let Some(descriptor) =
+                characteristic.descriptors.iter().find(|d| Some(d.uuid) == ccc)
+            {
+                gatt.write_descriptor(
+                    client_id,
+                    address,
+                    descriptor.instance_id,
+                    0,
+                    ENABLE_NOTIFICATION_VALUE.to_vec(),
+                );
+            }
+        }
+    }
+
+    fn on_battery_level(&mut self, address: String, handle: i32, value: Vec<u8>) {
+        if self.handles.get(&address) != Some(&handle) {
+            return;
+        }
+
+        if let Some(level) = value.first() {
+            self.battery_manager.lock().unwrap().update_level(
+                &address,
+                BatterySource::Gatt,
+                *level as u32,
+            );
+        }
+    }
+}
+
+/// Finds the Battery Level characteristic of the first Battery Service among `services`.
+fn find_battery_level(services: &[BluetoothGattService]) -> Option<&BluetoothGattCharacteristic> {
+    let service_uuid = UuidHelper::from_string(BATTERY_SERVICE)?;
+    let level_uuid = UuidHelper::from_string(BATTERY_LEVEL)?;
+
+    services
+        .iter()
+        .filter(|service| service.uuid == service_uuid)
+        .flat_map(|service| service.characteristics.iter())
+        .find(|characteristic| characteristic.uuid == level_uuid)
+}
+
+/// Forwards the events of the Battery Service GATT client to the stack main loop.
+struct BatteryServiceCallback {
+    tx: Sender<Message>,
+}
+
+impl BatteryServiceCallback {
+    fn send(&self, action: BatteryServiceActions) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let _ = tx.send(Message::BatteryService(action)).await;
+        });
+    }
+}
+
+impl IBluetoothGattCallback for BatteryServiceCallback {
+    fn on_client_registered(&self, status: i32, client_id: i32) {
+        self.send(BatteryServiceActions::OnClientRegistered(status, client_id));
+    }
+
+    fn on_client_connection_state(
+        &self,
+        _status: i32,
+        _client_id: i32,
+        connected: bool,
+        addr: String,
+    ) {
+        self.send(BatteryServiceActions::OnClientConnectionState(addr, connected));
+    }
+
+    fn on_phy_update(&self, _addr: String, _tx_phy: LePhy, _rx_phy: LePhy, _status: GattStatus) {}
+
+    fn on_phy_read(&self, _addr: String, _tx_phy: LePhy, _rx_phy: LePhy, _status: GattStatus) {}
+
+    fn on_search_complete(&self, addr: String, services: Vec<BluetoothGattService>, status: i32) {
+        self.send(BatteryServiceActions::OnSearchComplete(addr, services, status));
+    }
+
+    fn on_characteristic_read(&self, addr: String, status: i32, handle: i32, value: Vec<u8>) {
+        self.send(BatteryServiceActions::OnCharacteristicRead(addr, status, handle, value));
+    }
+
+    fn on_characteristic_write(&self, _addr: String, _status: i32, _handle: i32) {}
+
+    fn on_execute_write(&self, _addr: String, _status: i32) {}
+
+    fn on_descriptor_read(&self, _addr: String, _status: i32, _handle: i32, _value: Vec<u8>) {}
+
+    fn on_descriptor_write(&self, _addr: String, _status: i32, _handle: i32) {}
+
+    fn on_notify(&self, addr: String, handle: i32, value: Vec<u8>) {
+        self.send(BatteryServiceActions::OnNotify(addr, handle, value));
+    }
+
+    fn on_read_remote_rssi(&self, _addr: String, _rssi: i32, _status: i32) {}
+
+    fn on_configure_mtu(&self, _addr: String, _mtu: i32, _status: i32) {}
+
+    fn on_connection_updated(
+        &self,
+        _addr: String,
+        _interval: i32,
+        _latency: i32,
+        _timeout: i32,
+        _status: i32,
+    ) {
+    }
+
+    fn on_connection_priority_updated(
+        &self,
+        _addr: String,
+        _priority: GattConnectionPriority,
+        _status: i32,
+    ) {
+    }
+
+    fn on_service_changed(&self, _addr: String) {}
+}
+
+/// The client lives in the stack, so it never disconnects.
+impl RPCProxy for BatteryServiceCallback {
+    fn register_disconnect(&mut self, _f: Box<dyn Fn(u32) + Send>) -> u32 {
+        0
+    }
+
+    fn get_object_id(&self) -> String {
+        String::from("battery_service")
+    }
+
+    fn unregister(&mut self, _id: u32) -> bool {
+        false
+    }
+
+    fn export_for_rpc(self: Box<Self>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service_with_battery_level(service_uuid: &str, handle: i32) -> BluetoothGattService {
+        let mut service = BluetoothGattService::default();
+        service.uuid = UuidHelper::from_string(service_uuid).unwrap();
+        service.characteristics.push(BluetoothGattCharacteristic {
+            uuid: UuidHelper::from_string(BATTERY_LEVEL).unwrap(),
+            instance_id: handle,
+            ..Default::default()
+        });
+        service
+    }
+
+    #[test]
+    fn test_find_battery_level() {
+        // A Battery Level characteristic outside of the Battery Service doesn't count.
+        let other = service_with_battery_level("0000180A-0000-1000-8000-00805F9B34FB", 7);
+        assert!(find_battery_level(&[other]).is_none());
+
+        let services = [
+            service_with_battery_level("0000180A-0000-1000-8000-00805F9B34FB", 7),
+            service_with_battery_level(BATTERY_SERVICE, 42),
+        ];
+        assert_eq!(find_battery_level(&services).map(|c| c.instance_id), Some(42));
+    }
+}
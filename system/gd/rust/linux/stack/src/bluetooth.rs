@@ -2,12 +2,22 @@
 
 use bt_topshim::btif::{
     BaseCallbacks, BaseCallbacksDispatcher, BluetoothInterface, BluetoothProperty, BtAclState,
-    BtBondState, BtDeviceType, BtDiscoveryState, BtHciErrorCode, BtPinCode, BtPropertyType,
-    BtScanMode, BtSspVariant, BtState, BtStatus, BtTransport, RawAddress, Uuid, Uuid128Bit,
+    BtBondFailReason, BtBondState, BtDeviceType, BtDiscoveryState, BtHciErrorCode, BtPinCode,
+    BtPropertyType, BtScanMode, BtSspVariant, BtState, BtStatus, BtTransport, RawAddress, Uuid,
+    Uuid128Bit,
 };
 use bt_topshim::{
-    profiles::hid_host::{HHCallbacksDispatcher, HidHost},
+    controller::{
+        format_vendor_debug_dump, is_local_addr_override_verified, CodecDirection, CodecTransport,
+        Controller, ControllerCallbacks, ControllerCallbacksDispatcher, HciLinkType,
+        KeypressNotificationType, LeAddressType, LeConnectionMode, LocalCodec,
+        LocalCodecCapabilities, RPA_TIMEOUT_MAX, RPA_TIMEOUT_MIN,
+    },
+    profiles::hid_host::{
+        BthhConnectionState, BthhHidInfo, BthhStatus, HHCallbacks, HHCallbacksDispatcher, HidHost,
+    },
     profiles::sdp::{BtSdpRecord, Sdp, SdpCallbacks, SdpCallbacksDispatcher},
+    profiles::stats::{self, ProfileInfo},
     topstack,
 };
 
@@ -15,20 +25,47 @@ use btif_macros::{btif_callback, btif_callbacks_dispatcher};
 
 use log::{debug, warn};
 use num_traits::cast::ToPrimitive;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
+use std::time::SystemTime;
 use tokio::sync::mpsc::Sender;
 use tokio::task::JoinHandle;
 use tokio::time;
 
+use crate::background_connections::{
+    AcceptListUpdate, BackgroundConnectionKind, BackgroundConnections, BackgroundDevice,
+    ConnectionOrigin,
+};
+use crate::battery_manager::{BatteryManager, BatterySource};
+use crate::battery_service::BatteryServiceActions;
+use crate::bluetooth_gatt::BluetoothGatt;
 use crate::bluetooth_media::{BluetoothMedia, IBluetoothMedia, MediaActions};
-use crate::uuid::{Profile, UuidHelper};
+use crate::bond_repair::{BondRepairAction, BondRepairs};
+use crate::device_ranking::{
+    rank_devices, RankedDevice, RankingInput, RankingUseCase, RankingWeights,
+};
+use crate::feature_support::{CapabilityStatus, ControllerFeature, FeatureRegistry, OffloadCodec};
+use crate::hid_battery::HidBatteryReport;
+use crate::power_profile::{IPowerProfileHook, PowerModule, PowerProfile, PowerProfiles};
+use crate::radio_test::{
+    RadioTest, RadioTestKind, RadioTestParams, RadioTestResult, RadioTestStatus,
+};
+use crate::service_refresh::{ServiceRefresh, SERVICE_REFRESH_TICK};
+use crate::tracer::{TraceCategory, Tracer};
+use crate::traffic::{
+    BufferOverflows, DeviceTraffic, TrafficAccounting, TrafficSnapshot, TRAFFIC_SNAPSHOT_INTERVAL,
+};
+use crate::uuid::{Profile, ProfileState, UuidHelper};
 use crate::{BluetoothCallbackType, Message, RPCProxy};
 
 const DEFAULT_DISCOVERY_TIMEOUT_MS: u64 = 12800;
+
+/// HCI status for invalid command parameters.
+const HCI_INVALID_PARAMETERS: u8 = 0x12;
 const MIN_ADV_INSTANCES_FOR_MULTI_ADV: u8 = 5;
 
 /// Devices that were last seen longer than this duration are considered stale
@@ -36,6 +73,9 @@ const MIN_ADV_INSTANCES_FOR_MULTI_ADV: u8 = 5;
 /// clear event should be sent to clients.
 const FOUND_DEVICE_FRESHNESS: Duration = Duration::from_secs(30);
 
+/// Where `write_trace` puts the recorded spans.
+const TRACE_FILE: &str = "/var/log/bluetooth/btadapterd_trace.json";
+
 /// Defines the adapter API.
 pub trait IBluetooth {
     /// Adds a callback from a client who wishes to observe adapter events.
@@ -165,6 +205,130 @@ pub trait IBluetooth {
 
     /// Disconnect all profiles supported by device and enabled on adapter.
     fn disconnect_all_enabled_profiles(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Returns bonded and discovered devices ranked for the given use case, best first.
+    fn get_ranked_devices(&self, use_case: RankingUseCase) -> Vec<RankedDevice>;
+
+    /// Sets the weights used to score devices in `get_ranked_devices`.
+    fn set_device_ranking_weights(&mut self, weights: RankingWeights) -> bool;
+
+    /// Allows a bonded device to reconnect on its own. LE devices are put in the controller
+    /// filter accept list if there is room, HID devices first.
+    fn add_background_connection(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Stops a device from reconnecting on its own.
+    fn remove_background_connection(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Gets the devices allowed to reconnect on their own.
+    fn get_background_connections(&self) -> Vec<BackgroundDevice>;
+
+    /// Rediscovers the services of bonded devices once they are older than `interval_sec`, one
+    /// device per minute. 0 stops the periodic refresh.
+    fn set_service_refresh_interval(&mut self, interval_sec: u32) -> bool;
+
+    /// Rediscovers the services of a bonded device within the next minute, e.g. after it was
+    /// updated. Returns false if the device isn't bonded.
+    fn request_service_refresh(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Sets the system power profile, which all managers follow unless overridden.
+    fn set_power_profile(&mut self, profile: PowerProfile) -> bool;
+
+    /// Gets the system power profile.
+    fn get_power_profile(&self) -> PowerProfile;
+
+    /// Makes `module` follow `profile` instead of the system power profile.
+    fn set_power_profile_override(&mut self, module: PowerModule, profile: PowerProfile) -> bool;
+
+    /// Makes `module` follow the system power profile again.
+    fn clear_power_profile_override(&mut self, module: PowerModule) -> bool;
+
+    /// Gets the power profile `module` currently follows.
+    fn get_effective_power_profile(&self, module: PowerModule) -> PowerProfile;
+
+    /// Gets whether each capability registered by the managers is supported by the controller.
+    fn get_capabilities(&self) -> Vec<CapabilityStatus>;
+
+    /// Gets the codecs the controller can encode and decode itself, with their capabilities.
+    /// Empty until they were read from the controller, which happens when the adapter turns on.
+    fn get_offload_codecs(&self) -> Vec<OffloadCodec>;
+
+    /// Enters or leaves radio test mode. Entering requires that no device is connected; while in
+    /// test mode the adapter is neither connectable nor discoverable, background connections are
+    /// paused and discovery, bonding and connections are refused. The previous scan mode is
+    /// restored when leaving.
+    fn set_radio_test_mode(&mut self, enabled: bool) -> RadioTestStatus;
+
+    /// Starts an RF test. Only one test runs at a time and it ends on its own after
+    /// `params.timeout_ms`.
+    fn start_radio_test(&mut self, params: RadioTestParams) -> RadioTestStatus;
+
+    /// Ends the running RF test.
+    fn stop_radio_test(&mut self) -> RadioTestStatus;
+
+    /// Gets the results of the last RF tests, oldest first.
+    fn get_radio_test_results(&self) -> Vec<RadioTestResult>;
+
+    /// Removes the bond with a device and pairs with it again once the keys are gone. Meant to
+    /// resolve `on_bond_conflict`.
+    fn forget_and_repair(&mut self, device: BluetoothDevice) -> bool;
+
+    /// Tells a bonding device about the progress of the local passkey entry, so that it can show
+    /// it to the user.
+    fn send_keypress_notification(
+        &self,
+        device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    ) -> bool;
+
+    /// Sets how often the local resolvable private address is rotated, in seconds. The timeout
+    /// is kept while the adapter is off and applied whenever it is enabled.
+    fn set_rpa_timeout(&mut self, timeout_s: u16) -> bool;
+
+    /// Gets the RPA timeout in seconds set with `set_rpa_timeout`, or 0 if the controller
+    /// default is used.
+    fn get_rpa_timeout(&self) -> u16;
+
+    /// Programs a new public address into the controller, for factory provisioning. Only allowed
+    /// if btadapterd was started with `INIT_provisioning_mode=true` and no device is connected.
+    /// The address read back from the controller is reported with
+    /// `on_local_address_override_completed`. The stack keeps using the old address until it is
+    /// restarted.
+    fn override_local_address(&mut self, address: String) -> bool;
+
+    /// Asks the controller to send its vendor debug statistics. They arrive as vendor events
+    /// and are collected until read with `get_controller_debug_dump`. Returns false if the
+    /// adapter is off or the controller doesn't support it.
+    fn start_controller_debug_dump(&mut self) -> bool;
+
+    /// Returns the controller debug statistics collected since `start_controller_debug_dump`,
+    /// formatted for diagnostics. The collected data is dropped.
+    fn get_controller_debug_dump(&mut self) -> String;
+
+    /// Starts or stops recording latency spans of pairing, profile connections and GATT
+    /// operations. Both drop the spans recorded so far.
+    fn set_tracing_enabled(&mut self, enabled: bool) -> bool;
+
+    /// Writes the recorded spans to the trace file in the Chrome JSON trace format, which
+    /// Perfetto can open.
+    fn write_trace(&self) -> bool;
+
+    /// Gets the version of each profile shim and whether its profile initialized successfully.
+    fn get_profile_info(&self) -> Vec<ProfileInfo>;
+
+    /// Gets whether each known profile is supported, enabled, initialized and connected, with
+    /// the reason a profile can't be used.
+    fn get_profile_states(&self) -> Vec<ProfileState>;
+
+    /// Gets the bytes exchanged with each device per profile since the stack started.
+    fn get_device_traffic(&self) -> Vec<DeviceTraffic>;
+
+    /// Gets the totals of `get_device_traffic` as they were every 5 minutes over the last hour,
+    /// oldest first.
+    fn get_traffic_snapshots(&self) -> Vec<TrafficSnapshot>;
+
+    /// Gets how many times the controller ran out of data buffers since the stack started, per
+    /// link type.
+    fn get_buffer_overflows(&self) -> BufferOverflows;
 }
 
 /// Serializable device used in various apis.
@@ -205,6 +369,8 @@ struct BluetoothDeviceContext {
     pub acl_state: BtAclState,
     pub info: BluetoothDevice,
     pub last_seen: Instant,
+    /// Last time the ACL connection was established or dropped while connected.
+    pub last_connected: Option<Instant>,
     pub properties: HashMap<BtPropertyType, BluetoothProperty>,
 }
 
@@ -221,6 +387,7 @@ impl BluetoothDeviceContext {
             acl_state,
             info,
             last_seen,
+            last_connected: None,
             properties: HashMap::new(),
         };
         device.update_properties(properties);
@@ -278,13 +445,39 @@ pub trait IBluetoothCallback: RPCProxy {
         passkey: u32,
     );
 
-    /// When a bonding attempt has completed.
-    fn on_bond_state_changed(&self, status: u32, device_address: String, state: u32);
+    /// When a bonding attempt has completed. `fail_reason` tells why a failed attempt failed.
+    fn on_bond_state_changed(
+        &self,
+        status: u32,
+        device_address: String,
+        state: u32,
+        fail_reason: BtBondFailReason,
+    );
+
+    /// When authentication with a bonded device failed in a way that suggests the device deleted
+    /// its keys. The bond can be fixed with `forget_and_repair`.
+    fn on_bond_conflict(&self, remote_device: BluetoothDevice, fail_reason: BtBondFailReason);
+
+    /// When a bonding device reports the progress of a passkey entry on its side.
+    fn on_keypress_notification(
+        &self,
+        remote_device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    );
+
+    /// When an `override_local_address` request completed. `verified` tells whether the
+    /// controller accepted the address and `readback` matches `requested`.
+    fn on_local_address_override_completed(
+        &self,
+        requested: String,
+        readback: String,
+        verified: bool,
+    );
 }
 
 pub trait IBluetoothConnectionCallback: RPCProxy {
     /// Notification sent when a remote device completes HCI connection.
-    fn on_device_connected(&self, remote_device: BluetoothDevice);
+    fn on_device_connected(&self, remote_device: BluetoothDevice, origin: ConnectionOrigin);
 
     /// Notification sent when a remote device completes HCI disconnection.
     fn on_device_disconnected(&self, remote_device: BluetoothDevice);
@@ -294,21 +487,44 @@ pub trait IBluetoothConnectionCallback: RPCProxy {
 pub struct Bluetooth {
     intf: Arc<Mutex<BluetoothInterface>>,
 
+    accept_list_capacity: usize,
+    background_connections: BackgroundConnections,
+    bond_repairs: BondRepairs,
     bonded_devices: HashMap<String, BluetoothDeviceContext>,
+    bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>,
     bluetooth_media: Arc<Mutex<Box<BluetoothMedia>>>,
+    battery_manager: Arc<Mutex<Box<BatteryManager>>>,
     callbacks: HashMap<u32, Box<dyn IBluetoothCallback + Send>>,
     connection_callbacks: HashMap<u32, Box<dyn IBluetoothConnectionCallback + Send>>,
+    controller: Option<Controller>,
     discovering_started: Instant,
+    feature_registry: Arc<Mutex<FeatureRegistry>>,
     hh: Option<HidHost>,
+    /// Devices the HID host profile is connected to.
+    hid_connections: HashSet<String>,
+    /// Report holding the battery level of each connected HID device that has one.
+    hid_battery_reports: HashMap<String, HidBatteryReport>,
     is_connectable: bool,
     is_discovering: bool,
     local_address: Option<RawAddress>,
+    power_profiles: PowerProfiles,
     properties: HashMap<BtPropertyType, BluetoothProperty>,
     profiles_ready: bool,
+    radio_test: RadioTest,
+    /// Connectability and scan mode to go back to when leaving radio test mode.
+    radio_test_saved_scan_mode: Option<(bool, BtScanMode)>,
+    /// RPA timeout requested by a client, in seconds.
+    rpa_timeout: Option<u16>,
     found_devices: HashMap<String, BluetoothDeviceContext>,
     freshness_check: Option<JoinHandle<()>>,
+    ranking_weights: RankingWeights,
     sdp: Option<Sdp>,
+    service_refresh: ServiceRefresh,
+    service_refresh_tick: Option<JoinHandle<()>>,
     state: BtState,
+    tracer: Arc<Mutex<Tracer>>,
+    traffic: Arc<Mutex<TrafficAccounting>>,
+    traffic_snapshot_tick: Option<JoinHandle<()>>,
     tx: Sender<Message>,
     uuid_helper: UuidHelper,
     /// Used to delay connection until we have SDP results.
@@ -320,25 +536,49 @@ impl Bluetooth {
     pub fn new(
         tx: Sender<Message>,
         intf: Arc<Mutex<BluetoothInterface>>,
+        bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>,
         bluetooth_media: Arc<Mutex<Box<BluetoothMedia>>>,
+        battery_manager: Arc<Mutex<Box<BatteryManager>>>,
+        feature_registry: Arc<Mutex<FeatureRegistry>>,
+        tracer: Arc<Mutex<Tracer>>,
+        traffic: Arc<Mutex<TrafficAccounting>>,
     ) -> Bluetooth {
         Bluetooth {
+            accept_list_capacity: 0,
+            background_connections: BackgroundConnections::new(),
+            bond_repairs: BondRepairs::new(),
             bonded_devices: HashMap::new(),
             callbacks: HashMap::new(),
             connection_callbacks: HashMap::new(),
+            controller: None,
             hh: None,
+            hid_connections: HashSet::new(),
+            hid_battery_reports: HashMap::new(),
+            bluetooth_gatt,
             bluetooth_media,
+            battery_manager,
             discovering_started: Instant::now(),
+            feature_registry,
             intf,
             is_connectable: false,
             is_discovering: false,
             local_address: None,
+            power_profiles: PowerProfiles::new(),
             properties: HashMap::new(),
             profiles_ready: false,
+            radio_test: RadioTest::new(),
+            radio_test_saved_scan_mode: None,
+            rpa_timeout: None,
             found_devices: HashMap::new(),
             freshness_check: None,
+            ranking_weights: RankingWeights::default(),
             sdp: None,
+            service_refresh: ServiceRefresh::new(),
+            service_refresh_tick: None,
             state: BtState::Off,
+            tracer,
+            traffic,
+            traffic_snapshot_tick: None,
             tx,
             uuid_helper: UuidHelper::new(),
             wait_to_connect: false,
@@ -368,6 +608,25 @@ impl Bluetooth {
             }),
         });
 
+        let controllertx = self.tx.clone();
+        self.controller = Some(Controller::new());
+        self.controller.as_mut().unwrap().initialize(ControllerCallbacksDispatcher {
+            dispatch: Box::new(move |cb| {
+                let txl = controllertx.clone();
+                topstack::get_runtime().spawn(async move {
+                    let _ = txl.send(Message::Controller(cb)).await;
+                });
+            }),
+        });
+
+        let traffictx = self.tx.clone();
+        self.traffic_snapshot_tick = Some(tokio::spawn(async move {
+            loop {
+                time::sleep(TRAFFIC_SNAPSHOT_INTERVAL).await;
+                let _ = traffictx.send(Message::TrafficSnapshot).await;
+            }
+        }));
+
         // Mark profiles as ready
         self.profiles_ready = true;
     }
@@ -477,6 +736,152 @@ impl Bluetooth {
         Ok(())
     }
 
+    fn get_ranking_input(&self, d: &BluetoothDeviceContext) -> RankingInput {
+        let rssi = match d.properties.get(&BtPropertyType::RemoteRssi) {
+            Some(BluetoothProperty::RemoteRssi(rssi)) => Some(*rssi),
+            _ => None,
+        };
+
+        let profiles = self
+            .get_remote_uuids(d.info.clone())
+            .iter()
+            .filter_map(|uuid| self.uuid_helper.is_known_profile(uuid))
+            .collect();
+
+        RankingInput {
+            device: d.info.clone(),
+            bonded: d.bond_state == BtBondState::Bonded,
+            connected: d.acl_state == BtAclState::Connected,
+            rssi,
+            since_connected: d.last_connected.map(|t| t.elapsed()),
+            profiles,
+        }
+    }
+
+    fn apply_accept_list_updates(&mut self, updates: Vec<AcceptListUpdate>) {
+        let controller = match self.controller.as_mut() {
+            Some(c) => c,
+            None => return,
+        };
+
+        for update in updates {
+            match update {
+                AcceptListUpdate::Add(address, address_type) => {
+                    let addr = match RawAddress::from_string(address.clone()) {
+                        Some(a) => a,
+                        None => continue,
+                    };
+
+                    // The capacity doesn't account for the entries of the rest of the stack, so
+                    // the accept list may still be full.
+                    if let Err(e) =
+                        controller.le_connect(addr, address_type, LeConnectionMode::Background)
+                    {
+                        warn!("Failed to add [{}] to the accept list: {:?}", address, e);
+                        self.background_connections.accept_list_add_failed(&address);
+                    }
+                }
+                // The controller wrapper remembers the type the entry was added with.
+                AcceptListUpdate::Remove(address, _) => {
+                    if let Some(addr) = RawAddress::from_string(address) {
+                        controller.le_cancel_connect(&addr);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resizes the background connection share of the accept list to the controller capacity and
+    /// the adapter power profile.
+    fn update_background_capacity(&mut self) {
+        let policy = self.power_profiles.get_effective_profile(PowerModule::Adapter).get_policy();
+        let capacity = match policy.max_background_connections {
+            // Background connections are paused in radio test mode.
+            _ if self.radio_test.is_test_mode() => 0,
+            Some(max) => std::cmp::min(max, self.accept_list_capacity),
+            None => self.accept_list_capacity,
+        };
+
+        let updates = self.background_connections.set_capacity(capacity);
+        self.apply_accept_list_updates(updates);
+    }
+
+    fn get_connected_device_count(&self) -> usize {
+        self.bonded_devices
+            .values()
+            .chain(self.found_devices.values())
+            .filter(|d| d.acl_state == BtAclState::Connected)
+            .count()
+    }
+
+    /// Ends the running RF test once its timeout expired.
+    pub fn on_radio_test_timeout(&mut self, id: u32) {
+        if self.radio_test.on_timeout(id) {
+            debug!("Radio test timed out");
+            if let Some(controller) = self.controller.as_ref() {
+                controller.le_test_end();
+            }
+        }
+    }
+
+    fn send_battery_service_action(&self, action: BatteryServiceActions) {
+        let txl = self.tx.clone();
+        topstack::get_runtime().spawn(async move {
+            let _ = txl.send(Message::BatteryService(action)).await;
+        });
+    }
+
+    fn notify_power_profile_changes(&self, modules: Vec<PowerModule>) {
+        for module in modules {
+            let profile = self.power_profiles.get_effective_profile(module);
+            let txl = self.tx.clone();
+            topstack::get_runtime().spawn(async move {
+                let _ = txl.send(Message::PowerProfileChanged(module, profile)).await;
+            });
+        }
+    }
+
+    /// Starts the next service refresh tick unless one is pending or there is nothing to refresh.
+    fn schedule_service_refresh(&mut self) {
+        if self.service_refresh_tick.is_some() || !self.service_refresh.is_active() {
+            return;
+        }
+
+        let txl = self.tx.clone();
+        self.service_refresh_tick = Some(tokio::spawn(async move {
+            time::sleep(SERVICE_REFRESH_TICK).await;
+            let _ = txl.send(Message::ServiceRefresh).await;
+        }));
+    }
+
+    /// Rediscovers the services of the bonded device that is due next, if any.
+    pub(crate) fn refresh_next_device_services(&mut self) {
+        self.service_refresh_tick = None;
+
+        if self.state == BtState::On {
+            let bonded: Vec<String> = self.bonded_devices.keys().cloned().collect();
+            if let Some(address) = self.service_refresh.next_due(&bonded, Instant::now()) {
+                debug!("Refreshing services of {}", address);
+                if let Some(mut addr) = RawAddress::from_string(address) {
+                    self.intf.lock().unwrap().get_remote_services(&mut addr, BtTransport::Auto);
+                }
+            }
+        }
+
+        self.schedule_service_refresh();
+    }
+
+    /// Records the current traffic totals as a snapshot.
+    pub(crate) fn snapshot_traffic(&mut self) {
+        // A2DP is only sampled, bring it up to date first.
+        self.bluetooth_media.lock().unwrap().update_a2dp_traffic();
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        self.traffic.lock().unwrap().snapshot(timestamp_ms);
+    }
+
     /// Check whether found devices are still fresh. If they're outside the
     /// freshness window, send a notification to clear the device from clients.
     pub(crate) fn trigger_freshness_check(&mut self) {
@@ -555,7 +960,7 @@ pub(crate) trait BtifBluetoothCallbacks {
         status: BtStatus,
         addr: RawAddress,
         bond_state: BtBondState,
-        fail_reason: i32,
+        fail_reason: BtBondFailReason,
     );
 
     #[btif_callback(RemoteDeviceProperties)]
@@ -591,6 +996,18 @@ pub(crate) trait BtifSdpCallbacks {
     );
 }
 
+#[btif_callbacks_dispatcher(Bluetooth, dispatch_hid_host_callbacks, HHCallbacks)]
+pub(crate) trait BtifHHCallbacks {
+    #[btif_callback(ConnectionState)]
+    fn connection_state(&mut self, address: RawAddress, state: BthhConnectionState);
+
+    #[btif_callback(HidInfo)]
+    fn hid_info(&mut self, address: RawAddress, info: BthhHidInfo);
+
+    #[btif_callback(GetReport)]
+    fn get_report(&mut self, address: RawAddress, status: BthhStatus, data: Vec<u8>, size: i32);
+}
+
 pub fn get_bt_dispatcher(tx: Sender<Message>) -> BaseCallbacksDispatcher {
     BaseCallbacksDispatcher {
         dispatch: Box::new(move |cb| {
@@ -614,10 +1031,48 @@ impl BtifBluetoothCallbacks for Bluetooth {
 
         if self.state == BtState::On {
             self.bluetooth_media.lock().unwrap().initialize();
+            self.send_battery_service_action(BatteryServiceActions::Enable);
+
+            // The controller is up, so the accept list can be filled again. Entries added by the
+            // rest of the stack are left alone.
+            if let Some(controller) = self.controller.as_mut() {
+                let features = ControllerFeature::from_controller(&controller.read_features());
+                self.feature_registry.lock().unwrap().set_controller_features(Some(features));
+
+                self.accept_list_capacity = controller.le_accept_list_capacity();
+                self.update_background_capacity();
+
+                controller.register_keypress_notifications();
+                controller.register_transport_error_events();
+                controller.register_vendor_debug_events();
+                controller.read_local_supported_codecs();
+
+                // Advertise the TX Power Level the controller reports in the EIR.
+                controller.set_eir_tx_power_included(true);
+
+                // The controller is back to its default RPA timeout.
+                if let Some(timeout_s) = self.rpa_timeout {
+                    controller.set_rpa_timeout(timeout_s);
+                }
+            }
         }
 
         if self.state == BtState::Off {
             self.properties.clear();
+            self.background_connections.reset_accept_list();
+            if let Some(controller) = self.controller.as_mut() {
+                controller.reset_le_connections();
+            }
+            self.bluetooth_media.lock().unwrap().cleanup();
+            self.bluetooth_gatt.lock().unwrap().handle_adapter_off();
+            self.send_battery_service_action(BatteryServiceActions::Disable);
+            self.battery_manager.lock().unwrap().clear();
+            self.feature_registry.lock().unwrap().set_controller_features(None);
+            self.radio_test.reset();
+            self.radio_test_saved_scan_mode = None;
+            self.bond_repairs.clear();
+            self.hid_connections.clear();
+            self.hid_battery_reports.clear();
         } else {
             // Trigger properties update
             self.intf.lock().unwrap().get_adapter_properties();
@@ -759,13 +1214,35 @@ impl BtifBluetoothCallbacks for Bluetooth {
         status: BtStatus,
         addr: RawAddress,
         bond_state: BtBondState,
-        _fail_reason: i32,
+        fail_reason: BtBondFailReason,
     ) {
         let address = addr.to_string();
 
+        match bond_state {
+            // Also covers bonding started by the remote device.
+            BtBondState::Bonding => {
+                self.tracer.lock().unwrap().begin(TraceCategory::Pairing, "bond", &address)
+            }
+            BtBondState::Bonded => {
+                self.tracer.lock().unwrap().end(TraceCategory::Pairing, "bond", &address, "Bonded")
+            }
+            BtBondState::NotBonded => self.tracer.lock().unwrap().end(
+                TraceCategory::Pairing,
+                "bond",
+                &address,
+                &format!("{:?} {:?}", status, fail_reason),
+            ),
+        }
+
+        // Taken before the device is removed from the bonded list, to report a conflict.
+        let bonded_device = self.bonded_devices.get(&address).map(|d| d.info.clone());
+
         // Easy case of not bonded -- we remove the device from the bonded list and change the bond
         // state in the found list (in case it was previously bonding).
         if &bond_state == &BtBondState::NotBonded {
+            if let Some(updates) = self.background_connections.remove(&address) {
+                self.apply_accept_list_updates(updates);
+            }
             self.bonded_devices.remove(&address);
             self.found_devices
                 .entry(address.clone())
@@ -790,6 +1267,11 @@ impl BtifBluetoothCallbacks for Bluetooth {
                 ),
             };
 
+            // Bonding over an existing link doesn't reconnect, so look for the battery now.
+            if device.acl_state == BtAclState::Connected {
+                self.send_battery_service_action(BatteryServiceActions::Connect(address.clone()));
+            }
+
             self.bonded_devices.insert(address.clone(), device);
         } else {
             // If we're bonding, we need to update the found devices list
@@ -804,8 +1286,32 @@ impl BtifBluetoothCallbacks for Bluetooth {
                 status.to_u32().unwrap(),
                 address.clone(),
                 bond_state.to_u32().unwrap(),
+                fail_reason,
             );
         });
+
+        let was_bonded = bonded_device.is_some();
+        match self.bond_repairs.on_bond_state_changed(
+            &address,
+            was_bonded,
+            &bond_state,
+            fail_reason,
+        ) {
+            BondRepairAction::Rebond => {
+                let device = BluetoothDevice::new(address.clone(), String::from(""));
+                if !self.create_bond(device, BtTransport::Auto) {
+                    warn!("Failed to pair again with {}", address);
+                }
+            }
+            BondRepairAction::Conflict => {
+                warn!("Bond conflict with {}: {:?}", address, fail_reason);
+                let device = bonded_device.unwrap();
+                self.for_all_callbacks(|callback| {
+                    callback.on_bond_conflict(device.clone(), fail_reason);
+                });
+            }
+            BondRepairAction::None => (),
+        }
     }
 
     fn remote_device_properties_changed(
@@ -816,6 +1322,11 @@ impl BtifBluetoothCallbacks for Bluetooth {
         properties: Vec<BluetoothProperty>,
     ) {
         let address = addr.to_string();
+        let has_uuids = properties.iter().any(|p| match p {
+            BluetoothProperty::Uuids(_) => true,
+            _ => false,
+        });
+
         let device = match self.get_remote_device_if_found_mut(&address) {
             None => {
                 self.found_devices.insert(
@@ -840,6 +1351,10 @@ impl BtifBluetoothCallbacks for Bluetooth {
                 d.seen();
 
                 let info = d.info.clone();
+                if has_uuids {
+                    self.service_refresh.on_refreshed(&address, Instant::now());
+                }
+
                 let uuids = self.get_remote_uuids(info.clone());
                 if self.wait_to_connect && uuids.len() > 0 {
                     self.connect_all_enabled_profiles(info);
@@ -854,15 +1369,28 @@ impl BtifBluetoothCallbacks for Bluetooth {
         status: BtStatus,
         addr: RawAddress,
         state: BtAclState,
-        _link_type: BtTransport,
+        link_type: BtTransport,
         _hci_reason: BtHciErrorCode,
     ) {
         if status != BtStatus::Success {
             warn!("Connection to [{}] failed. Status: {:?}", addr.to_string(), status);
+            self.background_connections.clear_local_attempt(&addr.to_string());
             return;
         }
 
         let address = addr.to_string();
+        let origin = match state {
+            BtAclState::Connected => {
+                if link_type == BtTransport::Le {
+                    if let Some(controller) = self.controller.as_mut() {
+                        controller.on_le_connection_complete(&addr);
+                    }
+                }
+                self.background_connections.take_origin(&address)
+            }
+            BtAclState::Disconnected => ConnectionOrigin::Unknown,
+        };
+
         let device = match self.get_remote_device_if_found_mut(&address) {
             None => {
                 self.found_devices.insert(
@@ -888,14 +1416,24 @@ impl BtifBluetoothCallbacks for Bluetooth {
                 if prev_state != &state {
                     let device = found.info.clone();
                     found.acl_state = state.clone();
+                    found.last_connected = Some(Instant::now());
 
                     match state {
                         BtAclState::Connected => {
+                            if link_type == BtTransport::Le
+                                && self.bonded_devices.contains_key(&address)
+                            {
+                                self.send_battery_service_action(BatteryServiceActions::Connect(
+                                    address.clone(),
+                                ));
+                            }
                             self.for_all_connection_callbacks(|callback| {
-                                callback.on_device_connected(device.clone());
+                                callback.on_device_connected(device.clone(), origin);
                             });
                         }
                         BtAclState::Disconnected => {
+                            self.battery_manager.lock().unwrap().remove_device(&address);
+                            self.tracer.lock().unwrap().end_all(&address, "Disconnected");
                             self.for_all_connection_callbacks(|callback| {
                                 callback.on_device_disconnected(device.clone());
                             });
@@ -963,6 +1501,9 @@ impl IBluetooth for Bluetooth {
     }
 
     fn disable(&mut self) -> bool {
+        if let Some(controller) = self.controller.as_ref() {
+            controller.unregister_vendor_debug_events();
+        }
         self.intf.lock().unwrap().disable() == 0
     }
 
@@ -1037,6 +1578,11 @@ impl IBluetooth for Bluetooth {
     }
 
     fn set_discoverable(&self, mode: bool, duration: u32) -> bool {
+        if self.radio_test.is_test_mode() {
+            warn!("Can't change discoverability in radio test mode");
+            return false;
+        }
+
         self.intf
             .lock()
             .unwrap()
@@ -1077,6 +1623,11 @@ impl IBluetooth for Bluetooth {
     }
 
     fn start_discovery(&self) -> bool {
+        if self.radio_test.is_test_mode() {
+            warn!("Can't start discovery in radio test mode");
+            return false;
+        }
+
         self.intf.lock().unwrap().start_discovery() == 0
     }
 
@@ -1111,9 +1662,15 @@ impl IBluetooth for Bluetooth {
 
         let address = addr.unwrap();
 
+        if self.radio_test.is_test_mode() {
+            warn!("Can't create bond in radio test mode");
+            return false;
+        }
+
         // BREDR connection won't work when Inquiry is in progress.
         self.cancel_discovery();
 
+        self.tracer.lock().unwrap().begin(TraceCategory::Pairing, "bond", &address.to_string());
         self.intf.lock().unwrap().create_bond(&address, transport) == 0
     }
 
@@ -1354,6 +1911,13 @@ impl IBluetooth for Bluetooth {
             return false;
         }
 
+        if self.radio_test.is_test_mode() {
+            warn!("Can't connect profiles in radio test mode");
+            return false;
+        }
+
+        self.background_connections.mark_local_attempt(device.address.clone());
+
         // Check all remote uuids to see if they match enabled profiles and connect them.
         let mut has_enabled_uuids = false;
         let uuids = self.get_remote_uuids(device.clone());
@@ -1367,6 +1931,14 @@ impl IBluetooth for Bluetooth {
                             }
 
                             Profile::A2dpSink | Profile::A2dpSource => {
+                                // Started here so the spans include the hand-over to media.
+                                {
+                                    let key = addr.unwrap().to_string();
+                                    let mut tracer = self.tracer.lock().unwrap();
+                                    tracer.begin(TraceCategory::ProfileConnect, "a2dp", &key);
+                                    tracer.begin(TraceCategory::ProfileConnect, "hfp", &key);
+                                }
+
                                 let txl = self.tx.clone();
                                 let address = device.address.clone();
                                 topstack::get_runtime().spawn(async move {
@@ -1436,6 +2008,602 @@ impl IBluetooth for Bluetooth {
 
         return true;
     }
+
+    fn get_ranked_devices(&self, use_case: RankingUseCase) -> Vec<RankedDevice> {
+        let inputs: Vec<RankingInput> = self
+            .bonded_devices
+            .values()
+            .chain(self.found_devices.values())
+            .map(|d| self.get_ranking_input(d))
+            .collect();
+
+        rank_devices(&inputs, use_case, &self.ranking_weights)
+    }
+
+    fn set_device_ranking_weights(&mut self, weights: RankingWeights) -> bool {
+        self.ranking_weights = weights;
+        true
+    }
+
+    fn add_background_connection(&mut self, device: BluetoothDevice) -> bool {
+        if !self.bonded_devices.contains_key(&device.address) {
+            warn!("Can't add background connection for unbonded device [{}]", device.address);
+            return false;
+        }
+
+        let profiles: Vec<Profile> = self
+            .get_remote_uuids(device.clone())
+            .iter()
+            .filter_map(|uuid| self.uuid_helper.is_known_profile(uuid))
+            .collect();
+        let kind = BackgroundConnectionKind::from_profiles(&profiles);
+        let is_le = match self.get_remote_type(device.clone()) {
+            BtDeviceType::Ble | BtDeviceType::Dual => true,
+            _ => false,
+        };
+        let address_type =
+            match (self.controller.as_ref(), RawAddress::from_string(&device.address)) {
+                (Some(controller), Some(addr)) => controller.get_le_address_type(&addr),
+                _ => LeAddressType::Public,
+            };
+
+        let updates = self.background_connections.add(device.address, kind, is_le, address_type);
+        self.apply_accept_list_updates(updates);
+        true
+    }
+
+    fn remove_background_connection(&mut self, device: BluetoothDevice) -> bool {
+        match self.background_connections.remove(&device.address) {
+            Some(updates) => {
+                self.apply_accept_list_updates(updates);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_background_connections(&self) -> Vec<BackgroundDevice> {
+        self.background_connections.get_devices()
+    }
+
+    fn set_service_refresh_interval(&mut self, interval_sec: u32) -> bool {
+        let interval = match interval_sec {
+            0 => None,
+            sec => Some(Duration::from_secs(sec.into())),
+        };
+        self.service_refresh.set_interval(interval);
+        self.schedule_service_refresh();
+        true
+    }
+
+    fn request_service_refresh(&mut self, device: BluetoothDevice) -> bool {
+        if !self.bonded_devices.contains_key(&device.address) {
+            warn!("Won't refresh services of unbonded device {}", device.address);
+            return false;
+        }
+
+        self.service_refresh.request(device.address);
+        self.schedule_service_refresh();
+        true
+    }
+
+    fn set_power_profile(&mut self, profile: PowerProfile) -> bool {
+        let changed = self.power_profiles.set_profile(profile);
+        self.notify_power_profile_changes(changed);
+        true
+    }
+
+    fn get_power_profile(&self) -> PowerProfile {
+        self.power_profiles.get_profile()
+    }
+
+    fn set_power_profile_override(&mut self, module: PowerModule, profile: PowerProfile) -> bool {
+        let changed = self.power_profiles.set_override(module, Some(profile));
+        self.notify_power_profile_changes(changed);
+        true
+    }
+
+    fn clear_power_profile_override(&mut self, module: PowerModule) -> bool {
+        let changed = self.power_profiles.set_override(module, None);
+        self.notify_power_profile_changes(changed);
+        true
+    }
+
+    fn get_effective_power_profile(&self, module: PowerModule) -> PowerProfile {
+        self.power_profiles.get_effective_profile(module)
+    }
+
+    fn get_capabilities(&self) -> Vec<CapabilityStatus> {
+        self.feature_registry.lock().unwrap().get_all()
+    }
+
+    fn get_offload_codecs(&self) -> Vec<OffloadCodec> {
+        let controller = match self.controller.as_ref() {
+            Some(controller) => controller,
+            None => return vec![],
+        };
+
+        OffloadCodec::from_controller(
+            &controller.get_local_codecs().unwrap_or_default(),
+            &controller.get_local_codec_capabilities(),
+        )
+    }
+
+    fn set_radio_test_mode(&mut self, enabled: bool) -> RadioTestStatus {
+        if enabled == self.radio_test.is_test_mode() {
+            return RadioTestStatus::Success;
+        }
+
+        if enabled && (self.state != BtState::On || self.controller.is_none()) {
+            return RadioTestStatus::ControllerUnavailable;
+        }
+
+        let status = self.radio_test.set_test_mode(enabled, self.get_connected_device_count());
+        if status != RadioTestStatus::Success {
+            return status;
+        }
+
+        if enabled {
+            self.cancel_discovery();
+
+            let scan_mode = match self.properties.get(&BtPropertyType::AdapterScanMode) {
+                Some(BluetoothProperty::AdapterScanMode(mode)) => mode.clone(),
+                _ if self.is_connectable => BtScanMode::Connectable,
+                _ => BtScanMode::None_,
+            };
+            self.radio_test_saved_scan_mode = Some((self.is_connectable, scan_mode));
+            self.set_connectable(false);
+        } else {
+            if self.radio_test.is_running() {
+                self.controller.as_ref().unwrap().le_test_end();
+            }
+
+            if let Some((connectable, scan_mode)) = self.radio_test_saved_scan_mode.take() {
+                self.is_connectable = connectable;
+                self.intf
+                    .lock()
+                    .unwrap()
+                    .set_adapter_property(BluetoothProperty::AdapterScanMode(scan_mode));
+            }
+        }
+
+        // Keep devices from reconnecting through the accept list while testing.
+        self.update_background_capacity();
+        RadioTestStatus::Success
+    }
+
+    fn start_radio_test(&mut self, params: RadioTestParams) -> RadioTestStatus {
+        if self.state != BtState::On || self.controller.is_none() {
+            return RadioTestStatus::ControllerUnavailable;
+        }
+
+        let (id, timeout) =
+            match self.radio_test.start(params.clone(), self.get_connected_device_count()) {
+                Ok(v) => v,
+                Err(status) => return status,
+            };
+
+        let controller = self.controller.as_ref().unwrap();
+        let started = match params.kind {
+            RadioTestKind::LeTransmitter => controller.le_transmitter_test(
+                params.channel,
+                params.length,
+                params.payload,
+                params.phy,
+            ),
+            RadioTestKind::LeReceiver => controller.le_receiver_test(params.channel, params.phy),
+        };
+
+        if let Err(e) = started {
+            warn!("Can't start radio test: {:?}", e);
+            self.radio_test.on_started(HCI_INVALID_PARAMETERS);
+            return RadioTestStatus::InvalidParameters;
+        }
+
+        let txl = self.tx.clone();
+        topstack::get_runtime().spawn(async move {
+            time::sleep(timeout).await;
+            let _ = txl.send(Message::RadioTestTimeout(id)).await;
+        });
+
+        RadioTestStatus::Success
+    }
+
+    fn stop_radio_test(&mut self) -> RadioTestStatus {
+        if !self.radio_test.is_running() {
+            return RadioTestStatus::NotRunning;
+        }
+
+        match self.controller.as_ref() {
+            Some(controller) => {
+                controller.le_test_end();
+                RadioTestStatus::Success
+            }
+            None => RadioTestStatus::ControllerUnavailable,
+        }
+    }
+
+    fn get_radio_test_results(&self) -> Vec<RadioTestResult> {
+        self.radio_test.get_results()
+    }
+
+    fn forget_and_repair(&mut self, device: BluetoothDevice) -> bool {
+        if !self.bonded_devices.contains_key(&device.address) {
+            warn!("Can't repair {}, it is not bonded", device.address);
+            return false;
+        }
+
+        if self.radio_test.is_test_mode() {
+            warn!("Can't repair bond in radio test mode");
+            return false;
+        }
+
+        let address = device.address.clone();
+        if !self.remove_bond(device) {
+            return false;
+        }
+
+        // Pairing starts again once the bond removal is reported.
+        self.bond_repairs.request(address);
+        true
+    }
+
+    fn send_keypress_notification(
+        &self,
+        device: BluetoothDevice,
+        notification_type: KeypressNotificationType,
+    ) -> bool {
+        let addr = RawAddress::from_string(device.address.clone());
+
+        if addr.is_none() {
+            warn!("Can't send keypress notification. Address {} is not valid.", device.address);
+            return false;
+        }
+
+        let is_bonding = match self.found_devices.get(&device.address) {
+            Some(d) => d.bond_state == BtBondState::Bonding,
+            None => false,
+        };
+
+        if !is_bonding {
+            warn!("Can't send keypress notification. Device {} isn't bonding.", device.address);
+            return false;
+        }
+
+        match self.controller.as_ref() {
+            Some(controller) => {
+                controller.send_keypress_notification(addr.unwrap(), notification_type);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_rpa_timeout(&mut self, timeout_s: u16) -> bool {
+        if !(RPA_TIMEOUT_MIN..=RPA_TIMEOUT_MAX).contains(&timeout_s) {
+            warn!("Invalid RPA timeout {}s", timeout_s);
+            return false;
+        }
+
+        self.rpa_timeout = Some(timeout_s);
+        match self.controller.as_ref() {
+            Some(controller) if self.state == BtState::On => controller.set_rpa_timeout(timeout_s),
+            _ => true,
+        }
+    }
+
+    fn get_rpa_timeout(&self) -> u16 {
+        self.rpa_timeout.unwrap_or(0)
+    }
+
+    fn start_controller_debug_dump(&mut self) -> bool {
+        match self.controller.as_ref() {
+            Some(controller) if self.state == BtState::On => controller.start_vendor_debug_dump(),
+            _ => false,
+        }
+    }
+
+    fn get_controller_debug_dump(&mut self) -> String {
+        match self.controller.as_ref() {
+            Some(controller) => format_vendor_debug_dump(&controller.take_vendor_debug_dump(), &[]),
+            None => String::new(),
+        }
+    }
+
+    fn override_local_address(&mut self, address: String) -> bool {
+        let addr = match RawAddress::from_string(address.clone()) {
+            Some(addr) => addr,
+            None => {
+                warn!("Can't override local address. Address {} is not valid.", address);
+                return false;
+            }
+        };
+
+        match self.controller.as_mut() {
+            Some(controller) => match controller.override_local_addr(addr) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Can't override local address with {}: {:?}", address, e);
+                    false
+                }
+            },
+            None => false,
+        }
+    }
+
+    fn set_tracing_enabled(&mut self, enabled: bool) -> bool {
+        self.tracer.lock().unwrap().set_enabled(enabled);
+        true
+    }
+
+    fn write_trace(&self) -> bool {
+        let tracer = self.tracer.lock().unwrap();
+        if !tracer.is_enabled() {
+            warn!("Can't write trace, tracing is disabled");
+            return false;
+        }
+
+        match tracer.write(Path::new(TRACE_FILE)) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Failed to write trace to {}: {}", TRACE_FILE, e);
+                false
+            }
+        }
+    }
+
+    fn get_profile_info(&self) -> Vec<ProfileInfo> {
+        let mut infos = vec![
+            self.hh
+                .as_ref()
+                .map_or_else(|| stats::get_profile_info("hidhost", 0), |p| p.query_profile_info()),
+            self.sdp
+                .as_ref()
+                .map_or_else(|| stats::get_profile_info("sdp", 0), |p| p.query_profile_info()),
+            self.bluetooth_gatt.lock().unwrap().get_profile_info(),
+        ];
+        infos.extend(self.bluetooth_media.lock().unwrap().get_profile_info());
+        infos
+    }
+
+    fn get_profile_states(&self) -> Vec<ProfileState> {
+        let mut connected = self.bluetooth_media.lock().unwrap().get_connected_profiles();
+        if !self.hid_connections.is_empty() {
+            connected.insert(Profile::Hid);
+            connected.insert(Profile::Hogp);
+        }
+
+        self.uuid_helper.get_profile_states(&self.get_profile_info(), &connected)
+    }
+
+    fn get_device_traffic(&self) -> Vec<DeviceTraffic> {
+        self.bluetooth_media.lock().unwrap().update_a2dp_traffic();
+        self.traffic.lock().unwrap().get_traffic()
+    }
+
+    fn get_traffic_snapshots(&self) -> Vec<TrafficSnapshot> {
+        self.traffic.lock().unwrap().get_snapshots()
+    }
+
+    fn get_buffer_overflows(&self) -> BufferOverflows {
+        self.traffic.lock().unwrap().get_buffer_overflows()
+    }
+}
+
+impl IPowerProfileHook for Bluetooth {
+    fn on_power_profile_changed(&mut self, _profile: PowerProfile) {
+        // The policy is looked up again so that the capacity also follows controller changes.
+        self.update_background_capacity();
+    }
+}
+
+#[btif_callbacks_dispatcher(Bluetooth, dispatch_controller_callbacks, ControllerCallbacks)]
+pub(crate) trait BtifControllerCallbacks {
+    #[btif_callback(LeTestStarted)]
+    fn le_test_started(&mut self, status: u8);
+
+    #[btif_callback(LeTestEnded)]
+    fn le_test_ended(&mut self, status: u8, num_packets: u16);
+
+    #[btif_callback(KeypressNotification)]
+    fn keypress_notification(
+        &mut self,
+        addr: RawAddress,
+        notification_type: KeypressNotificationType,
+    );
+
+    #[btif_callback(KeypressNotificationSent)]
+    fn keypress_notification_sent(&mut self, status: u8, addr: RawAddress);
+
+    #[btif_callback(DataBufferOverflow)]
+    fn data_buffer_overflow(&mut self, link_type: HciLinkType);
+
+    #[btif_callback(RpaTimeoutWritten)]
+    fn rpa_timeout_written(&mut self, status: u8, timeout_s: u16);
+
+    #[btif_callback(LocalSupportedCodecs)]
+    fn local_supported_codecs(&mut self, status: u8, codecs: Vec<LocalCodec>);
+
+    #[btif_callback(LocalCodecCapabilities)]
+    fn local_codec_capabilities(&mut self, status: u8, capabilities: LocalCodecCapabilities);
+
+    #[btif_callback(LocalAddrOverride)]
+    fn local_addr_override(&mut self, status: u8, requested: RawAddress, readback: RawAddress);
+}
+
+impl BtifControllerCallbacks for Bluetooth {
+    fn le_test_started(&mut self, status: u8) {
+        if status != 0 {
+            warn!("Radio test failed to start, status {:#x}", status);
+        }
+        self.radio_test.on_started(status);
+    }
+
+    fn le_test_ended(&mut self, status: u8, num_packets: u16) {
+        if let Some(result) = self.radio_test.on_ended(status, num_packets) {
+            debug!("Radio test ended: {:?}", result);
+        }
+    }
+
+    fn keypress_notification(
+        &mut self,
+        addr: RawAddress,
+        notification_type: KeypressNotificationType,
+    ) {
+        let address = addr.to_string();
+        let name = match self.get_remote_device_if_found(&address) {
+            Some(d) => d.info.name.clone(),
+            None => String::from(""),
+        };
+
+        self.for_all_callbacks(|callback| {
+            callback.on_keypress_notification(
+                BluetoothDevice::new(address.clone(), name.clone()),
+                notification_type,
+            );
+        });
+    }
+
+    fn keypress_notification_sent(&mut self, status: u8, addr: RawAddress) {
+        if status != 0 {
+            warn!(
+                "Failed to send keypress notification to {}, status {:#x}",
+                addr.to_string(),
+                status
+            );
+        }
+    }
+
+    fn data_buffer_overflow(&mut self, link_type: HciLinkType) {
+        warn!("Controller ran out of {:?} data buffers, data was dropped", link_type);
+        self.traffic.lock().unwrap().add_buffer_overflow(link_type);
+    }
+
+    fn rpa_timeout_written(&mut self, status: u8, timeout_s: u16) {
+        if status != 0 {
+            warn!("Failed to set RPA timeout to {}s, status {:#x}", timeout_s, status);
+        }
+    }
+
+    fn local_supported_codecs(&mut self, status: u8, codecs: Vec<LocalCodec>) {
+        if status != 0 {
+            warn!("Failed to read the controller codecs, status {:#x}", status);
+            return;
+        }
+
+        let controller = match self.controller.as_ref() {
+            Some(controller) => controller,
+            None => return,
+        };
+
+        // Only LE capabilities have a standard format, so don't bother with the others.
+        for codec in codecs {
+            debug!("Controller supports codec {:?}", codec);
+            let transports = match codec.transports {
+                Some(transports) => transports,
+                None => continue,
+            };
+
+            for (transport, supported) in [
+                (CodecTransport::LeCis, transports.le_cis),
+                (CodecTransport::LeBis, transports.le_bis),
+            ] {
+                if !supported {
+                    continue;
+                }
+
+                for direction in [CodecDirection::Input, CodecDirection::Output] {
+                    controller.read_local_codec_capabilities(codec.id, transport, direction);
+                }
+            }
+        }
+    }
+
+    fn local_codec_capabilities(&mut self, status: u8, capabilities: LocalCodecCapabilities) {
+        if status != 0 {
+            debug!(
+                "No capabilities for codec {:?} on {:?}, status {:#x}",
+                capabilities.codec, capabilities.transport, status
+            );
+            return;
+        }
+
+        debug!(
+            "Codec {:?} on {:?} ({:?}): sample rates {:?}, channel counts {:?}",
+            capabilities.codec,
+            capabilities.transport,
+            capabilities.direction,
+            capabilities.sample_rates,
+            capabilities.channel_counts
+        );
+    }
+
+    fn local_addr_override(&mut self, status: u8, requested: RawAddress, readback: RawAddress) {
+        let verified = is_local_addr_override_verified(status, &requested, &readback);
+        if !verified {
+            warn!(
+                "Failed to override local address with {}, status {:#x}, read back {}",
+                requested.to_string(),
+                status,
+                readback.to_string()
+            );
+        }
+
+        self.for_all_callbacks(|callback| {
+            callback.on_local_address_override_completed(
+                requested.to_string(),
+                readback.to_string(),
+                verified,
+            );
+        });
+    }
+}
+
+impl BtifHHCallbacks for Bluetooth {
+    fn connection_state(&mut self, address: RawAddress, state: BthhConnectionState) {
+        match state {
+            BthhConnectionState::Connected => {
+                self.hid_connections.insert(address.to_string());
+            }
+            BthhConnectionState::Disconnected => {
+                self.hid_connections.remove(&address.to_string());
+                self.hid_battery_reports.remove(&address.to_string());
+            }
+            _ => (),
+        }
+    }
+
+    fn hid_info(&mut self, mut address: RawAddress, info: BthhHidInfo) {
+        let len = std::cmp::min(info.dl_len.max(0) as usize, info.dsc_list.len());
+        let report = match HidBatteryReport::from_descriptor(&info.dsc_list[..len]) {
+            Some(report) => report,
+            None => {
+                self.hid_battery_reports.remove(&address.to_string());
+                return;
+            }
+        };
+
+        self.hid_battery_reports.insert(address.to_string(), report);
+        if let Some(hh) = self.hh.as_ref() {
+            hh.get_report(&mut address, report.report_type, report.report_id, 0);
+        }
+    }
+
+    fn get_report(&mut self, address: RawAddress, status: BthhStatus, data: Vec<u8>, _size: i32) {
+        if status != BthhStatus::Ok {
+            return;
+        }
+
+        let address = address.to_string();
+        let level = match self.hid_battery_reports.get(&address) {
+            Some(report) => report.parse_level(&data),
+            None => None,
+        };
+
+        if let Some(level) = level {
+            self.battery_manager.lock().unwrap().update_level(&address, BatterySource::Hid, level);
+        }
+    }
 }
 
 impl BtifSdpCallbacks for Bluetooth {
@@ -6,25 +6,47 @@ use bt_topshim::bindings::root::bluetooth::Uuid;
 use bt_topshim::btif::{BluetoothInterface, RawAddress, Uuid128Bit};
 use bt_topshim::profiles::gatt::{
     BtGattDbElement, BtGattNotifyParams, BtGattReadParams, Gatt, GattClientCallbacks,
-    GattClientCallbacksDispatcher, GattScannerCallbacksDispatcher, GattServerCallbacksDispatcher,
-    GattStatus,
+    GattClientCallbacksDispatcher, GattScannerCallbacksDispatcher, GattScannerInbandCallbacks,
+    GattScannerInbandCallbacksDispatcher, GattServerCallbacksDispatcher, GattStatus,
 };
+use bt_topshim::profiles::stats::{self, ProfileInfo};
 use bt_topshim::topstack;
 
 use log::{debug, warn};
 use num_traits::cast::{FromPrimitive, ToPrimitive};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
-
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::feature_support::{ControllerFeature, FeatureRegistry};
+use crate::gatt_metrics::{GattAttributeMetrics, GattMetrics, GattOperation};
+use crate::periodic_sync::{
+    PeriodicSync, PeriodicSyncs, SyncTransferMode, MAX_SYNC_SKIP, MAX_SYNC_TIMEOUT,
+    MIN_SYNC_TIMEOUT,
+};
+use crate::power_profile::{IPowerProfileHook, PowerProfile};
+use crate::tracer::{TraceCategory, Tracer};
+use crate::traffic::{TrafficAccounting, TrafficProfile};
 use crate::{Message, RPCProxy};
 
+/// How often GATT clients whose owner went away are reclaimed.
+const ORPHAN_JANITOR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a connection priority request waits for the matching connection update.
+const PRIORITY_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 struct Client {
     id: Option<i32>,
     uuid: Uuid128Bit,
     callback: Box<dyn IBluetoothGattCallback + Send>,
     is_congested: bool,
 
+    // Set once the RPC client owning the callback disconnected without unregistering.
+    is_orphaned: bool,
+
     // Queued on_characteristic_write callback.
     congestion_queue: Vec<(String, i32, i32)>,
 }
@@ -94,6 +116,7 @@ impl ContextMap {
             uuid: uuid.clone(),
             callback,
             is_congested: false,
+            is_orphaned: false,
             congestion_queue: vec![],
         });
     }
@@ -111,6 +134,24 @@ impl ContextMap {
         client.unwrap().id = Some(id);
     }
 
+    fn set_orphaned(&mut self, uuid: &Uuid128Bit) {
+        if let Some(client) = self.clients.iter_mut().find(|client| client.uuid == *uuid) {
+            client.is_orphaned = true;
+        }
+    }
+
+    /// Orphaned clients that can be unregistered, i.e. whose registration completed.
+    fn get_orphaned_client_ids(&self) -> Vec<i32> {
+        self.clients.iter().filter(|client| client.is_orphaned).filter_map(|c| c.id).collect()
+    }
+
+    /// Removes the connections of a client and returns how many there were.
+    fn remove_client_connections(&mut self, client_id: i32) -> usize {
+        let before = self.connections.len();
+        self.connections.retain(|conn| conn.client_id != client_id);
+        before - self.connections.len()
+    }
+
     fn add_connection(&mut self, client_id: i32, conn_id: i32, address: &String) {
         if self.get_conn_id_from_address(client_id, address).is_some() {
             return;
@@ -256,6 +297,94 @@ pub trait IBluetoothGatt {
         min_ce_len: u16,
         max_ce_len: u16,
     );
+
+    /// Requests a connection parameter update using one of the connection priority presets.
+    ///
+    /// Returns false if the client is not connected to `addr`. The result is reported via
+    /// `IBluetoothGattCallback::on_connection_priority_updated` once the connection uses the
+    /// parameters of `priority`, or the update fails. Requests that see neither within 30 seconds
+    /// are reported with `GattStatus::Error`. A new request on the same connection replaces a
+    /// pending one, which gets no result.
+    fn request_connection_priority(
+        &mut self,
+        client_id: i32,
+        addr: String,
+        priority: GattConnectionPriority,
+    ) -> bool;
+
+    /// Unregisters the clients whose RPC owner disconnected without unregistering, which also
+    /// closes their connections. This also runs periodically. Returns the number of clients
+    /// reclaimed.
+    ///
+    /// Only GATT clients are reclaimed. Periodic sync callbacks are dropped as soon as their owner
+    /// disconnects, and scanners aren't implemented yet.
+    fn purge_orphans(&mut self) -> i32;
+
+    /// Gets how many orphaned GATT clients and connections were reclaimed so far, whether by
+    /// `purge_orphans` or periodically.
+    fn get_orphan_stats(&self) -> GattOrphanStats;
+
+    /// Gets the number of reads, writes and notifications per service and characteristic UUID,
+    /// over all clients and devices.
+    fn get_gatt_metrics(&self) -> Vec<GattAttributeMetrics>;
+
+    /// Clears the counters returned by `get_gatt_metrics`.
+    fn reset_gatt_metrics(&mut self);
+
+    /// Adds an observer of the periodic advertising syncs, including the syncs transferred by
+    /// peers.
+    ///
+    /// Returns the id of the callback, used to unregister it.
+    fn register_periodic_sync_callback(
+        &mut self,
+        callback: Box<dyn IPeriodicSyncCallback + Send>,
+    ) -> u32;
+
+    /// Removes an observer added by `register_periodic_sync_callback`.
+    ///
+    /// Returns false if `callback_id` is not recognized.
+    fn unregister_periodic_sync_callback(&mut self, callback_id: u32) -> bool;
+
+    /// Synchronizes to the periodic advertising train `sid` of `addr`. `skip` is the number of
+    /// periodic events that may be skipped and `timeout` the sync timeout in 10ms units.
+    ///
+    /// Returns false if the parameters are invalid, the controller can't sync or the train is
+    /// already being synced to. The result is reported via
+    /// `IPeriodicSyncCallback::on_sync_started`.
+    fn sync_to_periodic_advertising(
+        &mut self,
+        addr: String,
+        sid: u8,
+        skip: u16,
+        timeout: u16,
+    ) -> bool;
+
+    /// Cancels a pending `sync_to_periodic_advertising`. Returns false if it wasn't pending.
+    fn cancel_sync(&mut self, addr: String, sid: u8) -> bool;
+
+    /// Terminates an established sync. Returns false if `sync_handle` is unknown.
+    fn stop_sync(&mut self, sync_handle: u16) -> bool;
+
+    /// Transfers the established sync `sync_handle` to the connected peer `addr` (PAST).
+    /// `service_data` is passed on to the peer.
+    ///
+    /// Returns false if `sync_handle` is unknown. The result is reported via
+    /// `IPeriodicSyncCallback::on_sync_transferred`.
+    fn transfer_sync(&mut self, addr: String, service_data: u16, sync_handle: u16) -> bool;
+
+    /// Sets whether and how to accept syncs transferred by the connected peer `addr`.
+    ///
+    /// Returns false if the parameters are invalid.
+    fn set_sync_transfer_parameters(
+        &mut self,
+        addr: String,
+        mode: SyncTransferMode,
+        skip: u16,
+        timeout: u16,
+    ) -> bool;
+
+    /// Gets the periodic advertising trains currently synced to.
+    fn get_periodic_syncs(&self) -> Vec<PeriodicSync>;
 }
 
 #[derive(Debug, Default)]
@@ -337,6 +466,147 @@ impl BluetoothGattService {
             included_services: vec![],
         }
     }
+
+    /// Builds the services from the flat attribute list of a GATT database.
+    pub(crate) fn from_db(elements: Vec<BtGattDbElement>) -> Vec<BluetoothGattService> {
+        let mut db_out: Vec<BluetoothGattService> = vec![];
+
+        for elem in elements {
+            match GattDbElementType::from_u32(elem.type_).unwrap() {
+                GattDbElementType::PrimaryService | GattDbElementType::SecondaryService => {
+                    db_out.push(BluetoothGattService::new(
+                        elem.uuid.uu,
+                        elem.id as i32,
+                        elem.type_ as i32,
+                    ));
+                    // TODO(b/200065274): Mark restricted services.
+                }
+
+                GattDbElementType::Characteristic => {
+                    match db_out.last_mut() {
+                        Some(s) => s.characteristics.push(BluetoothGattCharacteristic::new(
+                            elem.uuid.uu,
+                            elem.id as i32,
+                            elem.properties as i32,
+                            0,
+                        )),
+                        None => {
+                            // TODO(b/193685325): Log error.
+                        }
+                    }
+                    // TODO(b/200065274): Mark restricted characteristics.
+                }
+
+                GattDbElementType::Descriptor => {
+                    match db_out.last_mut() {
+                        Some(s) => match s.characteristics.last_mut() {
+                            Some(c) => c.descriptors.push(BluetoothGattDescriptor::new(
+                                elem.uuid.uu,
+                                elem.id as i32,
+                                0,
+                            )),
+                            None => {
+                                // TODO(b/193685325): Log error.
+                            }
+                        },
+                        None => {
+                            // TODO(b/193685325): Log error.
+                        }
+                    }
+                    // TODO(b/200065274): Mark restricted descriptors.
+                }
+
+                GattDbElementType::IncludedService => {
+                    match db_out.last_mut() {
+                        Some(s) => {
+                            s.included_services.push(BluetoothGattService::new(
+                                elem.uuid.uu,
+                                elem.id as i32,
+                                elem.type_ as i32,
+                            ));
+                        }
+                        None => {
+                            // TODO(b/193685325): Log error.
+                        }
+                    }
+                }
+            }
+        }
+
+        db_out
+    }
+}
+
+/// Feeds a GATT client callback to the metrics, and the payload of the operations that succeeded
+/// to the traffic accounting.
+fn record_client_callback(
+    metrics: &mut GattMetrics,
+    traffic: &Mutex<TrafficAccounting>,
+    cb: GattClientCallbacks,
+) {
+    let success = GattStatus::Success.to_i32().unwrap();
+    let (conn_id, handle, op, succeeded, bytes) = match cb {
+        GattClientCallbacks::Connect(conn_id, status, _client_id, addr) => {
+            if status == success {
+                metrics.add_connection(conn_id, &addr.to_string());
+            }
+            return;
+        }
+        GattClientCallbacks::Disconnect(conn_id, _status, _client_id, _addr) => {
+            metrics.remove_connection(conn_id);
+            return;
+        }
+        GattClientCallbacks::GetGattDb(conn_id, elements, _count) => {
+            if let Some(address) = metrics.get_address(conn_id) {
+                metrics.set_database(&address, &BluetoothGattService::from_db(elements));
+            }
+            return;
+        }
+        GattClientCallbacks::ServiceChanged(conn_id) => {
+            // The handles are only known again after the next service discovery.
+            if let Some(address) = metrics.get_address(conn_id) {
+                metrics.remove_database(&address);
+            }
+            return;
+        }
+        GattClientCallbacks::Notify(conn_id, data) => {
+            (conn_id, data.handle as i32, GattOperation::Notify, true, data.len as usize)
+        }
+        GattClientCallbacks::ReadCharacteristic(conn_id, status, data)
+        | GattClientCallbacks::ReadDescriptor(conn_id, status, data) => (
+            conn_id,
+            data.handle as i32,
+            GattOperation::Read,
+            status == success,
+            data.value.len as usize,
+        ),
+        // A congested write was still accepted.
+        GattClientCallbacks::WriteCharacteristic(conn_id, status, handle, len, _value) => (
+            conn_id,
+            handle as i32,
+            GattOperation::Write,
+            status == success || status == GattStatus::Congested.to_i32().unwrap(),
+            len as usize,
+        ),
+        GattClientCallbacks::WriteDescriptor(conn_id, status, handle, len, _value) => {
+            (conn_id, handle as i32, GattOperation::Write, status == success, len as usize)
+        }
+        _ => return,
+    };
+
+    let address = match metrics.get_address(conn_id) {
+        Some(address) => address,
+        None => return,
+    };
+
+    metrics.record(&address, handle, op, succeeded);
+    if succeeded {
+        let (rx_bytes, tx_bytes) = match op {
+            GattOperation::Read | GattOperation::Notify => (bytes as u64, 0),
+            GattOperation::Write => (0, bytes as u64),
+        };
+        traffic.lock().unwrap().add(&address, TrafficProfile::Gatt, rx_bytes, tx_bytes);
+    }
 }
 
 /// Callback for GATT Client API.
@@ -396,10 +666,42 @@ pub trait IBluetoothGattCallback: RPCProxy {
         status: i32,
     );
 
+    /// The completion of IBluetoothGatt::request_connection_priority.
+    fn on_connection_priority_updated(
+        &self,
+        addr: String,
+        priority: GattConnectionPriority,
+        status: i32,
+    );
+
     /// When there is an addition, removal, or change of a GATT service.
     fn on_service_changed(&self, addr: String);
 }
 
+/// Callback for periodic advertising sync events, passed to
+/// `IBluetoothGatt::register_periodic_sync_callback`.
+pub trait IPeriodicSyncCallback: RPCProxy {
+    /// When a sync started or failed to, whether requested or transferred by a peer.
+    fn on_sync_started(&self, status: u8, sync: PeriodicSync);
+
+    /// When periodic advertising data is received. `data_status` tells whether `data` is
+    /// complete (0), more is coming (1) or it was truncated (2).
+    fn on_sync_report(
+        &self,
+        sync_handle: u16,
+        tx_power: i32,
+        rssi: i32,
+        data_status: u8,
+        data: Vec<u8>,
+    );
+
+    /// When an established sync is lost.
+    fn on_sync_lost(&self, sync_handle: u16);
+
+    /// The completion of `IBluetoothGatt::transfer_sync`.
+    fn on_sync_transferred(&self, status: u8, addr: String);
+}
+
 /// Interface for scanner callbacks to clients, passed to `IBluetoothGatt::register_scanner`.
 pub trait IScannerCallback {
     /// When the `register_scanner` request is done.
@@ -443,6 +745,59 @@ pub enum LePhy {
     PhyCoded = 3,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+/// Connection priority presets for `IBluetoothGatt::request_connection_priority`.
+pub enum GattConnectionPriority {
+    Balanced = 0,
+    High = 1,
+    LowPower = 2,
+}
+
+impl GattConnectionPriority {
+    /// Returns (min_interval, max_interval, latency, timeout) in controller units, i.e. 1.25ms
+    /// for intervals and 10ms for the supervision timeout.
+    fn get_parameters(&self) -> (i32, i32, i32, i32) {
+        match self {
+            GattConnectionPriority::Balanced => (24, 40, 0, 500),
+            GattConnectionPriority::High => (6, 8, 0, 500),
+            GattConnectionPriority::LowPower => (80, 100, 2, 500),
+        }
+    }
+}
+
+/// Connection priority request waiting for its connection update.
+struct PendingPriority {
+    priority: GattConnectionPriority,
+    /// Tells the timeout of this request from the one of a request it replaced.
+    request_id: u32,
+    /// Reports the request as failed once `PRIORITY_REQUEST_TIMEOUT` elapsed.
+    timeout: Option<JoinHandle<()>>,
+}
+
+impl PendingPriority {
+    fn new(priority: GattConnectionPriority, request_id: u32) -> Self {
+        PendingPriority { priority, request_id, timeout: None }
+    }
+
+    /// Whether the connection was updated to the parameters of the requested priority, rather than
+    /// by an unrelated update.
+    fn matches(&self, interval: u16, latency: u16, timeout: u16) -> bool {
+        let (min_interval, max_interval, req_latency, req_timeout) = self.priority.get_parameters();
+        (min_interval..=max_interval).contains(&(interval as i32))
+            && latency as i32 == req_latency
+            && timeout as i32 == req_timeout
+    }
+}
+
+impl Drop for PendingPriority {
+    fn drop(&mut self) {
+        if let Some(timeout) = self.timeout.take() {
+            timeout.abort();
+        }
+    }
+}
+
 #[derive(Debug, FromPrimitive, ToPrimitive)]
 #[repr(u32)]
 /// Scan type configuration.
@@ -478,6 +833,16 @@ pub struct ScanSettings {
 #[derive(Debug, Default)]
 pub struct ScanFilter {}
 
+/// Resources reclaimed from RPC clients that went away without unregistering, since the stack
+/// started.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GattOrphanStats {
+    /// GATT clients unregistered on behalf of their owner.
+    pub clients: u32,
+    /// Connections those clients still had open.
+    pub connections: u32,
+}
+
 /// Implementation of the GATT API (IBluetoothGatt).
 pub struct BluetoothGatt {
     intf: Arc<Mutex<BluetoothInterface>>,
@@ -485,20 +850,95 @@ pub struct BluetoothGatt {
 
     context_map: ContextMap,
     reliable_queue: HashSet<String>,
+
+    // Connection priority requests waiting for a connection update, keyed by conn_id.
+    pending_priority: HashMap<i32, PendingPriority>,
+    next_priority_request_id: u32,
+    power_profile: PowerProfile,
+    feature_registry: Arc<Mutex<FeatureRegistry>>,
+
+    tx: Option<Sender<Message>>,
+    orphan_janitor: Option<JoinHandle<()>>,
+    orphan_stats: GattOrphanStats,
+    metrics: Arc<Mutex<GattMetrics>>,
+    tracer: Arc<Mutex<Tracer>>,
+    traffic: Arc<Mutex<TrafficAccounting>>,
+    periodic_syncs: PeriodicSyncs,
+    sync_callbacks: HashMap<u32, Box<dyn IPeriodicSyncCallback + Send>>,
 }
 
 impl BluetoothGatt {
     /// Constructs a new IBluetoothGatt implementation.
-    pub fn new(intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothGatt {
+    pub fn new(
+        intf: Arc<Mutex<BluetoothInterface>>,
+        feature_registry: Arc<Mutex<FeatureRegistry>>,
+        tracer: Arc<Mutex<Tracer>>,
+        traffic: Arc<Mutex<TrafficAccounting>>,
+    ) -> BluetoothGatt {
+        {
+            let mut registry = feature_registry.lock().unwrap();
+            registry
+                .register("gatt.phy_2m", vec![ControllerFeature::Le, ControllerFeature::Le2mPhy]);
+            registry.register(
+                "gatt.phy_coded",
+                vec![ControllerFeature::Le, ControllerFeature::LeCodedPhy],
+            );
+            registry.register(
+                "gatt.batch_scan",
+                vec![ControllerFeature::Le, ControllerFeature::LeBatchScan],
+            );
+            registry.register(
+                "gatt.periodic_sync",
+                vec![ControllerFeature::Le, ControllerFeature::LePeriodicAdvertising],
+            );
+        }
+
         BluetoothGatt {
             intf: intf,
             gatt: None,
             context_map: ContextMap::new(),
             reliable_queue: HashSet::new(),
+            pending_priority: HashMap::new(),
+            next_priority_request_id: 0,
+            power_profile: PowerProfile::default(),
+            feature_registry,
+            tx: None,
+            orphan_janitor: None,
+            orphan_stats: GattOrphanStats::default(),
+            metrics: Arc::new(Mutex::new(GattMetrics::new())),
+            tracer,
+            traffic,
+            periodic_syncs: PeriodicSyncs::new(),
+            sync_callbacks: HashMap::new(),
         }
     }
 
     pub fn init_profiles(&mut self, tx: Sender<Message>) {
+        self.tx = Some(tx.clone());
+
+        let janitor_tx = tx.clone();
+        self.orphan_janitor = Some(tokio::spawn(async move {
+            loop {
+                time::sleep(ORPHAN_JANITOR_INTERVAL).await;
+                let _ = janitor_tx.send(Message::GattOrphanJanitor).await;
+            }
+        }));
+
+        // The metrics follow the client callbacks on their own, next to the handling below.
+        let metrics = self.metrics.clone();
+        let traffic = self.traffic.clone();
+        topstack::get_dispatchers()
+            .lock()
+            .unwrap()
+            .add_listener::<Arc<Mutex<GattClientCallbacksDispatcher>>>(Arc::new(Mutex::new(
+                GattClientCallbacksDispatcher {
+                    dispatch: Box::new(move |cb| {
+                        record_client_callback(&mut metrics.lock().unwrap(), &traffic, cb);
+                    }),
+                },
+            )));
+
+        let scanner_tx = tx.clone();
         self.gatt = Gatt::new(&self.intf.lock().unwrap());
         self.gatt.as_mut().unwrap().initialize(
             GattClientCallbacksDispatcher {
@@ -520,10 +960,101 @@ impl BluetoothGatt {
                     debug!("received Gatt scanner callback: {:?}", cb);
                 }),
             },
+            GattScannerInbandCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    let tx_clone = scanner_tx.clone();
+                    topstack::get_runtime().spawn(async move {
+                        let _ = tx_clone.send(Message::GattScannerInband(cb)).await;
+                    });
+                }),
+            },
+        );
+    }
+
+    pub fn get_profile_info(&self) -> ProfileInfo {
+        match self.gatt.as_ref() {
+            Some(gatt) => gatt.query_profile_info(),
+            None => stats::get_profile_info("gatt", 0),
+        }
+    }
+
+    /// Must be called once the adapter is off. The controller dropped its periodic syncs, so
+    /// clients are told they were lost.
+    pub fn handle_adapter_off(&mut self) {
+        for sync in self.periodic_syncs.clear() {
+            for callback in self.sync_callbacks.values() {
+                callback.on_sync_lost(sync.sync_handle);
+            }
+        }
+    }
+}
+
+impl BluetoothGatt {
+    /// Marks the client registered with `uuid` as orphaned. It is reclaimed by the next
+    /// `purge_orphans`.
+    pub(crate) fn callback_disconnected(&mut self, uuid: Uuid128Bit) {
+        self.context_map.set_orphaned(&uuid);
+    }
+
+    pub(crate) fn remove_sync_callback(&mut self, id: u32) -> bool {
+        match self.sync_callbacks.get_mut(&id) {
+            Some(callback) => {
+                callback.unregister(id);
+                self.sync_callbacks.remove(&id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reports the connection priority request `request_id` of `conn_id` as failed if the
+    /// connection wasn't updated in time.
+    pub(crate) fn on_priority_request_timeout(&mut self, conn_id: i32, request_id: u32) {
+        match self.pending_priority.get(&conn_id) {
+            Some(pending) if pending.request_id == request_id => (),
+            _ => return,
+        }
+
+        let priority = self.pending_priority.remove(&conn_id).unwrap().priority;
+        let address = match self.context_map.get_address_by_conn_id(conn_id) {
+            Some(address) => address,
+            None => return,
+        };
+
+        debug!("Connection priority request for {} timed out", address);
+        if let Some(client) = self.context_map.get_client_by_conn_id(conn_id) {
+            client.callback.on_connection_priority_updated(
+                address,
+                priority,
+                GattStatus::Error.to_i32().unwrap(),
+            );
+        }
+    }
+
+    fn unregister_orphan(&mut self, client_id: i32) {
+        let connections = self.context_map.remove_client_connections(client_id);
+        self.context_map.remove(client_id);
+
+        // Unregistering also closes the connections the client still had open.
+        self.gatt.as_ref().unwrap().client.unregister_client(client_id);
+
+        self.orphan_stats.clients += 1;
+        self.orphan_stats.connections += connections as u32;
+        warn!(
+            "Reclaimed orphaned GATT client {} with {} connections, {:?} reclaimed so far",
+            client_id, connections, self.orphan_stats
         );
     }
 }
 
+/// Gets the name of a GATT status for traces.
+fn get_status_name(status: i32) -> String {
+    match GattStatus::from_i32(status) {
+        Some(gatt_status) => format!("{:?}", gatt_status),
+        None => format!("{:#04x}", status),
+    }
+}
+
 // Temporary util that covers only basic string conversion.
 // TODO(b/193685325): Implement more UUID utils by using Uuid from gd/hci/uuid.h with cxx.
 fn parse_uuid_string<T: Into<String>>(uuid: T) -> Option<Uuid> {
@@ -575,10 +1106,21 @@ impl IBluetoothGatt for BluetoothGatt {
     fn register_client(
         &mut self,
         app_uuid: String,
-        callback: Box<dyn IBluetoothGattCallback + Send>,
+        mut callback: Box<dyn IBluetoothGattCallback + Send>,
         eatt_support: bool,
     ) {
         let uuid = parse_uuid_string(app_uuid).unwrap();
+
+        if let Some(tx) = self.tx.clone() {
+            let app_uuid = uuid.uu;
+            callback.register_disconnect(Box::new(move |_| {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let _ = tx.send(Message::GattClientCallbackDisconnected(app_uuid)).await;
+                });
+            }));
+        }
+
         self.context_map.add(&uuid.uu, callback);
         self.gatt.as_ref().unwrap().client.register_client(&uuid, eatt_support);
     }
@@ -602,6 +1144,7 @@ impl IBluetoothGatt for BluetoothGatt {
             Some(addr) => addr,
         };
 
+        self.tracer.lock().unwrap().begin(TraceCategory::Gatt, "connect", &address.to_string());
         self.gatt.as_ref().unwrap().client.connect(
             client_id,
             &address,
@@ -638,6 +1181,19 @@ impl IBluetoothGatt for BluetoothGatt {
             return;
         }
 
+        for phy in [tx_phy, rx_phy].iter() {
+            let capability = match phy {
+                LePhy::Phy2m => "gatt.phy_2m",
+                LePhy::PhyCoded => "gatt.phy_coded",
+                _ => continue,
+            };
+
+            if let Err(reason) = self.feature_registry.lock().unwrap().check(capability) {
+                warn!("Can't set preferred PHY {:?}: {}", phy, reason.get_message());
+                return;
+            }
+        }
+
         self.gatt.as_ref().unwrap().client.set_preferred_phy(
             &RawAddress::from_string(address).unwrap(),
             tx_phy.to_u8().unwrap(),
@@ -669,6 +1225,8 @@ impl IBluetoothGatt for BluetoothGatt {
             return;
         }
 
+        self.tracer.lock().unwrap().begin(TraceCategory::Gatt, "discover_services", &addr);
+
         self.gatt.as_ref().unwrap().client.search_service(conn_id.unwrap(), None);
     }
 
@@ -694,6 +1252,7 @@ impl IBluetoothGatt for BluetoothGatt {
 
         // TODO(b/200065274): Perform check on restricted handles.
 
+        self.tracer.lock().unwrap().begin(TraceCategory::Gatt, "read_characteristic", &addr);
         self.gatt.as_ref().unwrap().client.read_characteristic(
             conn_id.unwrap(),
             handle as u16,
@@ -753,6 +1312,7 @@ impl IBluetoothGatt for BluetoothGatt {
 
         // TODO(b/200070162): Handle concurrent write characteristic.
 
+        self.tracer.lock().unwrap().begin(TraceCategory::Gatt, "write_characteristic", &addr);
         self.gatt.as_ref().unwrap().client.write_characteristic(
             conn_id.unwrap(),
             handle as u16,
@@ -882,6 +1442,202 @@ impl IBluetoothGatt for BluetoothGatt {
             max_ce_len,
         );
     }
+
+    fn request_connection_priority(
+        &mut self,
+        client_id: i32,
+        addr: String,
+        priority: GattConnectionPriority,
+    ) -> bool {
+        let conn_id = match self.context_map.get_conn_id_from_address(client_id, &addr) {
+            None => return false,
+            Some(id) => id,
+        };
+
+        let priority = match priority {
+            GattConnectionPriority::High
+                if !self.power_profile.get_policy().allow_high_connection_priority =>
+            {
+                debug!("High connection priority not allowed in {:?}", self.power_profile);
+                GattConnectionPriority::Balanced
+            }
+            p => p,
+        };
+
+        let request_id = self.next_priority_request_id;
+        self.next_priority_request_id = self.next_priority_request_id.wrapping_add(1);
+
+        // Replacing an earlier request of the connection also cancels its timeout.
+        let mut pending = PendingPriority::new(priority, request_id);
+        if let Some(tx) = self.tx.clone() {
+            pending.timeout = Some(tokio::spawn(async move {
+                time::sleep(PRIORITY_REQUEST_TIMEOUT).await;
+                let _ = tx.send(Message::GattPriorityRequestTimeout(conn_id, request_id)).await;
+            }));
+        }
+        self.pending_priority.insert(conn_id, pending);
+
+        let (min_interval, max_interval, latency, timeout) = priority.get_parameters();
+        self.gatt.as_ref().unwrap().client.conn_parameter_update(
+            &RawAddress::from_string(addr).unwrap(),
+            min_interval,
+            max_interval,
+            latency,
+            timeout,
+            0,
+            0,
+        );
+        true
+    }
+
+    fn purge_orphans(&mut self) -> i32 {
+        if self.gatt.is_none() {
+            return 0;
+        }
+
+        let orphans = self.context_map.get_orphaned_client_ids();
+        for client_id in orphans.iter() {
+            self.unregister_orphan(*client_id);
+        }
+
+        orphans.len() as i32
+    }
+
+    fn get_orphan_stats(&self) -> GattOrphanStats {
+        self.orphan_stats.clone()
+    }
+
+    fn get_gatt_metrics(&self) -> Vec<GattAttributeMetrics> {
+        self.metrics.lock().unwrap().get_all()
+    }
+
+    fn reset_gatt_metrics(&mut self) {
+        self.metrics.lock().unwrap().reset();
+    }
+
+    fn register_periodic_sync_callback(
+        &mut self,
+        mut callback: Box<dyn IPeriodicSyncCallback + Send>,
+    ) -> u32 {
+        let tx = self.tx.clone();
+
+        let id = callback.register_disconnect(Box::new(move |cb_id| {
+            if let Some(tx) = tx.clone() {
+                tokio::spawn(async move {
+                    let _ = tx.send(Message::PeriodicSyncCallbackDisconnected(cb_id)).await;
+                });
+            }
+        }));
+
+        self.sync_callbacks.insert(id, callback);
+        id
+    }
+
+    fn unregister_periodic_sync_callback(&mut self, callback_id: u32) -> bool {
+        self.remove_sync_callback(callback_id)
+    }
+
+    fn sync_to_periodic_advertising(
+        &mut self,
+        addr: String,
+        sid: u8,
+        skip: u16,
+        timeout: u16,
+    ) -> bool {
+        let address = match RawAddress::from_string(addr) {
+            None => return false,
+            Some(addr) => addr,
+        };
+
+        if skip > MAX_SYNC_SKIP || timeout < MIN_SYNC_TIMEOUT || timeout > MAX_SYNC_TIMEOUT {
+            return false;
+        }
+
+        if let Err(reason) = self.feature_registry.lock().unwrap().check("gatt.periodic_sync") {
+            warn!("Can't sync to periodic advertising: {}", reason.get_message());
+            return false;
+        }
+
+        // Addresses are reported back in the format of RawAddress.
+        if !self.periodic_syncs.add_pending(&address.to_string(), sid) {
+            return false;
+        }
+
+        self.gatt.as_mut().unwrap().scanner.start_sync(sid, address, skip, timeout);
+        true
+    }
+
+    fn cancel_sync(&mut self, addr: String, sid: u8) -> bool {
+        let address = match RawAddress::from_string(addr) {
+            None => return false,
+            Some(addr) => addr,
+        };
+
+        if !self.periodic_syncs.remove_pending(&address.to_string(), sid) {
+            return false;
+        }
+
+        self.gatt.as_mut().unwrap().scanner.cancel_create_sync(sid, address);
+        true
+    }
+
+    fn stop_sync(&mut self, sync_handle: u16) -> bool {
+        if self.periodic_syncs.remove(sync_handle).is_none() {
+            return false;
+        }
+
+        self.gatt.as_mut().unwrap().scanner.stop_sync(sync_handle);
+        true
+    }
+
+    fn transfer_sync(&mut self, addr: String, service_data: u16, sync_handle: u16) -> bool {
+        let address = match RawAddress::from_string(addr) {
+            None => return false,
+            Some(addr) => addr,
+        };
+
+        if !self.periodic_syncs.get_all().iter().any(|sync| sync.sync_handle == sync_handle) {
+            return false;
+        }
+
+        self.gatt.as_mut().unwrap().scanner.transfer_sync(address, service_data, sync_handle);
+        true
+    }
+
+    fn set_sync_transfer_parameters(
+        &mut self,
+        addr: String,
+        mode: SyncTransferMode,
+        skip: u16,
+        timeout: u16,
+    ) -> bool {
+        let address = match RawAddress::from_string(addr) {
+            None => return false,
+            Some(addr) => addr,
+        };
+
+        if skip > MAX_SYNC_SKIP || timeout < MIN_SYNC_TIMEOUT || timeout > MAX_SYNC_TIMEOUT {
+            return false;
+        }
+
+        self.gatt.as_mut().unwrap().scanner.sync_tx_parameters(
+            address,
+            mode.to_u8().unwrap(),
+            skip,
+            timeout,
+        );
+        true
+    }
+
+    fn get_periodic_syncs(&self) -> Vec<PeriodicSync> {
+        self.periodic_syncs.get_all()
+    }
+}
+
+impl IPowerProfileHook for BluetoothGatt {
+    fn on_power_profile_changed(&mut self, profile: PowerProfile) {
+        self.power_profile = profile;
+    }
 }
 
 #[btif_callbacks_dispatcher(BluetoothGatt, dispatch_gatt_client_callbacks, GattClientCallbacks)]
@@ -981,11 +1737,28 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
+        // The owner went away before the registration completed, nobody is left to unregister.
+        if client.unwrap().is_orphaned {
+            if status == 0 {
+                self.unregister_orphan(client_id);
+            } else {
+                self.context_map.remove(client_id);
+            }
+            return;
+        }
+
         let callback = &client.unwrap().callback;
         callback.on_client_registered(status, client_id);
     }
 
     fn connect_cb(&mut self, conn_id: i32, status: i32, client_id: i32, addr: RawAddress) {
+        self.tracer.lock().unwrap().end(
+            TraceCategory::Gatt,
+            "connect",
+            &addr.to_string(),
+            &get_status_name(status),
+        );
+
         if status == 0 {
             self.context_map.add_connection(client_id, conn_id, &addr.to_string());
         }
@@ -1008,6 +1781,7 @@ impl BtifGattClientCallbacks for BluetoothGatt {
 
     fn disconnect_cb(&mut self, conn_id: i32, status: i32, client_id: i32, addr: RawAddress) {
         self.context_map.remove_connection(client_id, conn_id);
+        self.pending_priority.remove(&conn_id);
         let client = self.context_map.get_by_client_id(client_id);
         if client.is_none() {
             return;
@@ -1024,7 +1798,16 @@ impl BtifGattClientCallbacks for BluetoothGatt {
         );
     }
 
-    fn search_complete_cb(&mut self, conn_id: i32, _status: i32) {
+    fn search_complete_cb(&mut self, conn_id: i32, status: i32) {
+        if let Some(address) = self.context_map.get_address_by_conn_id(conn_id) {
+            self.tracer.lock().unwrap().end(
+                TraceCategory::Gatt,
+                "discover_services",
+                &address,
+                &get_status_name(status),
+            );
+        }
+
         // Gatt DB is ready!
         self.gatt.as_ref().unwrap().client.get_gatt_db(conn_id);
     }
@@ -1058,6 +1841,13 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
+        self.tracer.lock().unwrap().end(
+            TraceCategory::Gatt,
+            "read_characteristic",
+            address.as_ref().unwrap(),
+            &get_status_name(status),
+        );
+
         let client = self.context_map.get_client_by_conn_id(conn_id);
         if client.is_none() {
             return;
@@ -1084,6 +1874,13 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
+        self.tracer.lock().unwrap().end(
+            TraceCategory::Gatt,
+            "write_characteristic",
+            address.as_ref().unwrap(),
+            &get_status_name(status),
+        );
+
         // TODO(b/200070162): Design how to handle concurrent write characteristic to the same
         // peer.
 
@@ -1219,69 +2016,7 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
-        let mut db_out: Vec<BluetoothGattService> = vec![];
-
-        for elem in elements {
-            match GattDbElementType::from_u32(elem.type_).unwrap() {
-                GattDbElementType::PrimaryService | GattDbElementType::SecondaryService => {
-                    db_out.push(BluetoothGattService::new(
-                        elem.uuid.uu,
-                        elem.id as i32,
-                        elem.type_ as i32,
-                    ));
-                    // TODO(b/200065274): Mark restricted services.
-                }
-
-                GattDbElementType::Characteristic => {
-                    match db_out.last_mut() {
-                        Some(s) => s.characteristics.push(BluetoothGattCharacteristic::new(
-                            elem.uuid.uu,
-                            elem.id as i32,
-                            elem.properties as i32,
-                            0,
-                        )),
-                        None => {
-                            // TODO(b/193685325): Log error.
-                        }
-                    }
-                    // TODO(b/200065274): Mark restricted characteristics.
-                }
-
-                GattDbElementType::Descriptor => {
-                    match db_out.last_mut() {
-                        Some(s) => match s.characteristics.last_mut() {
-                            Some(c) => c.descriptors.push(BluetoothGattDescriptor::new(
-                                elem.uuid.uu,
-                                elem.id as i32,
-                                0,
-                            )),
-                            None => {
-                                // TODO(b/193685325): Log error.
-                            }
-                        },
-                        None => {
-                            // TODO(b/193685325): Log error.
-                        }
-                    }
-                    // TODO(b/200065274): Mark restricted descriptors.
-                }
-
-                GattDbElementType::IncludedService => {
-                    match db_out.last_mut() {
-                        Some(s) => {
-                            s.included_services.push(BluetoothGattService::new(
-                                elem.uuid.uu,
-                                elem.id as i32,
-                                elem.type_ as i32,
-                            ));
-                        }
-                        None => {
-                            // TODO(b/193685325): Log error.
-                        }
-                    }
-                }
-            }
-        }
+        let db_out = BluetoothGattService::from_db(elements);
 
         client.unwrap().callback.on_search_complete(address.unwrap().to_string(), db_out, 0);
     }
@@ -1344,13 +2079,30 @@ impl BtifGattClientCallbacks for BluetoothGatt {
             return;
         }
 
-        client.unwrap().callback.on_connection_updated(
-            address.unwrap(),
+        let client = client.unwrap();
+        let address = address.unwrap();
+        client.callback.on_connection_updated(
+            address.clone(),
             interval as i32,
             latency as i32,
             timeout as i32,
             status as i32,
         );
+
+        let pending = match self.pending_priority.get(&conn_id) {
+            None => return,
+            Some(pending) => pending,
+        };
+
+        // A failed update is reported for the pending request. A successful one only completes
+        // the request if the connection now uses the requested parameters.
+        let is_success = GattStatus::from_u8(status) == Some(GattStatus::Success);
+        if is_success && !pending.matches(interval, latency, timeout) {
+            return;
+        }
+
+        let priority = self.pending_priority.remove(&conn_id).unwrap().priority;
+        client.callback.on_connection_priority_updated(address, priority, status as i32);
     }
 
     fn service_changed_cb(&self, conn_id: i32) {
@@ -1368,6 +2120,99 @@ impl BtifGattClientCallbacks for BluetoothGatt {
     }
 }
 
+#[btif_callbacks_dispatcher(
+    BluetoothGatt,
+    dispatch_gatt_scanner_inband_callbacks,
+    GattScannerInbandCallbacks
+)]
+pub(crate) trait BtifGattScannerInbandCallbacks {
+    #[btif_callback(StartSyncCallback)]
+    fn start_sync_cb(
+        &mut self,
+        status: u8,
+        sync_handle: u16,
+        advertising_sid: u8,
+        address_type: u8,
+        address: RawAddress,
+        phy: u8,
+        interval: u16,
+    );
+
+    #[btif_callback(SyncReportCallback)]
+    fn sync_report_cb(
+        &mut self,
+        sync_handle: u16,
+        tx_power: i8,
+        rssi: i8,
+        status: u8,
+        data: Vec<u8>,
+    );
+
+    #[btif_callback(SyncLostCallback)]
+    fn sync_lost_cb(&mut self, sync_handle: u16);
+
+    #[btif_callback(SyncTransferCallback)]
+    fn sync_transfer_cb(&mut self, status: u8, address: RawAddress);
+}
+
+impl BtifGattScannerInbandCallbacks for BluetoothGatt {
+    fn start_sync_cb(
+        &mut self,
+        status: u8,
+        sync_handle: u16,
+        advertising_sid: u8,
+        _address_type: u8,
+        address: RawAddress,
+        phy: u8,
+        interval: u16,
+    ) {
+        let sync = PeriodicSync {
+            sync_handle,
+            address: address.to_string(),
+            sid: advertising_sid,
+            phy,
+            interval,
+        };
+
+        self.periodic_syncs.on_started(status, sync.clone());
+        for callback in self.sync_callbacks.values() {
+            callback.on_sync_started(status, sync.clone());
+        }
+    }
+
+    fn sync_report_cb(
+        &mut self,
+        sync_handle: u16,
+        tx_power: i8,
+        rssi: i8,
+        status: u8,
+        data: Vec<u8>,
+    ) {
+        for callback in self.sync_callbacks.values() {
+            callback.on_sync_report(
+                sync_handle,
+                tx_power as i32,
+                rssi as i32,
+                status,
+                data.clone(),
+            );
+        }
+    }
+
+    fn sync_lost_cb(&mut self, sync_handle: u16) {
+        self.periodic_syncs.remove(sync_handle);
+        for callback in self.sync_callbacks.values() {
+            callback.on_sync_lost(sync_handle);
+        }
+    }
+
+    fn sync_transfer_cb(&mut self, status: u8, address: RawAddress) {
+        for callback in self.sync_callbacks.values() {
+            callback.on_sync_transferred(status, address.to_string());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     struct TestBluetoothGattCallback {
@@ -1443,6 +2288,14 @@ mod tests {
         ) {
         }
 
+        fn on_connection_priority_updated(
+            &self,
+            _addr: String,
+            _priority: GattConnectionPriority,
+            _status: i32,
+        ) {
+        }
+
         fn on_service_changed(&self, _addr: String) {}
     }
 
@@ -1525,4 +2378,43 @@ mod tests {
         assert!(found.is_some());
         assert_eq!(4, found.unwrap());
     }
+
+    #[test]
+    fn test_context_map_orphans() {
+        let mut map = ContextMap::new();
+
+        let uuid1 = parse_uuid_string("00000000000000000000000000000001").unwrap().uu;
+        let uuid2 = parse_uuid_string("00000000000000000000000000000002").unwrap().uu;
+        map.add(&uuid1, Box::new(TestBluetoothGattCallback::new(String::from("Callback 1"))));
+        map.add(&uuid2, Box::new(TestBluetoothGattCallback::new(String::from("Callback 2"))));
+        map.set_client_id(&uuid1, 1);
+        map.add_connection(1, 3, &String::from("aa:bb:cc:dd:ee:ff"));
+        map.add_connection(1, 4, &String::from("11:22:33:44:55:66"));
+
+        // Clients still registering can't be unregistered yet.
+        map.set_orphaned(&uuid1);
+        map.set_orphaned(&uuid2);
+        assert_eq!(map.get_orphaned_client_ids(), vec![1]);
+
+        map.set_client_id(&uuid2, 2);
+        assert_eq!(map.get_orphaned_client_ids(), vec![1, 2]);
+
+        assert_eq!(map.remove_client_connections(1), 2);
+        assert!(map.get_conn_id_from_address(1, &String::from("aa:bb:cc:dd:ee:ff")).is_none());
+    }
+
+    #[test]
+    fn test_pending_priority() {
+        let pending = PendingPriority::new(GattConnectionPriority::High, 0);
+        assert!(pending.matches(6, 0, 500));
+        assert!(pending.matches(8, 0, 500));
+        assert!(!pending.matches(9, 0, 500));
+        assert!(!pending.matches(24, 0, 500));
+        assert!(!pending.matches(6, 2, 500));
+        assert!(!pending.matches(6, 0, 400));
+
+        let pending = PendingPriority::new(GattConnectionPriority::LowPower, 1);
+        assert!(pending.matches(90, 2, 500));
+        assert!(!pending.matches(90, 0, 500));
+    }
 }
@@ -11,11 +11,12 @@ use bt_topshim::profiles::hfp::{
     HfpCodecCapability,
 };
 
+use bt_topshim::profiles::stats::{self, ProfileInfo};
 use bt_topshim::topstack;
 
 use log::{info, warn};
 use num_traits::cast::ToPrimitive;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -24,7 +25,11 @@ use tokio::sync::mpsc::Sender;
 use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
 
+use crate::battery_manager::{BatteryManager, BatterySource};
 use crate::bluetooth::{Bluetooth, BluetoothDevice, IBluetooth};
+use crate::tracer::{TraceCategory, Tracer};
+use crate::traffic::{TrafficAccounting, TrafficProfile};
+use crate::uuid::Profile;
 use crate::Message;
 
 const DEFAULT_PROFILE_DISCOVERY_TIMEOUT_SEC: u64 = 5;
@@ -111,16 +116,29 @@ pub struct BluetoothMedia {
     a2dp: Option<A2dp>,
     avrcp: Option<Avrcp>,
     a2dp_states: HashMap<RawAddress, BtavConnectionState>,
+    /// Device set with `set_active_device`, which the A2DP audio is sent to.
+    a2dp_active_device: Option<RawAddress>,
+    /// Audio bytes the A2DP stream had read when the traffic was last sampled.
+    a2dp_bytes_read: u64,
     hfp: Option<Hfp>,
     hfp_states: HashMap<RawAddress, BthfConnectionState>,
     selectable_caps: HashMap<RawAddress, Vec<A2dpCodecConfig>>,
     hfp_caps: HashMap<RawAddress, HfpCodecCapability>,
     device_added_tasks: Arc<Mutex<HashMap<RawAddress, Option<JoinHandle<()>>>>>,
     absolute_volume: bool,
+    tracer: Arc<Mutex<Tracer>>,
+    battery_manager: Arc<Mutex<Box<BatteryManager>>>,
+    traffic: Arc<Mutex<TrafficAccounting>>,
 }
 
 impl BluetoothMedia {
-    pub fn new(tx: Sender<Message>, intf: Arc<Mutex<BluetoothInterface>>) -> BluetoothMedia {
+    pub fn new(
+        tx: Sender<Message>,
+        intf: Arc<Mutex<BluetoothInterface>>,
+        tracer: Arc<Mutex<Tracer>>,
+        battery_manager: Arc<Mutex<Box<BatteryManager>>>,
+        traffic: Arc<Mutex<TrafficAccounting>>,
+    ) -> BluetoothMedia {
         BluetoothMedia {
             intf,
             initialized: false,
@@ -131,12 +149,38 @@ impl BluetoothMedia {
             a2dp: None,
             avrcp: None,
             a2dp_states: HashMap::new(),
+            a2dp_active_device: None,
+            a2dp_bytes_read: 0,
             hfp: None,
             hfp_states: HashMap::new(),
             selectable_caps: HashMap::new(),
             hfp_caps: HashMap::new(),
             device_added_tasks: Arc::new(Mutex::new(HashMap::new())),
             absolute_volume: false,
+            tracer,
+            battery_manager,
+            traffic,
+        }
+    }
+
+    /// Adds the audio the A2DP stream read since the last sample to the traffic of the active
+    /// device.
+    pub fn update_a2dp_traffic(&mut self) {
+        let a2dp = match self.a2dp.as_ref() {
+            Some(a2dp) => a2dp,
+            None => return,
+        };
+
+        // The total starts over with every stream.
+        let total = a2dp.get_presentation_position().total_bytes_read;
+        let bytes = match total.checked_sub(self.a2dp_bytes_read) {
+            Some(bytes) => bytes,
+            None => total,
+        };
+        self.a2dp_bytes_read = total;
+
+        if let Some(addr) = self.a2dp_active_device {
+            self.traffic.lock().unwrap().add(&addr.to_string(), TrafficProfile::A2dp, 0, bytes);
         }
     }
 
@@ -152,6 +196,23 @@ impl BluetoothMedia {
                 {
                     return;
                 }
+                match state {
+                    BtavConnectionState::Connected | BtavConnectionState::Disconnected => {
+                        self.tracer.lock().unwrap().end(
+                            TraceCategory::ProfileConnect,
+                            "a2dp",
+                            &addr.to_string(),
+                            &format!("{:?}", state),
+                        );
+                    }
+                    _ => (),
+                }
+                if state == BtavConnectionState::Disconnected
+                    && self.a2dp_active_device == Some(addr)
+                {
+                    self.update_a2dp_traffic();
+                    self.a2dp_active_device = None;
+                }
                 match state {
                     BtavConnectionState::Connected => {
                         info!("[{}]: a2dp connected.", addr.to_string());
@@ -208,6 +269,17 @@ impl BluetoothMedia {
                 {
                     return;
                 }
+                match state {
+                    BthfConnectionState::SlcConnected | BthfConnectionState::Disconnected => {
+                        self.tracer.lock().unwrap().end(
+                            TraceCategory::ProfileConnect,
+                            "hfp",
+                            &addr.to_string(),
+                            &format!("{:?}", state),
+                        );
+                    }
+                    _ => (),
+                }
                 match state {
                     BthfConnectionState::Connected => {
                         info!("[{}]: hfp connected.", addr.to_string());
@@ -220,6 +292,10 @@ impl BluetoothMedia {
                     }
                     BthfConnectionState::Disconnected => {
                         info!("[{}]: hfp disconnected.", addr.to_string());
+                        self.battery_manager
+                            .lock()
+                            .unwrap()
+                            .remove_level(&addr.to_string(), BatterySource::Hfp);
                         match self.hfp_states.remove(&addr) {
                             Some(_) => self.notify_media_capability_removed(addr),
                             None => {
@@ -260,6 +336,13 @@ impl BluetoothMedia {
                     }
                 }
             }
+            HfpCallbacks::BatteryLevel(level, addr) => {
+                self.battery_manager.lock().unwrap().update_level(
+                    &addr.to_string(),
+                    BatterySource::Hfp,
+                    level.into(),
+                );
+            }
         }
     }
 
@@ -412,6 +495,32 @@ impl BluetoothMedia {
         }
         0
     }
+
+    /// Returns the profiles connected to at least one device.
+    pub fn get_connected_profiles(&self) -> HashSet<Profile> {
+        let mut profiles = HashSet::new();
+        if self.a2dp_states.values().any(|s| *s == BtavConnectionState::Connected) {
+            profiles.insert(Profile::A2dpSink);
+        }
+        if self.hfp_states.values().any(|s| *s == BthfConnectionState::SlcConnected) {
+            profiles.insert(Profile::Hfp);
+        }
+        profiles
+    }
+
+    pub fn get_profile_info(&self) -> Vec<ProfileInfo> {
+        vec![
+            self.a2dp
+                .as_ref()
+                .map_or_else(|| stats::get_profile_info("a2dp", 0), |p| p.query_profile_info()),
+            self.avrcp
+                .as_ref()
+                .map_or_else(|| stats::get_profile_info("avrcp", 0), |p| p.query_profile_info()),
+            self.hfp
+                .as_ref()
+                .map_or_else(|| stats::get_profile_info("hfp", 0), |p| p.query_profile_info()),
+        ]
+    }
 }
 
 fn get_a2dp_dispatcher(tx: Sender<Message>) -> A2dpCallbacksDispatcher {
@@ -480,6 +589,12 @@ impl IBluetoothMedia for BluetoothMedia {
 
     fn connect(&mut self, device: String) {
         if let Some(addr) = RawAddress::from_string(device.clone()) {
+            {
+                let key = addr.to_string();
+                let mut tracer = self.tracer.lock().unwrap();
+                tracer.begin(TraceCategory::ProfileConnect, "a2dp", &key);
+                tracer.begin(TraceCategory::ProfileConnect, "hfp", &key);
+            }
             self.a2dp.as_mut().unwrap().connect(addr);
             self.hfp.as_mut().unwrap().connect(addr);
         } else {
@@ -488,11 +603,32 @@ impl IBluetoothMedia for BluetoothMedia {
     }
 
     fn cleanup(&mut self) -> bool {
+        if !self.initialized {
+            return false;
+        }
+        self.initialized = false;
+
+        // The audio so far belongs to the active device.
+        self.update_a2dp_traffic();
+
+        if let Some(a2dp) = self.a2dp.as_mut() {
+            a2dp.cleanup();
+        }
+        if let Some(avrcp) = self.avrcp.as_mut() {
+            avrcp.cleanup();
+        }
+        if let Some(hfp) = self.hfp.as_mut() {
+            hfp.cleanup();
+        }
+
         true
     }
 
     fn set_active_device(&mut self, device: String) {
         if let Some(addr) = RawAddress::from_string(device.clone()) {
+            // The audio so far belongs to the previous device.
+            self.update_a2dp_traffic();
+            self.a2dp_active_device = Some(addr);
             self.a2dp.as_mut().unwrap().set_active_device(addr);
         } else {
             warn!("Invalid device string {}", device);
@@ -532,10 +668,13 @@ impl IBluetoothMedia for BluetoothMedia {
     }
 
     fn start_audio_request(&mut self) {
+        // Sample the previous stream before its total starts over.
+        self.update_a2dp_traffic();
         self.a2dp.as_mut().unwrap().start_audio_request();
     }
 
     fn stop_audio_request(&mut self) {
+        self.update_a2dp_traffic();
         self.a2dp.as_mut().unwrap().stop_audio_request();
     }
 
@@ -0,0 +1,171 @@
+//! Detection and repair of bond conflicts.
+//!
+//! A peer that was reset, or that paired with another host, no longer has the keys we stored for
+//! it. Encrypting with those keys then fails with an authentication failure or missing key, which
+//! is reported as a conflict if the device was bonded. Repairing a conflict removes the bond and
+//! pairs again, but the new pairing must wait until the stack reports the bond as removed, as
+//! pairing a device that is still bonded fails.
+
+use bt_topshim::btif::{BtBondFailReason, BtBondState};
+use std::collections::HashSet;
+
+/// What to do after the bond state of a device changed.
+#[derive(Debug, PartialEq)]
+pub enum BondRepairAction {
+    None,
+    /// The bond of a device waiting for a repair is gone, pair again.
+    Rebond,
+    /// The device lost the keys of its bond, report a conflict.
+    Conflict,
+}
+
+pub struct BondRepairs {
+    /// Devices to pair with again once their bond is removed.
+    pending: HashSet<String>,
+}
+
+impl BondRepairs {
+    pub fn new() -> Self {
+        BondRepairs { pending: HashSet::new() }
+    }
+
+    /// Whether a bonding failure with a bonded device means that the device lost its keys.
+    pub fn is_conflict(fail_reason: BtBondFailReason) -> bool {
+        matches!(
+            fail_reason,
+            BtBondFailReason::AuthenticationFailure | BtBondFailReason::KeyMissing
+        )
+    }
+
+    /// Pairs with `address` again once its bond is reported as removed.
+    pub fn request(&mut self, address: String) {
+        self.pending.insert(address);
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Handles a bond state change of `address`, where `was_bonded` tells whether the device was
+    /// bonded before the change. A pending repair takes precedence over a conflict, as the removal
+    /// of the bond being repaired may carry the failure that caused the conflict.
+    pub fn on_bond_state_changed(
+        &mut self,
+        address: &str,
+        was_bonded: bool,
+        bond_state: &BtBondState,
+        fail_reason: BtBondFailReason,
+    ) -> BondRepairAction {
+        if *bond_state == BtBondState::NotBonded && self.pending.remove(address) {
+            BondRepairAction::Rebond
+        } else if was_bonded && Self::is_conflict(fail_reason) {
+            BondRepairAction::Conflict
+        } else {
+            BondRepairAction::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "aa:bb:cc:dd:ee:ff";
+
+    #[test]
+    fn test_conflict_classification() {
+        let mut repairs = BondRepairs::new();
+        let conflicts = [BtBondFailReason::AuthenticationFailure, BtBondFailReason::KeyMissing];
+        let others = [
+            BtBondFailReason::None,
+            BtBondFailReason::PasskeyEntryFailed,
+            BtBondFailReason::RejectedByPeer,
+            BtBondFailReason::Timeout,
+            BtBondFailReason::Unknown,
+        ];
+
+        for reason in conflicts.iter() {
+            assert_eq!(
+                repairs.on_bond_state_changed(ADDRESS, true, &BtBondState::NotBonded, *reason),
+                BondRepairAction::Conflict
+            );
+            // Failing to pair with a new device is a plain pairing failure.
+            assert_eq!(
+                repairs.on_bond_state_changed(ADDRESS, false, &BtBondState::NotBonded, *reason),
+                BondRepairAction::None
+            );
+        }
+
+        for reason in others.iter() {
+            assert_eq!(
+                repairs.on_bond_state_changed(ADDRESS, true, &BtBondState::NotBonded, *reason),
+                BondRepairAction::None
+            );
+        }
+    }
+
+    #[test]
+    fn test_rebond_after_removal() {
+        let mut repairs = BondRepairs::new();
+        repairs.request(String::from(ADDRESS));
+
+        // Nothing happens until the bond is reported as removed.
+        assert_eq!(
+            repairs.on_bond_state_changed(
+                ADDRESS,
+                true,
+                &BtBondState::Bonded,
+                BtBondFailReason::None
+            ),
+            BondRepairAction::None
+        );
+        assert_eq!(
+            repairs.on_bond_state_changed(
+                "11:22:33:44:55:66",
+                true,
+                &BtBondState::NotBonded,
+                BtBondFailReason::None
+            ),
+            BondRepairAction::None
+        );
+
+        // The removal may carry the failure that caused the conflict.
+        assert_eq!(
+            repairs.on_bond_state_changed(
+                ADDRESS,
+                true,
+                &BtBondState::NotBonded,
+                BtBondFailReason::KeyMissing
+            ),
+            BondRepairAction::Rebond
+        );
+
+        // Pairing only starts again once.
+        assert_eq!(
+            repairs.on_bond_state_changed(
+                ADDRESS,
+                false,
+                &BtBondState::NotBonded,
+                BtBondFailReason::None
+            ),
+            BondRepairAction::None
+        );
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut repairs = BondRepairs::new();
+        repairs.request(String::from(ADDRESS));
+        repairs.clear();
+
+        assert_eq!(
+            repairs.on_bond_state_changed(
+                ADDRESS,
+                true,
+                &BtBondState::NotBonded,
+                BtBondFailReason::None
+            ),
+            BondRepairAction::None
+        );
+    }
+}
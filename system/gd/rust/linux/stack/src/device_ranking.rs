@@ -0,0 +1,223 @@
+//! Ranking of remote devices for UI pickers.
+//!
+//! Devices are scored from what the adapter already knows about them (bond state, connection
+//! state and history, RSSI and supported profiles) so that every UI doesn't need to come up with
+//! its own heuristics.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::bluetooth::BluetoothDevice;
+use crate::uuid::Profile;
+
+/// Connections older than this don't contribute to the recency score.
+const RECENCY_WINDOW: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// RSSI range that is mapped linearly onto the RSSI weight.
+const RSSI_FLOOR_DBM: i32 = -100;
+const RSSI_CEILING_DBM: i32 = -30;
+
+/// What the ranked list will be used for. Devices supporting a profile relevant to the use case
+/// are preferred.
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum RankingUseCase {
+    Any = 0,
+    AudioSink,
+    AudioSource,
+    Headset,
+    Input,
+}
+
+impl RankingUseCase {
+    fn get_profiles(&self) -> &'static [Profile] {
+        match self {
+            RankingUseCase::Any => &[],
+            RankingUseCase::AudioSink => &[Profile::A2dpSink, Profile::LeAudio],
+            RankingUseCase::AudioSource => &[Profile::A2dpSource],
+            RankingUseCase::Headset => &[Profile::Hfp, Profile::Hsp, Profile::LeAudio],
+            RankingUseCase::Input => &[Profile::Hid, Profile::Hogp],
+        }
+    }
+}
+
+/// Relative weight of each scoring criterion. A weight of 0 disables the criterion.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankingWeights {
+    pub bonded: i32,
+    pub connected: i32,
+    pub profile_match: i32,
+    /// Maximum contribution of RSSI, given to devices at or above -30 dBm.
+    pub rssi: i32,
+    /// Maximum contribution of a connection that just happened.
+    pub recency: i32,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        RankingWeights { bonded: 30, connected: 20, profile_match: 40, rssi: 20, recency: 25 }
+    }
+}
+
+/// Everything known about a device that is used for scoring.
+pub struct RankingInput {
+    pub device: BluetoothDevice,
+    pub bonded: bool,
+    pub connected: bool,
+    pub rssi: Option<i8>,
+    /// Time since the device was last connected, if ever.
+    pub since_connected: Option<Duration>,
+    pub profiles: Vec<Profile>,
+}
+
+/// A device with its score and a human readable explanation of how the score was computed.
+#[derive(Clone, Debug, Default)]
+pub struct RankedDevice {
+    pub device: BluetoothDevice,
+    pub score: i32,
+    pub explanation: String,
+}
+
+fn score_device(
+    input: &RankingInput,
+    use_case: RankingUseCase,
+    w: &RankingWeights,
+) -> RankedDevice {
+    let mut score = 0;
+    let mut reasons: Vec<String> = vec![];
+
+    if input.bonded && w.bonded != 0 {
+        score += w.bonded;
+        reasons.push(format!("bonded {:+}", w.bonded));
+    }
+
+    if input.connected && w.connected != 0 {
+        score += w.connected;
+        reasons.push(format!("connected {:+}", w.connected));
+    }
+
+    let wanted = use_case.get_profiles();
+    if let Some(p) = input.profiles.iter().find(|p| wanted.contains(p)) {
+        if w.profile_match != 0 {
+            score += w.profile_match;
+            reasons.push(format!("supports {:?} {:+}", p, w.profile_match));
+        }
+    }
+
+    if let Some(rssi) = input.rssi {
+        let clamped = (rssi as i32).clamp(RSSI_FLOOR_DBM, RSSI_CEILING_DBM);
+        let part = w.rssi * (clamped - RSSI_FLOOR_DBM) / (RSSI_CEILING_DBM - RSSI_FLOOR_DBM);
+        if part != 0 {
+            score += part;
+            reasons.push(format!("rssi {} dBm {:+}", rssi, part));
+        }
+    }
+
+    if let Some(age) = input.since_connected {
+        if age < RECENCY_WINDOW {
+            let remaining = (RECENCY_WINDOW - age).as_secs() as i64;
+            let part = (w.recency as i64 * remaining / RECENCY_WINDOW.as_secs() as i64) as i32;
+            if part != 0 {
+                score += part;
+                reasons.push(format!("connected {}s ago {:+}", age.as_secs(), part));
+            }
+        }
+    }
+
+    RankedDevice { device: input.device.clone(), score, explanation: reasons.join(", ") }
+}
+
+/// Scores all devices and returns them from best to worst. Ties keep the input order.
+///
+/// A device can be both bonded and discovered, so it may be given more than once. Only its best
+/// scoring entry is kept.
+pub fn rank_devices(
+    inputs: &[RankingInput],
+    use_case: RankingUseCase,
+    weights: &RankingWeights,
+) -> Vec<RankedDevice> {
+    let mut ranked: Vec<RankedDevice> =
+        inputs.iter().map(|i| score_device(i, use_case, weights)).collect();
+    ranked.sort_by_key(|r| std::cmp::Reverse(r.score));
+
+    let mut seen = HashSet::new();
+    ranked.retain(|r| seen.insert(r.device.address.clone()));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(address: &str) -> RankingInput {
+        RankingInput {
+            device: BluetoothDevice::new(address.to_string(), "".to_string()),
+            bonded: false,
+            connected: false,
+            rssi: None,
+            since_connected: None,
+            profiles: vec![],
+        }
+    }
+
+    #[test]
+    fn test_profile_match_and_bond_order() {
+        let mut speaker = input("00:00:00:00:00:01");
+        speaker.profiles = vec![Profile::A2dpSink];
+        let mut keyboard = input("00:00:00:00:00:02");
+        keyboard.profiles = vec![Profile::Hid];
+        keyboard.bonded = true;
+
+        let weights = RankingWeights::default();
+        let ranked = rank_devices(&[keyboard, speaker], RankingUseCase::AudioSink, &weights);
+        assert_eq!(ranked[0].device.address, "00:00:00:00:00:01");
+        assert_eq!(ranked[0].score, weights.profile_match);
+        assert_eq!(ranked[0].explanation, "supports A2dpSink +40");
+
+        let mut ranked = rank_devices(
+            &[input("00:00:00:00:00:03"), {
+                let mut i = input("00:00:00:00:00:04");
+                i.bonded = true;
+                i
+            }],
+            RankingUseCase::Any,
+            &weights,
+        );
+        assert_eq!(ranked.remove(0).device.address, "00:00:00:00:00:04");
+    }
+
+    #[test]
+    fn test_rssi_and_recency_scaling() {
+        let weights = RankingWeights::default();
+
+        let mut near = input("00:00:00:00:00:01");
+        near.rssi = Some(-20);
+        let mut far = input("00:00:00:00:00:02");
+        far.rssi = Some(-110);
+        let mut recent = input("00:00:00:00:00:03");
+        recent.since_connected = Some(Duration::from_secs(0));
+        let mut old = input("00:00:00:00:00:04");
+        old.since_connected = Some(RECENCY_WINDOW);
+
+        let ranked = rank_devices(&[far, near, old, recent], RankingUseCase::Any, &weights);
+        let scores: Vec<i32> = ranked.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![weights.recency, weights.rssi, 0, 0]);
+        assert_eq!(ranked[2].explanation, "");
+    }
+
+    #[test]
+    fn test_duplicate_address_keeps_best() {
+        let weights = RankingWeights::default();
+
+        let mut bonded = input("00:00:00:00:00:01");
+        bonded.bonded = true;
+        let mut found = input("00:00:00:00:00:01");
+        found.rssi = Some(-30);
+        let other = input("00:00:00:00:00:02");
+
+        let ranked = rank_devices(&[found, other, bonded], RankingUseCase::Any, &weights);
+        let addresses: Vec<&str> = ranked.iter().map(|r| r.device.address.as_str()).collect();
+        assert_eq!(addresses, vec!["00:00:00:00:00:01", "00:00:00:00:00:02"]);
+        assert_eq!(ranked[0].score, weights.bonded);
+    }
+}
@@ -0,0 +1,264 @@
+//! Controller feature gating.
+//!
+//! Managers register the controller features each of their capabilities needs (e.g. the 2M PHY
+//! for `gatt.phy_2m`) and ask the registry before using it. This gives every API the same answer
+//! and reason when the hardware can't do something, instead of each manager failing in its own
+//! way further down the stack.
+//!
+//! The codecs the controller can encode and decode itself are summarized here as well, so that
+//! users can check whether audio offload is available at all.
+
+use bt_topshim::controller::{
+    CodecId, CodecTransport, ControllerFeatures, LocalCodec, LocalCodecCapabilities,
+};
+
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum ControllerFeature {
+    Le = 0,
+    Le2mPhy,
+    LeCodedPhy,
+    LeExtendedAdvertising,
+    LePeriodicAdvertising,
+    LePrivacy,
+    LeIsoCentral,
+    LeIsoPeripheral,
+    LeIsoBroadcaster,
+    LeIsoSyncReceiver,
+    SecureConnections,
+    SniffSubrating,
+    LeBatchScan,
+}
+
+impl ControllerFeature {
+    /// Returns the features set in `features` as read from the controller.
+    pub fn from_controller(features: &ControllerFeatures) -> HashSet<ControllerFeature> {
+        [
+            (ControllerFeature::Le, features.le),
+            (ControllerFeature::Le2mPhy, features.le_2m_phy),
+            (ControllerFeature::LeCodedPhy, features.le_coded_phy),
+            (ControllerFeature::LeExtendedAdvertising, features.le_extended_advertising),
+            (ControllerFeature::LePeriodicAdvertising, features.le_periodic_advertising),
+            (ControllerFeature::LePrivacy, features.le_privacy),
+            (ControllerFeature::LeIsoCentral, features.le_cis_central),
+            (ControllerFeature::LeIsoPeripheral, features.le_cis_peripheral),
+            (ControllerFeature::LeIsoBroadcaster, features.le_iso_broadcaster),
+            (ControllerFeature::LeIsoSyncReceiver, features.le_sync_receiver),
+            (ControllerFeature::SecureConnections, features.secure_connections),
+            (ControllerFeature::SniffSubrating, features.sniff_subrating),
+            (ControllerFeature::LeBatchScan, features.le_batch_scan),
+        ]
+        .iter()
+        .filter(|(_, supported)| *supported)
+        .map(|(feature, _)| *feature)
+        .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnsupportedReason {
+    /// Nobody registered the capability.
+    UnknownCapability,
+    /// The controller features haven't been read yet, e.g. because the adapter is off.
+    ControllerNotReady,
+    /// The controller lacks the listed features.
+    MissingFeatures(Vec<ControllerFeature>),
+}
+
+impl UnsupportedReason {
+    pub fn get_message(&self) -> String {
+        match self {
+            UnsupportedReason::UnknownCapability => String::from("Unknown capability"),
+            UnsupportedReason::ControllerNotReady => {
+                String::from("Controller features are not known while the adapter is off")
+            }
+            UnsupportedReason::MissingFeatures(missing) => {
+                format!("Unsupported on this hardware, missing {:?}", missing)
+            }
+        }
+    }
+}
+
+/// Availability of a registered capability.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CapabilityStatus {
+    pub name: String,
+    pub available: bool,
+    /// Required features the controller lacks. Empty if the controller wasn't read yet.
+    pub missing_features: Vec<ControllerFeature>,
+    /// Why the capability is unavailable, empty if it is available.
+    pub reason: String,
+}
+
+pub struct FeatureRegistry {
+    supported: Option<HashSet<ControllerFeature>>,
+    requirements: BTreeMap<String, Vec<ControllerFeature>>,
+}
+
+impl FeatureRegistry {
+    pub fn new() -> Self {
+        FeatureRegistry { supported: None, requirements: BTreeMap::new() }
+    }
+
+    /// Registers `capability` as needing all of `required`. Registering again replaces the
+    /// requirements.
+    pub fn register(&mut self, capability: &str, required: Vec<ControllerFeature>) {
+        self.requirements.insert(String::from(capability), required);
+    }
+
+    /// Sets the features supported by the controller, or None once they're no longer known.
+    pub fn set_controller_features(&mut self, supported: Option<HashSet<ControllerFeature>>) {
+        self.supported = supported;
+    }
+
+    pub fn check(&self, capability: &str) -> Result<(), UnsupportedReason> {
+        let required = match self.requirements.get(capability) {
+            Some(r) => r,
+            None => return Err(UnsupportedReason::UnknownCapability),
+        };
+
+        let supported = match &self.supported {
+            Some(s) => s,
+            None => return Err(UnsupportedReason::ControllerNotReady),
+        };
+
+        let missing: Vec<ControllerFeature> =
+            required.iter().filter(|f| !supported.contains(f)).cloned().collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(UnsupportedReason::MissingFeatures(missing))
+        }
+    }
+
+    pub fn is_available(&self, capability: &str) -> bool {
+        self.check(capability).is_ok()
+    }
+
+    /// Returns the status of every registered capability, sorted by name.
+    pub fn get_all(&self) -> Vec<CapabilityStatus> {
+        self.requirements
+            .keys()
+            .map(|name| match self.check(name) {
+                Ok(()) => CapabilityStatus {
+                    name: name.clone(),
+                    available: true,
+                    missing_features: vec![],
+                    reason: String::new(),
+                },
+                Err(reason) => CapabilityStatus {
+                    name: name.clone(),
+                    available: false,
+                    missing_features: match &reason {
+                        UnsupportedReason::MissingFeatures(missing) => missing.clone(),
+                        _ => vec![],
+                    },
+                    reason: reason.get_message(),
+                },
+            })
+            .collect()
+    }
+}
+
+/// Codec the controller can encode and decode itself, i.e. that audio can be offloaded with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OffloadCodec {
+    pub codec: CodecId,
+    /// Logical transports the codec is supported on. Empty if the controller only supports the
+    /// first version of Read Local Supported Codecs, which doesn't tell them apart.
+    pub transports: Vec<CodecTransport>,
+    /// Capabilities read from the controller. Only LE transports are queried.
+    pub capabilities: Vec<LocalCodecCapabilities>,
+}
+
+impl OffloadCodec {
+    /// Joins the codecs read from the controller with the capabilities read for each of them.
+    pub fn from_controller(
+        codecs: &[LocalCodec],
+        capabilities: &[LocalCodecCapabilities],
+    ) -> Vec<OffloadCodec> {
+        codecs
+            .iter()
+            .map(|codec| OffloadCodec {
+                codec: codec.id,
+                transports: match codec.transports {
+                    Some(t) => [
+                        (CodecTransport::BrEdrAcl, t.br_edr_acl),
+                        (CodecTransport::BrEdrSco, t.br_edr_sco),
+                        (CodecTransport::LeCis, t.le_cis),
+                        (CodecTransport::LeBis, t.le_bis),
+                    ]
+                    .iter()
+                    .filter(|(_, supported)| *supported)
+                    .map(|(transport, _)| *transport)
+                    .collect(),
+                    None => vec![],
+                },
+                capabilities: capabilities
+                    .iter()
+                    .filter(|c| c.codec == codec.id)
+                    .cloned()
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bt_topshim::controller::{CodecDirection, CodecTransports};
+
+    #[test]
+    fn test_check_capabilities() {
+        let mut registry = FeatureRegistry::new();
+        registry.register("gatt.phy_2m", vec![ControllerFeature::Le, ControllerFeature::Le2mPhy]);
+        registry.register("gatt.phy_coded", vec![ControllerFeature::LeCodedPhy]);
+
+        assert_eq!(registry.check("gatt.phy_2m"), Err(UnsupportedReason::ControllerNotReady));
+        assert_eq!(registry.check("unknown"), Err(UnsupportedReason::UnknownCapability));
+
+        registry.set_controller_features(Some(
+            [ControllerFeature::Le, ControllerFeature::Le2mPhy].iter().cloned().collect(),
+        ));
+        assert!(registry.is_available("gatt.phy_2m"));
+        assert_eq!(
+            registry.check("gatt.phy_coded"),
+            Err(UnsupportedReason::MissingFeatures(vec![ControllerFeature::LeCodedPhy]))
+        );
+
+        let all = registry.get_all();
+        assert_eq!(all.len(), 2);
+        assert!(all[0].available && all[0].reason.is_empty());
+        assert_eq!(all[1].missing_features, vec![ControllerFeature::LeCodedPhy]);
+    }
+
+    #[test]
+    fn test_offload_codecs() {
+        let lc3 = CodecId { coding_format: 0x06, ..Default::default() };
+        let msbc = CodecId { coding_format: 0x05, ..Default::default() };
+        let codecs = vec![
+            LocalCodec {
+                id: lc3,
+                transports: Some(CodecTransports { le_cis: true, ..Default::default() }),
+            },
+            LocalCodec { id: msbc, transports: None },
+        ];
+        let capabilities = vec![LocalCodecCapabilities {
+            codec: lc3,
+            transport: CodecTransport::LeCis,
+            direction: CodecDirection::Output,
+            sample_rates: vec![16000, 48000],
+            ..Default::default()
+        }];
+
+        let offload = OffloadCodec::from_controller(&codecs, &capabilities);
+        assert_eq!(offload.len(), 2);
+        assert_eq!(offload[0].transports, vec![CodecTransport::LeCis]);
+        assert_eq!(offload[0].capabilities, capabilities);
+        assert_eq!(offload[1].codec, msbc);
+        assert!(offload[1].transports.is_empty() && offload[1].capabilities.is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+//! Usage counters for the GATT attributes accessed by clients.
+//!
+//! Operations are counted per service and characteristic UUID rather than per handle, so the
+//! counters of a characteristic add up across devices and service discoveries. Descriptor
+//! operations count towards the characteristic they belong to. The payload bytes are accounted per
+//! device in `crate::traffic`.
+//!
+//! The counters are fed by a listener on the GATT client callbacks rather than by the handlers in
+//! `crate::bluetooth_gatt`, so they follow the connections and discoveries on their own.
+
+use bt_topshim::btif::Uuid128Bit;
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::bluetooth_gatt::BluetoothGattService;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GattOperation {
+    Read,
+    Write,
+    Notify,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GattAttributeMetrics {
+    pub service_uuid: Uuid128Bit,
+    pub characteristic_uuid: Uuid128Bit,
+    pub reads: u32,
+    pub read_errors: u32,
+    pub writes: u32,
+    pub write_errors: u32,
+    pub notifications: u32,
+}
+
+pub struct GattMetrics {
+    /// Address of each connection, as the callbacks mostly identify the device by connection.
+    connections: HashMap<i32, String>,
+    /// Service and characteristic UUID of each attribute handle, per connected device. Filled from
+    /// the last service discovery.
+    handles: HashMap<String, HashMap<i32, (Uuid128Bit, Uuid128Bit)>>,
+    counters: BTreeMap<(Uuid128Bit, Uuid128Bit), GattAttributeMetrics>,
+}
+
+impl GattMetrics {
+    pub fn new() -> Self {
+        GattMetrics {
+            connections: HashMap::new(),
+            handles: HashMap::new(),
+            counters: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_connection(&mut self, conn_id: i32, address: &str) {
+        self.connections.insert(conn_id, String::from(address));
+    }
+
+    /// Forgets `conn_id`, and the attribute handles of its device once no connection to it is
+    /// left.
+    pub fn remove_connection(&mut self, conn_id: i32) {
+        let address = match self.connections.remove(&conn_id) {
+            Some(address) => address,
+            None => return,
+        };
+
+        if !self.connections.values().any(|a| *a == address) {
+            self.handles.remove(&address);
+        }
+    }
+
+    pub fn get_address(&self, conn_id: i32) -> Option<String> {
+        self.connections.get(&conn_id).cloned()
+    }
+
+    /// Learns the attribute handles of `address` from a service discovery, replacing the ones
+    /// known before.
+    pub fn set_database(&mut self, address: &str, services: &Vec<BluetoothGattService>) {
+        let mut handles = HashMap::new();
+        for service in services {
+            for characteristic in &service.characteristics {
+                let uuids = (service.uuid, characteristic.uuid);
+                handles.insert(characteristic.instance_id, uuids);
+                for descriptor in &characteristic.descriptors {
+                    handles.insert(descriptor.instance_id, uuids);
+                }
+            }
+        }
+
+        self.handles.insert(String::from(address), handles);
+    }
+
+    /// Forgets the attribute handles of `address`, e.g. because it disconnected or its services
+    /// changed. The counters are kept.
+    pub fn remove_database(&mut self, address: &str) {
+        self.handles.remove(address);
+    }
+
+    /// Counts an operation on `handle` of `address`. Operations on handles that no service
+    /// discovery reported are ignored.
+    pub fn record(&mut self, address: &str, handle: i32, op: GattOperation, success: bool) {
+        let (service_uuid, characteristic_uuid) =
+            match self.handles.get(address).and_then(|h| h.get(&handle)) {
+                Some(uuids) => *uuids,
+                None => return,
+            };
+
+        let metrics =
+            self.counters.entry((service_uuid, characteristic_uuid)).or_insert_with(|| {
+                GattAttributeMetrics { service_uuid, characteristic_uuid, ..Default::default() }
+            });
+
+        let (count, errors) = match op {
+            GattOperation::Read => (&mut metrics.reads, Some(&mut metrics.read_errors)),
+            GattOperation::Write => (&mut metrics.writes, Some(&mut metrics.write_errors)),
+            GattOperation::Notify => (&mut metrics.notifications, None),
+        };
+
+        *count = count.saturating_add(1);
+        if let (false, Some(errors)) = (success, errors) {
+            *errors = errors.saturating_add(1);
+        }
+    }
+
+    /// Returns the counters of every attribute used so far, sorted by UUID.
+    pub fn get_all(&self) -> Vec<GattAttributeMetrics> {
+        self.counters.values().cloned().collect()
+    }
+
+    pub fn reset(&mut self) {
+        self.counters.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth_gatt::{BluetoothGattCharacteristic, BluetoothGattDescriptor};
+
+    fn get_service() -> BluetoothGattService {
+        BluetoothGattService {
+            uuid: [1; 16],
+            instance_id: 1,
+            characteristics: vec![BluetoothGattCharacteristic {
+                uuid: [2; 16],
+                instance_id: 3,
+                descriptors: vec![BluetoothGattDescriptor {
+                    uuid: [3; 16],
+                    instance_id: 4,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_by_uuid() {
+        let mut metrics = GattMetrics::new();
+        metrics.set_database("aa:bb:cc:dd:ee:ff", &vec![get_service()]);
+        metrics.set_database("11:22:33:44:55:66", &vec![get_service()]);
+
+        metrics.record("aa:bb:cc:dd:ee:ff", 3, GattOperation::Read, true);
+        metrics.record("11:22:33:44:55:66", 3, GattOperation::Read, false);
+        metrics.record("aa:bb:cc:dd:ee:ff", 4, GattOperation::Write, true);
+        metrics.record("aa:bb:cc:dd:ee:ff", 3, GattOperation::Notify, true);
+
+        // Unknown handles and devices are ignored.
+        metrics.record("aa:bb:cc:dd:ee:ff", 9, GattOperation::Read, true);
+        metrics.record("00:00:00:00:00:01", 3, GattOperation::Read, true);
+
+        let all = metrics.get_all();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].characteristic_uuid, [2; 16]);
+        assert_eq!((all[0].reads, all[0].read_errors), (2, 1));
+        assert_eq!((all[0].writes, all[0].write_errors), (1, 0));
+        assert_eq!(all[0].notifications, 1);
+
+        // Handles of a forgotten database no longer count.
+        metrics.remove_database("aa:bb:cc:dd:ee:ff");
+        metrics.record("aa:bb:cc:dd:ee:ff", 3, GattOperation::Read, true);
+        assert_eq!(metrics.get_all()[0].reads, 2);
+
+        metrics.reset();
+        assert!(metrics.get_all().is_empty());
+    }
+
+    #[test]
+    fn test_connections() {
+        let mut metrics = GattMetrics::new();
+        metrics.add_connection(1, "aa:bb:cc:dd:ee:ff");
+        metrics.add_connection(2, "aa:bb:cc:dd:ee:ff");
+        metrics.set_database("aa:bb:cc:dd:ee:ff", &vec![get_service()]);
+        assert_eq!(metrics.get_address(2), Some(String::from("aa:bb:cc:dd:ee:ff")));
+
+        // The handles are kept while another connection to the device is left.
+        metrics.remove_connection(1);
+        assert_eq!(metrics.get_address(1), None);
+        metrics.record("aa:bb:cc:dd:ee:ff", 3, GattOperation::Read, true);
+        assert_eq!(metrics.get_all()[0].reads, 1);
+
+        metrics.remove_connection(2);
+        metrics.record("aa:bb:cc:dd:ee:ff", 3, GattOperation::Read, true);
+        assert_eq!(metrics.get_all()[0].reads, 1);
+    }
+}
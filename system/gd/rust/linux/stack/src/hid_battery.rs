@@ -0,0 +1,262 @@
+//! Battery level of classic HID devices.
+//!
+//! A HID device can report its battery strength in an input or feature report. The field is found
+//! in the report descriptor, which the HID host shares once the device connects, and the report
+//! is then read with GET_REPORT.
+
+use bt_topshim::profiles::hid_host::BthhReportType;
+use std::collections::HashMap;
+
+/// Battery strength usage, on the Generic Device Controls page.
+const USAGE_BATTERY_STRENGTH: u32 = 0x0006_0020;
+
+/// Most usages a usage range is expanded to.
+const MAX_USAGE_RANGE: u32 = 0x400;
+
+/// Where the battery strength is in the reports of a HID device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HidBatteryReport {
+    pub report_type: BthhReportType,
+    /// Report ID, 0 if the device doesn't use report IDs.
+    pub report_id: u8,
+    /// Offset of the field in bits, after the report ID.
+    offset: u32,
+    /// Size of the field in bits.
+    size: u32,
+    logical_min: i64,
+    logical_max: i64,
+}
+
+/// Global items of a report descriptor that matter to find a field.
+#[derive(Clone, Copy, Default)]
+struct GlobalItems {
+    usage_page: u32,
+    logical_min: i32,
+    logical_max: i32,
+    /// Logical maximum read as unsigned, for devices that don't sign-extend it.
+    logical_max_unsigned: u32,
+    report_size: u32,
+    report_id: u8,
+    report_count: u32,
+}
+
+impl HidBatteryReport {
+    /// Finds the first battery strength field of an input or feature report in `descriptor`.
+    pub fn from_descriptor(descriptor: &[u8]) -> Option<HidBatteryReport> {
+        let mut globals = GlobalItems::default();
+        let mut pushed: Vec<GlobalItems> = vec![];
+        let mut usages: Vec<u32> = vec![];
+        let mut usage_min = 0;
+        // Bits used so far by each report, by report type and ID.
+        let mut offsets: HashMap<(u32, u8), u32> = HashMap::new();
+
+        let mut i = 0;
+        while i < descriptor.len() {
+            let prefix = descriptor[i];
+            i += 1;
+
+            // Long items are reserved and carry nothing about reports.
+            if prefix == 0xfe {
+                i += 2 + *descriptor.get(i)? as usize;
+                continue;
+            }
+
+            let size = match prefix & 0x3 {
+                3 => 4,
+                size => size as usize,
+            };
+            let data = descriptor.get(i..i + size)?;
+            i += size;
+
+            let unsigned = data.iter().rev().fold(0u32, |value, byte| value << 8 | *byte as u32);
+            let signed = match size {
+                1 => unsigned as u8 as i8 as i32,
+                2 => unsigned as u16 as i16 as i32,
+                _ => unsigned as i32,
+            };
+            // Usages without a usage page take the current one.
+            let usage = match size {
+                4 => unsigned,
+                _ => globals.usage_page << 16 | unsigned,
+            };
+
+            let tag = prefix >> 4;
+            match (prefix >> 2) & 0x3 {
+                // Main items.
+                0 => {
+                    let report_type = match tag {
+                        0x8 => Some(BthhReportType::InputReport),
+                        0x9 => Some(BthhReportType::OutputReport),
+                        0xb => Some(BthhReportType::FeatureReport),
+                        _ => None,
+                    };
+
+                    if let Some(report_type) = report_type {
+                        let offset =
+                            offsets.entry((report_type as u32, globals.report_id)).or_insert(0);
+
+                        // Constant fields are padding.
+                        let constant = unsigned & 0x1 != 0;
+                        let readable = report_type != BthhReportType::OutputReport;
+                        if readable && !constant {
+                            for index in 0..globals.report_count {
+                                // The last usage applies to the remaining fields.
+                                let field_usage = usages.get(index as usize).or(usages.last());
+                                if field_usage != Some(&USAGE_BATTERY_STRENGTH) {
+                                    continue;
+                                }
+
+                                if globals.report_size == 0 || globals.report_size > 32 {
+                                    return None;
+                                }
+
+                                return Some(HidBatteryReport {
+                                    report_type,
+                                    report_id: globals.report_id,
+                                    offset: *offset + index * globals.report_size,
+                                    size: globals.report_size,
+                                    logical_min: globals.logical_min as i64,
+                                    logical_max: match globals.logical_min >= 0 {
+                                        true => globals.logical_max_unsigned as i64,
+                                        false => globals.logical_max as i64,
+                                    },
+                                });
+                            }
+                        }
+
+                        *offset += globals.report_size * globals.report_count;
+                    }
+
+                    usages.clear();
+                }
+                // Global items.
+                1 => match tag {
+                    0x0 => globals.usage_page = unsigned,
+                    0x1 => globals.logical_min = signed,
+                    0x2 => {
+                        globals.logical_max = signed;
+                        globals.logical_max_unsigned = unsigned;
+                    }
+                    0x7 => globals.report_size = unsigned,
+                    0x8 => globals.report_id = unsigned as u8,
+                    0x9 => globals.report_count = unsigned,
+                    0xa => pushed.push(globals),
+                    0xb => globals = pushed.pop()?,
+                    _ => (),
+                },
+                // Local items.
+                2 => match tag {
+                    0x0 => usages.push(usage),
+                    0x1 => usage_min = usage,
+                    0x2 => {
+                        let usage_max =
+                            std::cmp::min(usage, usage_min.saturating_add(MAX_USAGE_RANGE));
+                        usages.extend(usage_min..=usage_max);
+                    }
+                    _ => (),
+                },
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    /// Reads the battery level in percent from the data of a report returned by GET_REPORT.
+    pub fn parse_level(&self, data: &[u8]) -> Option<u32> {
+        let data = match self.report_id {
+            0 => data,
+            id => {
+                if *data.first()? != id {
+                    return None;
+                }
+                &data[1..]
+            }
+        };
+
+        let mut raw: u64 = 0;
+        for bit in 0..self.size {
+            let position = self.offset + bit;
+            let byte = *data.get((position / 8) as usize)?;
+            if (byte >> (position % 8)) & 0x1 != 0 {
+                raw |= 1 << bit;
+            }
+        }
+
+        let negative = (raw >> (self.size - 1)) & 0x1 != 0;
+        let value = match self.logical_min < 0 && negative {
+            true => raw as i64 - (1 << self.size),
+            false => raw as i64,
+        };
+
+        if self.logical_max <= self.logical_min
+            || value < self.logical_min
+            || value > self.logical_max
+        {
+            return None;
+        }
+
+        Some(((value - self.logical_min) * 100 / (self.logical_max - self.logical_min)) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_report() {
+        let descriptor = [
+            0x05, 0x01, // Usage Page (Generic Desktop)
+            0x09, 0x02, // Usage (Mouse)
+            0xa1, 0x01, // Collection (Application)
+            0x85, 0x01, //   Report ID (1)
+            0x05, 0x09, //   Usage Page (Button)
+            0x19, 0x01, //   Usage Minimum (1)
+            0x29, 0x03, //   Usage Maximum (3)
+            0x15, 0x00, //   Logical Minimum (0)
+            0x25, 0x01, //   Logical Maximum (1)
+            0x75, 0x01, //   Report Size (1)
+            0x95, 0x03, //   Report Count (3)
+            0x81, 0x02, //   Input (Data, Variable, Absolute)
+            0x85, 0x02, //   Report ID (2)
+            0x75, 0x04, //   Report Size (4)
+            0x95, 0x01, //   Report Count (1)
+            0xb1, 0x03, //   Feature (Constant)
+            0x05, 0x06, //   Usage Page (Generic Device Controls)
+            0x09, 0x20, //   Usage (Battery Strength)
+            0x75, 0x08, //   Report Size (8)
+            0x26, 0xff, 0x00, //   Logical Maximum (255)
+            0xb1, 0x02, //   Feature (Data, Variable, Absolute)
+            0xc0, // End Collection
+        ];
+
+        let report = HidBatteryReport::from_descriptor(&descriptor).unwrap();
+        assert_eq!(report.report_type, BthhReportType::FeatureReport);
+        assert_eq!(report.report_id, 2);
+
+        // The field follows 4 bits of padding, so it straddles two bytes.
+        assert_eq!(report.parse_level(&[0x02, 0x00, 0x00]), Some(0));
+        assert_eq!(report.parse_level(&[0x02, 0xf0, 0x0f]), Some(100));
+        assert_eq!(report.parse_level(&[0x02, 0x00, 0x08]), Some(50));
+
+        // Wrong report, or too short.
+        assert_eq!(report.parse_level(&[0x01, 0xf0, 0x0f]), None);
+        assert_eq!(report.parse_level(&[0x02, 0xf0]), None);
+    }
+
+    #[test]
+    fn test_no_battery() {
+        let descriptor = [
+            0x05, 0x06, // Usage Page (Generic Device Controls)
+            0x09, 0x20, // Usage (Battery Strength)
+            0x75, 0x08, // Report Size (8)
+            0x95, 0x01, // Report Count (1)
+            0x91, 0x02, // Output (Data, Variable, Absolute)
+        ];
+        assert_eq!(HidBatteryReport::from_descriptor(&descriptor), None);
+
+        // Truncated item.
+        assert_eq!(HidBatteryReport::from_descriptor(&[0x05, 0x06, 0x0a, 0x20]), None);
+    }
+}
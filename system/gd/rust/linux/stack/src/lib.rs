@@ -6,10 +6,25 @@
 #[macro_use]
 extern crate num_derive;
 
+pub mod background_connections;
+pub mod battery_manager;
+pub mod battery_service;
 pub mod bluetooth;
 pub mod bluetooth_gatt;
 pub mod bluetooth_media;
+pub mod bond_repair;
+pub mod device_ranking;
+pub mod feature_support;
+pub mod gatt_metrics;
+pub mod hid_battery;
+pub mod periodic_sync;
+pub mod power_profile;
+pub mod radio_test;
+pub mod service_refresh;
+pub mod startup;
 pub mod suspend;
+pub mod tracer;
+pub mod traffic;
 pub mod uuid;
 
 use log::debug;
@@ -17,15 +32,20 @@ use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::{Receiver, Sender};
 
+use crate::battery_manager::BatteryManager;
+use crate::battery_service::{BatteryService, BatteryServiceActions};
 use crate::bluetooth::Bluetooth;
-use crate::bluetooth_gatt::BluetoothGatt;
+use crate::bluetooth_gatt::{BluetoothGatt, IBluetoothGatt};
 use crate::bluetooth_media::{BluetoothMedia, MediaActions};
+use crate::power_profile::{IPowerProfileHook, PowerModule, PowerProfile};
 use crate::suspend::Suspend;
 use bt_topshim::{
-    btif::BaseCallbacks,
+    btif::{BaseCallbacks, Uuid128Bit},
+    controller::ControllerCallbacks,
     profiles::{
         a2dp::A2dpCallbacks, avrcp::AvrcpCallbacks, gatt::GattClientCallbacks,
-        gatt::GattServerCallbacks, hfp::HfpCallbacks, hid_host::HHCallbacks, sdp::SdpCallbacks,
+        gatt::GattScannerInbandCallbacks, gatt::GattServerCallbacks, hfp::HfpCallbacks,
+        hid_host::HHCallbacks, sdp::SdpCallbacks,
     },
 };
 
@@ -41,7 +61,9 @@ pub enum Message {
     A2dp(A2dpCallbacks),
     Avrcp(AvrcpCallbacks),
     Base(BaseCallbacks),
+    Controller(ControllerCallbacks),
     GattClient(GattClientCallbacks),
+    GattScannerInband(GattScannerInbandCallbacks),
     GattServer(GattServerCallbacks),
     HidHost(HHCallbacks),
     Hfp(HfpCallbacks),
@@ -49,16 +71,38 @@ pub enum Message {
 
     // Actions within the stack
     Media(MediaActions),
+    BatteryService(BatteryServiceActions),
+    RadioTestTimeout(u32),
 
     // Client callback disconnections
     BluetoothCallbackDisconnected(u32, BluetoothCallbackType),
+    GattClientCallbackDisconnected(Uuid128Bit),
+    PeriodicSyncCallbackDisconnected(u32),
+
+    // Reclaim GATT clients whose owner disconnected.
+    GattOrphanJanitor,
+
+    // A connection priority request got no connection update in time.
+    GattPriorityRequestTimeout(i32, u32),
 
     // Update list of found devices and remove old instances.
     DeviceFreshnessCheck,
 
+    // Rediscover the services of the next bonded device that is due.
+    ServiceRefresh,
+
+    // Record the traffic totals of every device.
+    TrafficSnapshot,
+
+    // The power profile effective for a module changed.
+    PowerProfileChanged(PowerModule, PowerProfile),
+
     // Suspend related
     SuspendCallbackRegistered(u32),
     SuspendCallbackDisconnected(u32),
+
+    // Battery related
+    BatteryCallbackDisconnected(u32),
 }
 
 /// Umbrella class for the Bluetooth stack.
@@ -77,6 +121,8 @@ impl Stack {
         bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>,
         bluetooth_media: Arc<Mutex<Box<BluetoothMedia>>>,
         suspend: Arc<Mutex<Box<Suspend>>>,
+        battery_manager: Arc<Mutex<Box<BatteryManager>>>,
+        battery_service: Arc<Mutex<Box<BatteryService>>>,
     ) {
         loop {
             let m = rx.recv().await;
@@ -103,6 +149,10 @@ impl Stack {
                     bluetooth_gatt.lock().unwrap().dispatch_gatt_client_callbacks(m);
                 }
 
+                Message::GattScannerInband(m) => {
+                    bluetooth_gatt.lock().unwrap().dispatch_gatt_scanner_inband_callbacks(m);
+                }
+
                 Message::GattServer(m) => {
                     // TODO(b/193685149): dispatch GATT server callbacks.
                     debug!("Unhandled Message::GattServer: {:?}", m);
@@ -112,27 +162,71 @@ impl Stack {
                     bluetooth_media.lock().unwrap().dispatch_hfp_callbacks(hf);
                 }
 
-                Message::HidHost(_h) => {
-                    // TODO(abps) - Handle hid host callbacks
-                    debug!("Received HH callback");
+                Message::HidHost(h) => {
+                    bluetooth.lock().unwrap().dispatch_hid_host_callbacks(h);
                 }
 
                 Message::Sdp(s) => {
                     bluetooth.lock().unwrap().dispatch_sdp_callbacks(s);
                 }
 
+                Message::Controller(c) => {
+                    bluetooth.lock().unwrap().dispatch_controller_callbacks(c);
+                }
+
+                Message::RadioTestTimeout(id) => {
+                    bluetooth.lock().unwrap().on_radio_test_timeout(id);
+                }
+
                 Message::Media(action) => {
                     bluetooth_media.lock().unwrap().dispatch_media_actions(action);
                 }
 
+                Message::BatteryService(action) => {
+                    battery_service.lock().unwrap().dispatch_battery_service_actions(action);
+                }
+
                 Message::BluetoothCallbackDisconnected(id, cb_type) => {
                     bluetooth.lock().unwrap().callback_disconnected(id, cb_type);
                 }
 
+                Message::GattClientCallbackDisconnected(uuid) => {
+                    bluetooth_gatt.lock().unwrap().callback_disconnected(uuid);
+                }
+
+                Message::PeriodicSyncCallbackDisconnected(id) => {
+                    bluetooth_gatt.lock().unwrap().remove_sync_callback(id);
+                }
+
+                Message::GattOrphanJanitor => {
+                    bluetooth_gatt.lock().unwrap().purge_orphans();
+                }
+
+                Message::GattPriorityRequestTimeout(conn_id, request_id) => {
+                    bluetooth_gatt.lock().unwrap().on_priority_request_timeout(conn_id, request_id);
+                }
+
                 Message::DeviceFreshnessCheck => {
                     bluetooth.lock().unwrap().trigger_freshness_check();
                 }
 
+                Message::ServiceRefresh => {
+                    bluetooth.lock().unwrap().refresh_next_device_services();
+                }
+
+                Message::TrafficSnapshot => {
+                    bluetooth.lock().unwrap().snapshot_traffic();
+                }
+
+                Message::PowerProfileChanged(module, profile) => match module {
+                    PowerModule::Adapter => {
+                        bluetooth.lock().unwrap().on_power_profile_changed(profile);
+                    }
+                    PowerModule::Gatt => {
+                        bluetooth_gatt.lock().unwrap().on_power_profile_changed(profile);
+                    }
+                },
+
                 Message::SuspendCallbackRegistered(id) => {
                     suspend.lock().unwrap().callback_registered(id);
                 }
@@ -140,6 +234,10 @@ impl Stack {
                 Message::SuspendCallbackDisconnected(id) => {
                     suspend.lock().unwrap().remove_callback(id);
                 }
+
+                Message::BatteryCallbackDisconnected(id) => {
+                    battery_manager.lock().unwrap().remove_callback(id);
+                }
             }
         }
     }
@@ -0,0 +1,138 @@
+//! Tracking of the periodic advertising trains the stack is synchronized to.
+//!
+//! A sync is pending from `sync_to_periodic_advertising` until the controller reports it started
+//! or failed. Syncs transferred by a peer (PAST) start without being pending. Established syncs
+//! are keyed by their sync handle until they are stopped or lost.
+
+use std::collections::{BTreeMap, HashSet};
+
+/// Status reported when a sync was established.
+pub const SYNC_STATUS_SUCCESS: u8 = 0;
+
+/// Highest number of periodic advertising events that may be skipped.
+pub const MAX_SYNC_SKIP: u16 = 0x01f3;
+
+/// Sync timeout bounds, in 10ms units.
+pub const MIN_SYNC_TIMEOUT: u16 = 0x000a;
+pub const MAX_SYNC_TIMEOUT: u16 = 0x4000;
+
+/// How a device synchronizes to a train transferred by a peer, as in the HCI LE Set Periodic
+/// Advertising Sync Transfer Parameters command.
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum SyncTransferMode {
+    /// Transferred syncs are ignored.
+    Disabled = 0,
+    /// Syncs are established but their reports are not forwarded.
+    ReportsDisabled = 1,
+    /// Syncs are established and their reports are forwarded.
+    ReportsEnabled = 2,
+}
+
+/// A periodic advertising train synchronized to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeriodicSync {
+    pub sync_handle: u16,
+    pub address: String,
+    pub sid: u8,
+    pub phy: u8,
+    /// Periodic advertising interval, in 1.25ms units.
+    pub interval: u16,
+}
+
+pub struct PeriodicSyncs {
+    pending: HashSet<(String, u8)>,
+    syncs: BTreeMap<u16, PeriodicSync>,
+}
+
+impl PeriodicSyncs {
+    pub fn new() -> Self {
+        PeriodicSyncs { pending: HashSet::new(), syncs: BTreeMap::new() }
+    }
+
+    /// Marks the train `sid` of `address` as being synced to. Returns false if it is already
+    /// pending or synchronized.
+    pub fn add_pending(&mut self, address: &str, sid: u8) -> bool {
+        if self.syncs.values().any(|sync| sync.address == address && sync.sid == sid) {
+            return false;
+        }
+
+        self.pending.insert((String::from(address), sid))
+    }
+
+    /// Returns false if the train `sid` of `address` wasn't pending.
+    pub fn remove_pending(&mut self, address: &str, sid: u8) -> bool {
+        self.pending.remove(&(String::from(address), sid))
+    }
+
+    /// Must be called when a sync started or failed to, whether it was pending or transferred.
+    pub fn on_started(&mut self, status: u8, sync: PeriodicSync) {
+        self.pending.remove(&(sync.address.clone(), sync.sid));
+        if status == SYNC_STATUS_SUCCESS {
+            self.syncs.insert(sync.sync_handle, sync);
+        }
+    }
+
+    /// Forgets the sync of `sync_handle` once stopped or lost. Returns None if it is unknown.
+    pub fn remove(&mut self, sync_handle: u16) -> Option<PeriodicSync> {
+        self.syncs.remove(&sync_handle)
+    }
+
+    /// Returns the established syncs, sorted by sync handle.
+    pub fn get_all(&self) -> Vec<PeriodicSync> {
+        self.syncs.values().cloned().collect()
+    }
+
+    /// Forgets all syncs, e.g. because the adapter went off. Returns the established syncs.
+    pub fn clear(&mut self) -> Vec<PeriodicSync> {
+        self.pending.clear();
+        std::mem::take(&mut self.syncs).into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_sync(sync_handle: u16, sid: u8) -> PeriodicSync {
+        PeriodicSync {
+            sync_handle,
+            address: String::from("aa:bb:cc:dd:ee:ff"),
+            sid,
+            phy: 1,
+            interval: 80,
+        }
+    }
+
+    #[test]
+    fn test_sync_lifecycle() {
+        let mut syncs = PeriodicSyncs::new();
+
+        assert!(syncs.add_pending("aa:bb:cc:dd:ee:ff", 1));
+        assert!(!syncs.add_pending("aa:bb:cc:dd:ee:ff", 1));
+        assert!(syncs.add_pending("aa:bb:cc:dd:ee:ff", 2));
+
+        // A failed sync is no longer pending and isn't tracked.
+        syncs.on_started(0x3e, get_sync(0, 2));
+        assert!(!syncs.remove_pending("aa:bb:cc:dd:ee:ff", 2));
+        assert!(syncs.get_all().is_empty());
+
+        syncs.on_started(SYNC_STATUS_SUCCESS, get_sync(4, 1));
+        assert!(!syncs.add_pending("aa:bb:cc:dd:ee:ff", 1));
+        assert_eq!(syncs.get_all(), vec![get_sync(4, 1)]);
+
+        // Syncs transferred by a peer were never pending.
+        syncs.on_started(SYNC_STATUS_SUCCESS, get_sync(2, 3));
+        assert_eq!(syncs.get_all(), vec![get_sync(2, 3), get_sync(4, 1)]);
+
+        assert_eq!(syncs.remove(4), Some(get_sync(4, 1)));
+        assert_eq!(syncs.remove(4), None);
+        assert!(syncs.add_pending("aa:bb:cc:dd:ee:ff", 1));
+        assert!(syncs.remove_pending("aa:bb:cc:dd:ee:ff", 1));
+
+        assert!(syncs.add_pending("aa:bb:cc:dd:ee:ff", 1));
+        assert_eq!(syncs.clear(), vec![get_sync(2, 3)]);
+        assert!(syncs.get_all().is_empty());
+        assert!(!syncs.remove_pending("aa:bb:cc:dd:ee:ff", 1));
+    }
+}
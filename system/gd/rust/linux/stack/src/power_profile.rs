@@ -0,0 +1,143 @@
+//! System power profiles (e.g. battery saver).
+//!
+//! A single profile is set for the whole stack and every manager adjusts its own behavior to it.
+//! A manager can be pinned to a different profile with an override, e.g. to keep HID reconnects
+//! fast while the rest of the stack saves power.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum PowerProfile {
+    Performance = 0,
+    Balanced,
+    BatterySaver,
+}
+
+impl Default for PowerProfile {
+    fn default() -> Self {
+        PowerProfile::Balanced
+    }
+}
+
+/// Managers that follow the power profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum PowerModule {
+    /// Background reconnects of the adapter.
+    Adapter = 0,
+    /// GATT client connection parameters.
+    Gatt,
+}
+
+/// What each manager is allowed to do under a profile.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerPolicy {
+    /// Maximum number of devices kept in the filter accept list for background reconnects. None
+    /// means as many as the controller supports.
+    pub max_background_connections: Option<usize>,
+    /// Whether GATT clients may request the high connection priority.
+    pub allow_high_connection_priority: bool,
+}
+
+impl PowerProfile {
+    pub fn get_policy(&self) -> PowerPolicy {
+        match self {
+            PowerProfile::Performance | PowerProfile::Balanced => PowerPolicy {
+                max_background_connections: None,
+                allow_high_connection_priority: true,
+            },
+            PowerProfile::BatterySaver => PowerPolicy {
+                max_background_connections: Some(2),
+                allow_high_connection_priority: false,
+            },
+        }
+    }
+}
+
+/// Implemented by managers that change behavior with the power profile.
+pub trait IPowerProfileHook {
+    /// Called with the profile effective for the manager whenever it changes.
+    fn on_power_profile_changed(&mut self, profile: PowerProfile);
+}
+
+/// Keeps the system profile and the per-module overrides.
+pub struct PowerProfiles {
+    profile: PowerProfile,
+    overrides: HashMap<PowerModule, PowerProfile>,
+}
+
+impl PowerProfiles {
+    pub fn new() -> Self {
+        PowerProfiles { profile: PowerProfile::default(), overrides: HashMap::new() }
+    }
+
+    pub fn get_profile(&self) -> PowerProfile {
+        self.profile
+    }
+
+    /// Profile that `module` should follow, taking overrides into account.
+    pub fn get_effective_profile(&self, module: PowerModule) -> PowerProfile {
+        *self.overrides.get(&module).unwrap_or(&self.profile)
+    }
+
+    /// Sets the system profile. Returns the modules whose effective profile changed.
+    pub fn set_profile(&mut self, profile: PowerProfile) -> Vec<PowerModule> {
+        let before = self.get_all_effective();
+        self.profile = profile;
+        self.get_changed(before)
+    }
+
+    /// Pins `module` to `profile`, or makes it follow the system profile again if None. Returns
+    /// the modules whose effective profile changed.
+    pub fn set_override(
+        &mut self,
+        module: PowerModule,
+        profile: Option<PowerProfile>,
+    ) -> Vec<PowerModule> {
+        let before = self.get_all_effective();
+        match profile {
+            Some(p) => self.overrides.insert(module, p),
+            None => self.overrides.remove(&module),
+        };
+        self.get_changed(before)
+    }
+
+    fn get_all_effective(&self) -> Vec<(PowerModule, PowerProfile)> {
+        [PowerModule::Adapter, PowerModule::Gatt]
+            .iter()
+            .map(|m| (*m, self.get_effective_profile(*m)))
+            .collect()
+    }
+
+    fn get_changed(&self, before: Vec<(PowerModule, PowerProfile)>) -> Vec<PowerModule> {
+        before
+            .into_iter()
+            .filter(|(m, p)| self.get_effective_profile(*m) != *p)
+            .map(|(m, _)| m)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_and_effective_profile() {
+        let mut profiles = PowerProfiles::new();
+        assert_eq!(profiles.get_profile(), PowerProfile::Balanced);
+
+        assert_eq!(
+            profiles.set_override(PowerModule::Adapter, Some(PowerProfile::Performance)),
+            vec![PowerModule::Adapter]
+        );
+        assert_eq!(profiles.set_profile(PowerProfile::BatterySaver), vec![PowerModule::Gatt]);
+        assert_eq!(profiles.get_effective_profile(PowerModule::Adapter), PowerProfile::Performance);
+        assert_eq!(profiles.get_effective_profile(PowerModule::Gatt), PowerProfile::BatterySaver);
+
+        assert_eq!(profiles.set_override(PowerModule::Adapter, None), vec![PowerModule::Adapter]);
+        assert!(profiles.set_profile(PowerProfile::BatterySaver).is_empty());
+        assert!(!PowerProfile::BatterySaver.get_policy().allow_high_connection_priority);
+    }
+}
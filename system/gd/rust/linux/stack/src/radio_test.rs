@@ -0,0 +1,269 @@
+//! RF tests (LE test mode) for certification and factory validation.
+//!
+//! Tests only run while the adapter is in radio test mode, which can only be entered without
+//! active connections and keeps new ones from being made. Every test ends on its own after a
+//! timeout so that a forgotten test doesn't keep the radio transmitting.
+
+use bt_topshim::controller::{LeTestPayload, LeTestPhy};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Timeout used if the caller doesn't pick one.
+const DEFAULT_TIMEOUT_MS: u32 = 10000;
+
+/// Longest a single test may run.
+const MAX_TIMEOUT_MS: u32 = 600000;
+
+/// Highest LE RF channel, i.e. 2480 MHz.
+const MAX_CHANNEL: u8 = 39;
+
+/// Number of results kept for `get_results`.
+const MAX_RESULTS: usize = 16;
+
+/// HCI status recorded for a test cut short because the controller went away.
+const STATUS_UNSPECIFIED_ERROR: u8 = 0x1f;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum RadioTestKind {
+    LeTransmitter = 0,
+    LeReceiver,
+}
+
+impl Default for RadioTestKind {
+    fn default() -> Self {
+        RadioTestKind::LeReceiver
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum RadioTestStatus {
+    Success = 0,
+    /// The adapter isn't in radio test mode.
+    NotInTestMode,
+    /// There are active connections.
+    ActiveConnections,
+    /// A test is already running.
+    AlreadyRunning,
+    /// No test is running.
+    NotRunning,
+    InvalidParameters,
+    /// The controller isn't available, e.g. because the adapter is off.
+    ControllerUnavailable,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RadioTestParams {
+    pub kind: RadioTestKind,
+    /// RF channel, 2402 + 2 * channel MHz.
+    pub channel: u8,
+    /// Packet length of a transmitter test. Ignored by receiver tests.
+    pub length: u8,
+    /// Packet payload of a transmitter test. Ignored by receiver tests.
+    pub payload: LeTestPayload,
+    pub phy: LeTestPhy,
+    /// The test is ended after this long. 0 picks the default.
+    pub timeout_ms: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RadioTestResult {
+    pub kind: RadioTestKind,
+    pub channel: u8,
+    /// HCI status of the test. A test that failed to start is reported with the start status.
+    pub status: u8,
+    /// Packets received by a receiver test.
+    pub num_packets: u16,
+    pub duration_ms: u64,
+    /// Whether the test was ended by the timeout rather than by the caller.
+    pub timed_out: bool,
+}
+
+struct RunningTest {
+    id: u32,
+    params: RadioTestParams,
+    started: Instant,
+    timed_out: bool,
+}
+
+/// Keeps the radio test mode state and enforces its interlocks. The caller talks to the
+/// controller and reports the outcome back.
+pub struct RadioTest {
+    test_mode: bool,
+    running: Option<RunningTest>,
+    next_id: u32,
+    results: VecDeque<RadioTestResult>,
+}
+
+impl RadioTest {
+    pub fn new() -> Self {
+        RadioTest { test_mode: false, running: None, next_id: 0, results: VecDeque::new() }
+    }
+
+    pub fn is_test_mode(&self) -> bool {
+        self.test_mode
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Enters or leaves radio test mode. Leaving it while a test runs is allowed; the caller has
+    /// to end the test if `is_running` is still true afterwards.
+    pub fn set_test_mode(&mut self, enabled: bool, connections: usize) -> RadioTestStatus {
+        if enabled && !self.test_mode && connections > 0 {
+            return RadioTestStatus::ActiveConnections;
+        }
+
+        self.test_mode = enabled;
+        RadioTestStatus::Success
+    }
+
+    /// Checks the interlocks and parameters and marks a test as running. Returns the id to pass
+    /// to `on_timeout` and how long the test may run.
+    pub fn start(
+        &mut self,
+        params: RadioTestParams,
+        connections: usize,
+    ) -> Result<(u32, Duration), RadioTestStatus> {
+        if !self.test_mode {
+            return Err(RadioTestStatus::NotInTestMode);
+        }
+
+        if connections > 0 {
+            return Err(RadioTestStatus::ActiveConnections);
+        }
+
+        if self.running.is_some() {
+            return Err(RadioTestStatus::AlreadyRunning);
+        }
+
+        if params.channel > MAX_CHANNEL || params.timeout_ms > MAX_TIMEOUT_MS {
+            return Err(RadioTestStatus::InvalidParameters);
+        }
+
+        let timeout_ms = match params.timeout_ms {
+            0 => DEFAULT_TIMEOUT_MS,
+            t => t,
+        };
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.running = Some(RunningTest { id, params, started: Instant::now(), timed_out: false });
+
+        Ok((id, Duration::from_millis(timeout_ms.into())))
+    }
+
+    /// Must be called with the HCI status of the test start.
+    pub fn on_started(&mut self, status: u8) {
+        if status != 0 {
+            self.finish(status, 0);
+        }
+    }
+
+    /// Returns whether the test with `id` is still running and has to be ended.
+    pub fn on_timeout(&mut self, id: u32) -> bool {
+        match self.running.as_mut() {
+            Some(test) if test.id == id => {
+                test.timed_out = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Must be called once the controller ended the test.
+    pub fn on_ended(&mut self, status: u8, num_packets: u16) -> Option<RadioTestResult> {
+        self.finish(status, num_packets)
+    }
+
+    /// Leaves test mode because the controller went away. A running test is recorded as failed.
+    pub fn reset(&mut self) {
+        self.test_mode = false;
+        self.finish(STATUS_UNSPECIFIED_ERROR, 0);
+    }
+
+    /// Results of the last tests, oldest first.
+    pub fn get_results(&self) -> Vec<RadioTestResult> {
+        self.results.iter().cloned().collect()
+    }
+
+    fn finish(&mut self, status: u8, num_packets: u16) -> Option<RadioTestResult> {
+        let test = self.running.take()?;
+        let result = RadioTestResult {
+            kind: test.params.kind,
+            channel: test.params.channel,
+            status,
+            num_packets: match test.params.kind {
+                RadioTestKind::LeReceiver => num_packets,
+                RadioTestKind::LeTransmitter => 0,
+            },
+            duration_ms: test.started.elapsed().as_millis() as u64,
+            timed_out: test.timed_out,
+        };
+
+        if self.results.len() >= MAX_RESULTS {
+            self.results.pop_front();
+        }
+        self.results.push_back(result.clone());
+
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receiver_params() -> RadioTestParams {
+        RadioTestParams {
+            kind: RadioTestKind::LeReceiver,
+            channel: 19,
+            length: 0,
+            payload: LeTestPayload::Prbs9,
+            phy: LeTestPhy::Le1m,
+            timeout_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_interlocks_and_timeout() {
+        let mut radio_test = RadioTest::new();
+        assert_eq!(radio_test.start(receiver_params(), 0), Err(RadioTestStatus::NotInTestMode));
+        assert_eq!(radio_test.set_test_mode(true, 1), RadioTestStatus::ActiveConnections);
+        assert_eq!(radio_test.set_test_mode(true, 0), RadioTestStatus::Success);
+
+        let mut params = receiver_params();
+        params.channel = 40;
+        assert_eq!(radio_test.start(params, 0), Err(RadioTestStatus::InvalidParameters));
+
+        let (id, timeout) = radio_test.start(receiver_params(), 0).unwrap();
+        assert_eq!(timeout, Duration::from_millis(DEFAULT_TIMEOUT_MS.into()));
+        assert_eq!(radio_test.start(receiver_params(), 0), Err(RadioTestStatus::AlreadyRunning));
+
+        // A stale timeout doesn't end the test.
+        assert!(!radio_test.on_timeout(id + 1));
+        assert!(radio_test.on_timeout(id));
+
+        let result = radio_test.on_ended(0, 1234).unwrap();
+        assert!(result.timed_out);
+        assert_eq!(result.num_packets, 1234);
+        assert!(!radio_test.is_running());
+        assert_eq!(radio_test.get_results(), vec![result]);
+    }
+
+    #[test]
+    fn test_failed_start_is_reported() {
+        let mut radio_test = RadioTest::new();
+        radio_test.set_test_mode(true, 0);
+        radio_test.start(receiver_params(), 0).unwrap();
+
+        // Unsupported feature or parameter value.
+        radio_test.on_started(0x11);
+        assert!(!radio_test.is_running());
+        assert_eq!(radio_test.get_results()[0].status, 0x11);
+        assert_eq!(radio_test.on_ended(0, 0), None);
+    }
+}
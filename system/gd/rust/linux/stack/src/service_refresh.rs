@@ -0,0 +1,124 @@
+//! Scheduling of service re-discovery for bonded devices.
+//!
+//! Peers can gain or lose services after a firmware update, while the stack keeps using the UUIDs
+//! found at pairing time. Bonded devices are therefore rediscovered once their services are older
+//! than the refresh interval, one device per tick so that a large bond list doesn't page every
+//! device at once. Devices can also be queued explicitly, e.g. when a change is suspected.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How often the scheduler looks for a device to refresh.
+pub const SERVICE_REFRESH_TICK: Duration = Duration::from_secs(60);
+
+pub struct ServiceRefresh {
+    /// Age after which the services of a bonded device are rediscovered, None to only refresh
+    /// queued devices.
+    interval: Option<Duration>,
+    /// When the services of each bonded device were last discovered or a discovery was started.
+    last_refresh: HashMap<String, Instant>,
+    /// Devices to refresh on the next ticks regardless of their age.
+    requested: VecDeque<String>,
+}
+
+impl ServiceRefresh {
+    pub fn new() -> Self {
+        ServiceRefresh { interval: None, last_refresh: HashMap::new(), requested: VecDeque::new() }
+    }
+
+    pub fn get_interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+    }
+
+    /// Queues `address` for a refresh on the next tick. Returns false if it was already queued.
+    pub fn request(&mut self, address: String) -> bool {
+        if self.requested.contains(&address) {
+            return false;
+        }
+
+        self.requested.push_back(address);
+        true
+    }
+
+    /// Whether ticks are needed, i.e. refreshes are periodic or devices are queued.
+    pub fn is_active(&self) -> bool {
+        self.interval.is_some() || !self.requested.is_empty()
+    }
+
+    /// Must be called when the services of `address` were discovered, whoever started it.
+    pub fn on_refreshed(&mut self, address: &str, now: Instant) {
+        if let Some(last) = self.last_refresh.get_mut(address) {
+            *last = now;
+        }
+    }
+
+    /// Picks the device to rediscover on this tick among `bonded`, queued devices first and then
+    /// the one whose services are the oldest. Devices seen for the first time count as fresh, so
+    /// enabling refreshes doesn't page every bonded device right away.
+    pub fn next_due(&mut self, bonded: &Vec<String>, now: Instant) -> Option<String> {
+        self.last_refresh.retain(|address, _| bonded.contains(address));
+        self.requested.retain(|address| bonded.contains(address));
+        for address in bonded {
+            self.last_refresh.entry(address.clone()).or_insert(now);
+        }
+
+        let next = match self.requested.pop_front() {
+            Some(address) => Some(address),
+            None => match self.interval {
+                Some(interval) => self
+                    .last_refresh
+                    .iter()
+                    .filter(|(_, last)| now.duration_since(**last) >= interval)
+                    .min_by_key(|(_, last)| **last)
+                    .map(|(address, _)| address.clone()),
+                None => None,
+            },
+        };
+
+        // Also counts failed discoveries so that an unreachable device isn't retried every tick.
+        if let Some(address) = &next {
+            self.last_refresh.insert(address.clone(), now);
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_due() {
+        let mut refresh = ServiceRefresh::new();
+        let bonded = vec![String::from("11:11:11:11:11:11"), String::from("22:22:22:22:22:22")];
+        let start = Instant::now();
+
+        // Nothing is due without an interval or a request.
+        assert_eq!(refresh.next_due(&bonded, start), None);
+        assert!(!refresh.is_active());
+
+        assert!(refresh.request(String::from("22:22:22:22:22:22")));
+        assert!(!refresh.request(String::from("22:22:22:22:22:22")));
+        assert!(refresh.request(String::from("33:33:33:33:33:33")));
+        assert_eq!(refresh.next_due(&bonded, start), Some(String::from("22:22:22:22:22:22")));
+
+        // Devices that are no longer bonded are dropped from the queue.
+        assert_eq!(refresh.next_due(&bonded, start), None);
+
+        refresh.set_interval(Some(Duration::from_secs(100)));
+        assert_eq!(refresh.next_due(&bonded, start + Duration::from_secs(50)), None);
+
+        refresh.on_refreshed("11:11:11:11:11:11", start + Duration::from_secs(60));
+        let later = start + Duration::from_secs(120);
+        assert_eq!(refresh.next_due(&bonded, later), Some(String::from("22:22:22:22:22:22")));
+        assert_eq!(refresh.next_due(&bonded, later), None);
+        assert_eq!(
+            refresh.next_due(&bonded, start + Duration::from_secs(160)),
+            Some(String::from("11:11:11:11:11:11"))
+        );
+    }
+}
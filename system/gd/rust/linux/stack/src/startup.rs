@@ -0,0 +1,249 @@
+//! Startup sequencing for the stack managers.
+//!
+//! Managers declare which other managers must be initialized before them and the orchestrator runs
+//! their initializers in dependency order. A missing or cyclic dependency is reported before any
+//! initializer is run.
+
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::{Duration, Instant};
+
+/// Initializer for a single module. Returns an error message on failure.
+pub type StartupInit = Box<dyn FnOnce() -> Result<(), String>>;
+
+struct StartupModule {
+    name: String,
+    dependencies: Vec<String>,
+    init: StartupInit,
+}
+
+/// Errors that prevent the startup sequence from completing.
+#[derive(Debug, PartialEq)]
+pub enum StartupError {
+    /// The same module name was registered more than once.
+    DuplicateModule(String),
+    /// A module depends on another module that was never registered.
+    MissingDependency { module: String, dependency: String },
+    /// The listed modules depend on each other.
+    DependencyCycle(Vec<String>),
+    /// A module initializer returned an error.
+    InitFailed { module: String, reason: String },
+}
+
+impl Display for StartupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            StartupError::DuplicateModule(module) => {
+                write!(f, "Module {} registered more than once", module)
+            }
+            StartupError::MissingDependency { module, dependency } => {
+                write!(f, "Module {} depends on missing module {}", module, dependency)
+            }
+            StartupError::DependencyCycle(modules) => {
+                write!(f, "Dependency cycle between modules: {}", modules.join(", "))
+            }
+            StartupError::InitFailed { module, reason } => {
+                write!(f, "Module {} failed to initialize: {}", module, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StartupError {}
+
+/// Timing for a single initialized module.
+#[derive(Debug, Clone)]
+pub struct StartupTiming {
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// Summary of a completed startup sequence.
+#[derive(Debug, Clone, Default)]
+pub struct StartupReport {
+    /// Modules in the order they were initialized.
+    pub timings: Vec<StartupTiming>,
+}
+
+impl StartupReport {
+    /// Total time spent in all module initializers.
+    pub fn total(&self) -> Duration {
+        self.timings.iter().map(|t| t.duration).sum()
+    }
+
+    /// Time spent initializing the given module, if it ran.
+    pub fn get_duration(&self, name: &str) -> Option<Duration> {
+        self.timings.iter().find(|t| t.name == name).map(|t| t.duration)
+    }
+}
+
+/// Initializes modules according to their declared dependencies.
+pub struct StartupOrchestrator {
+    modules: Vec<StartupModule>,
+}
+
+impl StartupOrchestrator {
+    pub fn new() -> Self {
+        StartupOrchestrator { modules: vec![] }
+    }
+
+    /// Registers a module with the names of the modules it depends on.
+    pub fn add_module(&mut self, name: &str, dependencies: &[&str], init: StartupInit) {
+        self.modules.push(StartupModule {
+            name: name.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            init,
+        });
+    }
+
+    /// Computes the initialization order without running anything.
+    fn resolve_order(&self) -> Result<Vec<usize>, StartupError> {
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for (i, module) in self.modules.iter().enumerate() {
+            if index.insert(module.name.as_str(), i).is_some() {
+                return Err(StartupError::DuplicateModule(module.name.clone()));
+            }
+        }
+
+        for module in self.modules.iter() {
+            for dep in module.dependencies.iter() {
+                if !index.contains_key(dep.as_str()) {
+                    return Err(StartupError::MissingDependency {
+                        module: module.name.clone(),
+                        dependency: dep.clone(),
+                    });
+                }
+            }
+        }
+
+        // Kahn's algorithm, keeping registration order among ready modules so the sequence is
+        // deterministic.
+        let mut remaining: Vec<usize> = self.modules.iter().map(|m| m.dependencies.len()).collect();
+        let mut done: HashSet<usize> = HashSet::new();
+        let mut order: Vec<usize> = vec![];
+
+        while order.len() < self.modules.len() {
+            let next = (0..self.modules.len()).find(|i| !done.contains(i) && remaining[*i] == 0);
+
+            match next {
+                Some(i) => {
+                    done.insert(i);
+                    order.push(i);
+
+                    let name = &self.modules[i].name;
+                    for (j, module) in self.modules.iter().enumerate() {
+                        remaining[j] -= module.dependencies.iter().filter(|d| *d == name).count();
+                    }
+                }
+                None => {
+                    let cycle = (0..self.modules.len())
+                        .filter(|i| !done.contains(i))
+                        .map(|i| self.modules[i].name.clone())
+                        .collect();
+                    return Err(StartupError::DependencyCycle(cycle));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Runs all module initializers in dependency order.
+    ///
+    /// Fails before running any initializer if the dependency graph is invalid and stops at the
+    /// first initializer that returns an error.
+    pub fn run(self) -> Result<StartupReport, StartupError> {
+        let order = self.resolve_order()?;
+
+        let mut modules: Vec<Option<StartupModule>> = self.modules.into_iter().map(Some).collect();
+        let mut report = StartupReport::default();
+
+        for i in order {
+            let module = modules[i].take().unwrap();
+            let start = Instant::now();
+            let result = (module.init)();
+            let duration = start.elapsed();
+
+            if let Err(reason) = result {
+                warn!("Startup: {} failed after {:?}: {}", module.name, duration, reason);
+                return Err(StartupError::InitFailed { module: module.name, reason });
+            }
+
+            info!("Startup: {} initialized in {:?}", module.name, duration);
+            report.timings.push(StartupTiming { name: module.name, duration });
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn recording_init(name: &'static str, log: &Rc<RefCell<Vec<&'static str>>>) -> StartupInit {
+        let log = log.clone();
+        Box::new(move || {
+            log.borrow_mut().push(name);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut orchestrator = StartupOrchestrator::new();
+        orchestrator.add_module("gatt", &["adapter"], recording_init("gatt", &log));
+        orchestrator.add_module("adapter", &["intf"], recording_init("adapter", &log));
+        orchestrator.add_module("intf", &[], recording_init("intf", &log));
+
+        let report = orchestrator.run().unwrap();
+        assert_eq!(*log.borrow(), vec!["intf", "adapter", "gatt"]);
+        assert_eq!(report.timings.len(), 3);
+        assert!(report.get_duration("adapter").is_some());
+    }
+
+    #[test]
+    fn test_missing_dependency_fails_before_init() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut orchestrator = StartupOrchestrator::new();
+        orchestrator.add_module("intf", &[], recording_init("intf", &log));
+        orchestrator.add_module("gatt", &["adapter"], recording_init("gatt", &log));
+
+        assert_eq!(
+            orchestrator.run().unwrap_err(),
+            StartupError::MissingDependency {
+                module: "gatt".to_string(),
+                dependency: "adapter".to_string()
+            }
+        );
+        assert!(log.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_cycle_and_init_failure() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut orchestrator = StartupOrchestrator::new();
+        orchestrator.add_module("a", &["b"], recording_init("a", &log));
+        orchestrator.add_module("b", &["a"], recording_init("b", &log));
+        assert_eq!(
+            orchestrator.run().unwrap_err(),
+            StartupError::DependencyCycle(vec!["a".to_string(), "b".to_string()])
+        );
+
+        let mut orchestrator = StartupOrchestrator::new();
+        orchestrator.add_module("a", &[], Box::new(|| Err("no controller".to_string())));
+        orchestrator.add_module("b", &["a"], recording_init("b", &log));
+        assert_eq!(
+            orchestrator.run().unwrap_err(),
+            StartupError::InitFailed {
+                module: "a".to_string(),
+                reason: "no controller".to_string()
+            }
+        );
+        assert!(log.borrow().is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+//! Latency spans of multi-step operations such as pairing or profile connections.
+//!
+//! A span is opened and closed by its category, name and key (usually the device address), so it
+//! can be begun by one manager and ended by another. Spans opened while another span with the
+//! same key is still open share its trace id, which ties e.g. the pairing, profile connection and
+//! service discovery of one device together. Spans that are never ended are closed when their key
+//! goes away, e.g. when the device disconnects, or after `SPAN_TIMEOUT`. Finished spans can be
+//! written out in the Chrome JSON trace format that Perfetto and chrome://tracing load.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Number of finished spans kept, the oldest are dropped first.
+const MAX_EVENTS: usize = 4096;
+
+/// Spans still open after this long are closed as timed out. Long enough for a pairing with user
+/// interaction.
+const SPAN_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Result of spans closed by `SPAN_TIMEOUT`.
+const TIMEOUT_RESULT: &str = "Timeout";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TraceCategory {
+    Pairing,
+    ProfileConnect,
+    Gatt,
+}
+
+impl TraceCategory {
+    fn get_name(&self) -> &'static str {
+        match self {
+            TraceCategory::Pairing => "pairing",
+            TraceCategory::ProfileConnect => "profile_connect",
+            TraceCategory::Gatt => "gatt",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEvent {
+    pub category: TraceCategory,
+    pub name: String,
+    pub key: String,
+    pub trace_id: u64,
+    /// Microseconds since tracing was enabled.
+    pub start_us: u64,
+    pub duration_us: u64,
+    /// How the operation ended, e.g. a status name.
+    pub result: String,
+}
+
+struct OpenSpan {
+    trace_id: u64,
+    started: Instant,
+}
+
+pub struct Tracer {
+    enabled: bool,
+    origin: Instant,
+    next_trace_id: u64,
+    open: HashMap<(TraceCategory, &'static str, String), OpenSpan>,
+    events: VecDeque<TraceEvent>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Tracer {
+            enabled: false,
+            origin: Instant::now(),
+            next_trace_id: 1,
+            open: HashMap::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enables or disables tracing. Both drop the spans collected so far.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.origin = Instant::now();
+        self.open.clear();
+        self.events.clear();
+    }
+
+    /// Opens the span `name` for `key` in `category`. Does nothing if it is already open, so the
+    /// manager that started an operation keeps the start time when another one picks it up.
+    pub fn begin(&mut self, category: TraceCategory, name: &'static str, key: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        self.expire();
+        if self.open.contains_key(&(category, name, String::from(key))) {
+            return;
+        }
+
+        let trace_id = match self.open.iter().find(|((_, _, k), _)| k == key) {
+            Some((_, span)) => span.trace_id,
+            None => {
+                let id = self.next_trace_id;
+                self.next_trace_id = self.next_trace_id.wrapping_add(1);
+                id
+            }
+        };
+
+        self.open.insert(
+            (category, name, String::from(key)),
+            OpenSpan { trace_id, started: Instant::now() },
+        );
+    }
+
+    /// Closes the span `name` open for `key` in `category`, if any.
+    pub fn end(&mut self, category: TraceCategory, name: &'static str, key: &str, result: &str) {
+        self.expire();
+        let span = match self.open.remove(&(category, name, String::from(key))) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let duration = span.started.elapsed();
+        self.record(category, name, key, span, duration, result);
+    }
+
+    /// Closes every span open for `key`, e.g. because the device disconnected and the operations
+    /// won't complete anymore.
+    pub fn end_all(&mut self, key: &str, result: &str) {
+        let ids: Vec<(TraceCategory, &'static str, String)> =
+            self.open.keys().filter(|(_, _, k)| k == key).cloned().collect();
+        for id in ids {
+            if let Some(span) = self.open.remove(&id) {
+                let duration = span.started.elapsed();
+                self.record(id.0, id.1, key, span, duration, result);
+            }
+        }
+    }
+
+    /// Closes the spans open for longer than `SPAN_TIMEOUT`.
+    fn expire(&mut self) {
+        let ids: Vec<(TraceCategory, &'static str, String)> = self
+            .open
+            .iter()
+            .filter(|(_, span)| span.started.elapsed() >= SPAN_TIMEOUT)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for (category, name, key) in ids {
+            if let Some(span) = self.open.remove(&(category, name, key.clone())) {
+                self.record(category, name, &key, span, SPAN_TIMEOUT, TIMEOUT_RESULT);
+            }
+        }
+    }
+
+    fn record(
+        &mut self,
+        category: TraceCategory,
+        name: &'static str,
+        key: &str,
+        span: OpenSpan,
+        duration: Duration,
+        result: &str,
+    ) {
+        if self.events.len() >= MAX_EVENTS {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(TraceEvent {
+            category,
+            name: String::from(name),
+            key: String::from(key),
+            trace_id: span.trace_id,
+            start_us: span.started.duration_since(self.origin).as_micros() as u64,
+            duration_us: duration.as_micros() as u64,
+            result: String::from(result),
+        });
+    }
+
+    /// Finished spans, oldest first.
+    pub fn get_events(&self) -> Vec<TraceEvent> {
+        self.events.iter().cloned().collect()
+    }
+
+    /// Formats the finished spans as a Chrome JSON trace. Spans sharing a trace id are put on the
+    /// same track.
+    pub fn to_chrome_json(&self) -> String {
+        let events: Vec<String> = self
+            .events
+            .iter()
+            .map(|e| {
+                format!(
+                    "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\
+                     \"tid\":{},\"args\":{{\"key\":\"{}\",\"result\":\"{}\"}}}}",
+                    escape(&e.name),
+                    e.category.get_name(),
+                    e.start_us,
+                    e.duration_us,
+                    e.trace_id,
+                    escape(&e.key),
+                    escape(&e.result)
+                )
+            })
+            .collect();
+
+        format!("{{\"traceEvents\":[{}]}}", events.join(","))
+    }
+
+    /// Writes the finished spans to `path` as a Chrome JSON trace.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, self.to_chrome_json())
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '"' => String::from("\\\""),
+            '\\' => String::from("\\\\"),
+            c if c.is_control() => format!("\\u{:04x}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_share_trace_id() {
+        let mut tracer = Tracer::new();
+
+        // Nothing is recorded while disabled.
+        tracer.begin(TraceCategory::Pairing, "create_bond", "aa:bb:cc:dd:ee:ff");
+        tracer.end(TraceCategory::Pairing, "create_bond", "aa:bb:cc:dd:ee:ff", "Bonded");
+        assert!(tracer.get_events().is_empty());
+
+        tracer.set_enabled(true);
+        tracer.begin(TraceCategory::Pairing, "create_bond", "aa:bb:cc:dd:ee:ff");
+        tracer.begin(TraceCategory::ProfileConnect, "connect", "aa:bb:cc:dd:ee:ff");
+        tracer.begin(TraceCategory::Gatt, "connect", "11:22:33:44:55:66");
+        tracer.end(TraceCategory::ProfileConnect, "connect", "aa:bb:cc:dd:ee:ff", "Connected");
+        tracer.end(TraceCategory::Pairing, "create_bond", "aa:bb:cc:dd:ee:ff", "Bonded");
+        tracer.end(TraceCategory::Gatt, "connect", "11:22:33:44:55:66", "Success");
+
+        // Closing a span that isn't open does nothing.
+        tracer.end(TraceCategory::Gatt, "connect", "11:22:33:44:55:66", "Success");
+        tracer.end(TraceCategory::Gatt, "read_characteristic", "aa:bb:cc:dd:ee:ff", "Success");
+
+        let events = tracer.get_events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].category, TraceCategory::ProfileConnect);
+        assert_eq!(events[0].trace_id, events[1].trace_id);
+        assert_ne!(events[0].trace_id, events[2].trace_id);
+
+        let json = tracer.to_chrome_json();
+        assert!(
+            json.starts_with("{\"traceEvents\":[{\"name\":\"connect\",\"cat\":\"profile_connect\"")
+        );
+        assert_eq!(json.matches("\"ph\":\"X\"").count(), 3);
+    }
+
+    #[test]
+    fn test_close_abandoned_spans() {
+        let mut tracer = Tracer::new();
+        tracer.set_enabled(true);
+
+        tracer.begin(TraceCategory::Pairing, "bond", "aa:bb:cc:dd:ee:ff");
+        tracer.begin(TraceCategory::Gatt, "connect", "aa:bb:cc:dd:ee:ff");
+        tracer.begin(TraceCategory::Gatt, "connect", "11:22:33:44:55:66");
+        tracer.end_all("aa:bb:cc:dd:ee:ff", "Disconnected");
+
+        let events = tracer.get_events();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.key == "aa:bb:cc:dd:ee:ff" && e.result == "Disconnected"));
+
+        // Pretend the remaining span was opened long ago.
+        for span in tracer.open.values_mut() {
+            span.started -= SPAN_TIMEOUT;
+        }
+        tracer.begin(TraceCategory::Pairing, "bond", "aa:bb:cc:dd:ee:ff");
+
+        let events = tracer.get_events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].key, "11:22:33:44:55:66");
+        assert_eq!(events[2].result, TIMEOUT_RESULT);
+        assert_eq!(events[2].duration_us, SPAN_TIMEOUT.as_micros() as u64);
+        assert_eq!(tracer.open.len(), 1);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\u000a");
+    }
+}
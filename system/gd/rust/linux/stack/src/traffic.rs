@@ -0,0 +1,189 @@
+//! Per-device traffic accounting.
+//!
+//! Adds up the bytes exchanged with each device per profile, so that usage can be attributed to
+//! devices and unexpectedly chatty peripherals stand out. The totals are also snapshotted
+//! periodically, which shows when the traffic happened without clients having to poll.
+//!
+//! GATT counts the payload of values read, written and notified. A2DP counts the audio read from
+//! the audio server while a device is the active sink, i.e. before encoding, and is only sampled
+//! when the totals are queried or snapshotted. RFCOMM and L2CAP sockets aren't served by this
+//! stack, so there is no socket traffic to count.
+//!
+//! The times the controller ran out of data buffers are counted per link type as well, since
+//! dropped data explains gaps in the traffic.
+
+use bt_topshim::controller::HciLinkType;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+/// How often the totals are snapshotted.
+pub const TRAFFIC_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Number of snapshots kept, i.e. an hour worth of them.
+const MAX_TRAFFIC_SNAPSHOTS: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum TrafficProfile {
+    Gatt = 0,
+    A2dp,
+}
+
+impl Default for TrafficProfile {
+    fn default() -> Self {
+        TrafficProfile::Gatt
+    }
+}
+
+/// Bytes exchanged with a device over one profile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceTraffic {
+    pub address: String,
+    pub profile: TrafficProfile,
+    /// Bytes received from the device.
+    pub rx_bytes: u64,
+    /// Bytes sent to the device.
+    pub tx_bytes: u64,
+}
+
+/// The totals at some point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrafficSnapshot {
+    /// Milliseconds since the UNIX epoch.
+    pub timestamp_ms: u64,
+    pub traffic: Vec<DeviceTraffic>,
+}
+
+/// Times the controller ran out of data buffers and dropped data, per link type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BufferOverflows {
+    pub acl: u32,
+    pub sco: u32,
+}
+
+pub struct TrafficAccounting {
+    totals: BTreeMap<(String, TrafficProfile), DeviceTraffic>,
+    /// Oldest first.
+    snapshots: VecDeque<TrafficSnapshot>,
+    overflows: BufferOverflows,
+}
+
+impl TrafficAccounting {
+    pub fn new() -> Self {
+        TrafficAccounting {
+            totals: BTreeMap::new(),
+            snapshots: VecDeque::new(),
+            overflows: BufferOverflows::default(),
+        }
+    }
+
+    pub fn add(&mut self, address: &str, profile: TrafficProfile, rx_bytes: u64, tx_bytes: u64) {
+        let traffic = self.totals.entry((String::from(address), profile)).or_insert_with(|| {
+            DeviceTraffic { address: String::from(address), profile, ..Default::default() }
+        });
+        traffic.rx_bytes = traffic.rx_bytes.saturating_add(rx_bytes);
+        traffic.tx_bytes = traffic.tx_bytes.saturating_add(tx_bytes);
+    }
+
+    /// Returns the totals of every device and profile, sorted by address.
+    pub fn get_traffic(&self) -> Vec<DeviceTraffic> {
+        self.totals.values().cloned().collect()
+    }
+
+    /// Records the current totals, dropping the oldest snapshot once there are too many.
+    pub fn snapshot(&mut self, timestamp_ms: u64) {
+        if self.snapshots.len() >= MAX_TRAFFIC_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(TrafficSnapshot { timestamp_ms, traffic: self.get_traffic() });
+    }
+
+    /// Returns the snapshots, oldest first.
+    pub fn get_snapshots(&self) -> Vec<TrafficSnapshot> {
+        self.snapshots.iter().cloned().collect()
+    }
+
+    /// Counts a data buffer overflow reported by the controller. Unknown link types are ignored.
+    pub fn add_buffer_overflow(&mut self, link_type: HciLinkType) {
+        let count = match link_type {
+            HciLinkType::Acl => &mut self.overflows.acl,
+            HciLinkType::Sco => &mut self.overflows.sco,
+            HciLinkType::Unknown => return,
+        };
+        *count = count.saturating_add(1);
+    }
+
+    pub fn get_buffer_overflows(&self) -> BufferOverflows {
+        self.overflows.clone()
+    }
+
+    pub fn reset(&mut self) {
+        self.totals.clear();
+        self.snapshots.clear();
+        self.overflows = BufferOverflows::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traffic_per_device_and_profile() {
+        let mut traffic = TrafficAccounting::new();
+        traffic.add("aa:bb:cc:dd:ee:ff", TrafficProfile::Gatt, 20, 0);
+        traffic.add("aa:bb:cc:dd:ee:ff", TrafficProfile::Gatt, 5, 2);
+        traffic.add("aa:bb:cc:dd:ee:ff", TrafficProfile::A2dp, 0, 1000);
+        traffic.add("11:22:33:44:55:66", TrafficProfile::Gatt, 8, 0);
+
+        let all = traffic.get_traffic();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].address, "11:22:33:44:55:66");
+        assert_eq!(
+            (all[1].profile, all[1].rx_bytes, all[1].tx_bytes),
+            (TrafficProfile::Gatt, 25, 2)
+        );
+        assert_eq!(
+            (all[2].profile, all[2].rx_bytes, all[2].tx_bytes),
+            (TrafficProfile::A2dp, 0, 1000)
+        );
+
+        traffic.reset();
+        assert!(traffic.get_traffic().is_empty());
+    }
+
+    #[test]
+    fn test_buffer_overflows() {
+        let mut traffic = TrafficAccounting::new();
+        traffic.add_buffer_overflow(HciLinkType::Acl);
+        traffic.add_buffer_overflow(HciLinkType::Acl);
+        traffic.add_buffer_overflow(HciLinkType::Sco);
+        traffic.add_buffer_overflow(HciLinkType::Unknown);
+        assert_eq!(traffic.get_buffer_overflows(), BufferOverflows { acl: 2, sco: 1 });
+
+        traffic.reset();
+        assert_eq!(traffic.get_buffer_overflows(), BufferOverflows::default());
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let mut traffic = TrafficAccounting::new();
+        traffic.snapshot(0);
+        traffic.add("aa:bb:cc:dd:ee:ff", TrafficProfile::Gatt, 20, 0);
+        traffic.snapshot(1);
+
+        let snapshots = traffic.get_snapshots();
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots[0].traffic.is_empty());
+        assert_eq!(snapshots[1].traffic[0].rx_bytes, 20);
+
+        // The oldest snapshots are dropped.
+        for i in 2..=MAX_TRAFFIC_SNAPSHOTS as u64 {
+            traffic.snapshot(i);
+        }
+        let snapshots = traffic.get_snapshots();
+        assert_eq!(snapshots.len(), MAX_TRAFFIC_SNAPSHOTS);
+        assert_eq!(snapshots[0].timestamp_ms, 1);
+    }
+}
@@ -3,6 +3,7 @@
 use std::collections::{HashMap, HashSet};
 
 use bt_topshim::btif::Uuid128Bit;
+use bt_topshim::profiles::stats::ProfileInfo;
 
 // List of profile uuids
 pub const A2DP_SINK: &str = "0000110B-0000-1000-8000-00805F9B34FB";
@@ -69,6 +70,47 @@ pub enum Profile {
     CoordinatedSet,
 }
 
+/// State of a profile on this adapter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileState {
+    pub profile: Profile,
+    /// The stack implements the profile.
+    pub supported: bool,
+    /// The profile is in the enabled profiles, which may be modified by policy.
+    pub enabled: bool,
+    /// The profile implementation initialized successfully and is running.
+    pub initialized: bool,
+    /// The profile is connected to at least one device.
+    pub connected: bool,
+    /// Why the profile can't be used, empty if it can.
+    pub reason: String,
+}
+
+impl Default for ProfileState {
+    fn default() -> Self {
+        ProfileState {
+            profile: Profile::A2dpSink,
+            supported: false,
+            enabled: false,
+            initialized: false,
+            connected: false,
+            reason: String::new(),
+        }
+    }
+}
+
+/// Name of the profile shim implementing `profile`, as used by `ProfileInfo`. None if the stack
+/// doesn't implement the profile.
+fn get_profile_shim(profile: &Profile) -> Option<&'static str> {
+    match profile {
+        Profile::A2dpSink | Profile::A2dpSource => Some("a2dp"),
+        Profile::AvrcpController | Profile::AvrcpTarget => Some("avrcp"),
+        Profile::Hfp | Profile::HfpAg => Some("hfp"),
+        Profile::Hid | Profile::Hogp => Some("hidhost"),
+        _ => None,
+    }
+}
+
 pub struct UuidHelper {
     /// A list of enabled profiles on the system. These may be modified by policy.
     pub enabled_profiles: HashSet<Profile>,
@@ -148,6 +190,52 @@ impl UuidHelper {
         self.enabled_profiles.clone()
     }
 
+    /// Returns the state of every known profile. `infos` describes the profile shims and
+    /// `connected` holds the profiles connected to any device.
+    pub fn get_profile_states(
+        &self,
+        infos: &Vec<ProfileInfo>,
+        connected: &HashSet<Profile>,
+    ) -> Vec<ProfileState> {
+        let mut profiles: Vec<Profile> = self.profiles.values().cloned().collect();
+        profiles.sort_by_key(|p| *p as u32);
+
+        profiles
+            .into_iter()
+            .map(|profile| {
+                let info = get_profile_shim(&profile)
+                    .and_then(|name| infos.iter().find(|info| info.name == name));
+                let supported = get_profile_shim(&profile).is_some();
+                let enabled = self.is_profile_enabled(&profile);
+                let initialized = info.map_or(false, |info| info.enabled);
+
+                let reason = if !supported {
+                    String::from("Not implemented by this stack")
+                } else if !enabled {
+                    String::from("Disabled by policy")
+                } else if !initialized {
+                    match info.map(|info| info.last_error.as_str()) {
+                        Some(error) if !error.is_empty() => {
+                            format!("Failed to initialize: {}", error)
+                        }
+                        _ => String::from("Not initialized"),
+                    }
+                } else {
+                    String::new()
+                };
+
+                ProfileState {
+                    profile,
+                    supported,
+                    enabled,
+                    initialized,
+                    connected: connected.contains(&profile),
+                    reason,
+                }
+            })
+            .collect()
+    }
+
     /// Converts a UUID byte array into a formatted string.
     pub fn to_string(uuid: &Uuid128Bit) -> String {
         return String::from(format!("{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
@@ -204,4 +292,35 @@ mod tests {
             });
         }
     }
+
+    #[test]
+    fn test_profile_states() {
+        let uuidhelper = UuidHelper::new();
+        let infos = vec![
+            ProfileInfo {
+                name: String::from("a2dp"),
+                version: 1,
+                enabled: true,
+                ..Default::default()
+            },
+            ProfileInfo {
+                name: String::from("hidhost"),
+                version: 1,
+                enabled: false,
+                last_error: String::from("Fail"),
+            },
+        ];
+        let connected: HashSet<Profile> = [Profile::A2dpSink].iter().cloned().collect();
+
+        let states = uuidhelper.get_profile_states(&infos, &connected);
+        assert_eq!(states.len(), uuidhelper.profiles.len());
+        assert_eq!(states[0].profile, Profile::A2dpSink);
+
+        let get = |profile| states.iter().find(|s| s.profile == profile).unwrap();
+        assert!(get(Profile::A2dpSink).connected && get(Profile::A2dpSink).reason.is_empty());
+        assert_eq!(get(Profile::AvrcpTarget).reason, "Disabled by policy");
+        assert_eq!(get(Profile::Hfp).reason, "Not initialized");
+        assert_eq!(get(Profile::Hid).reason, "Failed to initialize: Fail");
+        assert!(!get(Profile::Sap).supported);
+    }
 }
@@ -266,8 +266,13 @@ std::unique_ptr<AvrcpIntf> GetAvrcpProfile(const unsigned char* btif) {
 
 AvrcpIntf::~AvrcpIntf() {}
 
-void AvrcpIntf::init() {
+int AvrcpIntf::init() {
+  // The service itself has no way to report a failure, it only exists or not.
+  if (intf_ == nullptr) {
+    return BT_STATUS_NOT_READY;
+  }
   intf_->Init(&mAvrcpInterface, &mVolumeInterface);
+  return BT_STATUS_SUCCESS;
 }
 
 void AvrcpIntf::cleanup() {
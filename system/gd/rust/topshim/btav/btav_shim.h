@@ -63,7 +63,7 @@ class AvrcpIntf {
   AvrcpIntf(bluetooth::avrcp::ServiceInterface* intf) : intf_(intf) {}
   ~AvrcpIntf();
 
-  void init();
+  int init();
   void cleanup();
   int connect(RustRawAddress bt_addr);
   int disconnect(RustRawAddress bt_addr);
@@ -16,18 +16,59 @@
 
 #include "gd/rust/topshim/btif/btif_shim.h"
 
+#include <base/bind.h>
+
 #include <algorithm>
 #include <cstdlib>
 #include <cstring>
 #include <memory>
 
+#include "gd/rust/topshim/common/utils.h"
 #include "rust/cxx.h"
 #include "src/btif.rs.h"
+#include "stack/btm/neighbor_inquiry.h"
+#include "stack/include/btm_api.h"
+#include "stack/include/btu.h"
+#include "types/raw_address.h"
 
 namespace bluetooth {
 namespace topshim {
 namespace rust {
 
+namespace rusty = ::bluetooth::topshim::rust;
+
+namespace internal {
+// Device of the remote name request started here that is still in progress. BTM
+// only runs one request at a time, which may also have been started by the stack.
+static RawAddress g_remote_name_address = RawAddress::kEmpty;
+
+static void OnRemoteNameComplete(void* p) {
+  auto* result = static_cast<tBTM_REMOTE_DEV_NAME*>(p);
+  g_remote_name_address = RawAddress::kEmpty;
+  size_t len = strnlen(reinterpret_cast<const char*>(result->remote_bd_name), sizeof(result->remote_bd_name));
+
+  rusty::remote_name_complete_cb(
+      static_cast<uint8_t>(result->status), CopyToRustAddress(result->bd_addr), result->remote_bd_name, len);
+}
+
+static void ReadRemoteNameOnMain(RawAddress address, tBT_TRANSPORT transport) {
+  tBTM_STATUS status = BTM_ReadRemoteDeviceName(address, OnRemoteNameComplete, transport);
+  if (status != BTM_CMD_STARTED) {
+    rusty::remote_name_complete_cb(static_cast<uint8_t>(status), CopyToRustAddress(address), nullptr, 0);
+    return;
+  }
+
+  g_remote_name_address = address;
+}
+
+static void CancelRemoteNameOnMain(RawAddress address) {
+  // Leave requests alone that were not started here, or already completed.
+  if (g_remote_name_address != address) return;
+
+  BTM_CancelRemoteDeviceName();
+}
+}  // namespace internal
+
 InitFlags::InitFlags() {}
 InitFlags::~InitFlags() {
   if (flags_) {
@@ -60,6 +101,17 @@ std::unique_ptr<InitFlags> ConvertFlags(::rust::Vec<::rust::String> flags) {
   return ret;
 }
 
+void ReadRemoteName(RustRawAddress address, int32_t transport) {
+  do_in_main_thread(
+      FROM_HERE,
+      base::BindOnce(
+          internal::ReadRemoteNameOnMain, CopyFromRustAddress(address), static_cast<tBT_TRANSPORT>(transport)));
+}
+
+void CancelRemoteName(RustRawAddress address) {
+  do_in_main_thread(FROM_HERE, base::BindOnce(internal::CancelRemoteNameOnMain, CopyFromRustAddress(address)));
+}
+
 }  // namespace rust
 }  // namespace topshim
 }  // namespace bluetooth
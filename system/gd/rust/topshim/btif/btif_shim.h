@@ -24,6 +24,8 @@ namespace bluetooth {
 namespace topshim {
 namespace rust {
 
+struct RustRawAddress;
+
 class InitFlags {
  public:
   InitFlags();
@@ -40,6 +42,14 @@ class InitFlags {
 
 std::unique_ptr<InitFlags> ConvertFlags(::rust::Vec<::rust::String> flags);
 
+// Start a remote name request on the main thread. Completion (or failure to
+// start) is reported via the Rust remote_name_complete_cb callback.
+void ReadRemoteName(RustRawAddress address, int32_t transport);
+
+// Cancel the remote name request to the device if it was started by
+// ReadRemoteName and is still in progress. Its completion is still reported.
+void CancelRemoteName(RustRawAddress address);
+
 }  // namespace rust
 }  // namespace topshim
 }  // namespace bluetooth
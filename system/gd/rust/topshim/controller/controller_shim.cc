@@ -16,18 +16,235 @@
 
 #include "gd/rust/topshim/controller/controller_shim.h"
 
+#include <algorithm>
 #include <memory>
+#include <optional>
+#include <vector>
 
+#include "bta/include/bta_api.h"
+#include "gd/common/init_flags.h"
 #include "gd/rust/topshim/common/utils.h"
+#include "hci/controller.h"
+#include "hci/hci_layer.h"
+#include "hci/hci_packets.h"
+#include "hci/vendor_specific_event_manager.h"
+#include "stack/include/acl_api.h"
+#include "stack/include/btm_ble_api.h"
+#include "stack/include/btu.h"
+#include "main/shim/acl_api.h"
+#include "main/shim/entry.h"
+#include "main/shim/helpers.h"
+#include "main/shim/shim.h"
+#include "packet/raw_builder.h"
 #include "rust/cxx.h"
 #include "src/controller.rs.h"
+#include "types/ble_address_with_type.h"
 #include "types/raw_address.h"
 
 namespace bluetooth {
 namespace topshim {
 namespace rust {
+
+namespace rusty = ::bluetooth::topshim::rust;
+
 namespace internal {
 static ControllerIntf* g_controller_intf;
+static bool g_eir_tx_power_included = false;
+static bool g_debug_info_registered = false;
+
+static void OnLocalAddrReadback(RawAddress requested, hci::CommandCompleteView view) {
+  auto complete_view = hci::ReadBdAddrCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_local_addr_override(
+        static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR),
+        CopyToRustAddress(requested),
+        CopyToRustAddress(RawAddress::kEmpty));
+    return;
+  }
+
+  rusty::controller_on_local_addr_override(
+      static_cast<uint8_t>(complete_view.GetStatus()),
+      CopyToRustAddress(requested),
+      CopyToRustAddress(ToRawAddress(complete_view.GetBdAddr())));
+}
+
+// Vendor specific commands that program the public address, by manufacturer. Each of them takes
+// the address in little endian order as its only parameter.
+static std::optional<uint16_t> GetWriteBdAddrOpcode(uint16_t manufacturer) {
+  switch (manufacturer) {
+    case 0x0002:  // Intel
+      return 0xfc31;
+    case 0x000f:  // Broadcom
+    case 0x0131:  // Cypress, which kept the Broadcom commands
+      return 0xfc01;
+    case 0x0046:  // MediaTek
+      return 0xfc1a;
+    default:
+      return std::nullopt;
+  }
+}
+
+static void OnLocalAddrWritten(RawAddress requested, hci::CommandCompleteView view) {
+  // Vendor command complete events carry the status as the first return parameter.
+  auto payload = view.GetPayload();
+  uint8_t status = payload.size() > 0 ? payload[0] : static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR);
+  if (status != static_cast<uint8_t>(hci::ErrorCode::SUCCESS)) {
+    rusty::controller_on_local_addr_override(
+        status, CopyToRustAddress(requested), CopyToRustAddress(RawAddress::kEmpty));
+    return;
+  }
+
+  // Read the address back from the controller rather than trusting the cached value.
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::ReadBdAddrBuilder::Create(), shim::GetGdShimHandler()->BindOnce(OnLocalAddrReadback, requested));
+}
+
+static void OnInquiryResponseTxPowerRead(hci::CommandCompleteView view) {
+  auto complete_view = hci::ReadInquiryResponseTransmitPowerLevelCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_inquiry_response_tx_power(static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR), 0);
+    return;
+  }
+
+  auto status = complete_view.GetStatus();
+  int8_t tx_power = static_cast<int8_t>(complete_view.GetTxPower());
+  if (status == hci::ErrorCode::SUCCESS && g_eir_tx_power_included) {
+    BTA_DmSetEirTxPower(true, tx_power);
+  }
+
+  rusty::controller_on_inquiry_response_tx_power(static_cast<uint8_t>(status), tx_power);
+}
+
+static void OnInquiryTxPowerWritten(hci::CommandCompleteView view) {
+  auto complete_view = hci::WriteInquiryResponseTransmitPowerLevelCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_inquiry_tx_power_written(static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR));
+    return;
+  }
+
+  auto status = complete_view.GetStatus();
+  rusty::controller_on_inquiry_tx_power_written(static_cast<uint8_t>(status));
+
+  // The controller may have picked a different level than requested.
+  if (status == hci::ErrorCode::SUCCESS) {
+    shim::GetHciLayer()->EnqueueCommand(
+        hci::ReadInquiryResponseTransmitPowerLevelBuilder::Create(),
+        shim::GetGdShimHandler()->BindOnce(OnInquiryResponseTxPowerRead));
+  }
+}
+
+static void OnLeAdvTxPowerRead(hci::CommandCompleteView view) {
+  auto complete_view = hci::LeReadAdvertisingPhysicalChannelTxPowerCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_le_adv_tx_power(static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR), 0);
+    return;
+  }
+
+  rusty::controller_on_le_adv_tx_power(
+      static_cast<uint8_t>(complete_view.GetStatus()), static_cast<int8_t>(complete_view.GetTransmitPowerLevel()));
+}
+
+static void OnRpaTimeoutWritten(uint16_t timeout_s, hci::CommandCompleteView view) {
+  // The controller timeout only applies to addresses it generates, the host rotates its own.
+  // That doesn't depend on the controller, so it is applied even if the command failed.
+  if (BTM_BleLocalPrivacyEnabled()) {
+    shim::ACL_SetLePrivacyRotationTime(std::chrono::seconds(timeout_s));
+  }
+
+  auto complete_view = hci::LeSetResolvablePrivateAddressTimeoutCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_rpa_timeout_written(static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR), timeout_s);
+    return;
+  }
+
+  rusty::controller_on_rpa_timeout_written(static_cast<uint8_t>(complete_view.GetStatus()), timeout_s);
+}
+
+static void OnDebugInfoEvent(hci::VendorSpecificEventView view) {
+  auto payload = view.GetPayload();
+  ::rust::Vec<uint8_t> data;
+  for (auto it = payload.begin(); it != payload.end(); it++) {
+    data.push_back(*it);
+  }
+
+  rusty::controller_on_vendor_debug_event(std::move(data));
+}
+
+static void OnLeTestStarted(hci::CommandCompleteView view) {
+  // All four test start commands only return a status.
+  auto payload = view.GetPayload();
+  uint8_t status = payload.size() > 0 ? payload[0] : static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR);
+  rusty::controller_on_le_test_started(status);
+}
+
+static void OnLeTestEnded(hci::CommandCompleteView view) {
+  auto complete_view = hci::LeTestEndCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_le_test_ended(static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR), 0);
+    return;
+  }
+
+  rusty::controller_on_le_test_ended(
+      static_cast<uint8_t>(complete_view.GetStatus()), complete_view.GetNumberOfPackets());
+}
+
+static void OnKeypressNotification(const RawAddress& bd_addr, uint8_t notification_type) {
+  rusty::controller_on_keypress_notification(CopyToRustAddress(bd_addr), notification_type);
+}
+
+static void OnDataBufferOverflow(uint8_t link_type) {
+  rusty::controller_on_data_buffer_overflow(link_type);
+}
+
+// Pass the return parameters following the status on to Rust, which knows the format.
+static ::rust::Vec<uint8_t> GetReturnParameters(hci::CommandCompleteView view, uint8_t* status) {
+  auto payload = view.GetPayload();
+  ::rust::Vec<uint8_t> data;
+  if (payload.size() == 0) {
+    *status = static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR);
+    return data;
+  }
+
+  *status = payload[0];
+  for (size_t i = 1; i < payload.size(); i++) {
+    data.push_back(payload[i]);
+  }
+  return data;
+}
+
+static void OnLocalSupportedCodecsRead(bool is_v2, hci::CommandCompleteView view) {
+  uint8_t status;
+  auto data = GetReturnParameters(view, &status);
+  rusty::controller_on_local_supported_codecs(status, is_v2, std::move(data));
+}
+
+static void OnLocalCodecCapabilitiesRead(CodecCapabilitiesRequest request, hci::CommandCompleteView view) {
+  uint8_t status;
+  auto data = GetReturnParameters(view, &status);
+  rusty::controller_on_local_codec_capabilities(status, request, std::move(data));
+}
+
+static void OnKeypressNotificationSent(RawAddress address, hci::CommandCompleteView view) {
+  auto complete_view = hci::SendKeypressNotificationCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_keypress_notification_sent(
+        static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR), CopyToRustAddress(address));
+    return;
+  }
+
+  rusty::controller_on_keypress_notification_sent(
+      static_cast<uint8_t>(complete_view.GetStatus()), CopyToRustAddress(address));
+}
+
+static void OnDebugInfoComplete(hci::CommandCompleteView view) {
+  auto complete_view = hci::ControllerDebugInfoCompleteView::Create(view);
+  if (!complete_view.IsValid()) {
+    rusty::controller_on_vendor_debug_dump_started(static_cast<uint8_t>(hci::ErrorCode::UNSPECIFIED_ERROR));
+    return;
+  }
+
+  rusty::controller_on_vendor_debug_dump_started(static_cast<uint8_t>(complete_view.GetStatus()));
+}
 }  // namespace internal
 
 ControllerIntf::~ControllerIntf() {}
@@ -44,6 +261,252 @@ RustRawAddress ControllerIntf::read_local_addr() const {
   return CopyToRustAddress(*controller_->get_address());
 }
 
+bool ControllerIntf::is_provisioning_mode_enabled() const {
+  return bluetooth::common::init_flags::provisioning_mode_is_enabled();
+}
+
+uint8_t ControllerIntf::write_local_addr_override(RustRawAddress address) const {
+  if (!is_provisioning_mode_enabled()) {
+    return kLocalAddrOverrideNotProvisioning;
+  }
+  if (!shim::is_gd_stack_started_up()) {
+    return kLocalAddrOverrideStackNotRunning;
+  }
+  if (BTM_GetNumAclLinks() != 0) {
+    return kLocalAddrOverrideLinksActive;
+  }
+
+  auto manufacturer = shim::GetController()->GetLocalVersionInformation().manufacturer_name_;
+  auto opcode = internal::GetWriteBdAddrOpcode(manufacturer);
+  if (!opcode) {
+    return kLocalAddrOverrideUnsupported;
+  }
+
+  RawAddress requested = CopyFromRustAddress(address);
+
+  // BD_ADDR is sent over HCI in little endian order.
+  std::vector<uint8_t> params(requested.address, requested.address + RawAddress::kLength);
+  std::reverse(params.begin(), params.end());
+
+  auto payload = std::make_unique<packet::RawBuilder>();
+  payload->AddOctets(params);
+
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::CommandBuilder::Create(static_cast<hci::OpCode>(*opcode), std::move(payload)),
+      shim::GetGdShimHandler()->BindOnce(internal::OnLocalAddrWritten, requested));
+  return kLocalAddrOverrideStarted;
+}
+
+ControllerFeatures ControllerIntf::read_features() const {
+  if (!controller_) std::abort();
+  return ControllerFeatures{
+      .le = controller_->supports_ble(),
+      .le_2m_phy = controller_->supports_ble_2m_phy(),
+      .le_coded_phy = controller_->supports_ble_coded_phy(),
+      .le_extended_advertising = controller_->supports_ble_extended_advertising(),
+      .le_periodic_advertising = controller_->supports_ble_periodic_advertising(),
+      .le_privacy = controller_->supports_ble_privacy(),
+      .le_cis_central = controller_->supports_ble_connected_isochronous_stream_central(),
+      .le_cis_peripheral = controller_->supports_ble_connected_isochronous_stream_peripheral(),
+      .le_iso_broadcaster = controller_->supports_ble_isochronous_broadcaster(),
+      .le_sync_receiver = controller_->supports_ble_synchronized_receiver(),
+      .secure_connections = controller_->supports_secure_connections(),
+      .sniff_subrating = controller_->supports_sniff_subrating(),
+      // Vendor specific, only known to the GD controller.
+      .le_batch_scan = shim::GetController()->IsSupported(hci::OpCode::LE_BATCH_SCAN),
+  };
+}
+
+ControllerCapabilities ControllerIntf::read_capabilities() const {
+  if (!controller_) std::abort();
+  auto gd_controller = shim::GetController();
+  return ControllerCapabilities{
+      .supported_commands = gd_controller->GetLocalSupportedCommands(),
+      .acl_buffer_count = controller_->get_acl_buffer_count_classic(),
+      .acl_buffer_size = controller_->get_acl_data_size_classic(),
+      .le_acl_buffer_count = controller_->get_acl_buffer_count_ble(),
+      .le_acl_buffer_size = controller_->get_acl_data_size_ble(),
+      // The legacy controller interface doesn't track SCO buffers.
+      .sco_buffer_count = gd_controller->GetNumScoPacketBuffers(),
+      .sco_buffer_size = gd_controller->GetScoPacketLength(),
+      .iso_buffer_count = controller_->get_iso_buffer_count(),
+      .iso_buffer_size = controller_->get_iso_data_size(),
+      .le_max_advertising_data_length = controller_->get_ble_maximum_advertising_data_length(),
+      .le_number_of_advertising_sets = controller_->get_ble_number_of_supported_advertising_sets(),
+      .le_resolving_list_size = controller_->get_ble_resolving_list_max_size(),
+  };
+}
+
+uint8_t ControllerIntf::le_accept_list_size() const {
+  if (!controller_) std::abort();
+  return controller_->get_ble_acceptlist_size();
+}
+
+bool ControllerIntf::le_accept_connection_from(RustRawAddress address, uint8_t addr_type, bool is_direct) const {
+  tBLE_BD_ADDR address_with_type{
+      .type = to_ble_addr_type(addr_type),
+      .bda = CopyFromRustAddress(address),
+  };
+  return shim::ACL_AcceptLeConnectionFrom(address_with_type, is_direct);
+}
+
+void ControllerIntf::le_ignore_connection_from(RustRawAddress address, uint8_t addr_type) const {
+  tBLE_BD_ADDR address_with_type{
+      .type = to_ble_addr_type(addr_type),
+      .bda = CopyFromRustAddress(address),
+  };
+  shim::ACL_IgnoreLeConnectionFrom(address_with_type);
+}
+
+uint8_t ControllerIntf::le_address_type(RustRawAddress address) const {
+  tBT_DEVICE_TYPE dev_type;
+  tBLE_ADDR_TYPE addr_type;
+  BTM_ReadDevInfo(CopyFromRustAddress(address), &dev_type, &addr_type);
+  return static_cast<uint8_t>(addr_type);
+}
+
+void ControllerIntf::le_clear_accept_list() const {
+  shim::ACL_ClearAcceptList();
+}
+
+void ControllerIntf::read_inquiry_response_tx_power() const {
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::ReadInquiryResponseTransmitPowerLevelBuilder::Create(),
+      shim::GetGdShimHandler()->BindOnce(internal::OnInquiryResponseTxPowerRead));
+}
+
+void ControllerIntf::write_inquiry_tx_power(int8_t tx_power) const {
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::WriteInquiryTransmitPowerLevelBuilder::Create(static_cast<uint8_t>(tx_power)),
+      shim::GetGdShimHandler()->BindOnce(internal::OnInquiryTxPowerWritten));
+}
+
+void ControllerIntf::set_eir_tx_power_included(bool include) const {
+  internal::g_eir_tx_power_included = include;
+  if (include) {
+    // EIR is updated once the current level has been read.
+    read_inquiry_response_tx_power();
+  } else {
+    BTA_DmSetEirTxPower(false, 0);
+  }
+}
+
+void ControllerIntf::read_le_adv_tx_power() const {
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::LeReadAdvertisingPhysicalChannelTxPowerBuilder::Create(),
+      shim::GetGdShimHandler()->BindOnce(internal::OnLeAdvTxPowerRead));
+}
+
+void ControllerIntf::write_rpa_timeout(uint16_t timeout_s) const {
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::LeSetResolvablePrivateAddressTimeoutBuilder::Create(timeout_s),
+      shim::GetGdShimHandler()->BindOnce(internal::OnRpaTimeoutWritten, timeout_s));
+}
+
+bool ControllerIntf::vendor_debug_dump_supported() const {
+  return shim::GetController()->IsSupported(hci::OpCode::CONTROLLER_DEBUG_INFO);
+}
+
+void ControllerIntf::register_vendor_debug_events() const {
+  // The event manager asserts on a second handler for the same subevent.
+  if (internal::g_debug_info_registered) return;
+
+  shim::GetVendorSpecificEventManager()->RegisterEventHandler(
+      hci::VseSubeventCode::DEBUG_INFO, shim::GetGdShimHandler()->Bind(internal::OnDebugInfoEvent));
+  internal::g_debug_info_registered = true;
+}
+
+void ControllerIntf::unregister_vendor_debug_events() const {
+  if (!internal::g_debug_info_registered) return;
+
+  shim::GetVendorSpecificEventManager()->UnregisterEventHandler(hci::VseSubeventCode::DEBUG_INFO);
+  internal::g_debug_info_registered = false;
+}
+
+void ControllerIntf::start_vendor_debug_dump() const {
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::ControllerDebugInfoBuilder::Create(), shim::GetGdShimHandler()->BindOnce(internal::OnDebugInfoComplete));
+}
+
+void ControllerIntf::le_transmitter_test(uint8_t channel, uint8_t length, uint8_t payload, uint8_t phy) const {
+  auto packet_payload = static_cast<hci::LeTestPayload>(payload);
+  auto phy_type = static_cast<hci::PhyType>(phy);
+  if (phy_type == hci::PhyType::LE_1M) {
+    shim::GetHciLayer()->EnqueueCommand(
+        hci::LeTransmitterTestBuilder::Create(channel, length, packet_payload),
+        shim::GetGdShimHandler()->BindOnce(internal::OnLeTestStarted));
+    return;
+  }
+
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::LeEnhancedTransmitterTestBuilder::Create(channel, length, packet_payload, phy_type),
+      shim::GetGdShimHandler()->BindOnce(internal::OnLeTestStarted));
+}
+
+void ControllerIntf::le_receiver_test(uint8_t channel, uint8_t phy) const {
+  auto phy_type = static_cast<hci::PhyType>(phy);
+  if (phy_type == hci::PhyType::LE_1M) {
+    shim::GetHciLayer()->EnqueueCommand(
+        hci::LeReceiverTestBuilder::Create(channel), shim::GetGdShimHandler()->BindOnce(internal::OnLeTestStarted));
+    return;
+  }
+
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::LeEnhancedReceiverTestBuilder::Create(channel, phy_type, hci::ModulationIndex::STANDARD),
+      shim::GetGdShimHandler()->BindOnce(internal::OnLeTestStarted));
+}
+
+void ControllerIntf::le_test_end() const {
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::LeTestEndBuilder::Create(), shim::GetGdShimHandler()->BindOnce(internal::OnLeTestEnded));
+}
+
+void ControllerIntf::register_keypress_notifications() const {
+  // The event keeps going through the legacy stack, which only lets us observe it.
+  btu_hcif_register_keypress_notif_cb(internal::OnKeypressNotification);
+}
+
+void ControllerIntf::send_keypress_notification(RustRawAddress address, uint8_t notification_type) const {
+  RawAddress addr = CopyFromRustAddress(address);
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::SendKeypressNotificationBuilder::Create(
+          ToGdAddress(addr), static_cast<hci::KeypressNotificationType>(notification_type)),
+      shim::GetGdShimHandler()->BindOnce(internal::OnKeypressNotificationSent, addr));
+}
+
+void ControllerIntf::register_transport_error_events() const {
+  // As with keypress notifications, the legacy stack stays the owner of the event.
+  btu_hcif_register_data_buf_overflow_cb(internal::OnDataBufferOverflow);
+}
+
+void ControllerIntf::read_local_supported_codecs() const {
+  if (shim::GetController()->IsSupported(hci::OpCode::READ_LOCAL_SUPPORTED_CODECS_V2)) {
+    shim::GetHciLayer()->EnqueueCommand(
+        hci::ReadLocalSupportedCodecsV2Builder::Create(),
+        shim::GetGdShimHandler()->BindOnce(internal::OnLocalSupportedCodecsRead, true));
+    return;
+  }
+
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::ReadLocalSupportedCodecsV1Builder::Create(),
+      shim::GetGdShimHandler()->BindOnce(internal::OnLocalSupportedCodecsRead, false));
+}
+
+void ControllerIntf::read_local_codec_capabilities(CodecCapabilitiesRequest request) const {
+  // The packet definition models the logical transport as a bit field while the spec expects a
+  // single value, so build the command by hand.
+  auto payload = std::make_unique<packet::RawBuilder>();
+  payload->AddOctets1(request.coding_format);
+  payload->AddOctets2(request.company_id);
+  payload->AddOctets2(request.vendor_codec_id);
+  payload->AddOctets1(request.transport);
+  payload->AddOctets1(request.direction);
+
+  shim::GetHciLayer()->EnqueueCommand(
+      hci::CommandBuilder::Create(hci::OpCode::READ_LOCAL_SUPPORTED_CODEC_CAPABILITIES, std::move(payload)),
+      shim::GetGdShimHandler()->BindOnce(internal::OnLocalCodecCapabilitiesRead, request));
+}
+
 }  // namespace rust
 }  // namespace topshim
 }  // namespace bluetooth
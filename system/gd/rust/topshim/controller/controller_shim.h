@@ -27,6 +27,16 @@ namespace topshim {
 namespace rust {
 
 struct RustRawAddress;
+struct ControllerFeatures;
+struct ControllerCapabilities;
+struct CodecCapabilitiesRequest;
+
+// Results of ControllerIntf::write_local_addr_override. Mirrored in controller.rs.
+constexpr uint8_t kLocalAddrOverrideStarted = 0;
+constexpr uint8_t kLocalAddrOverrideStackNotRunning = 1;
+constexpr uint8_t kLocalAddrOverrideLinksActive = 2;
+constexpr uint8_t kLocalAddrOverrideNotProvisioning = 3;
+constexpr uint8_t kLocalAddrOverrideUnsupported = 4;
 
 class ControllerIntf {
  public:
@@ -35,6 +45,109 @@ class ControllerIntf {
 
   RustRawAddress read_local_addr() const;
 
+  ControllerFeatures read_features() const;
+
+  // Supported commands, buffer sizes and LE list and advertising limits.
+  ControllerCapabilities read_capabilities() const;
+
+  // Whether the stack was started with INIT_provisioning_mode=true, which allows
+  // reprogramming the controller BD_ADDR.
+  bool is_provisioning_mode_enabled() const;
+
+  // Program the controller BD_ADDR with the vendor specific command of its
+  // manufacturer and read it back once the command completes. The result is
+  // reported via the Rust controller_on_local_addr_override callback.
+  //
+  // Only allowed in provisioning mode. The HCI layer is only available while
+  // the stack is running, and the address must not change under existing
+  // links. Returns one of the kLocalAddrOverride* values above, nothing is sent
+  // unless it is kLocalAddrOverrideStarted.
+  uint8_t write_local_addr_override(RustRawAddress address) const;
+
+  // Number of entries the controller filter accept list can hold.
+  uint8_t le_accept_list_size() const;
+
+  // Start a direct or background LE connection to the device. Both add the
+  // device to the filter accept list. Returns false if the accept list, which
+  // is shared with the rest of the stack, is full.
+  bool le_accept_connection_from(RustRawAddress address, uint8_t addr_type, bool is_direct) const;
+
+  // Cancel a pending LE connection to the device and remove it from the
+  // filter accept list.
+  void le_ignore_connection_from(RustRawAddress address, uint8_t addr_type) const;
+
+  void le_clear_accept_list() const;
+
+  // Address type of a known device, e.g. the identity address type of a
+  // bonded LE device. Public for unknown devices.
+  uint8_t le_address_type(RustRawAddress address) const;
+
+  // Read the TX power used for inquiry responses. Reported via the Rust
+  // controller_on_inquiry_response_tx_power callback.
+  void read_inquiry_response_tx_power() const;
+
+  // Set the TX power used for inquiry. The controller picks the closest level
+  // it supports, which is read back afterwards.
+  void write_inquiry_tx_power(int8_t tx_power) const;
+
+  // Include the inquiry response TX power read from the controller in the EIR.
+  void set_eir_tx_power_included(bool include) const;
+
+  // Set how often the resolvable private address is rotated, in seconds. The
+  // host generated address follows if LE privacy is enabled. Reported via the
+  // Rust controller_on_rpa_timeout_written callback.
+  void write_rpa_timeout(uint16_t timeout_s) const;
+
+  // Read the TX power used for legacy advertising. Reported via the Rust
+  // controller_on_le_adv_tx_power callback.
+  void read_le_adv_tx_power() const;
+
+  // Whether the controller supports the vendor debug info command.
+  bool vendor_debug_dump_supported() const;
+
+  // Pass debug info vendor events on to the Rust
+  // controller_on_vendor_debug_event callback. The handler belongs to the GD
+  // stack, so it must be unregistered before the stack is disabled.
+  void register_vendor_debug_events() const;
+  void unregister_vendor_debug_events() const;
+
+  // Ask the controller to emit its debug statistics. The statistics arrive as
+  // debug info vendor events, see register_vendor_debug_events.
+  void start_vendor_debug_dump() const;
+
+  // Start an LE transmitter or receiver test. The enhanced commands are only
+  // used for PHYs other than LE 1M, so that older controllers keep working.
+  // Reported via the Rust controller_on_le_test_started callback.
+  void le_transmitter_test(uint8_t channel, uint8_t length, uint8_t payload, uint8_t phy) const;
+  void le_receiver_test(uint8_t channel, uint8_t phy) const;
+
+  // End the running LE test. Reported via the Rust controller_on_le_test_ended
+  // callback.
+  void le_test_end() const;
+
+  // Observe keypress notification events handled by the legacy stack, which
+  // only logs them, and pass them on to the Rust
+  // controller_on_keypress_notification callback. Registering again is a no-op.
+  void register_keypress_notifications() const;
+
+  // Send a keypress notification to the device during passkey entry. Reported
+  // via the Rust controller_on_keypress_notification_sent callback.
+  void send_keypress_notification(RustRawAddress address, uint8_t notification_type) const;
+
+  // Observe data buffer overflow events, which the legacy stack only logs, and
+  // pass them on to the Rust controller_on_data_buffer_overflow callback.
+  // Registering again is a no-op.
+  void register_transport_error_events() const;
+
+  // Read the codecs the controller can encode and decode itself, using the
+  // second version of the command if supported so the transports are known.
+  // Reported via the Rust controller_on_local_supported_codecs callback.
+  void read_local_supported_codecs() const;
+
+  // Read the capabilities of a codec on one logical transport and direction.
+  // Reported via the Rust controller_on_local_codec_capabilities callback.
+  void read_local_codec_capabilities(CodecCapabilitiesRequest request) const;
+
  private:
   const controller_t* controller_;
 };
@@ -41,6 +41,11 @@ static void audio_state_cb(bluetooth::headset::bthf_audio_state_t state, RawAddr
   rusty::hfp_audio_state_callback(state, raddr);
 }
 
+static void battery_level_cb(uint8_t battery_level, RawAddress* addr) {
+  RustRawAddress raddr = rusty::CopyToRustAddress(*addr);
+  rusty::hfp_battery_level_callback(battery_level, raddr);
+}
+
 }  // namespace internal
 
 class DBusHeadsetCallbacks : public headset::Callbacks {
@@ -156,6 +161,12 @@ class DBusHeadsetCallbacks : public headset::Callbacks {
   }
 
   void AtBievCallback(headset::bthf_hf_ind_type_t ind_id, int ind_value, RawAddress* bd_addr) override {
+    // The stack already checked the value is within the range of the indicator.
+    if (ind_id == headset::BTHF_HF_IND_BATTERY_LEVEL_STATUS) {
+      topshim::rust::internal::battery_level_cb(static_cast<uint8_t>(ind_value), bd_addr);
+      return;
+    }
+
     LOG_WARN(
         "AT+BIEV=%d,%d from addr %s: Bluetooth HF Indicators is not supported.",
         ind_id,
@@ -91,6 +91,10 @@ impl Parse for CbVariant {
 ///
 /// Example:
 ///     u32 -> _
+///
+/// The variant is built once for each listener registered for the dispatcher and once for the
+/// main dispatcher, so the args (after the statements ran) must implement Clone. A dispatcher may
+/// have listeners only. Callbacks that arrive while neither is registered are logged and dropped.
 pub fn cb_variant(input: TokenStream) -> TokenStream {
     let parsed_cptr = parse_macro_input!(input as CbVariant);
 
@@ -99,6 +103,8 @@ pub fn cb_variant(input: TokenStream) -> TokenStream {
 
     let mut params = proc_macro2::TokenStream::new();
     let mut args = proc_macro2::TokenStream::new();
+    // Listeners get their own copy of the args since the main dispatcher consumes them.
+    let mut listener_args = proc_macro2::TokenStream::new();
     for (i, (start, end)) in parsed_cptr.arg_pairs.iter().enumerate() {
         let ident = format_ident!("_{}", i);
         params.extend(quote! { #ident: #start, });
@@ -108,8 +114,10 @@ pub fn cb_variant(input: TokenStream) -> TokenStream {
                 // Argument needs an into translation if it doesn't match the start
                 if start != v {
                     args.extend(quote! { #end::from(#ident), });
+                    listener_args.extend(quote! { #end::from(Clone::clone(&#ident)), });
                 } else {
                     args.extend(quote! {#ident,});
+                    listener_args.extend(quote! { Clone::clone(&#ident), });
                 }
             }
             // If there's no end type, just consume it instead.
@@ -124,11 +132,31 @@ pub fn cb_variant(input: TokenStream) -> TokenStream {
 
     let tokens = quote! {
         #[no_mangle]
+        #[allow(clippy::clone_on_copy)]
         extern "C" fn #ident(#params) {
             #stmts
 
+            // Don't hold the container lock while dispatching so consumers can (un)register.
+            let (main, listeners) = {
+                let dispatchers = get_dispatchers();
+                let container = dispatchers.lock().unwrap();
+                (container.get::<#dispatcher>(), container.get_listeners::<#dispatcher>())
+            };
+
+            let observed = !listeners.is_empty();
             unsafe {
-                (get_dispatchers().lock().unwrap().get::<#dispatcher>().unwrap().clone().lock().unwrap().dispatch)(#rpath(#args));
+                for listener in listeners {
+                    (listener.lock().unwrap().dispatch)(#rpath(#listener_args));
+                }
+
+                match main {
+                    Some(main) => (main.lock().unwrap().dispatch)(#rpath(#args)),
+                    None if !observed => log::warn!(
+                        "Dropped {} callback, no dispatcher or listener is set",
+                        stringify!(#rpath)
+                    ),
+                    None => (),
+                }
             }
         }
     };
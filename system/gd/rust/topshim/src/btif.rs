@@ -3,13 +3,15 @@
 //! This is a shim interface for calling the C++ bluetooth interface via Rust.
 
 use crate::bindings::root as bindings;
-use crate::topstack::get_dispatchers;
+use crate::topstack::{get_dispatchers, get_runtime};
 use num_traits::cast::{FromPrimitive, ToPrimitive};
 use std::cmp;
+use std::collections::VecDeque;
 use std::fmt::{Debug, Formatter, Result};
 use std::mem;
 use std::os::raw::c_char;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::vec::Vec;
 use topshim_macros::cb_variant;
 
@@ -81,6 +83,79 @@ impl From<bindings::bt_bond_state_t> for BtBondState {
     }
 }
 
+/// Why a bonding attempt failed, decoded from the HCI or SMP reason reported with the bond state.
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[repr(u32)]
+pub enum BtBondFailReason {
+    None = 0,
+    /// The keys didn't match, e.g. the wrong PIN was entered or a stale link key was used.
+    AuthenticationFailure,
+    /// The peer has no link key for us anymore.
+    KeyMissing,
+    /// The passkey entry was canceled or failed.
+    PasskeyEntryFailed,
+    /// The user rejected the numeric comparison.
+    NumericComparisonFailed,
+    OobFailed,
+    /// The peer doesn't support the pairing method or the requested security.
+    PairingNotSupported,
+    /// The peer doesn't allow pairing right now.
+    PairingNotAllowed,
+    /// The peer refused or the user on the peer side terminated the connection.
+    RejectedByPeer,
+    InsufficientSecurity,
+    /// Too many attempts, the peer requires waiting before trying again.
+    RepeatedAttempts,
+    /// The peer didn't respond in time.
+    Timeout,
+    Unknown,
+}
+
+/// BTA_DM_AUTH_FAIL_BASE, i.e. HCI_ERR_MAX_ERR (0x43) + 10. BTA reports failed LE pairings as
+/// this value plus the SMP reason, which keeps them clear of the HCI error codes.
+const BOND_FAIL_SMP_BASE: i32 = 0x43 + 10;
+
+/// The fail reason of a bond state change is `pairing_cb.fail_reason` in btif_dm.cc, which holds
+/// one of two kinds of codes:
+///
+/// * HCI error codes (0x00 to 0x43), for BR/EDR pairing and for the link failing during pairing,
+///   e.g. 0x05 Authentication Failure or 0x06 PIN or Key Missing.
+/// * SMP pairing failed reasons offset by `BOND_FAIL_SMP_BASE`, for LE pairing and cross
+///   transport key derivation, see BTA_DM_AUTH_CONVERT_SMP_CODE in bta_api.h. The offset also
+///   covers the SMP reasons internal to the stack, e.g. 0x16 SMP_RSP_TIMEOUT.
+///
+/// Codes without a matching reason map to `Unknown`.
+impl From<i32> for BtBondFailReason {
+    fn from(item: i32) -> Self {
+        if item >= BOND_FAIL_SMP_BASE {
+            return match item - BOND_FAIL_SMP_BASE {
+                0x01 => BtBondFailReason::PasskeyEntryFailed,
+                0x02 => BtBondFailReason::OobFailed,
+                0x03 | 0x04 | 0x0B => BtBondFailReason::AuthenticationFailure,
+                0x05 => BtBondFailReason::PairingNotSupported,
+                0x06 => BtBondFailReason::InsufficientSecurity,
+                0x09 => BtBondFailReason::RepeatedAttempts,
+                0x0C => BtBondFailReason::NumericComparisonFailed,
+                0x16 | 0x19 => BtBondFailReason::Timeout,
+                _ => BtBondFailReason::Unknown,
+            };
+        }
+
+        match item {
+            0x00 => BtBondFailReason::None,
+            0x05 => BtBondFailReason::AuthenticationFailure,
+            0x06 => BtBondFailReason::KeyMissing,
+            0x04 | 0x08 | 0x10 | 0x22 => BtBondFailReason::Timeout,
+            0x0E | 0x13 => BtBondFailReason::RejectedByPeer,
+            0x17 => BtBondFailReason::RepeatedAttempts,
+            0x18 => BtBondFailReason::PairingNotAllowed,
+            0x25 | 0x2F => BtBondFailReason::InsufficientSecurity,
+            0x26 | 0x29 | 0x37 => BtBondFailReason::PairingNotSupported,
+            _ => BtBondFailReason::Unknown,
+        }
+    }
+}
+
 #[derive(Clone, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
 #[repr(u32)]
 pub enum BtConnectionState {
@@ -586,6 +661,19 @@ mod ffi {
         // Convert flgas into an InitFlags object
         fn ConvertFlags(flags: Vec<String>) -> UniquePtr<InitFlags>;
         fn GetFlagsPtr(self: &InitFlags) -> *mut *const c_char;
+
+        fn ReadRemoteName(address: RustRawAddress, transport: i32);
+        fn CancelRemoteName(address: RustRawAddress);
+    }
+
+    extern "Rust" {
+        // Generated by cb_variant! below.
+        unsafe fn remote_name_complete_cb(
+            status: u8,
+            address: RustRawAddress,
+            name: *const u8,
+            len: usize,
+        );
     }
 }
 
@@ -700,7 +788,7 @@ pub enum BaseCallbacks {
     DiscoveryState(BtDiscoveryState),
     PinRequest(RawAddress, String, u32, bool),
     SspRequest(RawAddress, String, u32, BtSspVariant, u32),
-    BondState(BtStatus, RawAddress, BtBondState, i32),
+    BondState(BtStatus, RawAddress, BtBondState, BtBondFailReason),
     AddressConsolidate(RawAddress, RawAddress),
     LeAddressAssociate(RawAddress, RawAddress),
     AclState(BtStatus, RawAddress, BtAclState, BtTransport, BtHciErrorCode),
@@ -748,7 +836,7 @@ cb_variant!(BaseCb, ssp_request_cb -> BaseCallbacks::SspRequest,
     let _1 = String::from(unsafe{*_1});
 });
 cb_variant!(BaseCb, bond_state_cb -> BaseCallbacks::BondState,
-u32 -> BtStatus, *mut FfiAddress, bindings::bt_bond_state_t -> BtBondState, i32, {
+u32 -> BtStatus, *mut FfiAddress, bindings::bt_bond_state_t -> BtBondState, i32 -> BtBondFailReason, {
     let _1 = unsafe { *(_1 as *const RawAddress) };
 });
 
@@ -862,7 +950,7 @@ impl BluetoothInterface {
         let flags = (*converted).GetFlagsPtr();
 
         if get_dispatchers().lock().unwrap().set::<BaseCb>(Arc::new(Mutex::new(callbacks))) {
-            panic!("Tried to set dispatcher for BaseCallbacks but it already existed");
+            log::warn!("Replaced the dispatcher for BaseCallbacks");
         }
 
         // Fill up callbacks struct to pass to init function (will be copied so
@@ -1059,6 +1147,313 @@ pub fn get_btinterface() -> Option<BluetoothInterface> {
     ret
 }
 
+/// Raw completion of a remote name request as reported by the stack.
+#[derive(Clone, Debug)]
+pub enum RemoteNameCallbacks {
+    /// BTM status, remote address and remote name (empty on failure).
+    RemoteNameComplete(u8, RawAddress, String),
+}
+
+pub struct RemoteNameCallbacksDispatcher {
+    pub dispatch: Box<dyn Fn(RemoteNameCallbacks) + Send>,
+}
+
+type RemoteNameCb = Arc<Mutex<RemoteNameCallbacksDispatcher>>;
+
+cb_variant!(RemoteNameCb, remote_name_complete_cb -> RemoteNameCallbacks::RemoteNameComplete,
+u8, ffi::RustRawAddress -> RawAddress, *const u8, usize -> _, {
+    let _1 = RawAddress { val: _1.address };
+    let _2 = match _2.is_null() {
+        true => String::new(),
+        false => String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(_2, _3) })
+            .into_owned(),
+    };
+});
+
+/// BTM status for a successful remote name request.
+const BTM_SUCCESS: u8 = 0;
+
+/// Default time to wait for a remote name before giving up. This is much shorter than the
+/// controller page timeout so discovery UIs aren't held up by unresponsive devices.
+pub const DEFAULT_REMOTE_NAME_TIMEOUT_MS: u64 = 5000;
+
+/// Outcome of a remote name request made through [`RemoteNameRequests`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteNameResult {
+    Success(String),
+    /// The request failed with the given BTM status.
+    Failed(u8),
+    Cancelled,
+    TimedOut,
+}
+
+/// The request currently sent to the controller.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ActiveRemoteName {
+    addr: RawAddress,
+    generation: u32,
+    /// Set once the request was cancelled or timed out and the result reported. The next request
+    /// is still only started once the stack reports the completion.
+    abandoned: bool,
+}
+
+/// What to do when the timer of an active request fires.
+#[derive(Debug, PartialEq)]
+enum RemoteNameExpiry {
+    /// The request is no longer active.
+    None,
+    /// The request took too long and was abandoned; cancel it in the stack.
+    TimedOut,
+    /// No completion arrived for an abandoned request either; stop waiting for it.
+    Stalled,
+}
+
+/// Queue of remote name requests, kept apart from the FFI so the sequencing can be tested.
+struct RemoteNameState {
+    active: Option<ActiveRemoteName>,
+    /// Requests waiting for the active request to finish.
+    pending: VecDeque<(RawAddress, BtTransport)>,
+    generation: u32,
+    timeout: Duration,
+}
+
+impl RemoteNameState {
+    fn new(timeout: Duration) -> Self {
+        RemoteNameState { active: None, pending: VecDeque::new(), generation: 0, timeout }
+    }
+
+    fn contains(&self, addr: &RawAddress) -> bool {
+        self.active.map_or(false, |a| !a.abandoned && a.addr == *addr)
+            || self.pending.iter().any(|(a, _)| a == addr)
+    }
+
+    /// Queues a request. Returns false if one is already pending for `addr`.
+    fn push(&mut self, addr: RawAddress, transport: BtTransport) -> bool {
+        if self.contains(&addr) {
+            return false;
+        }
+
+        self.pending.push_back((addr, transport));
+        true
+    }
+
+    /// Makes the next queued request active, unless the controller is still busy.
+    fn start_next(&mut self) -> Option<(RawAddress, BtTransport, u32)> {
+        if self.active.is_some() {
+            return None;
+        }
+
+        let (addr, transport) = self.pending.pop_front()?;
+        self.generation = self.generation.wrapping_add(1);
+        self.active =
+            Some(ActiveRemoteName { addr, generation: self.generation, abandoned: false });
+        Some((addr, transport, self.generation))
+    }
+
+    /// Abandons the request for `addr`. Returns None if there is no such request, otherwise
+    /// whether it is active and has to be cancelled in the stack.
+    fn cancel(&mut self, addr: &RawAddress) -> Option<bool> {
+        match self.active.as_mut() {
+            Some(active) if !active.abandoned && active.addr == *addr => {
+                active.abandoned = true;
+                Some(true)
+            }
+            _ => {
+                let len = self.pending.len();
+                self.pending.retain(|(a, _)| a != addr);
+                match len == self.pending.len() {
+                    true => None,
+                    false => Some(false),
+                }
+            }
+        }
+    }
+
+    /// Handles the completion reported by the stack. Returns whether the result still has to be
+    /// reported, or None for a completion that doesn't belong to the active request.
+    fn complete(&mut self, addr: &RawAddress) -> Option<bool> {
+        match self.active {
+            Some(active) if active.addr == *addr => {
+                self.active = None;
+                Some(!active.abandoned)
+            }
+            _ => None,
+        }
+    }
+
+    /// Handles the timer of request `generation` firing.
+    fn expire(&mut self, generation: u32) -> RemoteNameExpiry {
+        match self.active.as_mut() {
+            Some(active) if active.generation == generation => {
+                if active.abandoned {
+                    self.active = None;
+                    RemoteNameExpiry::Stalled
+                } else {
+                    active.abandoned = true;
+                    RemoteNameExpiry::TimedOut
+                }
+            }
+            _ => RemoteNameExpiry::None,
+        }
+    }
+}
+
+type RemoteNameResultCb = Arc<Mutex<Box<dyn Fn(RawAddress, RemoteNameResult) + Send>>>;
+
+/// Serializes remote name requests (the controller only handles one at a time), tracks
+/// pending requests per device and allows them to be cancelled or to time out.
+///
+/// A cancelled or timed out request is reported right away, but the next request is only sent
+/// once the stack reported the completion of the previous one, since it would be rejected as
+/// busy otherwise.
+///
+/// The completions are observed as a listener, so other components can observe them too. Only
+/// one tracker should send requests though, as the controller rejects concurrent ones.
+pub struct RemoteNameRequests {
+    state: Arc<Mutex<RemoteNameState>>,
+    callback: RemoteNameResultCb,
+    listener_id: u32,
+}
+
+impl RemoteNameRequests {
+    /// Creates the tracker and listens for remote name completions until it is dropped.
+    /// `callback` is called exactly once for every accepted request.
+    pub fn new(
+        timeout_ms: u64,
+        callback: Box<dyn Fn(RawAddress, RemoteNameResult) + Send>,
+    ) -> RemoteNameRequests {
+        let state = Arc::new(Mutex::new(RemoteNameState::new(Duration::from_millis(timeout_ms))));
+        let callback: RemoteNameResultCb = Arc::new(Mutex::new(callback));
+
+        let listener_state = state.clone();
+        let listener_callback = callback.clone();
+        let dispatcher = RemoteNameCallbacksDispatcher {
+            dispatch: Box::new(move |cb| match cb {
+                RemoteNameCallbacks::RemoteNameComplete(status, addr, name) => {
+                    RemoteNameRequests::on_complete(
+                        &listener_state,
+                        &listener_callback,
+                        status,
+                        addr,
+                        name,
+                    );
+                }
+            }),
+        };
+
+        let listener_id = get_dispatchers()
+            .lock()
+            .unwrap()
+            .add_listener::<RemoteNameCb>(Arc::new(Mutex::new(dispatcher)));
+
+        RemoteNameRequests { state, callback, listener_id }
+    }
+
+    /// Queues a remote name request. Returns false if one is already pending for `addr`.
+    pub fn request(&self, addr: RawAddress, transport: BtTransport) -> bool {
+        if !self.state.lock().unwrap().push(addr, transport) {
+            return false;
+        }
+
+        RemoteNameRequests::start_next(&self.state, &self.callback);
+        true
+    }
+
+    /// Abandons the request for `addr`. Returns false if there was no such request.
+    pub fn cancel(&self, addr: RawAddress) -> bool {
+        match self.state.lock().unwrap().cancel(&addr) {
+            None => return false,
+            Some(true) => ffi::CancelRemoteName(ffi::RustRawAddress { address: addr.val }),
+            Some(false) => (),
+        }
+
+        (self.callback.lock().unwrap())(addr, RemoteNameResult::Cancelled);
+        true
+    }
+
+    /// Returns whether a request for `addr` is active or queued.
+    pub fn is_pending(&self, addr: &RawAddress) -> bool {
+        self.state.lock().unwrap().contains(addr)
+    }
+
+    /// Returns all addresses with an active or queued request.
+    pub fn get_pending(&self) -> Vec<RawAddress> {
+        let state = self.state.lock().unwrap();
+        state
+            .active
+            .iter()
+            .filter(|a| !a.abandoned)
+            .map(|a| a.addr)
+            .chain(state.pending.iter().map(|(a, _)| *a))
+            .collect()
+    }
+
+    fn on_complete(
+        state: &Arc<Mutex<RemoteNameState>>,
+        callback: &RemoteNameResultCb,
+        status: u8,
+        addr: RawAddress,
+        name: String,
+    ) {
+        let report = match state.lock().unwrap().complete(&addr) {
+            Some(report) => report,
+            // Late completion of a request we stopped waiting for.
+            None => return,
+        };
+
+        if report {
+            let result = match status {
+                BTM_SUCCESS => RemoteNameResult::Success(name),
+                _ => RemoteNameResult::Failed(status),
+            };
+            (callback.lock().unwrap())(addr, result);
+        }
+
+        RemoteNameRequests::start_next(state, callback);
+    }
+
+    fn start_next(state: &Arc<Mutex<RemoteNameState>>, callback: &RemoteNameResultCb) {
+        let (addr, generation, timeout) = {
+            let mut s = state.lock().unwrap();
+            let (addr, transport, generation) = match s.start_next() {
+                Some(next) => next,
+                None => return,
+            };
+
+            ffi::ReadRemoteName(ffi::RustRawAddress { address: addr.val }, transport.into());
+            (addr, generation, s.timeout)
+        };
+
+        let state = state.clone();
+        let callback = callback.clone();
+        get_runtime().spawn(async move {
+            loop {
+                tokio::time::sleep(timeout).await;
+
+                let expiry = state.lock().unwrap().expire(generation);
+                match expiry {
+                    RemoteNameExpiry::None => return,
+                    RemoteNameExpiry::TimedOut => {
+                        ffi::CancelRemoteName(ffi::RustRawAddress { address: addr.val });
+                        (callback.lock().unwrap())(addr, RemoteNameResult::TimedOut);
+                    }
+                    RemoteNameExpiry::Stalled => {
+                        log::warn!("No completion for remote name request to {}", addr.to_string());
+                        RemoteNameRequests::start_next(&state, &callback);
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl Drop for RemoteNameRequests {
+    fn drop(&mut self) {
+        get_dispatchers().lock().unwrap().remove_listener::<RemoteNameCb>(self.listener_id);
+    }
+}
+
 // Turns C-array T[] to Vec<U>.
 pub(crate) fn ptr_to_vec<T: Copy, U: From<T>>(start: *const T, length: usize) -> Vec<U> {
     unsafe { (0..length).map(|i| U::from(*start.offset(i as isize))).collect::<Vec<U>>() }
@@ -1117,6 +1512,21 @@ mod tests {
         assert_eq!("".to_string(), String::from(invalid_bdname));
     }
 
+    #[test]
+    fn test_bond_fail_reason() {
+        assert_eq!(BtBondFailReason::from(0), BtBondFailReason::None);
+        assert_eq!(BtBondFailReason::from(0x06), BtBondFailReason::KeyMissing);
+        assert_eq!(
+            BtBondFailReason::from(BOND_FAIL_SMP_BASE + 0x01),
+            BtBondFailReason::PasskeyEntryFailed
+        );
+        assert_eq!(
+            BtBondFailReason::from(BOND_FAIL_SMP_BASE + 0x05),
+            BtBondFailReason::PairingNotSupported
+        );
+        assert_eq!(BtBondFailReason::from(0x3f), BtBondFailReason::Unknown);
+    }
+
     #[test]
     fn test_ptr_to_vec() {
         let arr: [i32; 3] = [1, 2, 3];
@@ -1166,4 +1576,56 @@ mod tests {
             });
         }
     }
+
+    fn remote_name_addr(last: u8) -> RawAddress {
+        RawAddress { val: [0, 0, 0, 0, 0, last] }
+    }
+
+    #[test]
+    fn test_remote_name_cancel_waits_for_completion() {
+        let mut state = RemoteNameState::new(Duration::from_millis(100));
+        assert!(state.push(remote_name_addr(1), BtTransport::Bredr));
+        assert!(state.push(remote_name_addr(2), BtTransport::Bredr));
+        assert!(!state.push(remote_name_addr(2), BtTransport::Bredr));
+        assert_eq!(state.start_next(), Some((remote_name_addr(1), BtTransport::Bredr, 1)));
+
+        // Cancelling the active request needs the stack to cancel it, queued ones don't.
+        assert_eq!(state.cancel(&remote_name_addr(1)), Some(true));
+        assert_eq!(state.cancel(&remote_name_addr(1)), None);
+        assert!(!state.contains(&remote_name_addr(1)));
+
+        // The controller is still busy until the completion arrives.
+        assert_eq!(state.start_next(), None);
+        assert_eq!(state.complete(&remote_name_addr(2)), None);
+        assert_eq!(state.complete(&remote_name_addr(1)), Some(false));
+        assert_eq!(state.start_next(), Some((remote_name_addr(2), BtTransport::Bredr, 2)));
+
+        // A new request for a cancelled device can be queued right away.
+        assert!(state.push(remote_name_addr(1), BtTransport::Le));
+        assert_eq!(state.cancel(&remote_name_addr(1)), Some(false));
+        assert_eq!(state.complete(&remote_name_addr(2)), Some(true));
+        assert_eq!(state.start_next(), None);
+    }
+
+    #[test]
+    fn test_remote_name_timeout() {
+        let mut state = RemoteNameState::new(Duration::from_millis(100));
+        state.push(remote_name_addr(1), BtTransport::Bredr);
+        state.push(remote_name_addr(2), BtTransport::Bredr);
+        assert_eq!(state.start_next(), Some((remote_name_addr(1), BtTransport::Bredr, 1)));
+
+        assert_eq!(state.expire(1), RemoteNameExpiry::TimedOut);
+        assert_eq!(state.start_next(), None);
+
+        // A timed out request finishing only starts the next one.
+        assert_eq!(state.complete(&remote_name_addr(1)), Some(false));
+        assert_eq!(state.expire(1), RemoteNameExpiry::None);
+        assert_eq!(state.start_next(), Some((remote_name_addr(2), BtTransport::Bredr, 2)));
+
+        // Without a completion the queue only moves on after a second timeout.
+        assert_eq!(state.expire(2), RemoteNameExpiry::TimedOut);
+        assert_eq!(state.expire(2), RemoteNameExpiry::Stalled);
+        assert_eq!(state.complete(&remote_name_addr(2)), None);
+        assert_eq!(state.start_next(), None);
+    }
 }
@@ -1,9 +1,64 @@
+use crate::btif::RawAddress;
+use crate::topstack::get_dispatchers;
+
+use num_traits::cast::FromPrimitive;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use topshim_macros::cb_variant;
+
 #[cxx::bridge(namespace = bluetooth::topshim::rust)]
 mod ffi {
     pub struct RustRawAddress {
         address: [u8; 6],
     }
 
+    #[derive(Debug, Default, Copy, Clone, PartialEq)]
+    pub struct ControllerFeatures {
+        pub le: bool,
+        pub le_2m_phy: bool,
+        pub le_coded_phy: bool,
+        pub le_extended_advertising: bool,
+        pub le_periodic_advertising: bool,
+        pub le_privacy: bool,
+        pub le_cis_central: bool,
+        pub le_cis_peripheral: bool,
+        pub le_iso_broadcaster: bool,
+        pub le_sync_receiver: bool,
+        pub secure_connections: bool,
+        pub sniff_subrating: bool,
+        pub le_batch_scan: bool,
+    }
+
+    /// Capabilities of the controller. Buffer sizes are the maximum data lengths, without the
+    /// packet header.
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    pub struct ControllerCapabilities {
+        /// Bitmap as returned by HCI Read Local Supported Commands.
+        pub supported_commands: [u8; 64],
+        pub acl_buffer_count: u16,
+        pub acl_buffer_size: u16,
+        pub le_acl_buffer_count: u8,
+        pub le_acl_buffer_size: u16,
+        pub sco_buffer_count: u16,
+        pub sco_buffer_size: u8,
+        pub iso_buffer_count: u8,
+        pub iso_buffer_size: u16,
+        pub le_max_advertising_data_length: u16,
+        pub le_number_of_advertising_sets: u8,
+        pub le_resolving_list_size: u8,
+    }
+
+    /// Codec, logical transport and direction to read the capabilities of.
+    #[derive(Debug, Default, Copy, Clone, PartialEq)]
+    pub struct CodecCapabilitiesRequest {
+        pub coding_format: u8,
+        pub company_id: u16,
+        pub vendor_codec_id: u16,
+        pub transport: u8,
+        pub direction: u8,
+    }
+
     unsafe extern "C++" {
         include!("controller/controller_shim.h");
 
@@ -11,11 +66,695 @@ mod ffi {
 
         fn GetControllerInterface() -> UniquePtr<ControllerIntf>;
         fn read_local_addr(self: &ControllerIntf) -> RustRawAddress;
+        fn read_features(self: &ControllerIntf) -> ControllerFeatures;
+        fn read_capabilities(self: &ControllerIntf) -> ControllerCapabilities;
+        fn is_provisioning_mode_enabled(self: &ControllerIntf) -> bool;
+        fn write_local_addr_override(self: &ControllerIntf, address: RustRawAddress) -> u8;
+        fn le_accept_list_size(self: &ControllerIntf) -> u8;
+        fn le_accept_connection_from(
+            self: &ControllerIntf,
+            address: RustRawAddress,
+            addr_type: u8,
+            is_direct: bool,
+        ) -> bool;
+        fn le_ignore_connection_from(self: &ControllerIntf, address: RustRawAddress, addr_type: u8);
+        fn le_clear_accept_list(self: &ControllerIntf);
+        fn le_address_type(self: &ControllerIntf, address: RustRawAddress) -> u8;
+        fn read_inquiry_response_tx_power(self: &ControllerIntf);
+        fn write_inquiry_tx_power(self: &ControllerIntf, tx_power: i8);
+        fn set_eir_tx_power_included(self: &ControllerIntf, include: bool);
+        fn write_rpa_timeout(self: &ControllerIntf, timeout_s: u16);
+        fn read_le_adv_tx_power(self: &ControllerIntf);
+        fn vendor_debug_dump_supported(self: &ControllerIntf) -> bool;
+        fn register_vendor_debug_events(self: &ControllerIntf);
+        fn unregister_vendor_debug_events(self: &ControllerIntf);
+        fn start_vendor_debug_dump(self: &ControllerIntf);
+        fn le_transmitter_test(
+            self: &ControllerIntf,
+            channel: u8,
+            length: u8,
+            payload: u8,
+            phy: u8,
+        );
+        fn le_receiver_test(self: &ControllerIntf, channel: u8, phy: u8);
+        fn le_test_end(self: &ControllerIntf);
+        fn register_keypress_notifications(self: &ControllerIntf);
+        fn send_keypress_notification(
+            self: &ControllerIntf,
+            address: RustRawAddress,
+            notification_type: u8,
+        );
+        fn register_transport_error_events(self: &ControllerIntf);
+        fn read_local_supported_codecs(self: &ControllerIntf);
+        fn read_local_codec_capabilities(self: &ControllerIntf, request: CodecCapabilitiesRequest);
+    }
+
+    extern "Rust" {
+        // Generated by cb_variant! below.
+        fn controller_on_local_addr_override(
+            status: u8,
+            requested: RustRawAddress,
+            readback: RustRawAddress,
+        );
+        fn controller_on_inquiry_response_tx_power(status: u8, tx_power: i8);
+        fn controller_on_inquiry_tx_power_written(status: u8);
+        fn controller_on_rpa_timeout_written(status: u8, timeout_s: u16);
+        fn controller_on_le_adv_tx_power(status: u8, tx_power: i8);
+        fn controller_on_vendor_debug_dump_started(status: u8);
+        fn controller_on_le_test_started(status: u8);
+        fn controller_on_le_test_ended(status: u8, num_packets: u16);
+        fn controller_on_keypress_notification(address: RustRawAddress, notification_type: u8);
+        fn controller_on_keypress_notification_sent(status: u8, address: RustRawAddress);
+        fn controller_on_data_buffer_overflow(link_type: u8);
+        fn controller_on_local_supported_codecs(status: u8, is_v2: bool, data: Vec<u8>);
+        fn controller_on_local_codec_capabilities(
+            status: u8,
+            request: CodecCapabilitiesRequest,
+            data: Vec<u8>,
+        );
+
+        // Collects vendor debug events, see `VENDOR_DEBUG_DUMP`.
+        fn controller_on_vendor_debug_event(data: Vec<u8>);
+    }
+}
+
+pub type ControllerFeatures = ffi::ControllerFeatures;
+pub type ControllerCapabilities = ffi::ControllerCapabilities;
+
+impl ControllerCapabilities {
+    /// Whether the command at `octet` and `bit` of the supported commands bitmap is supported,
+    /// as numbered in the Core specification.
+    pub fn is_command_supported(&self, octet: usize, bit: u8) -> bool {
+        match self.supported_commands.get(octet) {
+            Some(mask) if bit < 8 => mask & (1 << bit) != 0,
+            _ => false,
+        }
+    }
+}
+
+/// HCI status code for a successful command.
+const HCI_SUCCESS: u8 = 0;
+
+/// Valid range for the inquiry TX power in dBm.
+const INQUIRY_TX_POWER_MIN: i8 = -70;
+const INQUIRY_TX_POWER_MAX: i8 = 20;
+
+/// Valid range for the RPA timeout in seconds, i.e. 1s to 11.5 hours.
+pub const RPA_TIMEOUT_MIN: u16 = 0x0001;
+pub const RPA_TIMEOUT_MAX: u16 = 0xa1b8;
+
+/// Last TX power levels successfully read from the controller.
+#[derive(Clone, Copy, Debug, Default)]
+struct TxPowerCache {
+    inquiry_response: Option<i8>,
+    le_advertising: Option<i8>,
+}
+
+/// Upper bound for a collected vendor debug dump. Anything beyond is dropped.
+const VENDOR_DEBUG_DUMP_MAX_LEN: usize = 64 * 1024;
+
+lazy_static! {
+    static ref TX_POWER_CACHE: Mutex<TxPowerCache> = Mutex::new(TxPowerCache::default());
+
+    /// Last RPA timeout the controller accepted. None while it uses its default.
+    static ref RPA_TIMEOUT: Mutex<Option<u16>> = Mutex::new(None);
+
+    /// Payloads of the debug info vendor events received since the last dump was started.
+    static ref VENDOR_DEBUG_DUMP: Mutex<Vec<u8>> = Mutex::new(vec![]);
+
+    static ref LOCAL_CODEC_CACHE: Mutex<LocalCodecCache> = Mutex::new(LocalCodecCache::default());
+}
+
+/// Codecs supported by the controller, as last read from it.
+#[derive(Clone, Debug, Default)]
+struct LocalCodecCache {
+    codecs: Option<Vec<LocalCodec>>,
+    capabilities: Vec<LocalCodecCapabilities>,
+}
+
+#[derive(Debug)]
+pub enum ControllerCallbacks {
+    /// Result of a local address override: HCI status, requested address and the address read
+    /// back from the controller after the write.
+    LocalAddrOverride(u8, RawAddress, RawAddress),
+    /// HCI status and TX power in dBm used for inquiry responses.
+    InquiryResponseTxPower(u8, i8),
+    /// HCI status of an inquiry TX power write.
+    InquiryTxPowerWritten(u8),
+    /// HCI status and the RPA timeout in seconds that was written.
+    RpaTimeoutWritten(u8, u16),
+    /// HCI status and TX power in dBm used for LE advertising.
+    LeAdvertisingTxPower(u8, i8),
+    /// HCI status of the command asking the controller to emit its debug statistics.
+    VendorDebugDumpStarted(u8),
+    /// HCI status of an LE transmitter or receiver test start.
+    LeTestStarted(u8),
+    /// HCI status and number of packets received by an LE receiver test (0 for a transmitter
+    /// test) once the test ended.
+    LeTestEnded(u8, u16),
+    /// Keypress notification received from a device entering a passkey.
+    KeypressNotification(RawAddress, KeypressNotificationType),
+    /// HCI status of a keypress notification sent to a device.
+    KeypressNotificationSent(u8, RawAddress),
+    /// The controller ran out of buffers for host data of this link type and dropped data.
+    DataBufferOverflow(HciLinkType),
+    /// HCI status and codecs the controller can encode and decode itself.
+    LocalSupportedCodecs(u8, Vec<LocalCodec>),
+    /// HCI status and capabilities of a codec supported by the controller.
+    LocalCodecCapabilities(u8, LocalCodecCapabilities),
+}
+
+pub struct ControllerCallbacksDispatcher {
+    pub dispatch: Box<dyn Fn(ControllerCallbacks) + Send>,
+}
+
+type ControllerCb = Arc<Mutex<ControllerCallbacksDispatcher>>;
+
+cb_variant!(
+    ControllerCb,
+    controller_on_local_addr_override -> ControllerCallbacks::LocalAddrOverride,
+    u8, ffi::RustRawAddress -> RawAddress, ffi::RustRawAddress -> RawAddress, {
+        let _1 = RawAddress { val: _1.address };
+        let _2 = RawAddress { val: _2.address };
+    }
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_inquiry_response_tx_power -> ControllerCallbacks::InquiryResponseTxPower,
+    u8, i8, {
+        if _0 == HCI_SUCCESS {
+            TX_POWER_CACHE.lock().unwrap().inquiry_response = Some(_1);
+        }
+    }
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_inquiry_tx_power_written -> ControllerCallbacks::InquiryTxPowerWritten,
+    u8
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_rpa_timeout_written -> ControllerCallbacks::RpaTimeoutWritten,
+    u8, u16, {
+        if _0 == HCI_SUCCESS {
+            *RPA_TIMEOUT.lock().unwrap() = Some(_1);
+        }
+    }
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_le_adv_tx_power -> ControllerCallbacks::LeAdvertisingTxPower,
+    u8, i8, {
+        if _0 == HCI_SUCCESS {
+            TX_POWER_CACHE.lock().unwrap().le_advertising = Some(_1);
+        }
+    }
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_vendor_debug_dump_started -> ControllerCallbacks::VendorDebugDumpStarted,
+    u8
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_le_test_started -> ControllerCallbacks::LeTestStarted,
+    u8
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_le_test_ended -> ControllerCallbacks::LeTestEnded,
+    u8, u16
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_keypress_notification -> ControllerCallbacks::KeypressNotification,
+    ffi::RustRawAddress -> RawAddress, u8 -> KeypressNotificationType, {
+        let _0 = RawAddress { val: _0.address };
+    }
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_keypress_notification_sent -> ControllerCallbacks::KeypressNotificationSent,
+    u8, ffi::RustRawAddress -> RawAddress, {
+        let _1 = RawAddress { val: _1.address };
+    }
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_data_buffer_overflow -> ControllerCallbacks::DataBufferOverflow,
+    u8 -> HciLinkType
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_local_supported_codecs -> ControllerCallbacks::LocalSupportedCodecs,
+    u8, bool -> _, Vec<u8>, {
+        let _2 = if _0 == HCI_SUCCESS { parse_local_supported_codecs(_1, &_2) } else { vec![] };
+        if _0 == HCI_SUCCESS {
+            *LOCAL_CODEC_CACHE.lock().unwrap() =
+                LocalCodecCache { codecs: Some(_2.clone()), capabilities: vec![] };
+        }
+    }
+);
+
+cb_variant!(
+    ControllerCb,
+    controller_on_local_codec_capabilities -> ControllerCallbacks::LocalCodecCapabilities,
+    u8, ffi::CodecCapabilitiesRequest -> _, Vec<u8>, {
+        let data: &[u8] = if _0 == HCI_SUCCESS { &_2 } else { &[] };
+        let _2 = LocalCodecCapabilities::new(&_1, data);
+        if _0 == HCI_SUCCESS {
+            let mut cache = LOCAL_CODEC_CACHE.lock().unwrap();
+            cache.capabilities.retain(|c| {
+                (c.codec, c.transport, c.direction) != (_2.codec, _2.transport, _2.direction)
+            });
+            cache.capabilities.push(_2.clone());
+        }
+    }
+);
+
+fn controller_on_vendor_debug_event(data: Vec<u8>) {
+    let mut dump = VENDOR_DEBUG_DUMP.lock().unwrap();
+    let room = VENDOR_DEBUG_DUMP_MAX_LEN.saturating_sub(dump.len());
+    dump.extend(data.into_iter().take(room));
+}
+
+/// Turns a vendor debug dump into something readable. Implemented per controller vendor.
+pub trait VendorDebugDecoder {
+    /// Returns None if the blob isn't in a format this decoder knows.
+    fn decode(&self, blob: &[u8]) -> Option<String>;
+}
+
+/// Formats a vendor debug dump for inclusion in a state dump, falling back to a hex dump if
+/// none of `decoders` understands it.
+pub fn format_vendor_debug_dump(blob: &[u8], decoders: &[&dyn VendorDebugDecoder]) -> String {
+    let mut out = format!("Controller debug dump ({} bytes):\n", blob.len());
+    if let Some(decoded) = decoders.iter().find_map(|d| d.decode(blob)) {
+        out.push_str(&decoded);
+        return out;
+    }
+
+    for (i, line) in blob.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let _ = writeln!(out, "  {:04x}: {}", i * 16, hex.join(" "));
+    }
+
+    out
+}
+
+/// Returns whether a local address override completed and the readback matches the request.
+pub fn is_local_addr_override_verified(
+    status: u8,
+    requested: &RawAddress,
+    readback: &RawAddress,
+) -> bool {
+    status == HCI_SUCCESS && requested == readback
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AddressOverrideError {
+    /// The controller is not in provisioning mode.
+    NotProvisioning,
+    /// The stack isn't running, so there is no HCI layer to send the command through.
+    StackNotRunning,
+    /// There are connected devices, which would keep using the old address.
+    LinksActive,
+    /// The requested address is not a valid public address.
+    InvalidAddress,
+    /// There is no known command to program the address of this controller's manufacturer.
+    Unsupported,
+}
+
+// Results of ControllerIntf::write_local_addr_override, see controller_shim.h.
+const LOCAL_ADDR_OVERRIDE_STARTED: u8 = 0;
+const LOCAL_ADDR_OVERRIDE_STACK_NOT_RUNNING: u8 = 1;
+const LOCAL_ADDR_OVERRIDE_LINKS_ACTIVE: u8 = 2;
+const LOCAL_ADDR_OVERRIDE_NOT_PROVISIONING: u8 = 3;
+
+/// Checks an address override request before anything is sent to the controller.
+fn check_local_addr_override(
+    provisioning: bool,
+    addr: &RawAddress,
+) -> Result<(), AddressOverrideError> {
+    if !provisioning {
+        return Err(AddressOverrideError::NotProvisioning);
+    }
+
+    // Reject the empty/broadcast address and multicast (group) addresses.
+    if addr.val == [0; 6] || addr.val == [0xff; 6] || addr.val[0] & 0x01 != 0 {
+        return Err(AddressOverrideError::InvalidAddress);
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum LeAddressType {
+    Public = 0,
+    Random = 1,
+    PublicIdentity = 2,
+    RandomIdentity = 3,
+}
+
+impl Default for LeAddressType {
+    fn default() -> Self {
+        LeAddressType::Public
+    }
+}
+
+/// How a pending LE connection was requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeConnectionMode {
+    /// Connect as soon as possible using fast scan parameters. The entry is dropped once the
+    /// connection completes.
+    Direct,
+    /// Connect whenever the device shows up. The entry stays until explicitly canceled.
+    Background,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AcceptListError {
+    /// The filter accept list has no room left for the device. The list is shared with the rest
+    /// of the stack, e.g. GATT background connections, so this is the only reason the stack
+    /// refuses an entry.
+    Full,
+}
+
+/// Highest RF channel usable by the LE test mode, i.e. 2480 MHz.
+const LE_TEST_MAX_CHANNEL: u8 = 39;
+
+/// Packet payload sent by an LE transmitter test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum LeTestPayload {
+    Prbs9 = 0,
+    RepeatedF0 = 1,
+    RepeatedAa = 2,
+    Prbs15 = 3,
+    RepeatedFf = 4,
+    Repeated00 = 5,
+    Repeated0f = 6,
+    Repeated55 = 7,
+}
+
+impl Default for LeTestPayload {
+    fn default() -> Self {
+        LeTestPayload::Prbs9
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum LeTestPhy {
+    Le1m = 1,
+    Le2m = 2,
+    LeCoded = 3,
+}
+
+impl Default for LeTestPhy {
+    fn default() -> Self {
+        LeTestPhy::Le1m
+    }
+}
+
+/// Progress of a passkey entry, as sent in keypress notifications.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum KeypressNotificationType {
+    EntryStarted = 0,
+    DigitEntered = 1,
+    DigitErased = 2,
+    Cleared = 3,
+    EntryCompleted = 4,
+}
+
+impl From<u8> for KeypressNotificationType {
+    fn from(item: u8) -> Self {
+        // Reserved values can't be shown as progress, so treat them as the entry being reset.
+        KeypressNotificationType::from_u8(item).unwrap_or(KeypressNotificationType::Cleared)
+    }
+}
+
+/// Link type of the data reported in transport errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum HciLinkType {
+    Sco = 0,
+    Acl = 1,
+    Unknown = 0xff,
+}
+
+impl From<u8> for HciLinkType {
+    fn from(item: u8) -> Self {
+        HciLinkType::from_u8(item).unwrap_or(HciLinkType::Unknown)
+    }
+}
+
+/// Coding format of the codecs identified by a company and vendor codec id.
+pub const CODING_FORMAT_VENDOR: u8 = 0xff;
+
+/// Codec as identified over HCI. The company and vendor codec ids are only set for vendor codecs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CodecId {
+    /// Assigned number of the coding format, e.g. 0x02 for CVSD or 0x06 for LC3.
+    pub coding_format: u8,
+    pub company_id: u16,
+    pub vendor_codec_id: u16,
+}
+
+impl CodecId {
+    pub fn is_vendor(&self) -> bool {
+        self.coding_format == CODING_FORMAT_VENDOR
+    }
+}
+
+/// Logical transports a codec is supported on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CodecTransports {
+    pub br_edr_acl: bool,
+    pub br_edr_sco: bool,
+    pub le_cis: bool,
+    pub le_bis: bool,
+}
+
+impl From<u8> for CodecTransports {
+    fn from(item: u8) -> Self {
+        CodecTransports {
+            br_edr_acl: item & 0x01 != 0,
+            br_edr_sco: item & 0x02 != 0,
+            le_cis: item & 0x04 != 0,
+            le_bis: item & 0x08 != 0,
+        }
+    }
+}
+
+/// Codec the controller can encode and decode itself, i.e. that audio can be offloaded with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalCodec {
+    pub id: CodecId,
+    /// None if the controller only supports the first version of the command, which doesn't
+    /// tell the transports apart.
+    pub transports: Option<CodecTransports>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum CodecTransport {
+    BrEdrAcl = 0,
+    BrEdrSco = 1,
+    LeCis = 2,
+    LeBis = 3,
+}
+
+impl Default for CodecTransport {
+    fn default() -> Self {
+        CodecTransport::BrEdrAcl
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum CodecDirection {
+    /// Audio sent by the host to the controller.
+    Input = 0,
+    /// Audio sent by the controller to the host.
+    Output = 1,
+}
+
+impl Default for CodecDirection {
+    fn default() -> Self {
+        CodecDirection::Input
     }
 }
 
+/// LTV types of the LE codec specific capabilities, see the Bluetooth Assigned Numbers.
+const LTV_SUPPORTED_SAMPLING_FREQUENCIES: u8 = 0x01;
+const LTV_SUPPORTED_AUDIO_CHANNEL_COUNTS: u8 = 0x03;
+
+/// Sample rate in Hz of each bit of the supported sampling frequencies.
+const LE_SAMPLE_RATES: [u32; 13] =
+    [8000, 11025, 16000, 22050, 24000, 32000, 44100, 48000, 88200, 96000, 176400, 192000, 384000];
+
+/// Capabilities of a codec on one logical transport and direction.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocalCodecCapabilities {
+    pub codec: CodecId,
+    pub transport: CodecTransport,
+    pub direction: CodecDirection,
+    /// Sample rates in Hz. Only decoded for LE transports, where the format is standardized.
+    pub sample_rates: Vec<u32>,
+    /// Numbers of audio channels. Only decoded for LE transports.
+    pub channel_counts: Vec<u8>,
+    /// Capabilities as sent by the controller, in a codec specific format.
+    pub raw: Vec<Vec<u8>>,
+}
+
+impl LocalCodecCapabilities {
+    /// Builds the capabilities from the return parameters following the status.
+    fn new(request: &ffi::CodecCapabilitiesRequest, data: &[u8]) -> Self {
+        let transport =
+            CodecTransport::from_u8(request.transport).unwrap_or(CodecTransport::BrEdrAcl);
+        let direction = CodecDirection::from_u8(request.direction).unwrap_or(CodecDirection::Input);
+        let mut caps = LocalCodecCapabilities {
+            codec: CodecId {
+                coding_format: request.coding_format,
+                company_id: request.company_id,
+                vendor_codec_id: request.vendor_codec_id,
+            },
+            transport,
+            direction,
+            sample_rates: vec![],
+            channel_counts: vec![],
+            raw: parse_codec_capabilities(data),
+        };
+
+        if transport != CodecTransport::LeCis && transport != CodecTransport::LeBis {
+            return caps;
+        }
+
+        for (ltv_type, value) in caps.raw.iter().flat_map(|c| parse_ltv(c)) {
+            match (ltv_type, value.len()) {
+                (LTV_SUPPORTED_SAMPLING_FREQUENCIES, 2) => {
+                    let bits = u16::from_le_bytes([value[0], value[1]]);
+                    caps.sample_rates.extend(
+                        LE_SAMPLE_RATES
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| bits & (1 << i) != 0)
+                            .map(|(_, r)| r),
+                    );
+                }
+                (LTV_SUPPORTED_AUDIO_CHANNEL_COUNTS, 1) => {
+                    caps.channel_counts
+                        .extend((0..8u8).filter(|i| value[0] & (1 << i) != 0).map(|i| i + 1));
+                }
+                _ => (),
+            }
+        }
+
+        caps
+    }
+}
+
+/// Parses the return parameters following the status of Read Local Supported Codecs. Stops at
+/// the first truncated entry.
+fn parse_local_supported_codecs(is_v2: bool, data: &[u8]) -> Vec<LocalCodec> {
+    let mut codecs = vec![];
+    let mut pos = 0;
+
+    let (entry_len, vendor_entry_len) = if is_v2 { (2, 5) } else { (1, 4) };
+    let transports = |entry: &[u8], at: usize| {
+        if is_v2 {
+            Some(CodecTransports::from(entry[at]))
+        } else {
+            None
+        }
+    };
+
+    let count = *data.get(pos).unwrap_or(&0) as usize;
+    pos += 1;
+    for entry in data.get(pos..).unwrap_or(&[]).chunks_exact(entry_len).take(count) {
+        codecs.push(LocalCodec {
+            id: CodecId { coding_format: entry[0], ..Default::default() },
+            transports: transports(entry, 1),
+        });
+    }
+
+    pos += count * entry_len;
+    let count = *data.get(pos).unwrap_or(&0) as usize;
+    pos += 1;
+    for entry in data.get(pos..).unwrap_or(&[]).chunks_exact(vendor_entry_len).take(count) {
+        codecs.push(LocalCodec {
+            id: CodecId {
+                coding_format: CODING_FORMAT_VENDOR,
+                company_id: u16::from_le_bytes([entry[0], entry[1]]),
+                vendor_codec_id: u16::from_le_bytes([entry[2], entry[3]]),
+            },
+            transports: transports(entry, 4),
+        });
+    }
+
+    codecs
+}
+
+/// Splits the return parameters following the status of Read Local Supported Codec
+/// Capabilities into the individual capabilities.
+fn parse_codec_capabilities(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut capabilities = vec![];
+    let count = *data.first().unwrap_or(&0);
+    let mut rest = data.get(1..).unwrap_or(&[]);
+
+    for _ in 0..count {
+        let len = match rest.first() {
+            Some(len) if rest.len() > *len as usize => *len as usize,
+            _ => break,
+        };
+        capabilities.push(rest[1..=len].to_vec());
+        rest = &rest[len + 1..];
+    }
+
+    capabilities
+}
+
+/// Splits LE codec specific capabilities into their type and value.
+fn parse_ltv(data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut ltvs = vec![];
+    let mut rest = data;
+
+    // The length covers the type and the value.
+    while let Some(len) = rest.first().map(|l| *l as usize) {
+        if len == 0 || rest.len() <= len {
+            break;
+        }
+        ltvs.push((rest[1], &rest[2..=len]));
+        rest = &rest[len + 1..];
+    }
+
+    ltvs
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LeTestError {
+    /// The channel is above `LE_TEST_MAX_CHANNEL`.
+    InvalidChannel,
+}
+
 pub struct Controller {
     internal: cxx::UniquePtr<ffi::ControllerIntf>,
+
+    /// Devices we asked the stack to connect to, i.e. the entries in the filter accept list.
+    le_connections: HashMap<RawAddress, (LeAddressType, LeConnectionMode)>,
 }
 
 unsafe impl Send for Controller {}
@@ -23,10 +762,433 @@ unsafe impl Send for Controller {}
 impl Controller {
     pub fn new() -> Controller {
         let intf = ffi::GetControllerInterface();
-        Controller { internal: intf }
+        Controller { internal: intf, le_connections: HashMap::new() }
+    }
+
+    pub fn initialize(&mut self, callbacks: ControllerCallbacksDispatcher) {
+        if get_dispatchers().lock().unwrap().set::<ControllerCb>(Arc::new(Mutex::new(callbacks))) {
+            log::warn!("Replaced the dispatcher for ControllerCallbacks");
+        }
     }
 
     pub fn read_local_addr(&mut self) -> [u8; 6] {
         self.internal.read_local_addr().address
     }
+
+    /// Reads the features supported by the controller. Only valid while the adapter is enabled.
+    pub fn read_features(&self) -> ControllerFeatures {
+        self.internal.read_features()
+    }
+
+    /// Reads the capabilities of the controller. Only valid while the adapter is enabled.
+    pub fn read_capabilities(&self) -> ControllerCapabilities {
+        self.internal.read_capabilities()
+    }
+
+    /// Whether reprogramming the controller address is allowed. Factory flows enable it by
+    /// starting the stack with the `INIT_provisioning_mode=true` init flag.
+    pub fn is_provisioning(&self) -> bool {
+        self.internal.is_provisioning_mode_enabled()
+    }
+
+    /// Program the controller BD_ADDR using the vendor specific command of its manufacturer.
+    ///
+    /// Only allowed in provisioning mode. The stack has to be running to reach the controller, but
+    /// no device may be connected. The shim checks both against the actual stack state. The
+    /// result, including the address read back from the controller, is reported via
+    /// [`ControllerCallbacks::LocalAddrOverride`].
+    pub fn override_local_addr(&mut self, addr: RawAddress) -> Result<(), AddressOverrideError> {
+        check_local_addr_override(self.is_provisioning(), &addr)?;
+
+        match self
+            .internal
+            .write_local_addr_override(ffi::RustRawAddress { address: addr.to_byte_arr() })
+        {
+            LOCAL_ADDR_OVERRIDE_STARTED => Ok(()),
+            LOCAL_ADDR_OVERRIDE_STACK_NOT_RUNNING => Err(AddressOverrideError::StackNotRunning),
+            LOCAL_ADDR_OVERRIDE_LINKS_ACTIVE => Err(AddressOverrideError::LinksActive),
+            LOCAL_ADDR_OVERRIDE_NOT_PROVISIONING => Err(AddressOverrideError::NotProvisioning),
+            _ => Err(AddressOverrideError::Unsupported),
+        }
+    }
+
+    /// Number of devices the controller filter accept list can hold.
+    ///
+    /// The rest of the stack adds its own entries to the same list, so this is only an upper bound
+    /// for the devices added via [`Controller::le_connect`].
+    pub fn le_accept_list_capacity(&self) -> usize {
+        self.internal.le_accept_list_size().into()
+    }
+
+    /// Number of devices currently in the filter accept list.
+    pub fn le_accept_list_len(&self) -> usize {
+        self.le_connections.len()
+    }
+
+    pub fn get_le_connection_mode(&self, addr: &RawAddress) -> Option<LeConnectionMode> {
+        self.le_connections.get(addr).map(|(_, mode)| *mode)
+    }
+
+    /// Starts a direct or background LE connection to `addr`.
+    ///
+    /// Requesting a device that is already pending only updates its mode. Fails if the stack has
+    /// no room left in the accept list, which also counts the entries of the rest of the stack.
+    pub fn le_connect(
+        &mut self,
+        addr: RawAddress,
+        addr_type: LeAddressType,
+        mode: LeConnectionMode,
+    ) -> Result<(), AcceptListError> {
+        let is_direct = mode == LeConnectionMode::Direct;
+        let added = self.internal.le_accept_connection_from(
+            ffi::RustRawAddress { address: addr.to_byte_arr() },
+            addr_type as u8,
+            is_direct,
+        );
+        if !added {
+            return Err(AcceptListError::Full);
+        }
+
+        self.le_connections.insert(addr, (addr_type, mode));
+        Ok(())
+    }
+
+    /// Cancels a pending LE connection and removes the device from the accept list.
+    ///
+    /// Returns false if no connection to `addr` was pending.
+    pub fn le_cancel_connect(&mut self, addr: &RawAddress) -> bool {
+        match self.le_connections.remove(addr) {
+            Some((addr_type, _)) => {
+                self.internal.le_ignore_connection_from(
+                    ffi::RustRawAddress { address: addr.to_byte_arr() },
+                    addr_type as u8,
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels all pending LE connections.
+    pub fn le_clear_accept_list(&mut self) {
+        self.le_connections.clear();
+        self.internal.le_clear_accept_list();
+    }
+
+    /// Forgets the pending LE connections without touching the stack, e.g. because the stack
+    /// went away together with its accept list.
+    pub fn reset_le_connections(&mut self) {
+        self.le_connections.clear();
+    }
+
+    /// Address type the stack knows `addr` by, e.g. the identity address type of a bonded LE
+    /// device. Public for unknown devices.
+    pub fn get_le_address_type(&self, addr: &RawAddress) -> LeAddressType {
+        let addr_type =
+            self.internal.le_address_type(ffi::RustRawAddress { address: addr.to_byte_arr() });
+        LeAddressType::from_u8(addr_type).unwrap_or(LeAddressType::Public)
+    }
+
+    /// Reads the TX power used for inquiry responses. The result is reported via
+    /// [`ControllerCallbacks::InquiryResponseTxPower`].
+    pub fn read_inquiry_response_tx_power(&self) {
+        self.internal.read_inquiry_response_tx_power();
+    }
+
+    /// Sets the TX power used for inquiry, in dBm. The controller uses the closest level it
+    /// supports, which is read back and reported via
+    /// [`ControllerCallbacks::InquiryResponseTxPower`].
+    ///
+    /// Returns false if `tx_power` is outside of the range allowed by the spec.
+    pub fn set_inquiry_tx_power(&self, tx_power: i8) -> bool {
+        if !(INQUIRY_TX_POWER_MIN..=INQUIRY_TX_POWER_MAX).contains(&tx_power) {
+            return false;
+        }
+
+        self.internal.write_inquiry_tx_power(tx_power);
+        true
+    }
+
+    /// Includes the TX Power Level reported by the controller in the EIR, instead of leaving it
+    /// out. The level is read again whenever it changes via `set_inquiry_tx_power`.
+    pub fn set_eir_tx_power_included(&self, include: bool) {
+        self.internal.set_eir_tx_power_included(include);
+    }
+
+    /// Sets how often the resolvable private address is rotated, in seconds. The result is
+    /// reported via [`ControllerCallbacks::RpaTimeoutWritten`].
+    ///
+    /// Returns false if `timeout_s` is outside of the range allowed by the spec.
+    pub fn set_rpa_timeout(&self, timeout_s: u16) -> bool {
+        if !(RPA_TIMEOUT_MIN..=RPA_TIMEOUT_MAX).contains(&timeout_s) {
+            return false;
+        }
+
+        self.internal.write_rpa_timeout(timeout_s);
+        true
+    }
+
+    /// Last RPA timeout in seconds the controller accepted, None while it uses its default.
+    pub fn get_rpa_timeout(&self) -> Option<u16> {
+        *RPA_TIMEOUT.lock().unwrap()
+    }
+
+    /// Reads the TX power used for LE advertising. The result is reported via
+    /// [`ControllerCallbacks::LeAdvertisingTxPower`].
+    pub fn read_le_advertising_tx_power(&self) {
+        self.internal.read_le_adv_tx_power();
+    }
+
+    /// Last inquiry response TX power read from the controller, for building EIR data.
+    pub fn get_inquiry_response_tx_power(&self) -> Option<i8> {
+        TX_POWER_CACHE.lock().unwrap().inquiry_response
+    }
+
+    /// Last LE advertising TX power read from the controller, for building advertising data.
+    pub fn get_le_advertising_tx_power(&self) -> Option<i8> {
+        TX_POWER_CACHE.lock().unwrap().le_advertising
+    }
+
+    /// Whether the controller can emit its debug statistics via vendor events.
+    pub fn is_vendor_debug_dump_supported(&self) -> bool {
+        self.internal.vendor_debug_dump_supported()
+    }
+
+    /// Starts collecting the debug statistics the controller sends as vendor events. Only valid
+    /// while the adapter is enabled.
+    pub fn register_vendor_debug_events(&self) {
+        if self.is_vendor_debug_dump_supported() {
+            self.internal.register_vendor_debug_events();
+        }
+    }
+
+    /// Stops collecting debug statistics. Must be called before the adapter is disabled, since
+    /// the handler is owned by the stack being torn down.
+    pub fn unregister_vendor_debug_events(&self) {
+        self.internal.unregister_vendor_debug_events();
+    }
+
+    /// Asks the controller to emit its debug statistics. Previously collected data is dropped.
+    /// The command status is reported via [`ControllerCallbacks::VendorDebugDumpStarted`] and
+    /// the statistics can be picked up with `take_vendor_debug_dump` once the controller had
+    /// some time to send them. Requires `register_vendor_debug_events`.
+    ///
+    /// Returns false if the controller doesn't support it.
+    pub fn start_vendor_debug_dump(&self) -> bool {
+        if !self.is_vendor_debug_dump_supported() {
+            return false;
+        }
+
+        VENDOR_DEBUG_DUMP.lock().unwrap().clear();
+        self.internal.start_vendor_debug_dump();
+        true
+    }
+
+    /// Returns the debug statistics collected so far, as sent by the controller.
+    pub fn take_vendor_debug_dump(&self) -> Vec<u8> {
+        std::mem::take(&mut *VENDOR_DEBUG_DUMP.lock().unwrap())
+    }
+
+    /// Starts an LE transmitter test sending `length` byte packets with `payload` on RF channel
+    /// `channel` (2402 + 2 * channel MHz). The start is reported via
+    /// [`ControllerCallbacks::LeTestStarted`]; the test runs until `le_test_end`.
+    pub fn le_transmitter_test(
+        &self,
+        channel: u8,
+        length: u8,
+        payload: LeTestPayload,
+        phy: LeTestPhy,
+    ) -> Result<(), LeTestError> {
+        if channel > LE_TEST_MAX_CHANNEL {
+            return Err(LeTestError::InvalidChannel);
+        }
+
+        self.internal.le_transmitter_test(channel, length, payload as u8, phy as u8);
+        Ok(())
+    }
+
+    /// Starts an LE receiver test on RF channel `channel`. The start is reported via
+    /// [`ControllerCallbacks::LeTestStarted`]; the test runs until `le_test_end`.
+    pub fn le_receiver_test(&self, channel: u8, phy: LeTestPhy) -> Result<(), LeTestError> {
+        if channel > LE_TEST_MAX_CHANNEL {
+            return Err(LeTestError::InvalidChannel);
+        }
+
+        self.internal.le_receiver_test(channel, phy as u8);
+        Ok(())
+    }
+
+    /// Ends the running LE test. The packet count is reported via
+    /// [`ControllerCallbacks::LeTestEnded`].
+    pub fn le_test_end(&self) {
+        self.internal.le_test_end();
+    }
+
+    /// Starts reporting keypress notifications from devices entering a passkey via
+    /// [`ControllerCallbacks::KeypressNotification`]. Only valid while the adapter is enabled.
+    pub fn register_keypress_notifications(&self) {
+        self.internal.register_keypress_notifications();
+    }
+
+    /// Tells `addr` about the progress of the local passkey entry. Only meaningful while pairing
+    /// with keypress notifications enabled. The result is reported via
+    /// [`ControllerCallbacks::KeypressNotificationSent`].
+    pub fn send_keypress_notification(
+        &self,
+        addr: RawAddress,
+        notification_type: KeypressNotificationType,
+    ) {
+        self.internal.send_keypress_notification(
+            ffi::RustRawAddress { address: addr.to_byte_arr() },
+            notification_type as u8,
+        );
+    }
+
+    /// Starts reporting transport errors of the controller, such as
+    /// [`ControllerCallbacks::DataBufferOverflow`]. Only valid while the adapter is enabled.
+    pub fn register_transport_error_events(&self) {
+        self.internal.register_transport_error_events();
+    }
+
+    /// Reads the codecs the controller can encode and decode itself. The result is reported via
+    /// [`ControllerCallbacks::LocalSupportedCodecs`]. Only valid while the adapter is enabled.
+    pub fn read_local_supported_codecs(&self) {
+        self.internal.read_local_supported_codecs();
+    }
+
+    /// Reads the capabilities of `codec` on `transport` in `direction`, such as its sample rates.
+    /// The result is reported via [`ControllerCallbacks::LocalCodecCapabilities`].
+    pub fn read_local_codec_capabilities(
+        &self,
+        codec: CodecId,
+        transport: CodecTransport,
+        direction: CodecDirection,
+    ) {
+        self.internal.read_local_codec_capabilities(ffi::CodecCapabilitiesRequest {
+            coding_format: codec.coding_format,
+            company_id: codec.company_id,
+            vendor_codec_id: codec.vendor_codec_id,
+            transport: transport as u8,
+            direction: direction as u8,
+        });
+    }
+
+    /// Codecs last read from the controller, None if they weren't read yet.
+    pub fn get_local_codecs(&self) -> Option<Vec<LocalCodec>> {
+        LOCAL_CODEC_CACHE.lock().unwrap().codecs.clone()
+    }
+
+    /// Codec capabilities read from the controller since the codecs were last read.
+    pub fn get_local_codec_capabilities(&self) -> Vec<LocalCodecCapabilities> {
+        LOCAL_CODEC_CACHE.lock().unwrap().capabilities.clone()
+    }
+
+    /// Must be called when an LE connection to `addr` completes. Direct connections are removed
+    /// from the accept list by the stack once connected, so drop them here as well.
+    pub fn on_le_connection_complete(&mut self, addr: &RawAddress) {
+        if self.get_le_connection_mode(addr) == Some(LeConnectionMode::Direct) {
+            self.le_connections.remove(addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_local_supported_codecs() {
+        // CVSD and mSBC, then one vendor codec.
+        let v1 = [2, 0x02, 0x05, 1, 0x0a, 0x00, 0x01, 0x00];
+        let codecs = parse_local_supported_codecs(false, &v1);
+        assert_eq!(codecs.len(), 3);
+        assert_eq!(codecs[1].id, CodecId { coding_format: 0x05, ..Default::default() });
+        assert_eq!(codecs[1].transports, None);
+        assert_eq!(
+            codecs[2].id,
+            CodecId { coding_format: CODING_FORMAT_VENDOR, company_id: 0x0a, vendor_codec_id: 1 }
+        );
+
+        // LC3 on CIS and BIS, then a truncated vendor codec.
+        let v2 = [1, 0x06, 0x0c, 1, 0x0a, 0x00, 0x01];
+        let codecs = parse_local_supported_codecs(true, &v2);
+        assert_eq!(codecs.len(), 1);
+        assert_eq!(
+            codecs[0].transports,
+            Some(CodecTransports { le_cis: true, le_bis: true, ..Default::default() })
+        );
+    }
+
+    #[test]
+    fn test_local_addr_override_checks() {
+        let addr = RawAddress { val: [0x00, 0x1a, 0x7d, 0xda, 0x71, 0x13] };
+        assert_eq!(check_local_addr_override(true, &addr), Ok(()));
+        assert_eq!(
+            check_local_addr_override(false, &addr),
+            Err(AddressOverrideError::NotProvisioning)
+        );
+
+        for invalid in [[0; 6], [0xff; 6], [0x01, 0, 0, 0, 0, 1]] {
+            assert_eq!(
+                check_local_addr_override(true, &RawAddress { val: invalid }),
+                Err(AddressOverrideError::InvalidAddress)
+            );
+        }
+
+        let other = RawAddress { val: [0x00, 0x1a, 0x7d, 0xda, 0x71, 0x14] };
+        assert!(is_local_addr_override_verified(HCI_SUCCESS, &addr, &addr));
+        assert!(!is_local_addr_override_verified(HCI_SUCCESS, &addr, &other));
+        assert!(!is_local_addr_override_verified(0x0c, &addr, &addr));
+    }
+
+    #[test]
+    fn test_is_command_supported() {
+        let mut supported_commands = [0u8; 64];
+        // LE Set Extended Advertising Parameters is octet 36 bit 2.
+        supported_commands[36] = 1 << 2;
+        let caps = ControllerCapabilities {
+            supported_commands,
+            acl_buffer_count: 0,
+            acl_buffer_size: 0,
+            le_acl_buffer_count: 0,
+            le_acl_buffer_size: 0,
+            sco_buffer_count: 0,
+            sco_buffer_size: 0,
+            iso_buffer_count: 0,
+            iso_buffer_size: 0,
+            le_max_advertising_data_length: 0,
+            le_number_of_advertising_sets: 0,
+            le_resolving_list_size: 0,
+        };
+
+        assert!(caps.is_command_supported(36, 2));
+        assert!(!caps.is_command_supported(36, 1));
+        assert!(!caps.is_command_supported(36, 14));
+        assert!(!caps.is_command_supported(64, 0));
+    }
+
+    #[test]
+    fn test_codec_capabilities() {
+        let request = ffi::CodecCapabilitiesRequest {
+            coding_format: 0x06,
+            transport: CodecTransport::LeCis as u8,
+            direction: CodecDirection::Output as u8,
+            ..Default::default()
+        };
+
+        // 16 and 48 kHz, 1 or 2 channels, then a truncated capability.
+        let data = [3, 4, 3, 0x01, 0x84, 0x00, 3, 2, 0x03, 0x03, 9, 0x00];
+        let caps = LocalCodecCapabilities::new(&request, &data);
+        assert_eq!(caps.direction, CodecDirection::Output);
+        assert_eq!(caps.raw.len(), 2);
+        assert_eq!(caps.sample_rates, vec![16000, 48000]);
+        assert_eq!(caps.channel_counts, vec![1, 2]);
+
+        // Capabilities on other transports aren't decoded.
+        let request =
+            ffi::CodecCapabilitiesRequest { transport: CodecTransport::BrEdrSco as u8, ..request };
+        let caps = LocalCodecCapabilities::new(&request, &data);
+        assert_eq!(caps.raw.len(), 2);
+        assert!(caps.sample_rates.is_empty());
+    }
 }
@@ -1,8 +1,10 @@
-use crate::btif::{BluetoothInterface, RawAddress};
+use crate::btif::{BluetoothInterface, BtStatus, RawAddress};
+use crate::profiles::stats::{self, ProfileInfo};
 use crate::topstack::get_dispatchers;
 
 use num_traits::cast::FromPrimitive;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use topshim_macros::cb_variant;
 
 #[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
@@ -285,12 +287,20 @@ impl A2dp {
         A2dp { internal: a2dpif, _is_init: false }
     }
 
+    /// Returns the version of this shim and whether the profile is up.
+    pub fn query_profile_info(&self) -> ProfileInfo {
+        stats::get_profile_info("a2dp", stats::SHIM_VERSION)
+    }
+
     pub fn initialize(&mut self, callbacks: A2dpCallbacksDispatcher) -> bool {
         if get_dispatchers().lock().unwrap().set::<A2dpCb>(Arc::new(Mutex::new(callbacks))) {
-            panic!("Tried to set dispatcher for A2dp callbacks while it already exists");
+            log::warn!("Replaced the dispatcher for A2dp callbacks");
         }
-        self.internal.init();
-        true
+        let started = Instant::now();
+        let init = self.internal.init();
+        let status = BtStatus::from(init as u32);
+        stats::record_init_status("a2dp", started, status.clone());
+        status == BtStatus::Success
     }
 
     pub fn connect(&mut self, addr: RawAddress) {
@@ -321,6 +331,11 @@ impl A2dp {
     pub fn get_presentation_position(&self) -> PresentationPosition {
         self.internal.get_presentation_position()
     }
+
+    pub fn cleanup(&mut self) {
+        self.internal.cleanup();
+        stats::record_cleanup("a2dp");
+    }
 }
 
 #[derive(Debug)]
@@ -352,12 +367,20 @@ impl A2dpSink {
         A2dpSink { internal: a2dp_sink, _is_init: false }
     }
 
+    /// Returns the version of this shim and whether the profile is up.
+    pub fn query_profile_info(&self) -> ProfileInfo {
+        stats::get_profile_info("a2dp_sink", stats::SHIM_VERSION)
+    }
+
     pub fn initialize(&mut self, callbacks: A2dpSinkCallbacksDispatcher) -> bool {
         if get_dispatchers().lock().unwrap().set::<A2dpSinkCb>(Arc::new(Mutex::new(callbacks))) {
-            panic!("Tried to set dispatcher for A2dp Sink Callbacks while it already exists");
+            log::warn!("Replaced the dispatcher for A2dp Sink Callbacks");
         }
-        self.internal.init();
-        true
+        let started = Instant::now();
+        let init = self.internal.init();
+        let status = BtStatus::from(init as u32);
+        stats::record_init_status("a2dp_sink", started, status.clone());
+        status == BtStatus::Success
     }
 
     pub fn connect(&mut self, bt_addr: RawAddress) {
@@ -372,7 +395,10 @@ impl A2dpSink {
         self.internal.set_active_device(bt_addr.into());
     }
 
-    pub fn cleanup(&mut self) {}
+    pub fn cleanup(&mut self) {
+        self.internal.cleanup();
+        stats::record_cleanup("a2dp_sink");
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,9 @@
-use crate::btif::BluetoothInterface;
+use crate::btif::{BluetoothInterface, BtStatus};
+use crate::profiles::stats::{self, ProfileInfo};
 use crate::topstack::get_dispatchers;
 
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use topshim_macros::cb_variant;
 
 #[cxx::bridge(namespace = bluetooth::topshim::rust)]
@@ -13,7 +15,7 @@ pub mod ffi {
 
         unsafe fn GetAvrcpProfile(btif: *const u8) -> UniquePtr<AvrcpIntf>;
 
-        fn init(self: Pin<&mut AvrcpIntf>);
+        fn init(self: Pin<&mut AvrcpIntf>) -> i32;
         fn cleanup(self: Pin<&mut AvrcpIntf>);
         fn set_volume(self: Pin<&mut AvrcpIntf>, volume: i8);
 
@@ -66,16 +68,25 @@ impl Avrcp {
         Avrcp { internal: avrcpif, _is_init: false }
     }
 
+    /// Returns the version of this shim and whether the profile is up.
+    pub fn query_profile_info(&self) -> ProfileInfo {
+        stats::get_profile_info("avrcp", stats::SHIM_VERSION)
+    }
+
     pub fn initialize(&mut self, callbacks: AvrcpCallbacksDispatcher) -> bool {
         if get_dispatchers().lock().unwrap().set::<AvrcpCb>(Arc::new(Mutex::new(callbacks))) {
-            panic!("Tried to set dispatcher for Avrcp callbacks while it already exists");
+            log::warn!("Replaced the dispatcher for Avrcp callbacks");
         }
-        self.internal.pin_mut().init();
-        true
+        let started = Instant::now();
+        let init = self.internal.pin_mut().init();
+        let status = BtStatus::from(init as u32);
+        stats::record_init_status("avrcp", started, status.clone());
+        status == BtStatus::Success
     }
 
     pub fn cleanup(&mut self) -> bool {
         self.internal.pin_mut().cleanup();
+        stats::record_cleanup("avrcp");
         true
     }
 
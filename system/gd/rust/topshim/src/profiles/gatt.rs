@@ -7,12 +7,16 @@ use crate::profiles::gatt::bindings::{
     btgatt_scanner_callbacks_t, btgatt_server_callbacks_t, btgatt_server_interface_t,
     BleAdvertiserInterface, BleScannerInterface,
 };
+use crate::profiles::stats::{self, ProfileInfo};
 use crate::topstack::get_dispatchers;
 use crate::{cast_to_ffi_address, ccall, deref_ffi_address, mutcxxcall};
 
 use num_traits::cast::FromPrimitive;
 
+use std::collections::HashMap;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use topshim_macros::cb_variant;
 
@@ -1308,6 +1312,128 @@ impl GattServer {
     // TODO(b/193916778): Figure out how to shim read_phy which accepts base::Callback
 }
 
+/// What a batch scan stores for each advertisement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(i32)]
+pub enum BatchScanMode {
+    /// Address, TX power, RSSI and timestamp only.
+    Truncated = 1,
+    /// The advertising data and scan response as well.
+    Full = 2,
+    TruncatedAndFull = 3,
+}
+
+/// Which results the controller drops once its storage is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(i32)]
+pub enum BatchScanDiscardRule {
+    Oldest = 0,
+    WeakestRssi = 1,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchScanSettings {
+    pub mode: BatchScanMode,
+    pub scan_interval: u16,
+    pub scan_window: u16,
+    /// Own address type used while scanning.
+    pub addr_type: i32,
+    pub discard_rule: BatchScanDiscardRule,
+    /// Share of the controller storage in percent used for full results.
+    pub full_max: i32,
+    /// Share of the controller storage in percent used for truncated results.
+    pub trunc_max: i32,
+    /// Storage use in percent at which [`GattScannerCallbacks::OnBatchScanThresholdCrossed`] is
+    /// reported.
+    pub notify_threshold: i32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BatchScanError {
+    /// The storage shares or the threshold are out of range, or the window is longer than the
+    /// interval.
+    InvalidSettings,
+}
+
+/// A single advertisement stored by a batch scan.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchScanResult {
+    pub address: RawAddress,
+    pub addr_type: u8,
+    pub tx_power: i8,
+    pub rssi: i8,
+    /// Controller timestamp of the advertisement, in units of 50 ms.
+    pub timestamp: u16,
+    /// Empty for truncated results.
+    pub adv_data: Vec<u8>,
+    /// Empty for truncated results.
+    pub scan_response: Vec<u8>,
+}
+
+/// Length of the fields common to truncated and full batch scan records.
+const BATCH_SCAN_RECORD_HEADER_LEN: usize = 11;
+
+/// Splits the data of [`GattScannerCallbacks::OnBatchScanReports`] into results. Parsing stops at
+/// the first malformed record.
+pub fn parse_batch_scan_reports(
+    report_format: i32,
+    num_records: i32,
+    data: &[u8],
+) -> Vec<BatchScanResult> {
+    let full = match BatchScanMode::from_i32(report_format) {
+        Some(BatchScanMode::Truncated) => false,
+        Some(BatchScanMode::Full) => true,
+        _ => return vec![],
+    };
+
+    let mut results = vec![];
+    let mut pos = 0;
+    for _ in 0..num_records {
+        if data.len() < pos + BATCH_SCAN_RECORD_HEADER_LEN {
+            break;
+        }
+
+        let header = &data[pos..pos + BATCH_SCAN_RECORD_HEADER_LEN];
+        pos += BATCH_SCAN_RECORD_HEADER_LEN;
+
+        // BD_ADDR is sent over HCI in little endian order.
+        let mut address = RawAddress { val: [0; 6] };
+        address.val.copy_from_slice(&header[0..6]);
+        address.val.reverse();
+
+        let mut result = BatchScanResult {
+            address,
+            addr_type: header[6],
+            tx_power: header[7] as i8,
+            rssi: header[8] as i8,
+            timestamp: u16::from_le_bytes([header[9], header[10]]),
+            adv_data: vec![],
+            scan_response: vec![],
+        };
+
+        if full {
+            let mut read_field = || -> Option<Vec<u8>> {
+                let len = *data.get(pos)? as usize;
+                let field = data.get(pos + 1..pos + 1 + len)?.to_vec();
+                pos += 1 + len;
+                Some(field)
+            };
+
+            match (read_field(), read_field()) {
+                (Some(adv_data), Some(scan_response)) => {
+                    result.adv_data = adv_data;
+                    result.scan_response = scan_response;
+                }
+                _ => break,
+            }
+        }
+
+        results.push(result);
+    }
+
+    results
+}
+
 pub struct BleScanner {
     _internal: RawBleScannerWrapper,
     internal_cxx: cxx::UniquePtr<ffi::BleScannerIntf>,
@@ -1416,6 +1542,57 @@ impl BleScanner {
         mutcxxcall!(self, BatchscanReadReports, scanner_id, scan_mode);
     }
 
+    /// Lets the controller collect advertisements for `scanner_id` instead of reporting each one.
+    /// Results are only delivered when flushed, which is worth doing once
+    /// [`GattScannerCallbacks::OnBatchScanThresholdCrossed`] is reported.
+    pub fn start_batch_scan(
+        &mut self,
+        scanner_id: u8,
+        settings: &BatchScanSettings,
+    ) -> Result<(), BatchScanError> {
+        let in_range = |v: i32| (0..=100).contains(&v);
+        if !in_range(settings.full_max)
+            || !in_range(settings.trunc_max)
+            || settings.full_max + settings.trunc_max > 100
+            || !in_range(settings.notify_threshold)
+            || settings.scan_window > settings.scan_interval
+        {
+            return Err(BatchScanError::InvalidSettings);
+        }
+
+        self.batchscan_config_storage(
+            scanner_id,
+            settings.full_max,
+            settings.trunc_max,
+            settings.notify_threshold,
+        );
+        self.batchscan_enable(
+            settings.mode as i32,
+            settings.scan_interval,
+            settings.scan_window,
+            settings.addr_type,
+            settings.discard_rule as i32,
+        );
+        Ok(())
+    }
+
+    /// Reads out the results collected so far. They are reported via
+    /// [`GattScannerCallbacks::OnBatchScanReports`], once per format, and can be split with
+    /// [`parse_batch_scan_reports`].
+    pub fn flush_batch_scan(&mut self, scanner_id: u8, mode: BatchScanMode) {
+        if mode != BatchScanMode::Full {
+            self.batchscan_read_reports(scanner_id, BatchScanMode::Truncated as i32);
+        }
+
+        if mode != BatchScanMode::Truncated {
+            self.batchscan_read_reports(scanner_id, BatchScanMode::Full as i32);
+        }
+    }
+
+    pub fn stop_batch_scan(&mut self) {
+        self.batchscan_disable();
+    }
+
     pub fn start_sync(&mut self, sid: u8, address: RawAddress, skip: u16, timeout: u16) {
         let addr = unsafe { *((&address as *const RawAddress) as *const ffi::RustRawAddress) };
         mutcxxcall!(self, StartSync, sid, addr, skip, timeout);
@@ -1446,9 +1623,282 @@ impl BleScanner {
     }
 }
 
+/// Error reported for a single advertising set. Params: Status
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdvertisingSetError {
+    StartFailed(u8),
+    EnableFailed(u8),
+    DataFailed(u8),
+    ScanResponseFailed(u8),
+    ParametersFailed(u8),
+    PeriodicFailed(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdvertisingSetState {
+    /// Waiting for the controller to start the set.
+    Starting,
+    Enabled,
+    Disabled,
+    /// The set couldn't be started.
+    Failed,
+}
+
+struct AdvertisingSet {
+    client_id: u32,
+    adv_id: Option<u8>,
+    state: AdvertisingSetState,
+    tx_power: i8,
+    last_error: Option<AdvertisingSetError>,
+    /// Status of the last advertising data update if it failed.
+    data_error: Option<u8>,
+    /// Status of the last scan response data update if it failed.
+    scan_rsp_error: Option<u8>,
+    /// The handle is gone, unregister the set once it is started.
+    dropped: bool,
+}
+
+/// Advertising sets started with [`BleAdvertiser::start_advertising_set_for`], keyed by reg id.
+///
+/// The advertising callbacks only carry the reg id the set was started with, so reg ids are split
+/// between two owners: these sets use negative ids, while callers of
+/// [`BleAdvertiser::start_advertising_set`] pick their own non-negative ids.
+#[derive(Default)]
+struct AdvertisingSets {
+    sets: HashMap<i32, AdvertisingSet>,
+    /// Offset of the next reg id from -1.
+    next_reg_id: i32,
+}
+
+impl AdvertisingSets {
+    fn add(&mut self, client_id: u32) -> i32 {
+        // Runs from -1 down to i32::MIN + 1 and starts over, skipping ids still in use.
+        let reg_id = loop {
+            let reg_id = -1 - self.next_reg_id;
+            self.next_reg_id = (self.next_reg_id + 1) % i32::MAX;
+            if !self.sets.contains_key(&reg_id) {
+                break reg_id;
+            }
+        };
+        self.sets.insert(
+            reg_id,
+            AdvertisingSet {
+                client_id,
+                adv_id: None,
+                state: AdvertisingSetState::Starting,
+                tx_power: 0,
+                last_error: None,
+                data_error: None,
+                scan_rsp_error: None,
+                dropped: false,
+            },
+        );
+        reg_id
+    }
+
+    fn get_by_adv_id(&mut self, adv_id: u8) -> Option<&mut AdvertisingSet> {
+        self.sets.values_mut().find(|s| s.adv_id == Some(adv_id))
+    }
+
+    /// Updates the set a callback is about. Returns the advertiser id to unregister if the set
+    /// was started after its handle was dropped.
+    fn on_callback(&mut self, cb: &GattAdvCallbacks) -> Option<u8> {
+        let (adv_id, status, error): (u8, u8, fn(u8) -> AdvertisingSetError) = match *cb {
+            GattAdvCallbacks::OnAdvertisingSetStarted(reg_id, adv_id, tx_power, status) => {
+                let set = self.sets.get_mut(&reg_id)?;
+                if status != 0 {
+                    set.state = AdvertisingSetState::Failed;
+                    set.last_error = Some(AdvertisingSetError::StartFailed(status));
+                    if set.dropped {
+                        self.sets.remove(&reg_id);
+                    }
+                    return None;
+                }
+
+                set.adv_id = Some(adv_id);
+                set.state = AdvertisingSetState::Enabled;
+                set.tx_power = tx_power;
+                if set.dropped {
+                    self.sets.remove(&reg_id);
+                    return Some(adv_id);
+                }
+                return None;
+            }
+            GattAdvCallbacks::OnAdvertisingEnabled(adv_id, enabled, status) => {
+                let set = self.get_by_adv_id(adv_id)?;
+                if status == 0 {
+                    set.state = match enabled {
+                        true => AdvertisingSetState::Enabled,
+                        false => AdvertisingSetState::Disabled,
+                    };
+                }
+                (adv_id, status, AdvertisingSetError::EnableFailed)
+            }
+            GattAdvCallbacks::OnAdvertisingDataSet(adv_id, status) => {
+                let set = self.get_by_adv_id(adv_id)?;
+                set.data_error = if status == 0 { None } else { Some(status) };
+                (adv_id, status, AdvertisingSetError::DataFailed)
+            }
+            GattAdvCallbacks::OnScanResponseDataSet(adv_id, status) => {
+                let set = self.get_by_adv_id(adv_id)?;
+                set.scan_rsp_error = if status == 0 { None } else { Some(status) };
+                (adv_id, status, AdvertisingSetError::ScanResponseFailed)
+            }
+            GattAdvCallbacks::OnAdvertisingParametersUpdated(adv_id, tx_power, status) => {
+                let set = self.get_by_adv_id(adv_id)?;
+                if status == 0 {
+                    set.tx_power = tx_power;
+                }
+                (adv_id, status, AdvertisingSetError::ParametersFailed)
+            }
+            GattAdvCallbacks::OnPeriodicAdvertisingParametersUpdated(adv_id, status)
+            | GattAdvCallbacks::OnPeriodicAdvertisingDataSet(adv_id, status)
+            | GattAdvCallbacks::OnPeriodicAdvertisingEnabled(adv_id, _, status) => {
+                (adv_id, status, AdvertisingSetError::PeriodicFailed)
+            }
+            GattAdvCallbacks::OnOwnAddressRead(..) => return None,
+        };
+
+        if status != 0 {
+            if let Some(set) = self.get_by_adv_id(adv_id) {
+                set.last_error = Some(error(status));
+            }
+        }
+        None
+    }
+}
+
+/// `BleAdvertiserIntf` shared between the advertiser and the advertising set handles.
+struct AdvertiserIntf(cxx::UniquePtr<ffi::BleAdvertiserIntf>);
+
+// The C++ object only posts to the stack thread so it's ok to call from any thread.
+unsafe impl Send for AdvertiserIntf {}
+
+impl AdvertiserIntf {
+    fn pin_mut(&mut self) -> Pin<&mut ffi::BleAdvertiserIntf> {
+        self.0.pin_mut()
+    }
+}
+
+/// Owns an advertising set started with [`BleAdvertiser::start_advertising_set_for`]. The set is
+/// unregistered when the handle is dropped, including a set that is still starting.
+pub struct AdvertisingSetHandle {
+    reg_id: i32,
+    sets: Arc<Mutex<AdvertisingSets>>,
+    intf: Arc<Mutex<AdvertiserIntf>>,
+}
+
+impl AdvertisingSetHandle {
+    pub fn get_client_id(&self) -> u32 {
+        self.sets.lock().unwrap().sets.get(&self.reg_id).map_or(0, |s| s.client_id)
+    }
+
+    /// Advertiser id given by the controller, None until the set is started.
+    pub fn get_adv_id(&self) -> Option<u8> {
+        self.sets.lock().unwrap().sets.get(&self.reg_id).and_then(|s| s.adv_id)
+    }
+
+    pub fn get_state(&self) -> AdvertisingSetState {
+        self.sets
+            .lock()
+            .unwrap()
+            .sets
+            .get(&self.reg_id)
+            .map_or(AdvertisingSetState::Failed, |s| s.state)
+    }
+
+    pub fn get_tx_power(&self) -> i8 {
+        self.sets.lock().unwrap().sets.get(&self.reg_id).map_or(0, |s| s.tx_power)
+    }
+
+    /// Returns and clears the last error reported for this set.
+    pub fn take_error(&self) -> Option<AdvertisingSetError> {
+        self.sets.lock().unwrap().sets.get_mut(&self.reg_id).and_then(|s| s.last_error.take())
+    }
+
+    /// Returns the status of the last advertising data (or scan response data if `scan_rsp`)
+    /// update if it failed. Each half completes on its own, so only the failed one needs to be
+    /// sent again.
+    pub fn get_data_error(&self, scan_rsp: bool) -> Option<u8> {
+        self.sets.lock().unwrap().sets.get(&self.reg_id).and_then(|s| match scan_rsp {
+            true => s.scan_rsp_error,
+            false => s.data_error,
+        })
+    }
+
+    /// Enables or disables the set. Returns false if the set isn't started.
+    pub fn enable(&self, enable: bool, duration: u16, max_ext_adv_events: u8) -> bool {
+        match self.get_adv_id() {
+            Some(adv_id) => {
+                self.intf.lock().unwrap().pin_mut().Enable(
+                    adv_id,
+                    enable,
+                    duration,
+                    max_ext_adv_events,
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the advertising or scan response data. Returns false if the set isn't started.
+    ///
+    /// The update completes with `OnAdvertisingDataSet` or `OnScanResponseDataSet` respectively.
+    pub fn set_data(&self, set_scan_rsp: bool, data: Vec<u8>) -> bool {
+        match self.get_adv_id() {
+            Some(adv_id) => {
+                self.intf.lock().unwrap().pin_mut().SetData(adv_id, set_scan_rsp, data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets only the scan response data, leaving the advertising data as it is. Returns false if
+    /// the set isn't started.
+    pub fn set_scan_response_data(&self, data: Vec<u8>) -> bool {
+        self.set_data(true, data)
+    }
+
+    /// Sets the advertising parameters. Returns false if the set isn't started.
+    pub fn set_parameters(&self, params: AdvertiseParameters) -> bool {
+        match self.get_adv_id() {
+            Some(adv_id) => {
+                self.intf.lock().unwrap().pin_mut().SetParameters(adv_id, params);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Drop for AdvertisingSetHandle {
+    fn drop(&mut self) {
+        let mut sets = self.sets.lock().unwrap();
+        let adv_id = match sets.sets.get_mut(&self.reg_id) {
+            Some(set) if set.state == AdvertisingSetState::Starting => {
+                // Unregistered once started, see `AdvertisingSets::on_callback`.
+                set.dropped = true;
+                return;
+            }
+            Some(set) => set.adv_id,
+            None => return,
+        };
+
+        sets.sets.remove(&self.reg_id);
+        if let Some(adv_id) = adv_id {
+            self.intf.lock().unwrap().pin_mut().Unregister(adv_id);
+        }
+    }
+}
+
 pub struct BleAdvertiser {
     _internal: RawBleAdvertiserWrapper,
-    internal_cxx: cxx::UniquePtr<ffi::BleAdvertiserIntf>,
+    intf: Arc<Mutex<AdvertiserIntf>>,
+    sets: Arc<Mutex<AdvertisingSets>>,
+    /// Listener tracking `sets`, removed again when the advertiser goes away.
+    listener_id: u32,
 }
 
 impl BleAdvertiser {
@@ -1456,34 +1906,60 @@ impl BleAdvertiser {
         raw_gatt: *const btgatt_interface_t,
         internal_cxx: cxx::UniquePtr<ffi::BleAdvertiserIntf>,
     ) -> Self {
+        let intf = Arc::new(Mutex::new(AdvertiserIntf(internal_cxx)));
+        let sets = Arc::new(Mutex::new(AdvertisingSets::default()));
+
+        // Track the sets owned by handles next to whoever consumes the advertising callbacks.
+        let listener_intf = intf.clone();
+        let listener_sets = sets.clone();
+        let listener_id = get_dispatchers().lock().unwrap().add_listener::<GDAdvCb>(Arc::new(
+            Mutex::new(GattAdvCallbacksDispatcher {
+                dispatch: Box::new(move |cb| {
+                    let unregister = listener_sets.lock().unwrap().on_callback(&cb);
+                    if let Some(adv_id) = unregister {
+                        listener_intf.lock().unwrap().pin_mut().Unregister(adv_id);
+                    }
+                }),
+            }),
+        ));
+
         BleAdvertiser {
             _internal: RawBleAdvertiserWrapper {
                 _raw: unsafe { (*raw_gatt).advertiser as *const BleAdvertiserInterface },
             },
-            internal_cxx,
+            intf,
+            sets,
+            listener_id,
         }
     }
 
+    pub(crate) fn register_callbacks(&mut self) {
+        self.intf.lock().unwrap().pin_mut().RegisterCallbacks();
+    }
+
     pub fn register_advertiser(&mut self) {
-        mutcxxcall!(self, RegisterAdvertiser);
+        self.intf.lock().unwrap().pin_mut().RegisterAdvertiser();
     }
 
     pub fn unregister(&mut self, adv_id: u8) {
-        mutcxxcall!(self, Unregister, adv_id);
+        self.intf.lock().unwrap().pin_mut().Unregister(adv_id);
     }
 
     pub fn get_own_address(&mut self, adv_id: u8) {
-        mutcxxcall!(self, GetOwnAddress, adv_id);
+        self.intf.lock().unwrap().pin_mut().GetOwnAddress(adv_id);
     }
 
     pub fn set_parameters(&mut self, adv_id: u8, params: AdvertiseParameters) {
-        mutcxxcall!(self, SetParameters, adv_id, params);
+        self.intf.lock().unwrap().pin_mut().SetParameters(adv_id, params);
     }
     pub fn set_data(&mut self, adv_id: u8, set_scan_rsp: bool, data: Vec<u8>) {
-        mutcxxcall!(self, SetData, adv_id, set_scan_rsp, data);
+        self.intf.lock().unwrap().pin_mut().SetData(adv_id, set_scan_rsp, data);
+    }
+    pub fn set_scan_response_data(&mut self, adv_id: u8, data: Vec<u8>) {
+        self.set_data(adv_id, true, data);
     }
     pub fn enable(&mut self, adv_id: u8, enable: bool, duration: u16, max_ext_adv_events: u8) {
-        mutcxxcall!(self, Enable, adv_id, enable, duration, max_ext_adv_events);
+        self.intf.lock().unwrap().pin_mut().Enable(adv_id, enable, duration, max_ext_adv_events);
     }
     pub fn start_advertising(
         &mut self,
@@ -1493,16 +1969,16 @@ impl BleAdvertiser {
         scan_response_data: Vec<u8>,
         timeout_in_sec: i32,
     ) {
-        mutcxxcall!(
-            self,
-            StartAdvertising,
+        self.intf.lock().unwrap().pin_mut().StartAdvertising(
             adv_id,
             params,
             advertise_data,
             scan_response_data,
-            timeout_in_sec
+            timeout_in_sec,
         );
     }
+    /// Starts an advertising set whose callbacks are handled by the caller. `reg_id` must not be
+    /// negative, negative ids belong to sets started with `start_advertising_set_for`.
     pub fn start_advertising_set(
         &mut self,
         reg_id: i32,
@@ -1514,9 +1990,12 @@ impl BleAdvertiser {
         duration: u16,
         max_ext_adv_events: u8,
     ) {
-        mutcxxcall!(
-            self,
-            StartAdvertisingSet,
+        if reg_id < 0 {
+            log::warn!("Refusing to start advertising set with reserved reg id {}", reg_id);
+            return;
+        }
+
+        self.intf.lock().unwrap().pin_mut().StartAdvertisingSet(
             reg_id,
             params,
             advertise_data,
@@ -1524,21 +2003,68 @@ impl BleAdvertiser {
             periodic_params,
             periodic_data,
             duration,
-            max_ext_adv_events
+            max_ext_adv_events,
         );
     }
+
+    /// Starts an advertising set owned by `client_id`. A client can own any number of sets. The
+    /// reg id is picked by the advertiser from the negative ids and reported in
+    /// `OnAdvertisingSetStarted`.
+    pub fn start_advertising_set_for(
+        &mut self,
+        client_id: u32,
+        params: AdvertiseParameters,
+        advertise_data: Vec<u8>,
+        scan_response_data: Vec<u8>,
+        periodic_params: PeriodicAdvertisingParameters,
+        periodic_data: Vec<u8>,
+        duration: u16,
+        max_ext_adv_events: u8,
+    ) -> AdvertisingSetHandle {
+        let reg_id = self.sets.lock().unwrap().add(client_id);
+        self.intf.lock().unwrap().pin_mut().StartAdvertisingSet(
+            reg_id,
+            params,
+            advertise_data,
+            scan_response_data,
+            periodic_params,
+            periodic_data,
+            duration,
+            max_ext_adv_events,
+        );
+
+        AdvertisingSetHandle { reg_id, sets: self.sets.clone(), intf: self.intf.clone() }
+    }
+
+    /// Advertiser ids of the started sets owned by `client_id`.
+    pub fn get_client_adv_ids(&self, client_id: u32) -> Vec<u8> {
+        self.sets
+            .lock()
+            .unwrap()
+            .sets
+            .values()
+            .filter(|s| s.client_id == client_id && !s.dropped)
+            .filter_map(|s| s.adv_id)
+            .collect()
+    }
     pub fn set_periodic_advertising_parameters(
         &mut self,
         adv_id: u8,
         params: PeriodicAdvertisingParameters,
     ) {
-        mutcxxcall!(self, SetPeriodicAdvertisingParameters, adv_id, params);
+        self.intf.lock().unwrap().pin_mut().SetPeriodicAdvertisingParameters(adv_id, params);
     }
     pub fn set_periodic_advertising_data(&mut self, adv_id: u8, data: Vec<u8>) {
-        mutcxxcall!(self, SetPeriodicAdvertisingData, adv_id, data);
+        self.intf.lock().unwrap().pin_mut().SetPeriodicAdvertisingData(adv_id, data);
     }
     pub fn set_periodic_advertising_enable(&mut self, adv_id: u8, enable: bool) {
-        mutcxxcall!(self, SetPeriodicAdvertisingEnable, adv_id, enable);
+        self.intf.lock().unwrap().pin_mut().SetPeriodicAdvertisingEnable(adv_id, enable);
+    }
+}
+
+impl Drop for BleAdvertiser {
+    fn drop(&mut self) {
+        get_dispatchers().lock().unwrap().remove_listener::<GDAdvCb>(self.listener_id);
     }
 }
 
@@ -1599,6 +2125,11 @@ impl Gatt {
         })
     }
 
+    /// Returns the version of this shim and whether the profile is up.
+    pub fn query_profile_info(&self) -> ProfileInfo {
+        stats::get_profile_info("gatt", stats::SHIM_VERSION)
+    }
+
     pub fn is_initialized(&self) -> bool {
         self.is_init
     }
@@ -1608,14 +2139,17 @@ impl Gatt {
         gatt_client_callbacks_dispatcher: GattClientCallbacksDispatcher,
         gatt_server_callbacks_dispatcher: GattServerCallbacksDispatcher,
         gatt_scanner_callbacks_dispatcher: GattScannerCallbacksDispatcher,
+        gatt_scanner_inband_callbacks_dispatcher: GattScannerInbandCallbacksDispatcher,
     ) -> bool {
+        let started = Instant::now();
+
         // Register dispatcher
         if get_dispatchers()
             .lock()
             .unwrap()
             .set::<GattClientCb>(Arc::new(Mutex::new(gatt_client_callbacks_dispatcher)))
         {
-            panic!("Tried to set dispatcher for GattClientCallbacks but it already existed");
+            log::warn!("Replaced the dispatcher for GattClientCallbacks");
         }
 
         if get_dispatchers()
@@ -1623,7 +2157,7 @@ impl Gatt {
             .unwrap()
             .set::<GattServerCb>(Arc::new(Mutex::new(gatt_server_callbacks_dispatcher)))
         {
-            panic!("Tried to set dispatcher for GattServerCallbacks but it already existed");
+            log::warn!("Replaced the dispatcher for GattServerCallbacks");
         }
 
         if get_dispatchers()
@@ -1631,7 +2165,13 @@ impl Gatt {
             .unwrap()
             .set::<GDScannerCb>(Arc::new(Mutex::new(gatt_scanner_callbacks_dispatcher)))
         {
-            panic!("Tried to set dispatcher for GattScannerCallbacks but it already existed");
+            log::warn!("Replaced the dispatcher for GattScannerCallbacks");
+        }
+
+        if get_dispatchers().lock().unwrap().set::<GDScannerInbandCb>(Arc::new(Mutex::new(
+            gatt_scanner_inband_callbacks_dispatcher,
+        ))) {
+            log::warn!("Replaced the dispatcher for GattScannerInbandCallbacks");
         }
 
         let mut gatt_client_callbacks = Box::new(btgatt_client_callbacks_t {
@@ -1696,6 +2236,7 @@ impl Gatt {
 
         let init = ccall!(self, init, rawcb);
         self.is_init = init == 0;
+        stats::record_init_status("gatt", started, BtStatus::from(init as u32));
         self.callbacks = Some(callbacks);
         self.gatt_client_callbacks = Some(gatt_client_callbacks);
         self.gatt_server_callbacks = Some(gatt_server_callbacks);
@@ -1703,8 +2244,82 @@ impl Gatt {
 
         // Register callbacks for gatt scanner and advertiser
         mutcxxcall!(self.scanner, RegisterCallbacks);
-        mutcxxcall!(self.advertiser, RegisterCallbacks);
+        self.advertiser.register_callbacks();
 
         return self.is_init;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advertising_set_tracking() {
+        let mut sets = AdvertisingSets::default();
+        let first = sets.add(1);
+        let second = sets.add(1);
+
+        assert_eq!(
+            sets.on_callback(&GattAdvCallbacks::OnAdvertisingSetStarted(first, 3, -7, 0)),
+            None
+        );
+        sets.on_callback(&GattAdvCallbacks::OnAdvertisingDataSet(3, 0x12));
+        assert_eq!(sets.sets[&first].state, AdvertisingSetState::Enabled);
+        assert_eq!(sets.sets[&first].last_error, Some(AdvertisingSetError::DataFailed(0x12)));
+        assert_eq!(sets.sets[&second].last_error, None);
+
+        // The advertising and scan response data fail and recover independently.
+        sets.on_callback(&GattAdvCallbacks::OnScanResponseDataSet(3, 0x07));
+        assert_eq!(
+            sets.sets[&first].last_error,
+            Some(AdvertisingSetError::ScanResponseFailed(0x07))
+        );
+        assert_eq!(
+            (sets.sets[&first].data_error, sets.sets[&first].scan_rsp_error),
+            (Some(0x12), Some(0x07))
+        );
+        sets.on_callback(&GattAdvCallbacks::OnAdvertisingDataSet(3, 0));
+        assert_eq!(
+            (sets.sets[&first].data_error, sets.sets[&first].scan_rsp_error),
+            (None, Some(0x07))
+        );
+
+        // A set whose handle is gone is unregistered as soon as it starts.
+        sets.sets.get_mut(&second).unwrap().dropped = true;
+        assert_eq!(
+            sets.on_callback(&GattAdvCallbacks::OnAdvertisingSetStarted(second, 4, 0, 0)),
+            Some(4)
+        );
+        assert!(!sets.sets.contains_key(&second));
+    }
+
+    #[test]
+    fn test_advertising_set_reg_ids() {
+        let mut sets = AdvertisingSets::default();
+        assert_eq!(sets.add(1), -1);
+        assert_eq!(sets.add(1), -2);
+
+        // Reg ids stay negative when wrapping around and skip sets still starting.
+        sets.next_reg_id = i32::MAX - 1;
+        assert_eq!(sets.add(1), i32::MIN + 1);
+        assert_eq!(sets.add(1), -3);
+    }
+
+    #[test]
+    fn test_parse_batch_scan_reports() {
+        let header = [0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x01, 0xf9, 0xc4, 0x10, 0x00];
+
+        let truncated = parse_batch_scan_reports(1, 2, &[&header[..], &header[..]].concat());
+        assert_eq!(truncated.len(), 2);
+        assert_eq!(truncated[0].address.val, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        assert_eq!((truncated[0].tx_power, truncated[0].rssi), (-7, -60));
+        assert_eq!(truncated[0].timestamp, 16);
+
+        let record = [&header[..], &[2, 0x01, 0x06, 0][..]].concat();
+        let full = parse_batch_scan_reports(2, 2, &[&record[..], &record[..3]].concat());
+        assert_eq!(full.len(), 1);
+        assert_eq!(full[0].adv_data, vec![0x01, 0x06]);
+        assert!(full[0].scan_response.is_empty());
+    }
+}
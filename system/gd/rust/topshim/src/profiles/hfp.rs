@@ -1,9 +1,11 @@
-use crate::btif::{BluetoothInterface, RawAddress};
+use crate::btif::{BluetoothInterface, BtStatus, RawAddress};
+use crate::profiles::stats::{self, ProfileInfo};
 use crate::topstack::get_dispatchers;
 
 use num_traits::cast::FromPrimitive;
 use std::convert::{TryFrom, TryInto};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use topshim_macros::cb_variant;
 
 #[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
@@ -85,6 +87,7 @@ pub mod ffi {
     extern "Rust" {
         fn hfp_connection_state_callback(state: u32, addr: RustRawAddress);
         fn hfp_audio_state_callback(state: u32, addr: RustRawAddress);
+        fn hfp_battery_level_callback(battery_level: u8, addr: RustRawAddress);
     }
 }
 
@@ -104,6 +107,8 @@ impl Into<RawAddress> for ffi::RustRawAddress {
 pub enum HfpCallbacks {
     ConnectionState(BthfConnectionState, RawAddress),
     AudioState(BthfAudioState, RawAddress),
+    /// Battery level in percent reported by the hands-free device.
+    BatteryLevel(u8, RawAddress),
 }
 
 pub struct HfpCallbacksDispatcher {
@@ -128,6 +133,14 @@ cb_variant!(
     }
 );
 
+cb_variant!(
+    HfpCb,
+    hfp_battery_level_callback -> HfpCallbacks::BatteryLevel,
+    u8, ffi::RustRawAddress -> RawAddress, {
+        let _1 = _1.into();
+    }
+);
+
 pub struct Hfp {
     internal: cxx::UniquePtr<ffi::HfpIntf>,
     _is_init: bool,
@@ -146,12 +159,20 @@ impl Hfp {
         Hfp { internal: hfpif, _is_init: false }
     }
 
+    /// Returns the version of this shim and whether the profile is up.
+    pub fn query_profile_info(&self) -> ProfileInfo {
+        stats::get_profile_info("hfp", stats::SHIM_VERSION)
+    }
+
     pub fn initialize(&mut self, callbacks: HfpCallbacksDispatcher) -> bool {
         if get_dispatchers().lock().unwrap().set::<HfpCb>(Arc::new(Mutex::new(callbacks))) {
-            panic!("Tried to set dispatcher for HFP callbacks while it already exists");
+            log::warn!("Replaced the dispatcher for HFP callbacks");
         }
-        self.internal.pin_mut().init();
-        true
+        let started = Instant::now();
+        let init = self.internal.pin_mut().init();
+        let status = BtStatus::from(init as u32);
+        stats::record_init_status("hfp", started, status.clone());
+        status == BtStatus::Success
     }
 
     pub fn connect(&mut self, addr: RawAddress) {
@@ -172,6 +193,7 @@ impl Hfp {
 
     pub fn cleanup(&mut self) -> bool {
         self.internal.pin_mut().cleanup();
+        stats::record_cleanup("hfp");
         true
     }
 }
@@ -1,11 +1,13 @@
 use crate::bindings::root as bindings;
 use crate::btif::{BluetoothInterface, BtStatus, RawAddress, SupportedProfiles};
 use crate::profiles::hid_host::bindings::bthh_interface_t;
+use crate::profiles::stats::{self, ProfileInfo};
 use crate::topstack::get_dispatchers;
 use crate::{cast_to_ffi_address, ccall, deref_ffi_address};
 
 use num_traits::cast::{FromPrimitive, ToPrimitive};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use topshim_macros::cb_variant;
 
 #[derive(Debug, FromPrimitive, PartialEq, PartialOrd)]
@@ -73,7 +75,7 @@ impl From<BthhProtocolMode> for bindings::bthh_protocol_mode_t {
     }
 }
 
-#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, FromPrimitive, ToPrimitive, PartialEq, PartialOrd)]
 #[repr(u32)]
 pub enum BthhReportType {
     InputReport = 1,
@@ -97,6 +99,7 @@ fn convert_report(count: i32, raw: *mut u8) -> Vec<u8> {
     return v;
 }
 
+#[derive(Debug)]
 pub enum HHCallbacks {
     ConnectionState(RawAddress, BthhConnectionState),
     VirtualUnplug(RawAddress, BthhStatus),
@@ -171,14 +174,21 @@ impl HidHost {
         }
     }
 
+    /// Returns the version of this shim and whether the profile is up.
+    pub fn query_profile_info(&self) -> ProfileInfo {
+        stats::get_profile_info("hidhost", stats::SHIM_VERSION)
+    }
+
     pub fn is_initialized(&self) -> bool {
         self.is_init
     }
 
     pub fn initialize(&mut self, callbacks: HHCallbacksDispatcher) -> bool {
+        let started = Instant::now();
+
         // Register dispatcher
         if get_dispatchers().lock().unwrap().set::<HHCb>(Arc::new(Mutex::new(callbacks))) {
-            panic!("Tried to set dispatcher for HHCallbacks but it already existed");
+            log::warn!("Replaced the dispatcher for HHCallbacks");
         }
 
         let mut callbacks = Box::new(bindings::bthh_callbacks_t {
@@ -197,6 +207,7 @@ impl HidHost {
         let init = ccall!(self, init, rawcb);
         self.is_init = BtStatus::from(init) == BtStatus::Success;
         self.callbacks = Some(callbacks);
+        stats::record_init_status("hidhost", started, BtStatus::from(init));
 
         return self.is_init;
     }
@@ -286,6 +297,7 @@ impl HidHost {
     }
 
     pub fn cleanup(&self) {
+        stats::record_cleanup("hidhost");
         ccall!(self, cleanup)
     }
 }
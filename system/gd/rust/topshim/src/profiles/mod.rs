@@ -6,3 +6,4 @@ pub mod gatt;
 pub mod hfp;
 pub mod hid_host;
 pub mod sdp;
+pub mod stats;
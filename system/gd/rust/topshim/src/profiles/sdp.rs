@@ -1,6 +1,7 @@
 use num_traits::cast::{FromPrimitive, ToPrimitive};
 use std::os::raw::c_char;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::vec::Vec;
 
 use crate::bindings::root as bindings;
@@ -8,6 +9,7 @@ use crate::btif::{
     ascii_to_string, ptr_to_vec, BluetoothInterface, BtStatus, FfiAddress, RawAddress,
     SupportedProfiles, Uuid,
 };
+use crate::profiles::stats::{self, ProfileInfo};
 use crate::topstack::get_dispatchers;
 use crate::{cast_to_ffi_address, ccall, deref_const_ffi_address};
 use topshim_macros::cb_variant;
@@ -368,13 +370,20 @@ impl Sdp {
         }
     }
 
+    /// Returns the version of this shim and whether the profile is up.
+    pub fn query_profile_info(&self) -> ProfileInfo {
+        stats::get_profile_info("sdp", stats::SHIM_VERSION)
+    }
+
     pub fn is_initialized(&self) -> bool {
         self.is_init
     }
 
     pub fn initialize(&mut self, callbacks: SdpCallbacksDispatcher) -> bool {
+        let started = Instant::now();
+
         if get_dispatchers().lock().unwrap().set::<SdpCb>(Arc::new(Mutex::new(callbacks))) {
-            panic!("Tried to set dispatcher for SdpCallbacks but it already existed");
+            log::warn!("Replaced the dispatcher for SdpCallbacks");
         }
 
         let mut callbacks = Box::new(bindings::btsdp_callbacks_t {
@@ -387,6 +396,7 @@ impl Sdp {
         let init = ccall!(self, init, rawcb);
         self.is_init = BtStatus::from(init) == BtStatus::Success;
         self.callbacks = Some(callbacks);
+        stats::record_init_status("sdp", started, BtStatus::from(init));
 
         return self.is_init;
     }
@@ -0,0 +1,150 @@
+//! Central bookkeeping of profile init/cleanup for debugging.
+//!
+//! Every profile wrapper records its init attempts and cleanups here so that a profile which
+//! failed to initialize can be spotted in a state dump or through `query_profile_info`.
+
+use crate::btif::BtStatus;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Counters and last error for a single profile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProfileStats {
+    pub init_count: u32,
+    pub init_failures: u32,
+    pub cleanup_count: u32,
+    pub last_error: Option<String>,
+    pub last_init_duration: Option<Duration>,
+    /// Whether the last init succeeded and the profile wasn't cleaned up since.
+    pub active: bool,
+}
+
+/// Version and health of a profile shim, as returned by its `query_profile_info`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProfileInfo {
+    pub name: String,
+    /// Version of the shim, bumped when its interface to the stack changes. 0 for a shim that
+    /// wasn't created.
+    pub version: u32,
+    /// Whether the profile initialized successfully and wasn't cleaned up since.
+    pub enabled: bool,
+    /// Error of the last failed init, empty if none failed.
+    pub last_error: String,
+}
+
+/// Version of the profile shims, bumped when the interface of any of them to the stack changes.
+pub const SHIM_VERSION: u32 = 1;
+
+lazy_static! {
+    static ref PROFILE_STATS: Mutex<BTreeMap<&'static str, ProfileStats>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// Records the outcome of a profile init that started at `started`.
+pub(crate) fn record_init(profile: &'static str, started: Instant, result: Result<(), String>) {
+    let mut stats = PROFILE_STATS.lock().unwrap();
+    let entry = stats.entry(profile).or_default();
+
+    entry.init_count += 1;
+    entry.last_init_duration = Some(started.elapsed());
+    entry.active = result.is_ok();
+    if let Err(e) = result {
+        entry.init_failures += 1;
+        entry.last_error = Some(e);
+    }
+}
+
+/// Records the outcome of a profile init returning a `BtStatus`.
+pub(crate) fn record_init_status(profile: &'static str, started: Instant, status: BtStatus) {
+    let result = match status {
+        BtStatus::Success => Ok(()),
+        _ => Err(format!("init returned {:?}", status)),
+    };
+    record_init(profile, started, result);
+}
+
+/// Records a profile cleanup.
+pub(crate) fn record_cleanup(profile: &'static str) {
+    let mut stats = PROFILE_STATS.lock().unwrap();
+    let entry = stats.entry(profile).or_default();
+
+    entry.cleanup_count += 1;
+    entry.active = false;
+}
+
+/// Returns the info of `profile` for a shim of `version`. Also used by the stack for shims it
+/// didn't create, with version 0.
+///
+/// `enabled` follows the status the last init returned and any cleanup since. A shim that wasn't
+/// created is never enabled, whatever an earlier shim recorded.
+pub fn get_profile_info(profile: &str, version: u32) -> ProfileInfo {
+    let stats = PROFILE_STATS.lock().unwrap();
+    let entry = stats.get(profile);
+
+    ProfileInfo {
+        name: String::from(profile),
+        version,
+        enabled: version != 0 && entry.map_or(false, |s| s.active),
+        last_error: entry.and_then(|s| s.last_error.clone()).unwrap_or_default(),
+    }
+}
+
+/// Returns a snapshot of the stats of every profile that was initialized at least once.
+pub fn get_profile_stats() -> BTreeMap<String, ProfileStats> {
+    PROFILE_STATS.lock().unwrap().iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+}
+
+/// Formats the profile stats for inclusion in a state dump.
+pub fn dump_profile_stats() -> String {
+    let mut out = String::from("Profiles:\n");
+    for (profile, s) in get_profile_stats() {
+        let _ = writeln!(
+            out,
+            "  {}: init={} failed={} cleanup={} last_init={:?} last_error={}",
+            profile,
+            s.init_count,
+            s.init_failures,
+            s.cleanup_count,
+            s.last_init_duration.unwrap_or_default(),
+            s.last_error.as_deref().unwrap_or("none")
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_info() {
+        assert_eq!(
+            get_profile_info("test_unknown", 0),
+            ProfileInfo { name: String::from("test_unknown"), ..Default::default() }
+        );
+
+        record_init("test_profile", Instant::now(), Err(String::from("failed")));
+        let info = get_profile_info("test_profile", 1);
+        assert!(!info.enabled);
+        assert_eq!(info.last_error, "failed");
+
+        record_init_status("test_profile", Instant::now(), BtStatus::Success);
+        assert!(get_profile_info("test_profile", SHIM_VERSION).enabled);
+        assert!(!get_profile_info("test_profile", 0).enabled);
+
+        record_init_status("test_profile", Instant::now(), BtStatus::NotReady);
+        let info = get_profile_info("test_profile", SHIM_VERSION);
+        assert!(!info.enabled);
+        assert_eq!(info.last_error, "init returned NotReady");
+
+        record_init("test_profile", Instant::now(), Ok(()));
+        assert!(get_profile_info("test_profile", 1).enabled);
+
+        record_cleanup("test_profile");
+        assert!(!get_profile_info("test_profile", 1).enabled);
+    }
+}
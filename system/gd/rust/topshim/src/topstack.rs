@@ -26,7 +26,7 @@ pub fn get_runtime() -> Arc<Runtime> {
 
 lazy_static! {
     static ref CB_DISPATCHER: Arc<Mutex<DispatchContainer>> =
-        Arc::new(Mutex::new(DispatchContainer { instances: HashMap::new() }));
+        Arc::new(Mutex::new(DispatchContainer::new()));
 }
 
 /// A Box-ed struct that implements a `dispatch` fn.
@@ -65,11 +65,27 @@ pub type InstanceBox = Box<dyn Any + Send + Sync>;
 /// libbluetooth that convert callback args into an enum variant and call the
 /// dispatcher for that enum. The dispatcher will then queue that enum into the
 /// channel (using a captured channel tx in the closure).
+///
+/// Each enum has at most one main dispatcher (see [`DispatchContainer::set`]) and any number of
+/// listeners (see [`DispatchContainer::add_listener`]). Listeners get a copy of every callback
+/// before the main dispatcher, so components such as metrics can observe a profile without
+/// owning it. Setting the main dispatcher again replaces it, so a profile can be initialized
+/// again without tearing down its listeners.
 pub struct DispatchContainer {
     instances: HashMap<TypeId, InstanceBox>,
+    listeners: HashMap<TypeId, Vec<(u32, InstanceBox)>>,
+    next_listener_id: u32,
 }
 
 impl DispatchContainer {
+    fn new() -> Self {
+        DispatchContainer {
+            instances: HashMap::new(),
+            listeners: HashMap::new(),
+            next_listener_id: 0,
+        }
+    }
+
     /// Find registered dispatcher for enum specialization.
     ///
     /// # Return
@@ -86,7 +102,8 @@ impl DispatchContainer {
         None
     }
 
-    /// Set dispatcher for an enum specialization.
+    /// Set dispatcher for an enum specialization, replacing the one set before if any. The
+    /// listeners are kept.
     ///
     /// # Arguments
     ///
@@ -99,9 +116,95 @@ impl DispatchContainer {
     pub fn set<T: 'static + Clone + Send + Sync>(&mut self, obj: T) -> bool {
         self.instances.insert(TypeId::of::<T>(), Box::new(obj)).is_some()
     }
+
+    /// Find all listeners registered for an enum specialization, in registration order.
+    pub fn get_listeners<T: 'static + Clone + Send + Sync>(&self) -> Vec<T> {
+        match self.listeners.get(&TypeId::of::<T>()) {
+            Some(listeners) => listeners
+                .iter()
+                .map(|(_, value)| value.downcast_ref::<T>().unwrap().clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Add a listener for an enum specialization. Listeners can be added at any time, whether or
+    /// not the main dispatcher is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `obj` - Same as for [`DispatchContainer::set`].
+    ///
+    /// # Returns
+    ///
+    /// Id to pass to [`DispatchContainer::remove_listener`].
+    pub fn add_listener<T: 'static + Clone + Send + Sync>(&mut self, obj: T) -> u32 {
+        let id = self.next_listener_id;
+        self.next_listener_id = self.next_listener_id.wrapping_add(1);
+        self.listeners.entry(TypeId::of::<T>()).or_default().push((id, Box::new(obj)));
+        id
+    }
+
+    /// Remove a listener added with [`DispatchContainer::add_listener`].
+    ///
+    /// # Returns
+    ///
+    /// True if the listener was found.
+    pub fn remove_listener<T: 'static + Clone + Send + Sync>(&mut self, id: u32) -> bool {
+        match self.listeners.get_mut(&TypeId::of::<T>()) {
+            Some(listeners) => {
+                let len = listeners.len();
+                listeners.retain(|(listener_id, _)| *listener_id != id);
+                listeners.len() != len
+            }
+            None => false,
+        }
+    }
 }
 
 /// Take a clone of the static dispatcher container.
 pub fn get_dispatchers() -> Arc<Mutex<DispatchContainer>> {
     CB_DISPATCHER.clone()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct Dispatcher(Arc<Mutex<Vec<u32>>>);
+
+    #[test]
+    fn test_listeners() {
+        let mut container = DispatchContainer::new();
+        let main = Dispatcher(Arc::new(Mutex::new(vec![])));
+        let first = container.add_listener(Dispatcher(Arc::new(Mutex::new(vec![1]))));
+        let second = container.add_listener(Dispatcher(Arc::new(Mutex::new(vec![2]))));
+
+        // Listeners don't count as the main dispatcher.
+        assert!(container.get::<Dispatcher>().is_none());
+        assert!(!container.set(main));
+
+        let listeners = container.get_listeners::<Dispatcher>();
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(*listeners[1].0.lock().unwrap(), vec![2]);
+
+        assert!(container.remove_listener::<Dispatcher>(first));
+        assert!(!container.remove_listener::<Dispatcher>(first));
+        assert_eq!(*container.get_listeners::<Dispatcher>()[0].0.lock().unwrap(), vec![2]);
+        assert!(container.remove_listener::<Dispatcher>(second));
+        assert!(container.get_listeners::<Dispatcher>().is_empty());
+        assert!(container.get::<Dispatcher>().is_some());
+    }
+
+    #[test]
+    fn test_replace_dispatcher() {
+        let mut container = DispatchContainer::new();
+        container.add_listener(Dispatcher(Arc::new(Mutex::new(vec![1]))));
+
+        assert!(!container.set(Dispatcher(Arc::new(Mutex::new(vec![2])))));
+        assert!(container.set(Dispatcher(Arc::new(Mutex::new(vec![3])))));
+        assert_eq!(*container.get::<Dispatcher>().unwrap().0.lock().unwrap(), vec![3]);
+        assert_eq!(container.get_listeners::<Dispatcher>().len(), 1);
+    }
+}
@@ -84,6 +84,16 @@ void bluetooth::shim::ACL_ConfigureLePrivacy(bool is_le_privacy_enabled) {
           maximum_rotation_time);
 }
 
+void bluetooth::shim::ACL_SetLePrivacyRotationTime(
+    std::chrono::milliseconds rotation_time) {
+  /* Keeps the IRK and the current address, unlike setting the policy again */
+  Stack::GetInstance()
+      ->GetStackManager()
+      ->GetInstance<bluetooth::hci::AclManager>()
+      ->GetLeAddressManager()
+      ->SetPrivateAddressRotationTime(rotation_time, rotation_time);
+}
+
 void bluetooth::shim::ACL_Disconnect(uint16_t handle, bool is_classic,
                                      tHCI_STATUS reason, std::string comment) {
   (is_classic)
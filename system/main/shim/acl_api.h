@@ -16,6 +16,8 @@
 
 #pragma once
 
+#include <chrono>
+
 #include "stack/include/bt_hdr.h"
 #include "stack/include/bt_types.h"
 #include "stack/include/hci_error_code.h"
@@ -35,6 +37,7 @@ void ACL_Disconnect(uint16_t handle, bool is_classic, tHCI_STATUS reason,
                     std::string comment);
 void ACL_WriteData(uint16_t handle, BT_HDR* p_buf);
 void ACL_ConfigureLePrivacy(bool is_le_privacy_enabled);
+void ACL_SetLePrivacyRotationTime(std::chrono::milliseconds rotation_time);
 void ACL_Shutdown();
 void ACL_IgnoreAllLeConnections();
 
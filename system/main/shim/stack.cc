@@ -82,6 +82,31 @@ void CreatePidFile() {
   LOG_INFO("%s - Created pid file %s", __func__, pid_file.c_str());
 }
 
+// Controller version file format
+constexpr char version_file_format[] = "/var/run/bluetooth/bluetooth%d.version";
+
+// btmanagerd doesn't open the controller, so record its version for it. The
+// file is written before the pid file and kept once the stack stops.
+void CreateVersionFile(const hci::LocalVersionInformation& version) {
+  std::string version_file =
+      StringFormat(version_file_format, InitFlags::GetAdapterIndex());
+  int version_fd =
+      open(version_file.c_str(), O_WRONLY | O_CREAT | O_TRUNC, 0644);
+  if (version_fd < 0) {
+    LOG_WARN("%s - Unable to create version file %s", __func__,
+             version_file.c_str());
+    return;
+  }
+
+  dprintf(version_fd,
+          "HCI_VERSION=%u\nHCI_REVISION=%u\nLMP_VERSION=%u\n"
+          "LMP_SUBVERSION=%u\nMANUFACTURER=%u\n",
+          static_cast<unsigned>(version.hci_version_), version.hci_revision_,
+          static_cast<unsigned>(version.lmp_version_), version.lmp_subversion_,
+          version.manufacturer_name_);
+  close(version_fd);
+}
+
 void RemovePidFile() {
   std::string pid_file =
       StringFormat(pid_file_format, InitFlags::GetAdapterIndex());
@@ -205,6 +230,11 @@ void Stack::StartEverything() {
     bluetooth::shim::init_activity_attribution();
   }
 
+  if (stack_manager_.IsStarted<hci::Controller>()) {
+    CreateVersionFile(stack_manager_.GetInstance<hci::Controller>()
+                          ->GetLocalVersionInformation());
+  }
+
   // Create pid since we're up and running
   CreatePidFile();
 }
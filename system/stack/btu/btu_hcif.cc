@@ -91,12 +91,17 @@ static void btu_hcif_esco_connection_chg_evt(uint8_t* p);
 
 /* Simple Pairing Events */
 static void btu_hcif_io_cap_request_evt(const uint8_t* p);
+static void btu_hcif_keypress_notif_evt(const uint8_t* p);
 
 static void btu_ble_ll_conn_param_upd_evt(uint8_t* p, uint16_t evt_len);
 static void btu_ble_proc_ltk_req(uint8_t* p);
 static void btu_hcif_encryption_key_refresh_cmpl_evt(uint8_t* p);
 static void btu_ble_data_length_change_evt(uint8_t* p, uint16_t evt_len);
 static void btu_ble_rc_param_req_evt(uint8_t* p);
+static void btu_hcif_data_buf_overflow_evt(const uint8_t* p);
+
+static tBTU_KEYPRESS_NOTIF_CB* btu_keypress_notif_cb = nullptr;
+static tBTU_DATA_BUF_OVERFLOW_CB* btu_data_buf_overflow_cb = nullptr;
 
 /**
  * Log HCI event metrics that are not handled in special functions
@@ -282,6 +287,9 @@ void btu_hcif_process_event(UNUSED_ATTR uint8_t controller_id,
     case HCI_HARDWARE_ERROR_EVT:
       btu_hcif_hardware_error_evt(p);
       break;
+    case HCI_DATA_BUF_OVERFLOW_EVT:
+      btu_hcif_data_buf_overflow_evt(p);
+      break;
     case HCI_NUM_COMPL_DATA_PKTS_EVT:
       acl_process_num_completed_pkts(p, hci_evt_len);
       break;
@@ -333,6 +341,9 @@ void btu_hcif_process_event(UNUSED_ATTR uint8_t controller_id,
     case HCI_USER_PASSKEY_NOTIFY_EVT:
       btm_proc_sp_req_evt(BTM_SP_KEY_NOTIF_EVT, p);
       break;
+    case HCI_KEYPRESS_NOTIFY_EVT:
+      btu_hcif_keypress_notif_evt(p);
+      break;
 
     case HCI_BLE_EVENT: {
       STREAM_TO_UINT8(ble_sub_code, p);
@@ -1485,6 +1496,30 @@ static void btu_hcif_hardware_error_evt(uint8_t* p) {
   BTA_sys_signal_hw_error();
 }
 
+/*******************************************************************************
+ *
+ * Function         btu_hcif_data_buf_overflow_evt
+ *
+ * Description      Process event HCI_DATA_BUF_OVERFLOW_EVT
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+static void btu_hcif_data_buf_overflow_evt(const uint8_t* p) {
+  uint8_t link_type;
+
+  STREAM_TO_UINT8(link_type, p);
+
+  LOG_WARN("Controller data buffer overflow, link_type:%hhu", link_type);
+  if (btu_data_buf_overflow_cb != nullptr) {
+    btu_data_buf_overflow_cb(link_type);
+  }
+}
+
+void btu_hcif_register_data_buf_overflow_cb(tBTU_DATA_BUF_OVERFLOW_CB* p_cb) {
+  btu_data_buf_overflow_cb = p_cb;
+}
+
 /*******************************************************************************
  *
  * Function         btu_hcif_mode_change_evt
@@ -1566,6 +1601,31 @@ static void btu_hcif_read_clock_off_comp_evt(uint8_t* p) {
  * Simple Pairing Events
  **********************************************/
 
+/*******************************************************************************
+ *
+ * Function         btu_hcif_keypress_notif_evt
+ *
+ * Description      Process event HCI_KEYPRESS_NOTIFY_EVT
+ *
+ * Returns          void
+ *
+ ******************************************************************************/
+static void btu_hcif_keypress_notif_evt(const uint8_t* p) {
+  RawAddress bda;
+  uint8_t notification_type;
+
+  STREAM_TO_BDADDR(bda, p);
+  STREAM_TO_UINT8(notification_type, p);
+
+  if (btu_keypress_notif_cb != nullptr) {
+    btu_keypress_notif_cb(bda, notification_type);
+  }
+}
+
+void btu_hcif_register_keypress_notif_cb(tBTU_KEYPRESS_NOTIF_CB* p_cb) {
+  btu_keypress_notif_cb = p_cb;
+}
+
 /*******************************************************************************
  *
  * Function         btu_hcif_io_cap_request_evt
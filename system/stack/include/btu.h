@@ -39,6 +39,7 @@
 #include "osi/include/alarm.h"
 #include "osi/include/osi.h"  // UNUSED_ATTR
 #include "stack/include/bt_hdr.h"
+#include "types/raw_address.h"
 
 /* Global BTU data */
 extern uint8_t btu_trace_level;
@@ -53,6 +54,17 @@ void btu_hcif_send_cmd_with_cb(const base::Location& posted_from,
                                uint16_t opcode, uint8_t* params,
                                uint8_t params_len,
                                base::OnceCallback<void(uint8_t*, uint16_t)> cb);
+
+/* Observers for events the legacy stack doesn't act on itself. Only one
+ * observer is kept per event, registering again replaces it and nullptr
+ * removes it. Called on the main thread.
+ */
+typedef void(tBTU_KEYPRESS_NOTIF_CB)(const RawAddress& bd_addr,
+                                     uint8_t notification_type);
+typedef void(tBTU_DATA_BUF_OVERFLOW_CB)(uint8_t link_type);
+void btu_hcif_register_keypress_notif_cb(tBTU_KEYPRESS_NOTIF_CB* p_cb);
+void btu_hcif_register_data_buf_overflow_cb(tBTU_DATA_BUF_OVERFLOW_CB* p_cb);
+
 namespace bluetooth::legacy::testing {
 void btu_hcif_hdl_command_status(uint16_t opcode, uint8_t status,
                                  const uint8_t* p_cmd,
@@ -98,6 +98,7 @@ struct bta_dm_search_cmpl bta_dm_search_cmpl;
 struct bta_dm_search_result bta_dm_search_result;
 struct bta_dm_search_start bta_dm_search_start;
 struct bta_dm_set_dev_name bta_dm_set_dev_name;
+struct bta_dm_set_eir_tx_power bta_dm_set_eir_tx_power;
 struct bta_dm_set_encryption bta_dm_set_encryption;
 struct btm_dm_start_gatt_discovery btm_dm_start_gatt_discovery;
 struct handle_remote_features_complete handle_remote_features_complete;
@@ -361,6 +362,10 @@ void bta_dm_set_dev_name(const std::vector<uint8_t>& name) {
   mock_function_count_map[__func__]++;
   test::mock::bta_dm_act::bta_dm_set_dev_name(name);
 }
+void bta_dm_set_eir_tx_power(bool include, int8_t tx_power) {
+  mock_function_count_map[__func__]++;
+  test::mock::bta_dm_act::bta_dm_set_eir_tx_power(include, tx_power);
+}
 void bta_dm_set_encryption(const RawAddress& bd_addr, tBT_TRANSPORT transport,
                            tBTA_DM_ENCRYPT_CBACK* p_callback,
                            tBTM_BLE_SEC_ACT sec_act) {
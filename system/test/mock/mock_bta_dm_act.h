@@ -735,6 +735,16 @@ struct bta_dm_set_dev_name {
 };
 extern struct bta_dm_set_dev_name bta_dm_set_dev_name;
 
+// Name: bta_dm_set_eir_tx_power
+// Params: bool include, int8_t tx_power
+// Return: void
+struct bta_dm_set_eir_tx_power {
+  std::function<void(bool include, int8_t tx_power)> body{
+      [](bool include, int8_t tx_power) {}};
+  void operator()(bool include, int8_t tx_power) { body(include, tx_power); };
+};
+extern struct bta_dm_set_eir_tx_power bta_dm_set_eir_tx_power;
+
 // Name: bta_dm_set_encryption
 // Params: const RawAddress& bd_addr, tBT_TRANSPORT transport,
 // tBTA_DM_ENCRYPT_CBACK* p_callback, tBTM_BLE_SEC_ACT sec_act Return: void
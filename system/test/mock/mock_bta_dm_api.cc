@@ -67,6 +67,7 @@ struct BTA_DmSearch BTA_DmSearch;
 struct BTA_DmSearchCancel BTA_DmSearchCancel;
 struct BTA_DmSetBlePrefConnParams BTA_DmSetBlePrefConnParams;
 struct BTA_DmSetDeviceName BTA_DmSetDeviceName;
+struct BTA_DmSetEirTxPower BTA_DmSetEirTxPower;
 struct BTA_DmSetEncryption BTA_DmSetEncryption;
 struct BTA_DmSetLocalDiRecord BTA_DmSetLocalDiRecord;
 struct BTA_EnableTestMode BTA_EnableTestMode;
@@ -205,6 +206,10 @@ void BTA_DmSetDeviceName(const char* p_name) {
   mock_function_count_map[__func__]++;
   test::mock::bta_dm_api::BTA_DmSetDeviceName(p_name);
 }
+void BTA_DmSetEirTxPower(bool include, int8_t tx_power) {
+  mock_function_count_map[__func__]++;
+  test::mock::bta_dm_api::BTA_DmSetEirTxPower(include, tx_power);
+}
 void BTA_DmSetEncryption(const RawAddress& bd_addr, tBT_TRANSPORT transport,
                          tBTA_DM_ENCRYPT_CBACK* p_callback,
                          tBTM_BLE_SEC_ACT sec_act) {
@@ -387,6 +387,16 @@ struct BTA_DmSetDeviceName {
 };
 extern struct BTA_DmSetDeviceName BTA_DmSetDeviceName;
 
+// Name: BTA_DmSetEirTxPower
+// Params: bool include, int8_t tx_power
+// Return: void
+struct BTA_DmSetEirTxPower {
+  std::function<void(bool include, int8_t tx_power)> body{
+      [](bool include, int8_t tx_power) {}};
+  void operator()(bool include, int8_t tx_power) { body(include, tx_power); };
+};
+extern struct BTA_DmSetEirTxPower BTA_DmSetEirTxPower;
+
 // Name: BTA_DmSetEncryption
 // Params: const RawAddress& bd_addr, tBT_TRANSPORT transport,
 // tBTA_DM_ENCRYPT_CBACK* p_callback, tBTM_BLE_SEC_ACT sec_act Return: void
@@ -57,6 +57,10 @@ void bluetooth::shim::ACL_IgnoreLeConnectionFrom(
 void bluetooth::shim::ACL_ConfigureLePrivacy(bool is_le_privacy_enabled) {
   mock_function_count_map[__func__]++;
 }
+void bluetooth::shim::ACL_SetLePrivacyRotationTime(
+    std::chrono::milliseconds rotation_time) {
+  mock_function_count_map[__func__]++;
+}
 void bluetooth::shim::ACL_WriteData(uint16_t handle, BT_HDR* p_buf) {
   mock_function_count_map[__func__]++;
 }
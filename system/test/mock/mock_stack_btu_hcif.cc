@@ -31,6 +31,7 @@ extern std::map<std::string, int> mock_function_count_map;
 #include <base/location.h>
 
 #include "stack/include/bt_hdr.h"
+#include "types/raw_address.h"
 
 #ifndef UNUSED_ATTR
 #define UNUSED_ATTR
@@ -58,6 +59,13 @@ void btu_hcif_send_cmd_with_cb(const base::Location& posted_from,
                                uint8_t params_len, hci_cmd_cb cb) {
   mock_function_count_map[__func__]++;
 }
+void btu_hcif_register_keypress_notif_cb(
+    void (*p_cb)(const RawAddress& bd_addr, uint8_t notification_type)) {
+  mock_function_count_map[__func__]++;
+}
+void btu_hcif_register_data_buf_overflow_cb(void (*p_cb)(uint8_t link_type)) {
+  mock_function_count_map[__func__]++;
+}
 void cmd_with_cb_data_cleanup(cmd_with_cb_data* cb_wrapper) {
   mock_function_count_map[__func__]++;
 }